@@ -1,17 +1,26 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+/// Directories the server never indexes: version control metadata and its
+/// own `.coderlm` state. Unlike the defaults below, config can't re-allow
+/// them.
+pub const INTERNAL_IGNORE_DIRS: &[&str] = &[".git", ".hg", ".svn", ".coderlm"];
+
 /// Default ignore patterns applied on top of .gitignore rules.
-/// These are directory names or file patterns that are almost never useful
-/// for code intelligence.
+/// These are directory names that are almost never useful for code
+/// intelligence: dependencies, vendored code, build output, caches.
+/// Applied when `use_default_ignores` is on.
 pub const DEFAULT_IGNORE_DIRS: &[&str] = &[
     "node_modules",
+    "bower_components",
     "vendor",
     "__pycache__",
     ".pycache",
     "target",
     "dist",
     "build",
-    ".git",
-    ".hg",
-    ".svn",
     ".next",
     ".nuxt",
     ".output",
@@ -31,7 +40,19 @@ pub const DEFAULT_IGNORE_DIRS: &[&str] = &[
     ".terraform",
     ".serverless",
     ".lake",
-    ".coderlm",
+];
+
+/// Lock files and dependency checksums, as `.gitignore` patterns. Large,
+/// machine-written, and noisy in grep output. Applied when
+/// `use_default_ignores` is on.
+pub const DEFAULT_IGNORE_FILES: &[&str] = &[
+    "*.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "pnpm-lock.yaml",
+    "bun.lockb",
+    "go.sum",
+    "Package.resolved",
 ];
 
 /// File extensions that are binary or otherwise useless for code reading.
@@ -40,20 +61,426 @@ pub const DEFAULT_IGNORE_EXTENSIONS: &[&str] = &[
     "jar", "war", "ear", "zip", "tar", "gz", "bz2", "xz", "7z", "rar", "png", "jpg", "jpeg",
     "gif", "bmp", "ico", "svg", "webp", "mp3", "mp4", "avi", "mov", "wmv", "flv", "woff",
     "woff2", "ttf", "eot", "otf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "db",
-    "sqlite", "sqlite3", "lock", "map", "olean",
+    "sqlite", "sqlite3", "map", "olean",
 ];
 
 /// Maximum file size (in bytes) to index by default. Files larger than this
 /// are still listed in the tree but are not parsed for symbols.
 pub const DEFAULT_MAX_FILE_SIZE: u64 = 20_000_000_000; // 20 GB
 
-pub fn should_ignore_dir(name: &str) -> bool {
-    DEFAULT_IGNORE_DIRS.iter().any(|&d| d == name)
+pub fn is_internal_dir(name: &str) -> bool {
+    INTERNAL_IGNORE_DIRS.iter().any(|&d| d == name)
 }
 
-pub fn should_ignore_extension(path: &str) -> bool {
+/// The entry of `DEFAULT_IGNORE_EXTENSIONS` that `path` ends with, if any.
+pub fn ignored_extension(path: &str) -> Option<&'static str> {
     let lower = path.to_lowercase();
     DEFAULT_IGNORE_EXTENSIONS
         .iter()
-        .any(|ext| lower.ends_with(&format!(".{}", ext)))
+        .find(|ext| {
+            lower
+                .strip_suffix(**ext)
+                .is_some_and(|rest| rest.ends_with('.'))
+        })
+        .copied()
+}
+
+/// Maximum number of per-file watches across all projects. Each one holds an
+/// inotify watch, so this keeps a runaway agent from exhausting descriptors.
+pub const MAX_FILE_WATCHES: usize = 20;
+
+/// Seconds in-flight requests get to finish after shutdown is requested.
+pub const SHUTDOWN_DRAIN_SECS: u64 = 10;
+
+/// Default and maximum wait for `GET /vars/final/wait`, in seconds.
+pub const FINAL_WAIT_DEFAULT_SECS: u64 = 30;
+pub const FINAL_WAIT_MAX_SECS: u64 = 300;
+
+/// Default pattern `POST /vars/final/validate` uses to find citations: a
+/// path with an extension, optionally followed by `:line` or
+/// `:start-end`. Callers may pass their own with the same named groups.
+pub const CITATION_PATTERN: &str = r"(?P<path>(?:\.{1,2}/|/)?(?:[\w\-][\w.\-]*/)*[\w\-][\w.\-]*\.[A-Za-z][A-Za-z0-9]{0,7})(?::(?P<line>\d+)(?:-(?P<end>\d+))?)?";
+
+/// Extensions accepted as citations even though no indexed language claims
+/// them. Bare names with any other unknown extension (`e.g`, `self.value`,
+/// `example.com`) are not treated as paths unless they contain a `/`.
+pub const CITATION_EXTRA_EXTENSIONS: &[&str] = &["txt", "lock", "cfg", "ini", "xml", "proto", "mk"];
+
+/// How far (in lines) around a cited line a quoted snippet may appear and
+/// still count as found.
+pub const CITATION_SNIPPET_WINDOW_LINES: usize = 5;
+
+/// Default and maximum captures returned by `POST /query`.
+pub const RAW_QUERY_DEFAULT_LIMIT: usize = 100;
+pub const RAW_QUERY_MAX_LIMIT: usize = 1000;
+
+/// Wall-clock budget for running one `POST /query`, in milliseconds.
+/// Pathological patterns are cut off here and reported as `timed_out`.
+pub const RAW_QUERY_TIMEOUT_MS: u64 = 2000;
+
+/// In-progress matches a `POST /query` cursor may track at once.
+pub const RAW_QUERY_MATCH_LIMIT: u32 = 256;
+
+/// Capture text longer than this is cut off (at a char boundary).
+pub const RAW_QUERY_TEXT_MAX_BYTES: usize = 500;
+
+/// Wall-clock budget for one page of `POST /query/scan`, in milliseconds.
+pub const RAW_QUERY_SCAN_TIMEOUT_MS: u64 = 10_000;
+
+/// How often a PDF conversion checks whether its request was cancelled.
+pub const CONVERTER_CANCEL_POLL_MS: u64 = 50;
+
+/// Most files `POST /query/scan` considers; the rest (by path) are left out
+/// and the response says so.
+pub const RAW_QUERY_SCAN_MAX_FILES: usize = 20_000;
+
+/// Extraction errors kept per project for `/health/detail`; the rest are
+/// only counted.
+pub const HEALTH_MAX_ERRORS: usize = 5;
+
+/// Largest `radius` accepted by `/symbols/graph_neighbors`; each hop can
+/// multiply the result size.
+pub const GRAPH_NEIGHBORS_MAX_RADIUS: usize = 3;
+
+/// Most files `/symbols/dependency_matrix` compares at once.
+pub const DEPENDENCY_MATRIX_MAX_FILES: usize = 30;
+
+/// Most linear buckets `/symbols/call_frequency_histogram` will produce.
+pub const HISTOGRAM_MAX_BUCKETS: usize = 100;
+
+/// Symbol fingerprint snapshots kept per project for `/symbols/diff`, besides
+/// the one taken when indexing finished.
+pub const SYMBOL_SNAPSHOT_LIMIT: usize = 2;
+
+/// Files larger than this stay out of the grep text index and are always
+/// scanned.
+pub const TEXT_INDEX_MAX_FILE_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Most lines `peek` with `expand=symbol` may add around the requested range
+/// to finish definitions it cuts through.
+pub const PEEK_EXPAND_MAX_LINES: usize = 200;
+
+/// Most files one `/multi_peek` request may name.
+pub const MULTI_PEEK_MAX_FILES: usize = 20;
+
+/// Files or directories whose presence marks a directory as a project root.
+/// A session opened elsewhere still works but gets a warning.
+pub const PROJECT_MARKERS: &[&str] = &[
+    ".git", ".hg", ".svn", "Cargo.toml", "package.json", "pyproject.toml", "setup.py",
+    "requirements.txt", "go.mod", "pom.xml", "build.gradle", "build.gradle.kts", "Makefile",
+    "CMakeLists.txt", "Gemfile", "composer.json", "lakefile.lean", "lakefile.toml", "flake.nix",
+    "deno.json",
+];
+
+/// Ignored paths `/structure?include_ignored=true` lists before truncating.
+pub const STRUCTURE_IGNORED_LIMIT: usize = 500;
+
+/// Files `/index/coverage` lists per page, and oversized files it lists.
+pub const COVERAGE_LIST_LIMIT: usize = 200;
+
+/// Token budget of a `/semantic_chunks/package` prompt unless the request
+/// sets `budget_tokens`.
+pub const CHUNK_PACKAGE_DEFAULT_BUDGET_TOKENS: usize = 8000;
+
+/// Symbol signatures a chunk package lists from each side of the chunk.
+pub const CHUNK_PACKAGE_NEIGHBORS: usize = 3;
+
+/// Token budget of a `/symbols/context` text unless the request sets
+/// `budget_tokens`.
+pub const SYMBOL_CONTEXT_DEFAULT_BUDGET_TOKENS: usize = 4000;
+
+/// Omitted symbols `/symbols/context` names per section.
+pub const SYMBOL_CONTEXT_OMITTED_LIMIT: usize = 20;
+
+/// Default and maximum names returned by `/symbols/complete`.
+pub const SYMBOL_COMPLETE_DEFAULT_LIMIT: usize = 20;
+pub const SYMBOL_COMPLETE_MAX_LIMIT: usize = 200;
+
+/// `/symbols/complete` ranks at most `limit` times this many names.
+pub const SYMBOL_COMPLETE_SCAN_FACTOR: usize = 20;
+
+/// Line starts that keep a comment, attribute or decorator block going
+/// above a symbol, for the doc `/symbols/context` includes.
+pub const SYMBOL_CONTEXT_COMMENT_PREFIXES: &[&str] = &["//", "/*", "*", "#", "@", "--", ";"];
+
+/// Findings and answer text above which a subcall result stored with
+/// `materialize` is moved into a buffer, and the length of the previews
+/// left in its place.
+pub const SUBCALL_MATERIALIZE_MIN_BYTES: usize = 2000;
+pub const SUBCALL_PREVIEW_BYTES: usize = 200;
+
+/// Buffer and variable names listed in a session's REPL summary.
+pub const SESSION_SUMMARY_MAX_NAMES: usize = 20;
+
+/// Default and maximum history entries in `GET /sessions/{id}/summary`.
+pub const SESSION_RECAP_HISTORY: usize = 10;
+pub const SESSION_RECAP_MAX_HISTORY: usize = 100;
+
+/// Symbols each session remembers for `/symbols/recently_used`.
+pub const RECENTLY_USED_LIMIT: usize = 100;
+
+/// Context blocks `/grep` with `format=blocks` returns per file unless the
+/// request sets `max_blocks_per_file`.
+pub const GREP_MAX_BLOCKS_PER_FILE: usize = 10;
+
+/// Close matches listed on symbol and file not-found errors, and how many
+/// index entries are examined to find them.
+pub const SUGGESTION_LIMIT: usize = 5;
+pub const SUGGESTION_SCAN_LIMIT: usize = 50_000;
+
+/// Largest unified diff `/git/diff` returns for one file; longer ones are cut.
+pub const GIT_DIFF_MAX_BYTES: usize = 1_000_000;
+
+/// Blame summaries a project caches for `/symbols/blame` before the cache
+/// is cleared.
+pub const BLAME_CACHE_LIMIT: usize = 1000;
+
+/// Git executable used for `/git/*` when none is configured.
+pub const DEFAULT_GIT_BINARY: &str = "git";
+
+/// Default TCP port when none is given on the CLI, env, or config file.
+pub const DEFAULT_PORT: u16 = 3000;
+
+/// Default bind address for TCP listening.
+pub const DEFAULT_BIND: &str = "127.0.0.1";
+
+/// Default maximum number of concurrently indexed projects.
+pub const DEFAULT_MAX_PROJECTS: usize = 5;
+
+/// Default number of blocking jobs (grep, indexing) allowed to run at once.
+pub const DEFAULT_MAX_BLOCKING: usize = 16;
+
+/// Default per-session rate limit: sustained requests per second, and the
+/// burst a session may spend before being throttled.
+pub const DEFAULT_RATE_LIMIT: f64 = 50.0;
+pub const DEFAULT_RATE_BURST: f64 = 200.0;
+
+/// Default estimated tokens of responses a session may receive before its
+/// responses carry a `budget_warning`.
+pub const DEFAULT_RESPONSE_TOKEN_BUDGET: usize = 200_000;
+
+/// Responses smaller than this many bytes are sent uncompressed.
+pub const DEFAULT_COMPRESSION_MIN_BYTES: u16 = 1024;
+
+/// Settings read from a `--config` TOML file. Every field is optional; CLI
+/// flags and environment variables take precedence over anything set here.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    pub bind: Option<String>,
+    pub port: Option<u16>,
+    pub unix_socket: Option<PathBuf>,
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    pub max_file_size: Option<u64>,
+    pub max_projects: Option<usize>,
+    pub max_blocking: Option<usize>,
+    pub rate_limit: Option<f64>,
+    pub rate_burst: Option<f64>,
+    pub response_token_budget: Option<usize>,
+    pub compression: Option<bool>,
+    pub compression_min_bytes: Option<u16>,
+    pub log_format: Option<LogFormat>,
+    /// `.gitignore`-style patterns for generated files, relative to each
+    /// project root. Config file only.
+    pub generated: Option<Vec<String>>,
+    /// Skip `DEFAULT_IGNORE_DIRS` and `DEFAULT_IGNORE_FILES` (default on).
+    pub use_default_ignores: Option<bool>,
+    /// `.gitignore`-style patterns to exclude from every project, on top of
+    /// the defaults. Config file only.
+    pub ignore: Option<Vec<String>>,
+    /// `.gitignore`-style patterns to index even though a default would
+    /// exclude them, e.g. `Cargo.lock` or `vendor/`. Config file only.
+    pub allow: Option<Vec<String>>,
+    pub text_index: Option<bool>,
+    pub git_binary: Option<PathBuf>,
+    /// Allow `POST /query` to run caller-supplied tree-sitter queries
+    /// (default on).
+    pub raw_queries: Option<bool>,
+    /// Secret that `POST /admin/shutdown` must be sent in `X-Admin-Token`.
+    /// Without one the endpoint is disabled.
+    pub admin_token: Option<String>,
+}
+
+impl FileConfig {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        toml::from_str(&text)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))
+    }
+}
+
+/// Where the server accepts connections.
+#[derive(Debug, Clone)]
+pub enum Listen {
+    Tcp {
+        bind: String,
+        port: u16,
+        tls: Option<TlsPaths>,
+    },
+    Unix(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+pub struct TlsPaths {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// Concurrency and rate limits. A `rate_limit` of 0 disables per-session
+/// rate limiting, and a `response_token_budget` of 0 disables budget
+/// warnings.
+#[derive(Debug, Clone)]
+pub struct LimitsConfig {
+    pub max_blocking: usize,
+    pub rate_limit: f64,
+    pub rate_burst: f64,
+    pub response_token_budget: usize,
+}
+
+/// How log lines are written to stderr.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, for log ingestion
+    Json,
+}
+
+/// Response compression (gzip, deflate, zstd, negotiated via Accept-Encoding).
+#[derive(Debug, Clone)]
+pub struct CompressionConfig {
+    pub enabled: bool,
+    pub min_bytes: u16,
+}
+
+/// Which paths are left out of every project's file tree, beyond
+/// `.gitignore`. `ignore` wins over `allow`, and `allow` wins over the
+/// defaults.
+#[derive(Debug, Clone)]
+pub struct IgnoreConfig {
+    pub use_defaults: bool,
+    pub ignore: Vec<String>,
+    pub allow: Vec<String>,
+}
+
+impl Default for IgnoreConfig {
+    fn default() -> Self {
+        Self {
+            use_defaults: true,
+            ignore: Vec::new(),
+            allow: Vec::new(),
+        }
+    }
+}
+
+/// Fully resolved server settings.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub listen: Listen,
+    pub max_file_size: u64,
+    pub max_projects: usize,
+    pub limits: LimitsConfig,
+    pub compression: CompressionConfig,
+    pub log_format: LogFormat,
+    pub generated_patterns: Vec<String>,
+    pub ignore: IgnoreConfig,
+    /// Keep a trigram index of each project's text to speed up grep.
+    pub text_index: bool,
+    /// Git executable for the read-only `/git/*` endpoints.
+    pub git_binary: PathBuf,
+    /// Whether `POST /query` accepts caller-supplied tree-sitter queries.
+    pub raw_queries: bool,
+    pub admin_token: Option<String>,
+}
+
+impl ServerConfig {
+    /// Merge CLI/env values (`cli`) over the config file (`file`) and apply
+    /// defaults. Rejects combinations that can't be served together.
+    pub fn resolve(cli: FileConfig, file: FileConfig) -> anyhow::Result<Self> {
+        let bind = cli.bind.or(file.bind);
+        let port = cli.port.or(file.port);
+        let unix_socket = cli.unix_socket.or(file.unix_socket);
+        let tls_cert = cli.tls_cert.or(file.tls_cert);
+        let tls_key = cli.tls_key.or(file.tls_key);
+
+        let tls = match (tls_cert, tls_key) {
+            (Some(cert), Some(key)) => Some(TlsPaths { cert, key }),
+            (None, None) => None,
+            _ => anyhow::bail!("TLS needs both tls_cert and tls_key"),
+        };
+
+        let listen = match unix_socket {
+            Some(path) => {
+                if bind.is_some() || port.is_some() || tls.is_some() {
+                    anyhow::bail!(
+                        "unix_socket is mutually exclusive with bind, port, and TLS settings"
+                    );
+                }
+                Listen::Unix(path)
+            }
+            None => Listen::Tcp {
+                bind: bind.unwrap_or_else(|| DEFAULT_BIND.to_string()),
+                port: port.unwrap_or(DEFAULT_PORT),
+                tls,
+            },
+        };
+
+        Ok(Self {
+            listen,
+            max_file_size: cli
+                .max_file_size
+                .or(file.max_file_size)
+                .unwrap_or(DEFAULT_MAX_FILE_SIZE),
+            max_projects: cli
+                .max_projects
+                .or(file.max_projects)
+                .unwrap_or(DEFAULT_MAX_PROJECTS),
+            limits: LimitsConfig {
+                max_blocking: cli
+                    .max_blocking
+                    .or(file.max_blocking)
+                    .unwrap_or(DEFAULT_MAX_BLOCKING),
+                rate_limit: cli
+                    .rate_limit
+                    .or(file.rate_limit)
+                    .unwrap_or(DEFAULT_RATE_LIMIT),
+                rate_burst: cli
+                    .rate_burst
+                    .or(file.rate_burst)
+                    .unwrap_or(DEFAULT_RATE_BURST),
+                response_token_budget: cli
+                    .response_token_budget
+                    .or(file.response_token_budget)
+                    .unwrap_or(DEFAULT_RESPONSE_TOKEN_BUDGET),
+            },
+            compression: CompressionConfig {
+                enabled: cli.compression.or(file.compression).unwrap_or(true),
+                min_bytes: cli
+                    .compression_min_bytes
+                    .or(file.compression_min_bytes)
+                    .unwrap_or(DEFAULT_COMPRESSION_MIN_BYTES),
+            },
+            log_format: cli.log_format.or(file.log_format).unwrap_or_default(),
+            generated_patterns: cli.generated.or(file.generated).unwrap_or_default(),
+            ignore: IgnoreConfig {
+                use_defaults: cli
+                    .use_default_ignores
+                    .or(file.use_default_ignores)
+                    .unwrap_or(true),
+                ignore: cli.ignore.or(file.ignore).unwrap_or_default(),
+                allow: cli.allow.or(file.allow).unwrap_or_default(),
+            },
+            text_index: cli.text_index.or(file.text_index).unwrap_or(false),
+            git_binary: cli
+                .git_binary
+                .or(file.git_binary)
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_GIT_BINARY)),
+            raw_queries: cli.raw_queries.or(file.raw_queries).unwrap_or(true),
+            admin_token: cli.admin_token.or(file.admin_token).filter(|t| !t.is_empty()),
+        })
+    }
 }
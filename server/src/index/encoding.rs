@@ -0,0 +1,148 @@
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// How a file's bytes differ from the text the index works on. Every read of
+/// a source file goes through `read_file`, so symbol byte ranges, line
+/// numbers, and peeked text all refer to the same normalized text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct SourceEncoding {
+    /// A UTF-8 byte order mark was stripped from the start.
+    pub bom: bool,
+    /// CRLF line endings were converted to LF.
+    pub crlf: bool,
+    /// The file is not valid UTF-8 and was decoded as Latin-1.
+    pub latin1: bool,
+}
+
+impl SourceEncoding {
+    /// Whether the normalized text differs from the bytes on disk.
+    pub fn is_normalized(&self) -> bool {
+        self.bom || self.crlf || self.latin1
+    }
+
+    /// `Some` only when the file needed normalizing.
+    pub fn note(self) -> Option<Self> {
+        self.is_normalized().then_some(self)
+    }
+
+    /// Inspect `bytes` without decoding them.
+    pub fn detect(bytes: &[u8]) -> Self {
+        let body = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+        Self {
+            bom: body.len() != bytes.len(),
+            crlf: body.windows(2).any(|w| w == b"\r\n"),
+            latin1: std::str::from_utf8(body).is_err(),
+        }
+    }
+}
+
+/// Turn raw file bytes into LF-terminated text: strip a UTF-8 BOM, decode
+/// invalid UTF-8 as Latin-1 (every byte maps to one character), and convert
+/// CRLF to LF. Lone `\r` is kept.
+pub fn decode(bytes: Vec<u8>) -> (String, SourceEncoding) {
+    let encoding = SourceEncoding::detect(&bytes);
+    let body = if encoding.bom {
+        bytes[UTF8_BOM.len()..].to_vec()
+    } else {
+        bytes
+    };
+    let text = match String::from_utf8(body) {
+        Ok(text) => text,
+        Err(e) => e.into_bytes().iter().map(|&b| b as char).collect(),
+    };
+    let text = if encoding.crlf {
+        text.replace("\r\n", "\n")
+    } else {
+        text
+    };
+    (text, encoding)
+}
+
+/// Read and normalize a source file.
+pub fn read_file(path: impl AsRef<Path>) -> io::Result<(String, SourceEncoding)> {
+    std::fs::read(path).map(decode)
+}
+
+/// Read and normalize a source file, dropping the encoding note.
+pub fn read_to_string(path: impl AsRef<Path>) -> io::Result<String> {
+    read_file(path).map(|(text, _)| text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::file_entry::Language;
+    use crate::server::cancel::CancelToken;
+    use crate::server::state::temp_project;
+    use crate::symbols::parser;
+
+    #[test]
+    fn plain_utf8_is_untouched() {
+        let (text, encoding) = decode("fn main() {}\n".into());
+        assert_eq!(text, "fn main() {}\n");
+        assert_eq!(encoding, SourceEncoding::default());
+        assert_eq!(encoding.note(), None);
+    }
+
+    #[test]
+    fn bom_is_stripped() {
+        let (text, encoding) = decode(b"\xEF\xBB\xBFfn main() {}\n".to_vec());
+        assert_eq!(text, "fn main() {}\n");
+        assert!(encoding.bom && !encoding.crlf && !encoding.latin1);
+        assert_eq!(encoding.note(), Some(encoding));
+    }
+
+    #[test]
+    fn crlf_becomes_lf_and_lone_cr_is_kept() {
+        let (text, encoding) = decode(b"a\r\nb\rc\r\n".to_vec());
+        assert_eq!(text, "a\nb\rc\n");
+        assert!(encoding.crlf && !encoding.bom && !encoding.latin1);
+    }
+
+    #[test]
+    fn invalid_utf8_is_decoded_as_latin1() {
+        // "café" and "ñ" in Latin-1.
+        let (text, encoding) = decode(b"caf\xE9 \xF1\n".to_vec());
+        assert_eq!(text, "café ñ\n");
+        assert!(encoding.latin1 && !encoding.bom && !encoding.crlf);
+    }
+
+    #[test]
+    fn every_issue_at_once() {
+        let (text, encoding) = decode(b"\xEF\xBB\xBFx = '\xE9'\r\ny = 1\r\n".to_vec());
+        assert_eq!(text, "x = 'é'\ny = 1\n");
+        assert!(encoding.bom && encoding.crlf && encoding.latin1);
+    }
+
+    #[test]
+    fn symbol_ranges_index_the_normalized_text() {
+        let dir = temp_project(&[]);
+        let fixtures: [(&str, &[u8]); 3] = [
+            ("bom.rs", b"\xEF\xBB\xBFpub fn bom() {}\n"),
+            ("crlf.rs", b"// one\r\n// two\r\npub fn crlf() {}\r\n"),
+            ("latin1.rs", b"// caf\xE9\npub fn latin1() {}\n"),
+        ];
+        for (file, bytes) in fixtures {
+            std::fs::write(dir.join(file), bytes).unwrap();
+            let (text, encoding) = read_file(dir.join(file)).unwrap();
+            assert!(encoding.is_normalized(), "{}", file);
+
+            let (symbols, source) =
+                parser::extract_symbols_from_file(&dir, file, Language::Rust, &CancelToken::default())
+                    .unwrap();
+            assert_eq!(source.encoding, encoding);
+            let name = file.trim_end_matches(".rs");
+            let sym = symbols.iter().find(|s| s.name == name).unwrap();
+            let (start, end) = sym.byte_range;
+            assert_eq!(&text[start..end], format!("pub fn {}() {{}}", name), "{}", file);
+            let line = text.lines().nth(sym.line_range.0 - 1).unwrap();
+            assert!(line.starts_with("pub fn"), "{}", file);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,8 +1,11 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
+use utoipa::ToSchema;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+use super::encoding::SourceEncoding;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum Language {
     Rust,
@@ -102,10 +105,22 @@ pub struct FileEntry {
     pub language: Language,
     /// Agent-set human-readable definition of what this file does.
     pub definition: Option<String>,
+    /// `definition` was synthesized by `/structure/auto_define` rather than
+    /// written by an agent, so define may replace it and it can be regenerated.
+    #[serde(default)]
+    pub definition_auto: bool,
     /// Agent-set marks for categorization.
     pub marks: Vec<FileMark>,
     /// Whether symbols have been extracted from this file.
     pub symbols_extracted: bool,
+    /// Set when extraction found a BOM, CRLF line endings, or non-UTF-8
+    /// bytes and worked on normalized text instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<SourceEncoding>,
+    /// `snapshot::content_hash` of the bytes symbols were last extracted
+    /// from; `None` until extraction succeeds.
+    #[serde(skip)]
+    pub content_hash: Option<String>,
 }
 
 impl FileEntry {
@@ -117,8 +132,11 @@ impl FileEntry {
             modified,
             language,
             definition: None,
+            definition_auto: false,
             marks: Vec::new(),
             symbols_extracted: false,
+            encoding: None,
+            content_hash: None,
         }
     }
 }
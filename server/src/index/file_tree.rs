@@ -1,12 +1,50 @@
 use dashmap::DashMap;
+use ignore::gitignore::Gitignore;
 use serde::Serialize;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::sync::OnceLock;
 
-use super::file_entry::{FileEntry, Language};
+use super::encoding;
+use super::file_entry::{FileEntry, FileMark, Language};
+use super::generated;
+use super::ignore_rules::IgnoreRules;
+use super::rel_path;
+use super::text_index::{TextIndex, TextIndexStats};
+use crate::config::{IgnoreConfig, TEXT_INDEX_MAX_FILE_BYTES};
 
 /// Thread-safe file tree backed by a DashMap for concurrent access.
 pub struct FileTree {
     pub files: DashMap<String, FileEntry>,
+    /// Configured patterns for generated files; matches are marked
+    /// `generated` as they are inserted.
+    generated: Option<Gitignore>,
+    /// Default and configured exclusions consulted by the walker, the
+    /// watcher, and partial re-indexing.
+    ignore_rules: IgnoreRules,
+    /// Trigram index consulted by grep, when enabled at startup or later
+    /// through `enable_text_index`. A file's entry is dropped whenever the
+    /// file is inserted or removed and rebuilt by `index_text_file`.
+    text_index: OnceLock<TextIndex>,
+}
+
+/// Selects files by their marks: those carrying `include` (every file when
+/// unset), minus those carrying `exclude`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MarkFilter {
+    pub include: Option<FileMark>,
+    pub exclude: Option<FileMark>,
+}
+
+impl MarkFilter {
+    pub fn is_empty(&self) -> bool {
+        self.include.is_none() && self.exclude.is_none()
+    }
+
+    pub fn allows(&self, entry: &FileEntry) -> bool {
+        self.include.is_none_or(|mark| entry.marks.contains(&mark))
+            && self.exclude.is_none_or(|mark| !entry.marks.contains(&mark))
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -19,29 +57,122 @@ impl FileTree {
     pub fn new() -> Self {
         Self {
             files: DashMap::new(),
+            generated: None,
+            ignore_rules: IgnoreRules::default(),
+            text_index: OnceLock::new(),
+        }
+    }
+
+    /// Mark files matching `patterns` (`.gitignore` syntax, relative to
+    /// `root`) as generated when they are inserted.
+    pub fn with_generated_patterns(mut self, root: &Path, patterns: &[String]) -> Self {
+        self.generated = generated::compile_patterns(root, patterns);
+        self
+    }
+
+    /// Leave paths out of the tree per `config` (patterns relative to
+    /// `root`) instead of the built-in defaults alone.
+    pub fn with_ignore_rules(mut self, root: &Path, config: &IgnoreConfig) -> Self {
+        self.ignore_rules = IgnoreRules::new(root, config);
+        self
+    }
+
+    pub fn ignore_rules(&self) -> &IgnoreRules {
+        &self.ignore_rules
+    }
+
+    /// Keep a trigram index of file text for grep to prefilter with. It rules
+    /// nothing out until `build_text_index` has run.
+    pub fn with_text_index(self, enabled: bool) -> Self {
+        if enabled {
+            self.enable_text_index();
+        }
+        self
+    }
+
+    pub fn text_index(&self) -> Option<&TextIndex> {
+        self.text_index.get()
+    }
+
+    /// Attach an empty trigram index if there isn't one yet. Like one set up
+    /// at startup, it rules nothing out until `build_text_index` has run.
+    pub fn enable_text_index(&self) -> &TextIndex {
+        self.text_index.get_or_init(TextIndex::new)
+    }
+
+    /// Index the text of every eligible file and start using the index.
+    pub fn build_text_index(&self, root: &Path) -> Option<TextIndexStats> {
+        let index = self.text_index.get()?;
+        for path in self.all_paths() {
+            self.index_text_file(root, &path);
+        }
+        let stats = index.mark_built();
+        tracing::info!(
+            "Text index for {}: {} files, {} distinct trigrams",
+            root.display(),
+            stats.files,
+            stats.trigrams
+        );
+        Some(stats)
+    }
+
+    /// Re-read `rel_path` into the text index. PDFs, files over
+    /// `TEXT_INDEX_MAX_FILE_BYTES`, and unreadable files stay out of it, so
+    /// grep always scans them.
+    pub fn index_text_file(&self, root: &Path, rel_path: &str) {
+        let Some(index) = self.text_index.get() else {
+            return;
+        };
+        let Some(entry) = self.get(rel_path) else {
+            return;
+        };
+        if entry.language == Language::Pdf || entry.size > TEXT_INDEX_MAX_FILE_BYTES {
+            return;
+        }
+        if let Ok(source) = encoding::read_to_string(root.join(rel_path)) {
+            index.index_file(rel_path, &source);
         }
     }
 
-    pub fn insert(&self, entry: FileEntry) {
+    pub fn insert(&self, mut entry: FileEntry) {
+        if let Some(matcher) = &self.generated
+            && matcher
+                .matched_path_or_any_parents(&entry.rel_path, false)
+                .is_ignore()
+            && !entry.marks.contains(&FileMark::Generated)
+        {
+            entry.marks.push(FileMark::Generated);
+        }
+        if let Some(index) = self.text_index.get() {
+            index.remove_file(&entry.rel_path);
+        }
         self.files.insert(entry.rel_path.clone(), entry);
     }
 
     pub fn remove(&self, rel_path: &str) -> Option<FileEntry> {
+        let rel_path = &*rel_path::normalize(rel_path);
+        if let Some(index) = self.text_index.get() {
+            index.remove_file(rel_path);
+        }
         self.files.remove(rel_path).map(|(_, v)| v)
     }
 
+    /// The entry at `rel_path`, given with either separator.
     pub fn get(&self, rel_path: &str) -> Option<FileEntry> {
-        self.files.get(rel_path).map(|r| r.value().clone())
+        self.files.get(&*rel_path::normalize(rel_path)).map(|r| r.value().clone())
     }
 
     pub fn len(&self) -> usize {
         self.files.len()
     }
 
-    pub fn language_breakdown(&self) -> Vec<LanguageBreakdown> {
+    /// Files per language among `paths`, most common first.
+    pub fn language_breakdown(&self, paths: &[String]) -> Vec<LanguageBreakdown> {
         let mut counts: HashMap<Language, usize> = HashMap::new();
-        for entry in self.files.iter() {
-            *counts.entry(entry.value().language).or_insert(0) += 1;
+        for path in paths {
+            if let Some(entry) = self.files.get(path) {
+                *counts.entry(entry.language).or_insert(0) += 1;
+            }
         }
         let mut breakdown: Vec<_> = counts
             .into_iter()
@@ -51,28 +182,42 @@ impl FileTree {
         breakdown
     }
 
+    /// Paths `filter` selects, or `None` when it selects every file.
+    pub fn paths_matching(&self, filter: &MarkFilter) -> Option<HashSet<String>> {
+        if filter.is_empty() {
+            return None;
+        }
+        Some(
+            self.files
+                .iter()
+                .filter(|entry| filter.allows(entry.value()))
+                .map(|entry| entry.key().clone())
+                .collect(),
+        )
+    }
+
     pub fn all_paths(&self) -> Vec<String> {
         self.files.iter().map(|r| r.key().clone()).collect()
     }
+}
 
-    /// Render a tree-like structure string, similar to the `tree` command.
-    /// `depth` limits how many directory levels deep to show (0 = unlimited).
-    pub fn render_tree(&self, depth: usize) -> String {
-        // Collect all paths into a sorted tree structure
-        let mut paths: Vec<String> = self.all_paths();
-        paths.sort();
-
-        // Build a tree from paths
-        let mut root: BTreeMap<String, TreeNode> = BTreeMap::new();
-        for path in &paths {
-            let parts: Vec<&str> = path.split('/').collect();
-            insert_into_tree(&mut root, &parts, 0);
-        }
+/// Render `paths` as a tree-like structure string, similar to the `tree`
+/// command. `depth` limits how many directory levels deep to show
+/// (0 = unlimited).
+pub fn render_paths(paths: &[String], depth: usize) -> String {
+    let mut paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+    paths.sort();
 
-        let mut output = String::new();
-        render_tree_node(&root, &mut output, "", depth, 0);
-        output
+    // Build a tree from paths
+    let mut root: BTreeMap<String, TreeNode> = BTreeMap::new();
+    for path in &paths {
+        let parts: Vec<&str> = path.split('/').collect();
+        insert_into_tree(&mut root, &parts, 0);
     }
+
+    let mut output = String::new();
+    render_tree_node(&root, &mut output, "", depth, 0);
+    output
 }
 
 enum TreeNode {
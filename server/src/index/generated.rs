@@ -0,0 +1,38 @@
+use std::path::Path;
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use tracing::warn;
+
+/// How many leading lines are checked for a generated-code marker.
+const HEADER_LINES: usize = 5;
+
+/// Whether the first few lines carry a generator's marker: `@generated`
+/// (protobuf, Thrift, Buck, many JS tools) or Go's
+/// `Code generated ... DO NOT EDIT.`
+pub fn has_generated_header(source: &str) -> bool {
+    source.lines().take(HEADER_LINES).any(|line| {
+        line.contains("@generated")
+            || (line.contains("Code generated") && line.contains("DO NOT EDIT"))
+    })
+}
+
+/// Compile `.gitignore`-syntax patterns for files to treat as generated.
+/// Invalid patterns are logged and skipped.
+pub fn compile_patterns(root: &Path, patterns: &[String]) -> Option<Gitignore> {
+    if patterns.is_empty() {
+        return None;
+    }
+    let mut builder = GitignoreBuilder::new(root);
+    for pattern in patterns {
+        if let Err(e) = builder.add_line(None, pattern) {
+            warn!("Ignoring invalid generated pattern '{}': {}", pattern, e);
+        }
+    }
+    match builder.build() {
+        Ok(matcher) => Some(matcher),
+        Err(e) => {
+            warn!("Failed to build generated patterns: {}", e);
+            None
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::path::Path;
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use serde::Serialize;
+use tracing::warn;
+use utoipa::ToSchema;
+
+use crate::config::{self, IgnoreConfig};
+
+/// Which rule left a path out of the file tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum IgnoreSource {
+    /// Version control or `.coderlm` state; never indexed.
+    Internal,
+    /// The built-in vendored-directory and lock-file list.
+    Default,
+    /// A configured `ignore` pattern.
+    Config,
+    /// A binary or otherwise unreadable extension.
+    Extension,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct IgnoreReason {
+    pub source: IgnoreSource,
+    /// The pattern that matched, e.g. `vendor/` or `*.lock`.
+    pub pattern: String,
+}
+
+/// Decides which walked paths stay out of a project's file tree, on top of
+/// `.gitignore`. Precedence: internal directories, then configured `ignore`
+/// patterns, then `allow` patterns, then the defaults and extension list.
+pub struct IgnoreRules {
+    /// `None` when `use_default_ignores` is off.
+    defaults: Option<Gitignore>,
+    ignore: Option<Gitignore>,
+    allow: Option<Gitignore>,
+}
+
+impl Default for IgnoreRules {
+    fn default() -> Self {
+        Self::new(Path::new(""), &IgnoreConfig::default())
+    }
+}
+
+impl IgnoreRules {
+    /// Compile `config` against a project `root`. Invalid patterns are
+    /// logged and skipped.
+    pub fn new(root: &Path, config: &IgnoreConfig) -> Self {
+        let defaults = config.use_defaults.then(|| {
+            let patterns: Vec<String> = config::DEFAULT_IGNORE_DIRS
+                .iter()
+                .map(|dir| format!("{}/", dir))
+                .chain(config::DEFAULT_IGNORE_FILES.iter().map(|f| f.to_string()))
+                .collect();
+            compile(root, "default ignore", &patterns)
+        });
+        Self {
+            defaults: defaults.flatten(),
+            ignore: compile(root, "ignore", &config.ignore),
+            allow: compile(root, "allow", &config.allow),
+        }
+    }
+
+    /// Why `rel_path` is left out, or `None` if it is indexed.
+    pub fn reason(&self, rel_path: &str, is_dir: bool) -> Option<IgnoreReason> {
+        if let Some(dir) = rel_path.split('/').find(|c| config::is_internal_dir(c)) {
+            return Some(IgnoreReason {
+                source: IgnoreSource::Internal,
+                pattern: format!("{}/", dir),
+            });
+        }
+        if let Some(pattern) = matched(&self.ignore, rel_path, is_dir) {
+            return Some(IgnoreReason {
+                source: IgnoreSource::Config,
+                pattern,
+            });
+        }
+        if matched(&self.allow, rel_path, is_dir).is_some() {
+            return None;
+        }
+        if let Some(pattern) = matched(&self.defaults, rel_path, is_dir) {
+            return Some(IgnoreReason {
+                source: IgnoreSource::Default,
+                pattern,
+            });
+        }
+        if is_dir {
+            return None;
+        }
+        config::ignored_extension(rel_path).map(|ext| IgnoreReason {
+            source: IgnoreSource::Extension,
+            pattern: format!("*.{}", ext),
+        })
+    }
+
+    /// Whether the file at `rel_path` is left out of the tree.
+    pub fn is_ignored(&self, rel_path: &str) -> bool {
+        self.reason(rel_path, false).is_some()
+    }
+
+    /// Whether an ignored directory can be skipped without looking inside.
+    /// Not when `allow` patterns exist, since one may re-allow a path below
+    /// it.
+    pub fn can_prune(&self) -> bool {
+        self.allow.is_none()
+    }
+}
+
+/// The pattern matching `rel_path` or one of its parent directories.
+fn matched(matcher: &Option<Gitignore>, rel_path: &str, is_dir: bool) -> Option<String> {
+    match matcher.as_ref()?.matched_path_or_any_parents(rel_path, is_dir) {
+        Match::Ignore(glob) => Some(glob.original().to_string()),
+        _ => None,
+    }
+}
+
+fn compile(root: &Path, what: &str, patterns: &[String]) -> Option<Gitignore> {
+    if patterns.is_empty() {
+        return None;
+    }
+    let mut builder = GitignoreBuilder::new(root);
+    for pattern in patterns {
+        if let Err(e) = builder.add_line(None, pattern) {
+            warn!("Ignoring invalid {} pattern '{}': {}", what, pattern, e);
+        }
+    }
+    match builder.build() {
+        Ok(matcher) => Some(matcher),
+        Err(e) => {
+            warn!("Failed to build {} patterns: {}", what, e);
+            None
+        }
+    }
+}
@@ -1,5 +1,10 @@
+pub mod encoding;
 pub mod file_entry;
 pub mod file_tree;
+pub mod generated;
+pub mod ignore_rules;
 pub mod pdf;
+pub mod rel_path;
+pub mod text_index;
 pub mod walker;
 pub mod watcher;
@@ -1,9 +1,31 @@
 use anyhow::{Context, Result};
+use parking_lot::Mutex;
+use std::collections::HashMap;
 use std::fs;
+use std::io::Read;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::LazyLock;
+use std::time::Duration;
 use tracing::debug;
 
+use crate::config;
+use crate::server::cancel::CancelToken;
+
+/// Converter processes currently running, keyed by pid, so shutdown can kill
+/// them instead of leaving orphaned python3 processes behind.
+static CONVERTERS: LazyLock<Mutex<HashMap<u32, Child>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Failed conversions per project root, reported by `/health/detail`.
+static FAILURES: LazyLock<Mutex<HashMap<PathBuf, usize>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Number of PDF conversions that have failed under `root`.
+pub fn failure_count(root: &Path) -> usize {
+    FAILURES.lock().get(root).copied().unwrap_or(0)
+}
+
 /// Returns the cache path for converted PDF markdown: `<root>/.coderlm/converted/<rel_path>.md`
 pub fn cache_path(root: &Path, rel_path: &str) -> PathBuf {
     root.join(".coderlm")
@@ -27,8 +49,18 @@ pub fn get_cached_markdown(root: &Path, rel_path: &str) -> Option<String> {
 }
 
 /// Convert a PDF to markdown using pymupdf4llm, caching the result.
-/// Returns the markdown content.
-pub fn convert_pdf(root: &Path, rel_path: &str) -> Result<String> {
+/// Returns the markdown content. The converter is killed once `cancel`
+/// fires; pass it explicitly, since rayon workers don't see the request's
+/// `cancel::current` token.
+pub fn convert_pdf(root: &Path, rel_path: &str, cancel: &CancelToken) -> Result<String> {
+    let result = convert_uncounted(root, rel_path, cancel);
+    if result.is_err() && !cancel.is_cancelled() {
+        *FAILURES.lock().entry(root.to_path_buf()).or_insert(0) += 1;
+    }
+    result
+}
+
+fn convert_uncounted(root: &Path, rel_path: &str, cancel: &CancelToken) -> Result<String> {
     // Check cache first
     if let Some(cached) = get_cached_markdown(root, rel_path) {
         debug!("Using cached markdown for {}", rel_path);
@@ -42,12 +74,13 @@ pub fn convert_pdf(root: &Path, rel_path: &str) -> Result<String> {
 
     debug!("Converting PDF to markdown: {}", rel_path);
 
-    let output = Command::new("python3")
+    let mut command = Command::new("python3");
+    command
         .arg("-c")
         .arg("import pymupdf4llm, sys; print(pymupdf4llm.to_markdown(sys.argv[1]))")
-        .arg(abs_str)
-        .output()
-        .context("Failed to spawn python3 for PDF conversion — is pymupdf4llm installed?")?;
+        .arg(abs_str);
+    let output = run_tracked(command, cancel)
+        .context("Failed to run python3 for PDF conversion — is pymupdf4llm installed?")?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -69,3 +102,71 @@ pub fn convert_pdf(root: &Path, rel_path: &str) -> Result<String> {
     debug!("Cached converted markdown for {} ({} bytes)", rel_path, markdown.len());
     Ok(markdown)
 }
+
+/// Run a converter to completion while keeping it in `CONVERTERS`, so
+/// `kill_converters` can terminate it mid-run. The converter is killed when
+/// `cancel` fires.
+fn run_tracked(mut command: Command, cancel: &CancelToken) -> Result<Output> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdout = child.stdout.take().context("converter stdout not captured")?;
+    let mut stderr = child.stderr.take().context("converter stderr not captured")?;
+    let pid = child.id();
+    CONVERTERS.lock().insert(pid, child);
+
+    // Drain stderr on a separate thread so a chatty converter can't block on a
+    // full pipe while we wait on stdout.
+    let stderr_reader = std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = stderr.read_to_end(&mut buf);
+        buf
+    });
+    let stdout_reader = std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        let result = stdout.read_to_end(&mut buf);
+        (buf, result)
+    });
+    while !stdout_reader.is_finished() {
+        if cancel.is_cancelled() {
+            if let Some(mut child) = CONVERTERS.lock().remove(&pid) {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+            let _ = stdout_reader.join();
+            let _ = stderr_reader.join();
+            anyhow::bail!("conversion was cancelled");
+        }
+        std::thread::sleep(Duration::from_millis(config::CONVERTER_CANCEL_POLL_MS));
+    }
+    let (out, read_result) = stdout_reader
+        .join()
+        .map_err(|_| anyhow::anyhow!("converter stdout reader panicked"))?;
+    let err = stderr_reader.join().unwrap_or_default();
+
+    let child = CONVERTERS.lock().remove(&pid);
+    let Some(mut child) = child else {
+        anyhow::bail!("converter was cancelled by shutdown");
+    };
+    let status = child.wait()?;
+    read_result?;
+
+    Ok(Output {
+        status,
+        stdout: out,
+        stderr: err,
+    })
+}
+
+/// Kill every running converter process. Returns how many were killed.
+pub fn kill_converters() -> usize {
+    let children: Vec<Child> = CONVERTERS.lock().drain().map(|(_, c)| c).collect();
+    let count = children.len();
+    for mut child in children {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+    count
+}
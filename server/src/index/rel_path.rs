@@ -0,0 +1,94 @@
+use std::borrow::Cow;
+use std::path::Path;
+
+use serde::{Deserialize, Deserializer};
+
+/// The canonical form of a project-relative path: forward slashes only,
+/// no leading `./`, no repeated separators. Index keys are stored this way,
+/// so `server\src\main.rs`, `./server/src/main.rs` and `server//src/main.rs`
+/// all name `server/src/main.rs`. A trailing slash is kept, since callers
+/// matching directory prefixes rely on it.
+pub fn normalize(path: &str) -> Cow<'_, str> {
+    if !path.contains('\\') && !path.contains("//") && !path.starts_with("./") {
+        return Cow::Borrowed(path);
+    }
+    let path = path.replace('\\', "/");
+    let mut rest = path.as_str();
+    while let Some(stripped) = rest.strip_prefix("./") {
+        rest = stripped.trim_start_matches('/');
+    }
+    let mut out = String::with_capacity(rest.len());
+    for c in rest.chars() {
+        if c == '/' && out.ends_with('/') {
+            continue;
+        }
+        out.push(c);
+    }
+    Cow::Owned(out)
+}
+
+/// `path` relative to `root`, normalized, or None when it lies outside.
+pub fn relative_to(path: &Path, root: &Path) -> Option<String> {
+    let rel = path.strip_prefix(root).ok()?;
+    Some(normalize(&rel.to_string_lossy()).into_owned())
+}
+
+/// `deserialize_with` helper normalizing a path parameter.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    String::deserialize(deserializer).map(|path| normalize(&path).into_owned())
+}
+
+/// `deserialize`, for an optional parameter; pair it with `default`.
+pub fn deserialize_option<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    Option::<String>::deserialize(deserializer)
+        .map(|path| path.map(|path| normalize(&path).into_owned()))
+}
+
+/// `deserialize`, for a list of paths.
+pub fn deserialize_vec<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    Vec::<String>::deserialize(deserializer)
+        .map(|paths| paths.iter().map(|path| normalize(path).into_owned()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backslashes_become_forward_slashes() {
+        assert_eq!(normalize(r"a\b/c"), "a/b/c");
+        assert_eq!(normalize(r"server\src\main.rs"), "server/src/main.rs");
+    }
+
+    #[test]
+    fn leading_dot_and_repeated_separators_are_dropped() {
+        assert_eq!(normalize("./a//b"), "a/b");
+        assert_eq!(normalize(".//./a///b"), "a/b");
+        assert_eq!(normalize(r".\a\\b"), "a/b");
+    }
+
+    #[test]
+    fn trailing_slash_is_kept() {
+        assert_eq!(normalize("a/b/"), "a/b/");
+        assert_eq!(normalize("a//b//"), "a/b/");
+        assert_eq!(normalize(r"a\b\"), "a/b/");
+    }
+
+    #[test]
+    fn canonical_paths_are_borrowed() {
+        assert!(matches!(normalize("src/main.rs"), Cow::Borrowed("src/main.rs")));
+        assert!(matches!(normalize("a/./b"), Cow::Borrowed(_)));
+        assert!(matches!(normalize(r"a\b"), Cow::Owned(_)));
+    }
+
+    #[test]
+    fn relative_to_strips_the_root() {
+        let root = Path::new("/repo");
+        assert_eq!(relative_to(Path::new("/repo/src/lib.rs"), root).as_deref(), Some("src/lib.rs"));
+        assert_eq!(relative_to(Path::new("/other/lib.rs"), root), None);
+    }
+}
@@ -0,0 +1,385 @@
+use std::collections::{HashMap, HashSet};
+
+use parking_lot::RwLock;
+
+/// Three bytes of file text, ASCII-lowercased.
+type Trigram = [u8; 3];
+
+/// In-memory inverted index from ASCII-lowercased trigrams to the files
+/// containing them. Grep uses it to skip files that cannot match a pattern's
+/// literal text. Files that are not indexed (too large, unreadable, changed since the
+/// last update) are never ruled out.
+pub struct TextIndex {
+    inner: RwLock<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Set once the initial build has finished; until then nothing is ruled
+    /// out.
+    built: bool,
+    ids: HashMap<String, u32>,
+    /// Trigrams of each file, so it can be taken out of `postings` again.
+    file_trigrams: HashMap<u32, Vec<Trigram>>,
+    postings: HashMap<Trigram, HashSet<u32>>,
+    next_id: u32,
+}
+
+/// Index size, for logging and `/admin/build_trigram_index`.
+#[derive(Debug, Clone, Copy)]
+pub struct TextIndexStats {
+    pub files: usize,
+    pub trigrams: usize,
+}
+
+impl TextIndex {
+    pub fn new() -> Self {
+        Self {
+            inner: RwLock::new(Inner::default()),
+        }
+    }
+
+    /// Replace the indexed text of `path` with `source`.
+    pub fn index_file(&self, path: &str, source: &str) {
+        let trigrams: Vec<Trigram> =
+            trigrams(&source.to_ascii_lowercase()).into_iter().collect();
+        let mut inner = self.inner.write();
+        inner.remove(path);
+        let id = inner.next_id;
+        inner.next_id += 1;
+        for trigram in &trigrams {
+            inner.postings.entry(*trigram).or_default().insert(id);
+        }
+        inner.ids.insert(path.to_string(), id);
+        inner.file_trigrams.insert(id, trigrams);
+    }
+
+    /// Forget `path`; grep scans it unconditionally until it is indexed again.
+    pub fn remove_file(&self, path: &str) {
+        self.inner.write().remove(path);
+    }
+
+    /// Drop everything, so grep scans every file until the next build.
+    pub fn clear(&self) {
+        *self.inner.write() = Inner::default();
+    }
+
+    pub fn mark_built(&self) -> TextIndexStats {
+        let mut inner = self.inner.write();
+        inner.built = true;
+        TextIndexStats {
+            files: inner.ids.len(),
+            trigrams: inner.postings.len(),
+        }
+    }
+
+    /// Drop from `items` the files that cannot match `pattern`: indexed files
+    /// missing one of the trigrams of its required literal text. Files not in
+    /// the index are kept. Returns false, leaving `items` alone, when the
+    /// index can't narrow the search (not built yet, or no usable literal).
+    pub fn retain_candidates<T>(
+        &self,
+        pattern: &str,
+        items: &mut Vec<T>,
+        path: impl Fn(&T) -> &str,
+    ) -> bool {
+        let Some(required) = required_trigrams(pattern) else {
+            return false;
+        };
+        let inner = self.inner.read();
+        if !inner.built {
+            return false;
+        }
+
+        let mut candidates: Option<HashSet<u32>> = None;
+        for trigram in &required {
+            let Some(ids) = inner.postings.get(trigram) else {
+                candidates = Some(HashSet::new());
+                break;
+            };
+            candidates = Some(match candidates {
+                None => ids.clone(),
+                Some(current) => current.intersection(ids).copied().collect(),
+            });
+        }
+        let candidates = candidates.unwrap_or_default();
+
+        items.retain(|item| match inner.ids.get(path(item)) {
+            Some(id) => candidates.contains(id),
+            None => true,
+        });
+        true
+    }
+}
+
+impl Inner {
+    fn remove(&mut self, path: &str) {
+        let Some(id) = self.ids.remove(path) else {
+            return;
+        };
+        for trigram in self.file_trigrams.remove(&id).unwrap_or_default() {
+            if let Some(ids) = self.postings.get_mut(&trigram) {
+                ids.remove(&id);
+                if ids.is_empty() {
+                    self.postings.remove(&trigram);
+                }
+            }
+        }
+    }
+}
+
+fn trigrams(text: &str) -> HashSet<Trigram> {
+    text.as_bytes()
+        .windows(3)
+        .map(|w| [w[0], w[1], w[2]])
+        .collect()
+}
+
+/// Trigrams that any match of `pattern` must contain, lowercased. Only ASCII
+/// literal runs outside groups, classes, and optional atoms are used, so the
+/// result is conservative; non-ASCII text can match case-insensitively in
+/// ways a byte trigram doesn't capture. `None` when no run is three bytes
+/// long, or when the pattern uses alternation or verbose mode, where literals
+/// aren't reliably required.
+fn required_trigrams(pattern: &str) -> Option<HashSet<Trigram>> {
+    if pattern.contains('|') || has_verbose_flag(pattern) {
+        return None;
+    }
+
+    let mut runs: Vec<String> = Vec::new();
+    let mut run = String::new();
+    let mut depth = 0usize;
+    let mut chars = pattern.chars().peekable();
+    let end_run = |run: &mut String, runs: &mut Vec<String>| {
+        if !run.is_empty() {
+            runs.push(std::mem::take(run));
+        }
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(e) if e.is_ascii_alphanumeric() => {
+                    // \d, \w, \b, \x41, \p{L}, ...: not plain text
+                    end_run(&mut run, &mut runs);
+                    if chars.peek() == Some(&'{') {
+                        for skipped in chars.by_ref() {
+                            if skipped == '}' {
+                                break;
+                            }
+                        }
+                    }
+                }
+                Some(e) if depth == 0 && e.is_ascii() => run.push(e.to_ascii_lowercase()),
+                _ => end_run(&mut run, &mut runs),
+            },
+            '(' => {
+                end_run(&mut run, &mut runs);
+                depth += 1;
+            }
+            ')' => {
+                end_run(&mut run, &mut runs);
+                depth = depth.saturating_sub(1);
+            }
+            '[' => {
+                end_run(&mut run, &mut runs);
+                skip_class(&mut chars);
+            }
+            '?' | '*' | '{' => {
+                // The previous atom may be absent.
+                run.pop();
+                end_run(&mut run, &mut runs);
+                if c == '{' {
+                    for skipped in chars.by_ref() {
+                        if skipped == '}' {
+                            break;
+                        }
+                    }
+                }
+            }
+            '+' | '.' | '^' | '$' => end_run(&mut run, &mut runs),
+            _ if depth == 0 && c.is_ascii() => run.push(c.to_ascii_lowercase()),
+            _ => end_run(&mut run, &mut runs),
+        }
+    }
+    end_run(&mut run, &mut runs);
+
+    let required: HashSet<Trigram> = runs.iter().flat_map(|r| trigrams(r)).collect();
+    (!required.is_empty()).then_some(required)
+}
+
+/// Consume a character class body up to its closing `]`.
+fn skip_class(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) {
+    let mut nested = 0usize;
+    let mut first = true;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '^' if first => continue,
+            ']' if first => {}
+            '[' => nested += 1,
+            ']' if nested > 0 => nested -= 1,
+            ']' => return,
+            _ => {}
+        }
+        first = false;
+    }
+}
+
+/// Whether the pattern turns on `x` (ignore whitespace) in any flag group.
+fn has_verbose_flag(pattern: &str) -> bool {
+    pattern.match_indices("(?").any(|(i, _)| {
+        pattern[i + 2..]
+            .chars()
+            .take_while(|c| c.is_ascii_alphabetic() || *c == '-')
+            .take_while(|c| *c != '-')
+            .any(|c| c == 'x')
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    fn required(pattern: &str) -> Option<Vec<String>> {
+        let mut trigrams: Vec<String> = required_trigrams(pattern)?
+            .into_iter()
+            .map(|t| String::from_utf8(t.to_vec()).unwrap())
+            .collect();
+        trigrams.sort();
+        Some(trigrams)
+    }
+
+    fn built(files: &[(&str, &str)]) -> TextIndex {
+        let index = TextIndex::new();
+        for (path, source) in files {
+            index.index_file(path, source);
+        }
+        index.mark_built();
+        index
+    }
+
+    fn candidates(index: &TextIndex, pattern: &str, paths: &[&str]) -> Option<Vec<String>> {
+        let mut items: Vec<String> = paths.iter().map(|p| p.to_string()).collect();
+        index
+            .retain_candidates(pattern, &mut items, |p| p.as_str())
+            .then_some(items)
+    }
+
+    #[test]
+    fn literal_runs_give_lowercased_trigrams() {
+        assert_eq!(required("Needle").unwrap(), ["dle", "edl", "eed", "nee"]);
+        assert_eq!(required(r"\bword\b").unwrap(), ["ord", "wor"]);
+        assert_eq!(required(r"foo\.bar").unwrap(), [".ba", "bar", "foo", "o.b", "oo."]);
+    }
+
+    #[test]
+    fn optional_atoms_groups_and_classes_are_not_required() {
+        // `o?` may be absent, so only "obar" is literal.
+        assert_eq!(required("fo?obar").unwrap(), ["bar", "oba"]);
+        assert_eq!(required("[abc]def").unwrap(), ["def"]);
+        assert_eq!(required("(xyz)?abcd").unwrap(), ["abc", "bcd"]);
+        assert_eq!(required(r"abc\d+xyz").unwrap(), ["abc", "xyz"]);
+        assert_eq!(required("ab.*cde").unwrap(), ["cde"]);
+    }
+
+    #[test]
+    fn unfilterable_patterns_fall_back_to_a_full_scan() {
+        assert_eq!(required("ab"), None);
+        assert_eq!(required("foo|bar"), None);
+        assert_eq!(required("(?x)foo bar"), None);
+        assert_eq!(required(r"\w+\s*\d"), None);
+        assert_eq!(required("a.b.c"), None);
+    }
+
+    #[test]
+    fn unbuilt_index_rules_nothing_out() {
+        let index = TextIndex::new();
+        index.index_file("a.rs", "needle");
+        assert_eq!(candidates(&index, "needle", &["a.rs", "b.rs"]), None);
+    }
+
+    #[test]
+    fn candidates_keep_matching_and_unindexed_files() {
+        let index = built(&[("a.rs", "let needle = 1;"), ("b.rs", "let haystack = 2;")]);
+        assert_eq!(
+            candidates(&index, "needle", &["a.rs", "b.rs", "new.rs"]).unwrap(),
+            ["a.rs", "new.rs"]
+        );
+        // A pattern too short to filter leaves the list alone.
+        assert_eq!(candidates(&index, "ne", &["a.rs", "b.rs"]), None);
+    }
+
+    #[test]
+    fn reindex_and_remove_update_postings() {
+        let index = built(&[("a.rs", "needle"), ("b.rs", "other")]);
+        index.index_file("a.rs", "nothing here");
+        index.index_file("b.rs", "a needle now");
+        assert_eq!(candidates(&index, "needle", &["a.rs", "b.rs"]).unwrap(), ["b.rs"]);
+
+        // A removed file is scanned again rather than ruled out.
+        index.remove_file("a.rs");
+        assert_eq!(candidates(&index, "needle", &["a.rs", "b.rs"]).unwrap(), ["a.rs", "b.rs"]);
+        let inner = index.inner.read();
+        assert!(inner.postings.values().all(|ids| !ids.is_empty()));
+        assert!(!inner.postings.contains_key(b"thi"));
+    }
+
+    #[test]
+    fn prefilter_never_drops_a_matching_file() {
+        let files = [
+            ("a.rs", "fn parse_args() -> Args { Args::default() }"),
+            ("b.rs", "struct Parser; impl Parser { fn PARSE(&self) {} }"),
+            ("c.rs", "// nothing to see"),
+            ("d.rs", "let x = foo.bar(baz);"),
+        ];
+        let index = built(&files);
+        let paths: Vec<&str> = files.iter().map(|(p, _)| *p).collect();
+        for pattern in [
+            "parse", "(?i)parse", "Parser", r"parse_\w+", "foo\\.bar", "fo?o.bar", "[Pp]arser",
+            r"Args::default\(\)", "nothing", "missing_text",
+        ] {
+            let re = Regex::new(pattern).unwrap();
+            let kept = candidates(&index, pattern, &paths)
+                .unwrap_or_else(|| paths.iter().map(|p| p.to_string()).collect());
+            for (path, source) in &files {
+                if re.is_match(source) {
+                    assert!(kept.iter().any(|k| k == path), "{} dropped {}", pattern, path);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn candidate_set_shrinks_on_a_fixture_repo() {
+        let files: Vec<(String, String)> = (0..200)
+            .map(|i| {
+                let body = if i % 50 == 0 {
+                    format!("fn handler_{}() {{ rate_limiter.acquire(); }}", i)
+                } else {
+                    format!("fn helper_{}() {{ compute({}); }}", i, i)
+                };
+                (format!("src/m{}.rs", i), body)
+            })
+            .collect();
+        let index = TextIndex::new();
+        for (path, source) in &files {
+            index.index_file(path, source);
+        }
+        let stats = index.mark_built();
+        let paths: Vec<&str> = files.iter().map(|(p, _)| p.as_str()).collect();
+
+        let kept = candidates(&index, r"rate_limiter\.acquire", &paths).unwrap();
+        println!(
+            "text index: {} files, {} trigrams; rate_limiter.acquire keeps {} of {}",
+            stats.files,
+            stats.trigrams,
+            kept.len(),
+            paths.len()
+        );
+        assert_eq!(kept, ["src/m0.rs", "src/m50.rs", "src/m100.rs", "src/m150.rs"]);
+    }
+}
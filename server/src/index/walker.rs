@@ -1,22 +1,40 @@
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use ignore::WalkBuilder;
+use serde::Serialize;
+use std::collections::BTreeMap;
 use std::path::Path;
 use std::sync::Arc;
 use tracing::info;
+use utoipa::ToSchema;
 
-use crate::config;
 use crate::index::file_entry::FileEntry;
 use crate::index::file_tree::FileTree;
+use crate::index::ignore_rules::{IgnoreReason, IgnoreSource};
+use crate::index::rel_path;
 
 /// Scan the codebase directory using the `ignore` crate (respects .gitignore)
-/// plus our built-in ignore patterns. Returns the number of files indexed.
+/// plus the tree's ignore rules. Returns the number of files indexed.
 pub fn scan_directory(root: &Path, file_tree: &Arc<FileTree>, max_file_size: u64) -> Result<usize> {
-    let walker = WalkBuilder::new(root)
-        .hidden(true) // skip dotfiles by default
-        .git_ignore(true) // respect .gitignore
-        .git_global(true)
-        .git_exclude(true)
+    let prune_root = root.to_path_buf();
+    let prune_tree = file_tree.clone();
+    let walker = base_walker(root)
+        .filter_entry(move |entry| {
+            // Don't descend into ignored directories unless an allow pattern
+            // might re-include something below them.
+            if !entry.file_type().is_some_and(|ft| ft.is_dir())
+                || !prune_tree.ignore_rules().can_prune()
+            {
+                return true;
+            }
+            match entry.path().strip_prefix(&prune_root) {
+                Ok(rel) if !rel.as_os_str().is_empty() => prune_tree
+                    .ignore_rules()
+                    .reason(&rel_path::normalize(&rel.to_string_lossy()), true)
+                    .is_none(),
+                _ => true,
+            }
+        })
         .build();
 
     let mut count = 0;
@@ -35,18 +53,13 @@ pub fn scan_directory(root: &Path, file_tree: &Arc<FileTree>, max_file_size: u64
         let path = entry.path();
 
         // Get the relative path
-        let rel_path = match path.strip_prefix(root) {
-            Ok(r) => r.to_string_lossy().to_string(),
-            Err(_) => continue,
+        let rel_path = match rel_path::relative_to(path, root) {
+            Some(r) => r,
+            None => continue,
         };
 
-        // Apply our additional ignore rules
-        if should_skip(&rel_path) {
-            continue;
-        }
-
-        // Check extension-based ignoring
-        if config::should_ignore_extension(&rel_path) {
+        // Apply the default and configured ignore rules
+        if file_tree.ignore_rules().is_ignored(&rel_path) {
             continue;
         }
 
@@ -77,12 +90,145 @@ pub fn scan_directory(root: &Path, file_tree: &Arc<FileTree>, max_file_size: u64
     Ok(count)
 }
 
-/// Check if any path component matches our built-in ignore directories.
-fn should_skip(rel_path: &str) -> bool {
-    for component in rel_path.split('/') {
-        if config::should_ignore_dir(component) {
-            return true;
+fn base_walker(root: &Path) -> WalkBuilder {
+    let mut builder = WalkBuilder::new(root);
+    builder
+        .hidden(true) // skip dotfiles by default
+        .git_ignore(true) // respect .gitignore
+        .git_global(true)
+        .git_exclude(true);
+    builder
+}
+
+/// A path the ignore rules keep out of the tree. Directories are reported
+/// once, at the outermost ignored level, with the files found below them.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct IgnoredPath {
+    /// Relative path; directories end in `/`.
+    pub path: String,
+    pub reason: IgnoreReason,
+    /// Files under the directory, or 1 for a file.
+    pub files: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct IgnoredList {
+    pub paths: Vec<IgnoredPath>,
+    /// More than `limit` paths were ignored; the rest were left out.
+    pub truncated: bool,
+}
+
+/// Walk `root` the way `scan_directory` does, but collect what the tree's
+/// ignore rules left out instead. `.gitignore`d and hidden paths aren't
+/// listed, nor are internal directories like `.git`.
+pub fn list_ignored(root: &Path, file_tree: &FileTree, limit: usize) -> IgnoredList {
+    let rules = file_tree.ignore_rules();
+    let mut found: BTreeMap<String, IgnoredPath> = BTreeMap::new();
+    let mut truncated = false;
+
+    for entry in base_walker(root).build().flatten() {
+        if entry.file_type().is_none_or(|ft| ft.is_dir()) {
+            continue;
+        }
+        let rel_path = match rel_path::relative_to(entry.path(), root) {
+            Some(r) => r,
+            None => continue,
+        };
+        let Some(file_reason) = rules.reason(&rel_path, false) else {
+            continue;
+        };
+        if file_reason.source == IgnoreSource::Internal {
+            continue;
+        }
+
+        // Attribute the file to its outermost ignored directory, if any.
+        let mut key = rel_path.clone();
+        let mut reason = file_reason;
+        let mut dir = String::new();
+        let components: Vec<&str> = rel_path.split('/').collect();
+        for component in &components[..components.len() - 1] {
+            if !dir.is_empty() {
+                dir.push('/');
+            }
+            dir.push_str(component);
+            if let Some(dir_reason) = rules.reason(&dir, true) {
+                key = format!("{}/", dir);
+                reason = dir_reason;
+                break;
+            }
+        }
+
+        if let Some(existing) = found.get_mut(&key) {
+            existing.files += 1;
+        } else if found.len() < limit {
+            found.insert(
+                key.clone(),
+                IgnoredPath {
+                    path: key,
+                    reason,
+                    files: 1,
+                },
+            );
+        } else {
+            truncated = true;
+        }
+    }
+
+    IgnoredList {
+        paths: found.into_values().collect(),
+        truncated,
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OversizedFile {
+    pub path: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OversizedList {
+    /// Largest first.
+    pub files: Vec<OversizedFile>,
+    /// More than `limit` files were over the limit; the smallest were left
+    /// out.
+    pub truncated: bool,
+}
+
+/// Walk `root` the way `scan_directory` does and collect the files it skips
+/// for being over `max_file_size`, largest first.
+pub fn list_oversized(
+    root: &Path,
+    file_tree: &FileTree,
+    max_file_size: u64,
+    limit: usize,
+) -> OversizedList {
+    let rules = file_tree.ignore_rules();
+    let mut files = Vec::new();
+    for entry in base_walker(root).build().flatten() {
+        if entry.file_type().is_none_or(|ft| ft.is_dir()) {
+            continue;
+        }
+        let Ok(size) = entry.metadata().map(|m| m.len()) else {
+            continue;
+        };
+        if size <= max_file_size {
+            continue;
+        }
+        let rel_path = match rel_path::relative_to(entry.path(), root) {
+            Some(r) => r,
+            None => continue,
+        };
+        if rules.is_ignored(&rel_path) {
+            continue;
         }
+        files.push(OversizedFile {
+            path: rel_path,
+            size,
+        });
     }
-    false
+    files.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    let truncated = files.len() > limit;
+    files.truncate(limit);
+    OversizedList { files, truncated }
 }
@@ -1,15 +1,20 @@
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use notify_debouncer_mini::{new_debouncer, DebouncedEventKind};
-use std::path::{Path, PathBuf};
+use std::collections::HashSet;
+use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
 use tracing::{debug, info, warn};
 
-use crate::config;
-use crate::index::file_entry::FileEntry;
+use crate::index::file_entry::{FileEntry, Language};
 use crate::index::file_tree::FileTree;
-use crate::symbols::parser::extract_symbols_from_file;
+use crate::index::rel_path;
+use crate::server::cancel::CancelToken;
+use crate::symbols::parser::{
+    extract_call_sites, extract_symbols_from_file, insert_file_symbols, record_extraction,
+    ExtractionErrors,
+};
 use crate::symbols::SymbolTable;
 
 /// Start the filesystem watcher. Returns a handle that keeps the watcher alive.
@@ -18,6 +23,7 @@ pub fn start_watcher(
     root: &Path,
     file_tree: Arc<FileTree>,
     symbol_table: Arc<SymbolTable>,
+    errors: Arc<ExtractionErrors>,
     max_file_size: u64,
 ) -> Result<WatcherHandle> {
     let root_buf = root.to_path_buf();
@@ -32,6 +38,7 @@ pub fn start_watcher(
                         &root_for_handler,
                         &file_tree,
                         &symbol_table,
+                        &errors,
                         max_file_size,
                         events,
                     );
@@ -59,32 +66,38 @@ pub struct WatcherHandle {
 }
 
 fn handle_events(
-    root: &PathBuf,
+    root: &Path,
     file_tree: &Arc<FileTree>,
     symbol_table: &Arc<SymbolTable>,
+    errors: &ExtractionErrors,
     max_file_size: u64,
     events: Vec<notify_debouncer_mini::DebouncedEvent>,
 ) {
+    let mut changed: Vec<String> = Vec::new();
+    let mut seen = HashSet::new();
     for event in events {
         let path = &event.path;
 
         // Get relative path
-        let rel_path = match path.strip_prefix(root) {
-            Ok(r) => r.to_string_lossy().to_string(),
-            Err(_) => continue,
+        let rel_path = match rel_path::relative_to(path, root) {
+            Some(r) => r,
+            None => continue,
         };
 
         // Skip ignored paths
-        if should_skip(&rel_path) {
+        if file_tree.ignore_rules().is_ignored(&rel_path) {
             continue;
         }
 
         match event.kind {
             DebouncedEventKind::Any => {
-                if path.is_file() {
-                    handle_file_change(root, file_tree, symbol_table, max_file_size, &rel_path, path);
-                } else if !path.exists() {
-                    handle_file_delete(file_tree, symbol_table, &rel_path);
+                // Our own reads of a file raise events too; only re-index
+                // when it actually changed.
+                if is_unchanged(file_tree, &rel_path, path) {
+                    continue;
+                }
+                if seen.insert(rel_path.clone()) {
+                    changed.push(rel_path);
                 }
             }
             DebouncedEventKind::AnyContinuous => {
@@ -93,29 +106,125 @@ fn handle_events(
             _ => {}
         }
     }
+    if changed.is_empty() {
+        return;
+    }
+
+    // A burst (e.g. a git pull) drops every changed file's call sites in one
+    // pass over the callee lists they touch; re-indexing each file below
+    // then finds none left to remove.
+    let files: Vec<&str> = changed.iter().map(String::as_str).collect();
+    symbol_table.remove_callers_from_files(&files);
+    for rel_path in &changed {
+        re_index_file(root, file_tree, symbol_table, errors, max_file_size, rel_path);
+    }
+}
+
+/// Whether `abs_path` still has the size and modification time recorded
+/// for it in the tree.
+fn is_unchanged(file_tree: &FileTree, rel_path: &str, abs_path: &Path) -> bool {
+    let (Some(entry), Ok(metadata)) = (file_tree.get(rel_path), std::fs::metadata(abs_path))
+    else {
+        return false;
+    };
+    let modified: Option<DateTime<Utc>> = metadata.modified().ok().map(DateTime::from);
+    metadata.len() == entry.size && modified == Some(entry.modified)
+}
+
+/// Bring the index for a single file in line with what's on disk: re-read and
+/// re-extract it if it exists, drop it from the index if it was deleted.
+pub fn re_index_file(
+    root: &Path,
+    file_tree: &FileTree,
+    symbol_table: &SymbolTable,
+    errors: &ExtractionErrors,
+    max_file_size: u64,
+    rel_path: &str,
+) {
+    let abs_path = root.join(rel_path);
+    if abs_path.is_file() {
+        handle_file_change(
+            root,
+            file_tree,
+            symbol_table,
+            errors,
+            max_file_size,
+            rel_path,
+            &abs_path,
+        );
+    } else if !abs_path.exists() {
+        handle_file_delete(file_tree, symbol_table, errors, rel_path);
+    }
 }
 
 fn handle_file_change(
-    root: &PathBuf,
-    file_tree: &Arc<FileTree>,
-    symbol_table: &Arc<SymbolTable>,
+    root: &Path,
+    file_tree: &FileTree,
+    symbol_table: &SymbolTable,
+    errors: &ExtractionErrors,
     max_file_size: u64,
     rel_path: &str,
     abs_path: &Path,
 ) {
-    // Check extension-based ignoring
-    if config::should_ignore_extension(rel_path) {
+    let Some(language) = refresh_file_entry(root, file_tree, max_file_size, rel_path, abs_path)
+    else {
         return;
+    };
+
+    match re_extract_symbols(root, file_tree, symbol_table, errors, rel_path, language) {
+        Ok(count) => debug!("Re-extracted {} symbols from {}", count, rel_path),
+        Err(e) => debug!("Failed to re-extract symbols from {}: {}", rel_path, e),
     }
+}
 
-    let metadata = match std::fs::metadata(abs_path) {
-        Ok(m) => m,
-        Err(_) => return,
-    };
+/// Replace `rel_path`'s symbols and call sites with freshly extracted ones.
+/// Returns how many symbols it now has. On failure the file is left with
+/// none, and the error is recorded.
+pub fn re_extract_symbols(
+    root: &Path,
+    file_tree: &FileTree,
+    symbol_table: &SymbolTable,
+    errors: &ExtractionErrors,
+    rel_path: &str,
+    language: Language,
+) -> Result<usize> {
+    symbol_table.remove_file(rel_path);
+    if !language.has_tree_sitter_support() {
+        return Ok(0);
+    }
+    let result = extract_symbols_from_file(root, rel_path, language, &CancelToken::default());
+    record_extraction(errors, rel_path, language, &result);
+    let (symbols, source) = result?;
+    let count = symbols.len();
+    insert_file_symbols(file_tree, symbol_table, rel_path, symbols, source);
+    // `remove_file` dropped this file's call sites along with its symbols
+    for (callee, line, text) in extract_call_sites(root, rel_path, language, &CancelToken::default()) {
+        symbol_table.add_caller(&callee, rel_path, line, &text);
+    }
+    Ok(count)
+}
+
+/// Re-create the file tree entry for `rel_path` from disk, keeping its
+/// annotations, and refresh its text index entry and PDF conversion. Returns
+/// the file's language, or `None` when the file is excluded by the ignore
+/// rules or size, or its metadata can't be read.
+pub fn refresh_file_entry(
+    root: &Path,
+    file_tree: &FileTree,
+    max_file_size: u64,
+    rel_path: &str,
+    abs_path: &Path,
+) -> Option<Language> {
+    // Check default and configured ignore rules
+    if file_tree.ignore_rules().is_ignored(rel_path) {
+        return None;
+    }
+
+    let metadata = std::fs::metadata(abs_path).ok()?;
 
     let size = metadata.len();
     if size > max_file_size {
-        return;
+        return None;
     }
 
     let modified: DateTime<Utc> = metadata
@@ -123,55 +232,37 @@ fn handle_file_change(
         .map(DateTime::from)
         .unwrap_or_else(|_| Utc::now());
 
-    // Update file tree
-    let entry = FileEntry::new(rel_path.to_string(), size, modified);
+    // Update file tree, keeping annotations (a `generated` mark must keep
+    // suppressing the file's symbols after it changes)
+    let mut entry = FileEntry::new(rel_path.to_string(), size, modified);
+    if let Some(previous) = file_tree.get(rel_path) {
+        entry.definition = previous.definition;
+        entry.definition_auto = previous.definition_auto;
+        entry.marks = previous.marks;
+    }
     let language = entry.language;
     file_tree.insert(entry);
+    file_tree.index_text_file(root, rel_path);
 
     // Re-convert PDF before symbol extraction
-    if language == crate::index::file_entry::Language::Pdf {
-        if let Err(e) = crate::index::pdf::convert_pdf(root, rel_path) {
+    if language == Language::Pdf {
+        if let Err(e) = crate::index::pdf::convert_pdf(root, rel_path, &CancelToken::default()) {
             debug!("Failed to re-convert PDF {}: {}", rel_path, e);
         }
     }
 
-    // Re-extract symbols
-    symbol_table.remove_file(rel_path);
-    if language.has_tree_sitter_support() {
-        match extract_symbols_from_file(root, rel_path, language) {
-            Ok(symbols) => {
-                let count = symbols.len();
-                for sym in symbols {
-                    symbol_table.insert(sym);
-                }
-                if let Some(mut entry) = file_tree.files.get_mut(rel_path) {
-                    entry.symbols_extracted = true;
-                }
-                debug!("Re-extracted {} symbols from {}", count, rel_path);
-            }
-            Err(e) => {
-                debug!("Failed to re-extract symbols from {}: {}", rel_path, e);
-            }
-        }
-    }
+    Some(language)
 }
 
 fn handle_file_delete(
-    file_tree: &Arc<FileTree>,
-    symbol_table: &Arc<SymbolTable>,
+    file_tree: &FileTree,
+    symbol_table: &SymbolTable,
+    errors: &ExtractionErrors,
     rel_path: &str,
 ) {
+    errors.remove(rel_path);
     if file_tree.remove(rel_path).is_some() {
         symbol_table.remove_file(rel_path);
         debug!("Removed {} from index", rel_path);
     }
 }
-
-fn should_skip(rel_path: &str) -> bool {
-    for component in rel_path.split('/') {
-        if config::should_ignore_dir(component) {
-            return true;
-        }
-    }
-    false
-}
@@ -4,11 +4,16 @@ mod ops;
 mod server;
 mod symbols;
 
+use std::os::unix::fs::FileTypeExt;
 use std::path::PathBuf;
+use std::time::Duration;
 
+use anyhow::Context;
 use clap::Parser;
 use tracing::info;
 
+use config::{FileConfig, Listen, LogFormat, ServerConfig};
+
 use server::state::AppState;
 
 #[derive(Parser)]
@@ -26,36 +31,94 @@ enum Commands {
         /// Optional initial project directory to pre-index
         path: Option<PathBuf>,
 
-        /// Port to listen on
-        #[arg(short, long, default_value = "3000")]
-        port: u16,
+        /// Port to listen on [default: 3000]
+        #[arg(short, long, env = "CODERLM_PORT")]
+        port: Option<u16>,
+
+        /// Bind address [default: 127.0.0.1]
+        #[arg(short, long, env = "CODERLM_BIND")]
+        bind: Option<String>,
+
+        /// Listen on a Unix domain socket instead of TCP
+        #[arg(long, env = "CODERLM_UNIX_SOCKET", conflicts_with_all = ["port", "bind", "tls_cert", "tls_key"])]
+        unix_socket: Option<PathBuf>,
+
+        /// PEM certificate chain; enables HTTPS together with --tls-key
+        #[arg(long, env = "CODERLM_TLS_CERT", requires = "tls_key")]
+        tls_cert: Option<PathBuf>,
+
+        /// PEM private key; enables HTTPS together with --tls-cert
+        #[arg(long, env = "CODERLM_TLS_KEY", requires = "tls_cert")]
+        tls_key: Option<PathBuf>,
+
+        /// TOML config file; CLI flags and env vars override its values
+        #[arg(long, env = "CODERLM_CONFIG")]
+        config: Option<PathBuf>,
+
+        /// Maximum file size in bytes to index [default: 20 GB]
+        #[arg(long)]
+        max_file_size: Option<u64>,
+
+        /// Maximum number of concurrent indexed projects [default: 5]
+        #[arg(long)]
+        max_projects: Option<usize>,
+
+        /// Maximum concurrent blocking jobs such as grep and indexing [default: 16]
+        #[arg(long, env = "CODERLM_MAX_BLOCKING")]
+        max_blocking: Option<usize>,
+
+        /// Per-session requests per second; 0 disables rate limiting [default: 50]
+        #[arg(long, env = "CODERLM_RATE_LIMIT")]
+        rate_limit: Option<f64>,
+
+        /// Per-session burst allowance before rate limiting kicks in [default: 200]
+        #[arg(long, env = "CODERLM_RATE_BURST")]
+        rate_burst: Option<f64>,
+
+        /// Estimated response tokens per session before responses carry a
+        /// budget warning; 0 disables [default: 200000]
+        #[arg(long, env = "CODERLM_RESPONSE_TOKEN_BUDGET")]
+        response_token_budget: Option<usize>,
+
+        /// Disable gzip/deflate/zstd response compression
+        #[arg(long)]
+        no_compression: bool,
+
+        /// Smallest response body, in bytes, worth compressing [default: 1024]
+        #[arg(long)]
+        compression_min_bytes: Option<u16>,
 
-        /// Bind address
-        #[arg(short, long, default_value = "127.0.0.1")]
-        bind: String,
+        /// Keep a trigram index of file text so grep can skip files that
+        /// can't match (uses more memory)
+        #[arg(long)]
+        text_index: bool,
 
-        /// Maximum file size in bytes to index
-        #[arg(long, default_value_t = config::DEFAULT_MAX_FILE_SIZE)]
-        max_file_size: u64,
+        /// Index vendored directories and lock files that are skipped by
+        /// default (node_modules, vendor, Cargo.lock, go.sum, ...)
+        #[arg(long)]
+        no_default_ignores: bool,
 
-        /// Maximum number of concurrent indexed projects
-        #[arg(long, default_value = "5")]
-        max_projects: usize,
+        /// Git executable for the read-only /git endpoints [default: git]
+        #[arg(long, env = "CODERLM_GIT")]
+        git_binary: Option<PathBuf>,
+
+        /// Refuse caller-supplied tree-sitter queries on POST /query
+        #[arg(long)]
+        no_raw_queries: bool,
+
+        /// Log output format [default: text]
+        #[arg(long, env = "CODERLM_LOG_FORMAT", value_enum)]
+        log_format: Option<LogFormat>,
+
+        /// Token required in X-Admin-Token by POST /admin/shutdown; the
+        /// endpoint is disabled without one
+        #[arg(long, env = "CODERLM_ADMIN_TOKEN", hide_env_values = true)]
+        admin_token: Option<String>,
     },
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
-        )
-        .init();
-
-    info!("coderlm v{}", env!("CARGO_PKG_VERSION"));
-
     let cli = Cli::parse();
 
     match cli.command {
@@ -63,47 +126,183 @@ async fn main() -> anyhow::Result<()> {
             path,
             port,
             bind,
+            unix_socket,
+            tls_cert,
+            tls_key,
+            config,
             max_file_size,
             max_projects,
+            max_blocking,
+            rate_limit,
+            rate_burst,
+            response_token_budget,
+            no_compression,
+            compression_min_bytes,
+            text_index,
+            no_default_ignores,
+            git_binary,
+            no_raw_queries,
+            log_format,
+            admin_token,
         } => {
-            run_server(path, port, bind, max_file_size, max_projects).await?;
+            let file_config = match config {
+                Some(ref p) => FileConfig::load(p)?,
+                None => FileConfig::default(),
+            };
+            let cli_config = FileConfig {
+                bind,
+                port,
+                unix_socket,
+                tls_cert,
+                tls_key,
+                max_file_size,
+                max_projects,
+                max_blocking,
+                rate_limit,
+                rate_burst,
+                response_token_budget,
+                compression: no_compression.then_some(false),
+                compression_min_bytes,
+                log_format,
+                generated: None,
+                use_default_ignores: no_default_ignores.then_some(false),
+                ignore: None,
+                allow: None,
+                text_index: text_index.then_some(true),
+                git_binary,
+                raw_queries: no_raw_queries.then_some(false),
+                admin_token,
+            };
+            let server_config = ServerConfig::resolve(cli_config, file_config)?;
+            init_tracing(server_config.log_format);
+            info!("coderlm v{}", env!("CARGO_PKG_VERSION"));
+            run_server(path, server_config).await?;
         }
     }
 
     Ok(())
 }
 
-async fn run_server(
-    path: Option<PathBuf>,
-    port: u16,
-    bind: String,
-    max_file_size: u64,
-    max_projects: usize,
-) -> anyhow::Result<()> {
+fn init_tracing(format: LogFormat) {
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
+    match format {
+        LogFormat::Text => tracing_subscriber::fmt().with_env_filter(filter).init(),
+        // One JSON object per line; request span fields (request_id, session,
+        // method, path, ...) land under "span".
+        LogFormat::Json => tracing_subscriber::fmt()
+            .with_env_filter(filter)
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .init(),
+    }
+}
+
+async fn run_server(path: Option<PathBuf>, config: ServerConfig) -> anyhow::Result<()> {
     // Create shared state
-    let state = AppState::new(max_projects, max_file_size);
+    let state = AppState::new(
+        config.max_projects,
+        config.max_file_size,
+        &config.limits,
+        config.generated_patterns.clone(),
+        config.ignore.clone(),
+        config.text_index,
+        config.git_binary.clone(),
+        config.raw_queries,
+        config.admin_token.clone(),
+    );
 
     // If an initial path was provided, pre-index it
     if let Some(ref p) = path {
         info!("Pre-indexing project: {}", p.display());
-        state.get_or_create_project(p).map_err(|e| {
+        state.get_or_create_project(p, None, true).map_err(|e| {
             anyhow::anyhow!("Failed to index '{}': {}", p.display(), e)
         })?;
     }
 
+    server::shutdown::spawn_signal_listener(state.clone());
+
     // Build router
-    let app = server::build_router(state);
+    let app = server::build_router(state.clone(), &config.compression);
 
-    let addr = format!("{}:{}", bind, port);
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    let serving = match path {
+        Some(ref p) => format!("coderlm serving {}", p.display()),
+        None => "coderlm server (no project pre-indexed)".to_string(),
+    };
 
-    if let Some(ref p) = path {
-        info!("coderlm serving {} on http://{}", p.display(), addr);
-    } else {
-        info!("coderlm server listening on http://{} (no project pre-indexed)", addr);
+    match config.listen {
+        Listen::Unix(socket) => {
+            // A socket file left behind by a previous run would make bind
+            // fail. Anything else at that path is left alone.
+            if let Ok(metadata) = std::fs::symlink_metadata(&socket) {
+                if !metadata.file_type().is_socket() {
+                    anyhow::bail!(
+                        "{} exists and is not a socket; refusing to replace it",
+                        socket.display()
+                    );
+                }
+                std::fs::remove_file(&socket).with_context(|| {
+                    format!("Failed to remove stale socket {}", socket.display())
+                })?;
+            }
+            let listener = tokio::net::UnixListener::bind(&socket)
+                .with_context(|| format!("Failed to bind unix socket {}", socket.display()))?;
+            info!("{} listening on unix:{}", serving, socket.display());
+            let server = axum::serve(listener, app)
+                .with_graceful_shutdown(server::shutdown::requested(state.clone()));
+            server::shutdown::serve_with_drain(server, state.clone()).await?;
+            let _ = std::fs::remove_file(&socket);
+        }
+        Listen::Tcp { bind, port, tls } => {
+            let addr = tokio::net::lookup_host((bind.as_str(), port))
+                .await
+                .with_context(|| format!("Failed to resolve bind address {}:{}", bind, port))?
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("No address found for {}:{}", bind, port))?;
+
+            match tls {
+                Some(tls) => {
+                    let _ = rustls::crypto::ring::default_provider().install_default();
+                    let rustls_config = axum_server::tls_rustls::RustlsConfig::from_pem_file(
+                        &tls.cert, &tls.key,
+                    )
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "Failed to load TLS cert {} / key {}",
+                            tls.cert.display(),
+                            tls.key.display()
+                        )
+                    })?;
+                    info!("{} listening on https://{}", serving, addr);
+                    let handle = axum_server::Handle::new();
+                    let shutdown_handle = handle.clone();
+                    let shutdown_state = state.clone();
+                    tokio::spawn(async move {
+                        server::shutdown::requested(shutdown_state).await;
+                        shutdown_handle.graceful_shutdown(Some(Duration::from_secs(
+                            config::SHUTDOWN_DRAIN_SECS,
+                        )));
+                    });
+                    axum_server::bind_rustls(addr, rustls_config)
+                        .handle(handle)
+                        .serve(app.into_make_service())
+                        .await?;
+                }
+                None => {
+                    let listener = tokio::net::TcpListener::bind(addr).await?;
+                    info!("{} listening on http://{}", serving, listener.local_addr()?);
+                    let server = axum::serve(listener, app)
+                        .with_graceful_shutdown(server::shutdown::requested(state.clone()));
+                    server::shutdown::serve_with_drain(server, state.clone()).await?;
+                }
+            }
+        }
     }
 
-    axum::serve(listener, app).await?;
+    // Connections are drained (or timed out); persist what would be lost.
+    server::shutdown::flush(&state);
 
     Ok(())
 }
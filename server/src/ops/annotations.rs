@@ -1,36 +1,47 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::path::Path;
 use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
-use tracing::{debug, warn};
+use utoipa::ToSchema;
+use tracing::{debug, info, warn};
 
 use crate::index::file_entry::FileMark;
 use crate::index::file_tree::FileTree;
+use crate::index::rel_path;
 use crate::symbols::SymbolTable;
 
 const ANNOTATIONS_FILE: &str = ".coderlm/annotations.json";
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
 pub struct AnnotationData {
     /// File definitions: rel_path -> definition string
     #[serde(default)]
     pub file_definitions: HashMap<String, String>,
+    /// Files whose definition was generated by `/structure/auto_define`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub auto_file_definitions: Vec<String>,
     /// File marks: rel_path -> list of mark strings
     #[serde(default)]
     pub file_marks: HashMap<String, Vec<String>>,
     /// Symbol definitions: "file::name" -> definition string
     #[serde(default)]
     pub symbol_definitions: HashMap<String, String>,
+    /// Some paths were loaded in an old form and normalized; the file needs
+    /// saving again.
+    #[serde(skip)]
+    pub migrated: bool,
 }
 
 /// Save all annotations (file definitions, marks, symbol definitions)
-/// to `.coderlm/annotations.json` in the project root.
+/// to `.coderlm/annotations.json` in the project root. Returns what was
+/// written.
 pub fn save_annotations(
     root: &Path,
     file_tree: &Arc<FileTree>,
     symbol_table: &Arc<SymbolTable>,
-) -> Result<(), String> {
+) -> Result<AnnotationData, String> {
     let mut data = AnnotationData::default();
 
     // Collect file definitions and marks
@@ -39,6 +50,9 @@ pub fn save_annotations(
         if let Some(def) = &fe.definition {
             data.file_definitions
                 .insert(fe.rel_path.clone(), def.clone());
+            if fe.definition_auto {
+                data.auto_file_definitions.push(fe.rel_path.clone());
+            }
         }
         if !fe.marks.is_empty() {
             let mark_strs: Vec<String> = fe
@@ -77,7 +91,7 @@ pub fn save_annotations(
         data.symbol_definitions.len()
     );
 
-    Ok(())
+    Ok(data)
 }
 
 /// Load annotations from `.coderlm/annotations.json` and apply them
@@ -94,13 +108,19 @@ pub fn load_annotations(
 
     let json = std::fs::read_to_string(&annotations_path)
         .map_err(|e| format!("Failed to read annotations: {}", e))?;
-    let data: AnnotationData = serde_json::from_str(&json)
+    let mut data: AnnotationData = serde_json::from_str(&json)
         .map_err(|e| format!("Failed to parse annotations: {}", e))?;
+    let migrated = normalize_keys(&mut data);
+    if migrated > 0 {
+        info!("Normalized {} annotation path(s) to forward slashes", migrated);
+        data.migrated = true;
+    }
 
     // Apply file definitions
     for (path, def) in &data.file_definitions {
         if let Some(mut entry) = file_tree.files.get_mut(path.as_str()) {
             entry.definition = Some(def.clone());
+            entry.definition_auto = data.auto_file_definitions.contains(path);
         } else {
             debug!("Annotation for missing file: {}", path);
         }
@@ -114,6 +134,9 @@ pub fn load_annotations(
                     if !entry.marks.contains(&mark) {
                         entry.marks.push(mark);
                     }
+                    if mark == FileMark::Generated {
+                        symbol_table.set_generated(path, true);
+                    }
                 } else {
                     warn!("Unknown mark '{}' for file '{}'", mark_str, path);
                 }
@@ -139,3 +162,89 @@ pub fn load_annotations(
 
     Ok(data)
 }
+
+/// Rewrite paths saved with backslashes (or other non-canonical forms) the
+/// way the index now keys them. Returns how many keys changed; the next
+/// save writes them back normalized.
+fn normalize_keys(data: &mut AnnotationData) -> usize {
+    fn normalize_map<V>(map: &mut HashMap<String, V>) -> usize {
+        let stale: Vec<String> = map
+            .keys()
+            .filter(|key| matches!(rel_path::normalize(key), Cow::Owned(_)))
+            .cloned()
+            .collect();
+        for key in &stale {
+            if let Some(value) = map.remove(key) {
+                map.insert(rel_path::normalize(key).into_owned(), value);
+            }
+        }
+        stale.len()
+    }
+
+    let mut changed = normalize_map(&mut data.file_definitions)
+        + normalize_map(&mut data.file_marks)
+        + normalize_map(&mut data.symbol_definitions);
+    for path in &mut data.auto_file_definitions {
+        if let Cow::Owned(normalized) = rel_path::normalize(path) {
+            *path = normalized;
+            changed += 1;
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DEFAULT_MAX_FILE_SIZE;
+    use crate::index::walker;
+    use crate::server::state::temp_project;
+
+    #[test]
+    fn normalize_keys_rewrites_old_paths() {
+        let mut data = AnnotationData::default();
+        data.file_definitions.insert(r"src\lib.rs".into(), "library".into());
+        data.file_definitions.insert("src/main.rs".into(), "entry".into());
+        data.file_marks.insert("./src//lib.rs".into(), vec!["test".into()]);
+        data.symbol_definitions.insert(r"src\lib.rs::parse".into(), "parses".into());
+        data.auto_file_definitions.push(r"src\lib.rs".into());
+
+        assert_eq!(normalize_keys(&mut data), 4);
+        assert_eq!(data.file_definitions["src/lib.rs"], "library");
+        assert_eq!(data.file_definitions["src/main.rs"], "entry");
+        assert!(data.file_marks.contains_key("src/lib.rs"));
+        assert_eq!(data.symbol_definitions["src/lib.rs::parse"], "parses");
+        assert_eq!(data.auto_file_definitions, ["src/lib.rs"]);
+
+        // Already normalized: nothing left to do.
+        assert_eq!(normalize_keys(&mut data), 0);
+    }
+
+    #[test]
+    fn loading_old_paths_applies_them_and_flags_a_resave() {
+        let dir = temp_project(&[
+            ("src/lib.rs", "pub fn parse() {}\n"),
+            (
+                ANNOTATIONS_FILE,
+                r#"{ "file_definitions": { "src\\lib.rs": "library" }, "file_marks": { "./src//lib.rs": ["test"] } }"#,
+            ),
+        ]);
+        let file_tree = Arc::new(FileTree::new());
+        walker::scan_directory(&dir, &file_tree, DEFAULT_MAX_FILE_SIZE).unwrap();
+        let symbol_table = Arc::new(SymbolTable::new());
+
+        let data = load_annotations(&dir, &file_tree, &symbol_table).unwrap();
+        assert!(data.migrated);
+        let entry = file_tree.get("src/lib.rs").unwrap();
+        assert_eq!(entry.definition.as_deref(), Some("library"));
+        assert!(entry.marks.contains(&FileMark::Test));
+
+        // Saving writes the normalized keys back.
+        save_annotations(&dir, &file_tree, &symbol_table).unwrap();
+        let reloaded = load_annotations(&dir, &file_tree, &symbol_table).unwrap();
+        assert!(!reloaded.migrated);
+        assert!(reloaded.file_definitions.contains_key("src/lib.rs"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
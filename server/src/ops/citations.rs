@@ -0,0 +1,500 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use crate::config;
+use crate::index::encoding;
+use crate::index::file_entry::Language;
+use crate::index::file_tree::FileTree;
+use crate::index::rel_path;
+use crate::ops::content;
+use crate::server::cancel;
+
+/// Verdict for one cited location.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CitationStatus {
+    /// The file exists, the lines are in range, and any quoted snippet was
+    /// found near them.
+    Ok,
+    FileNotFound,
+    LineOutOfRange,
+    SnippetNotFound,
+    /// The file is indexed but could not be read.
+    Unreadable,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CitationVerdict {
+    /// The reference as it appeared in the text, e.g. `src/lib.rs:10`.
+    pub text: String,
+    pub file: String,
+    /// The indexed path `file` was matched to, when it differs (a `./`
+    /// prefix, an absolute path under the root, or a unique path suffix).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved_file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_line: Option<usize>,
+    pub status: CitationStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_lines: Option<usize>,
+    /// Backtick- or double-quoted text right after the reference, checked
+    /// against the cited lines.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet_found: Option<bool>,
+    /// Indexed paths with the same file name, for missing files.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CitationReport {
+    pub total: usize,
+    pub valid: usize,
+    pub invalid: usize,
+    pub citations: Vec<CitationVerdict>,
+}
+
+/// How references are found in the text and how strictly they are checked.
+pub struct CitationRules {
+    pattern: Regex,
+    /// `None` accepts any extension an indexed language claims, plus
+    /// `CITATION_EXTRA_EXTENSIONS`.
+    extensions: Option<HashSet<String>>,
+    check_snippets: bool,
+}
+
+impl CitationRules {
+    /// Compile the rules. `pattern` must have a `path` group and may have
+    /// `line` and `end` groups.
+    pub fn new(
+        pattern: Option<&str>,
+        extensions: Option<Vec<String>>,
+        check_snippets: bool,
+    ) -> Result<Self, String> {
+        let pattern = Regex::new(pattern.unwrap_or(config::CITATION_PATTERN))
+            .map_err(|e| format!("Invalid citation pattern: {}", e))?;
+        if !pattern.capture_names().any(|name| name == Some("path")) {
+            return Err("Citation pattern must have a named 'path' group".into());
+        }
+        let extensions = extensions.map(|exts| {
+            exts.into_iter()
+                .map(|e| e.trim_start_matches('.').to_lowercase())
+                .collect()
+        });
+        Ok(Self {
+            pattern,
+            extensions,
+            check_snippets,
+        })
+    }
+
+    fn accepts_extension(&self, path: &str) -> bool {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        let Some((_, ext)) = name.rsplit_once('.') else {
+            return false;
+        };
+        let ext = ext.to_lowercase();
+        match &self.extensions {
+            Some(exts) => exts.contains(&ext),
+            None => {
+                Language::from_extension(&ext) != Language::Other
+                    || config::CITATION_EXTRA_EXTENSIONS.contains(&ext.as_str())
+            }
+        }
+    }
+}
+
+/// A reference pulled out of the text, before it is checked.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Citation {
+    text: String,
+    path: String,
+    line: Option<usize>,
+    end_line: Option<usize>,
+    snippet: Option<String>,
+}
+
+/// Find and check every file reference in `value`. Strings are scanned
+/// wherever they appear in the JSON, including object keys.
+pub fn validate_citations(
+    root: &Path,
+    file_tree: &FileTree,
+    value: &Value,
+    rules: &CitationRules,
+) -> CitationReport {
+    let mut texts = Vec::new();
+    collect_strings(value, &mut texts);
+
+    let mut seen = HashSet::new();
+    let citations: Vec<Citation> = texts
+        .iter()
+        .flat_map(|text| extract_citations(text, rules))
+        .filter(|c| seen.insert(c.clone()))
+        .collect();
+
+    let mut sources: HashMap<String, Option<Vec<String>>> = HashMap::new();
+    let verdicts: Vec<CitationVerdict> = citations
+        .into_iter()
+        .map(|c| check_citation(root, file_tree, c, rules, &mut sources))
+        .collect();
+    let valid = verdicts
+        .iter()
+        .filter(|v| v.status == CitationStatus::Ok)
+        .count();
+    CitationReport {
+        total: verdicts.len(),
+        valid,
+        invalid: verdicts.len() - valid,
+        citations: verdicts,
+    }
+}
+
+fn collect_strings<'a>(value: &'a Value, out: &mut Vec<&'a str>) {
+    match value {
+        Value::String(s) => out.push(s),
+        Value::Array(items) => items.iter().for_each(|v| collect_strings(v, out)),
+        Value::Object(map) => {
+            for (key, v) in map {
+                out.push(key);
+                collect_strings(v, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// References in `text`. A match is dropped when it is glued to the text
+/// before it (a URL's `://`, a module path's `::`, an email's `@`), runs
+/// on into a word, or has an extension `rules` doesn't accept and no `/`.
+fn extract_citations(text: &str, rules: &CitationRules) -> Vec<Citation> {
+    let mut citations = Vec::new();
+    for caps in rules.pattern.captures_iter(text) {
+        let whole = caps.get(0).unwrap();
+        let Some(path) = caps.name("path") else {
+            continue;
+        };
+        let before = text[..whole.start()].chars().next_back();
+        if before.is_some_and(|c| c.is_alphanumeric() || "_-.:/@\\".contains(c)) {
+            continue;
+        }
+        let after = text[whole.end()..].chars().next();
+        if after.is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            continue;
+        }
+        if !path.as_str().contains('/') && !rules.accepts_extension(path.as_str()) {
+            continue;
+        }
+        let line = caps.name("line").and_then(|m| m.as_str().parse().ok());
+        let end_line = caps.name("end").and_then(|m| m.as_str().parse().ok());
+        let mut rest = text[whole.end()..].lines().next().unwrap_or("");
+        // Step past the closing quote when the reference itself is quoted.
+        if let Some(quote) = before.filter(|c| *c == '`' || *c == '"') {
+            rest = rest.strip_prefix(quote).unwrap_or(rest);
+        }
+        citations.push(Citation {
+            text: whole.as_str().to_string(),
+            path: path.as_str().to_string(),
+            line,
+            end_line,
+            snippet: quoted_snippet(rest),
+        });
+    }
+    citations
+}
+
+/// A backtick- or double-quoted string right after the reference, allowing
+/// only whitespace and a separator (`:`, `-`, `(`) in between.
+fn quoted_snippet(rest: &str) -> Option<String> {
+    let rest = rest.trim_start_matches(|c: char| c.is_whitespace() || ":-–—(".contains(c));
+    let quote = rest.chars().next().filter(|c| *c == '`' || *c == '"')?;
+    let body = &rest[1..];
+    let close = body.find(quote)?;
+    let snippet = body[..close].trim();
+    (!snippet.is_empty()).then(|| snippet.to_string())
+}
+
+/// The indexed path `path` refers to: as given (with either separator and
+/// without a leading `./`), relative to `root` when absolute, or the only
+/// indexed path ending in `/path`.
+fn resolve_path(root: &Path, file_tree: &FileTree, path: &str) -> Option<String> {
+    let normalized = rel_path::normalize(path);
+    let trimmed = normalized.as_ref();
+    if file_tree.get(trimmed).is_some() {
+        return Some(trimmed.to_string());
+    }
+    if let Some(rel) = rel_path::relative_to(Path::new(path), root)
+        && file_tree.get(&rel).is_some()
+    {
+        return Some(rel);
+    }
+    if path.starts_with('/') || path.starts_with("..") {
+        return None;
+    }
+    let suffix = format!("/{}", trimmed);
+    let mut matches = file_tree
+        .files
+        .iter()
+        .filter(|entry| entry.key().ends_with(&suffix))
+        .map(|entry| entry.key().clone());
+    let first = matches.next()?;
+    matches.next().is_none().then_some(first)
+}
+
+fn check_citation(
+    root: &Path,
+    file_tree: &FileTree,
+    citation: Citation,
+    rules: &CitationRules,
+    sources: &mut HashMap<String, Option<Vec<String>>>,
+) -> CitationVerdict {
+    let mut verdict = CitationVerdict {
+        text: citation.text,
+        file: citation.path.clone(),
+        resolved_file: None,
+        line: citation.line,
+        end_line: citation.end_line,
+        status: CitationStatus::Ok,
+        total_lines: None,
+        snippet: citation.snippet.clone(),
+        snippet_found: None,
+        suggestions: Vec::new(),
+    };
+    let Some(file) = resolve_path(root, file_tree, &citation.path) else {
+        verdict.status = CitationStatus::FileNotFound;
+        verdict.suggestions = content::suggest_files(file_tree, &citation.path);
+        return verdict;
+    };
+    if file != citation.path {
+        verdict.resolved_file = Some(file.clone());
+    }
+
+    let Some(start) = citation.line else {
+        return verdict;
+    };
+    let Some(lines) = sources
+        .entry(file.clone())
+        .or_insert_with(|| read_lines(root, file_tree, &file))
+    else {
+        verdict.status = CitationStatus::Unreadable;
+        return verdict;
+    };
+    verdict.total_lines = Some(lines.len());
+
+    let end = citation.end_line.unwrap_or(start);
+    if start == 0 || end < start || end > lines.len() {
+        verdict.status = CitationStatus::LineOutOfRange;
+        return verdict;
+    }
+    if let Some(snippet) = citation.snippet.as_deref().filter(|_| rules.check_snippets) {
+        let window = config::CITATION_SNIPPET_WINDOW_LINES;
+        let from = start.saturating_sub(window + 1);
+        let to = (end + window).min(lines.len());
+        let found = normalize(&lines[from..to].join("\n")).contains(&normalize(snippet));
+        verdict.snippet_found = Some(found);
+        if !found {
+            verdict.status = CitationStatus::SnippetNotFound;
+        }
+    }
+    verdict
+}
+
+fn read_lines(root: &Path, file_tree: &FileTree, file: &str) -> Option<Vec<String>> {
+    let entry = file_tree.get(file)?;
+    let source = if entry.language == Language::Pdf {
+        crate::index::pdf::convert_pdf(root, file, &cancel::current()).ok()?
+    } else {
+        encoding::read_file(root.join(file)).ok()?.0
+    };
+    Some(source.lines().map(str::to_string).collect())
+}
+
+/// Collapse runs of whitespace so a snippet matches regardless of how it
+/// was re-wrapped or re-indented.
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::config::DEFAULT_MAX_FILE_SIZE;
+    use crate::index::walker;
+    use crate::server::state::temp_project;
+
+    /// 20 lines; line 10 holds the call, the rest are numbered comments.
+    fn lib_source() -> String {
+        (1..=20)
+            .map(|i| match i {
+                10 => "    let total = compute(a,\n        b);\n".to_string(),
+                _ => format!("// line {}\n", i),
+            })
+            .collect()
+    }
+
+    fn fixture() -> (std::path::PathBuf, Arc<FileTree>) {
+        let dir = temp_project(&[
+            ("src/lib.rs", &lib_source()),
+            ("src/ops/repl.rs", "fn repl() {}\n"),
+            ("src/a/mod.rs", "mod a;\n"),
+            ("src/b/mod.rs", "mod b;\n"),
+        ]);
+        let file_tree = Arc::new(FileTree::new());
+        walker::scan_directory(&dir, &file_tree, DEFAULT_MAX_FILE_SIZE).unwrap();
+        (dir, file_tree)
+    }
+
+    fn rules(check_snippets: bool) -> CitationRules {
+        CitationRules::new(None, None, check_snippets).unwrap()
+    }
+
+    fn verdicts(dir: &Path, file_tree: &FileTree, text: &str, check_snippets: bool) -> Vec<CitationVerdict> {
+        validate_citations(dir, file_tree, &json!(text), &rules(check_snippets)).citations
+    }
+
+    fn extracted(text: &str) -> Vec<String> {
+        extract_citations(text, &rules(true))
+            .into_iter()
+            .map(|c| c.text)
+            .collect()
+    }
+
+    #[test]
+    fn resolve_path_accepts_the_usual_spellings() {
+        let (dir, file_tree) = fixture();
+        let resolve = |path: &str| resolve_path(&dir, &file_tree, path);
+
+        assert_eq!(resolve("src/lib.rs").as_deref(), Some("src/lib.rs"));
+        assert_eq!(resolve("./src/lib.rs").as_deref(), Some("src/lib.rs"));
+        assert_eq!(resolve(r"src\ops\repl.rs").as_deref(), Some("src/ops/repl.rs"));
+        let absolute = dir.join("src/lib.rs");
+        assert_eq!(resolve(absolute.to_str().unwrap()).as_deref(), Some("src/lib.rs"));
+        // A unique suffix is enough.
+        assert_eq!(resolve("ops/repl.rs").as_deref(), Some("src/ops/repl.rs"));
+        assert_eq!(resolve("repl.rs").as_deref(), Some("src/ops/repl.rs"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn resolve_path_rejects_ambiguous_and_outside_paths() {
+        let (dir, file_tree) = fixture();
+        let resolve = |path: &str| resolve_path(&dir, &file_tree, path);
+
+        assert_eq!(resolve("mod.rs"), None);
+        assert_eq!(resolve("a/mod.rs").as_deref(), Some("src/a/mod.rs"));
+        assert_eq!(resolve("../src/lib.rs"), None);
+        assert_eq!(resolve("/elsewhere/src/lib.rs"), None);
+        assert_eq!(resolve("src/missing.rs"), None);
+        // A suffix must end at a separator.
+        assert_eq!(resolve("ib.rs"), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn line_ranges_are_checked() {
+        let (dir, file_tree) = fixture();
+        let statuses: Vec<CitationStatus> = verdicts(
+            &dir,
+            &file_tree,
+            "See src/lib.rs:3, src/lib.rs:21-22, src/lib.rs:0, src/lib.rs:8-4 and src/lib.rs:1-21.",
+            true,
+        )
+        .into_iter()
+        .map(|v| v.status)
+        .collect();
+        assert_eq!(
+            statuses,
+            [
+                CitationStatus::Ok,
+                CitationStatus::LineOutOfRange,
+                CitationStatus::LineOutOfRange,
+                CitationStatus::LineOutOfRange,
+                CitationStatus::Ok,
+            ]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn snippets_are_found_within_the_window_ignoring_whitespace() {
+        let (dir, file_tree) = fixture();
+
+        // Re-wrapped onto one line, cited a few lines off.
+        let near = verdicts(&dir, &file_tree, "src/lib.rs:13 `let total = compute(a, b);`", true);
+        assert_eq!(near[0].snippet.as_deref(), Some("let total = compute(a, b);"));
+        assert_eq!(near[0].snippet_found, Some(true));
+        assert_eq!(near[0].status, CitationStatus::Ok);
+
+        let far = verdicts(&dir, &file_tree, r#"src/lib.rs:20 - "compute(a,""#, true);
+        assert_eq!(far[0].snippet_found, Some(false));
+        assert_eq!(far[0].status, CitationStatus::SnippetNotFound);
+
+        let made_up = verdicts(&dir, &file_tree, "`src/lib.rs:10` `fn does_not_exist()`", true);
+        assert_eq!(made_up[0].status, CitationStatus::SnippetNotFound);
+
+        // With snippet checks off, only the lines are checked.
+        let unchecked = verdicts(&dir, &file_tree, "src/lib.rs:20 `fn does_not_exist()`", false);
+        assert_eq!(unchecked[0].snippet_found, None);
+        assert_eq!(unchecked[0].status, CitationStatus::Ok);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_files_get_suggestions() {
+        let (dir, file_tree) = fixture();
+        let report = validate_citations(
+            &dir,
+            &file_tree,
+            &json!({ "answer": "It's in lib/repl.rs:1", "see": ["src/lib.rs"] }),
+            &rules(true),
+        );
+        assert_eq!((report.total, report.valid, report.invalid), (2, 1, 1));
+        let missing = &report.citations[0];
+        assert_eq!(missing.status, CitationStatus::FileNotFound);
+        assert_eq!(missing.suggestions, ["src/ops/repl.rs"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn path_like_strings_that_are_not_paths_are_ignored() {
+        assert!(extracted("see https://example.com/docs/index.html").is_empty());
+        assert!(extracted("mail me at dev@example.com").is_empty());
+        assert!(extracted("call std::fs::read.rs later").is_empty());
+        assert!(extracted("e.g. this, i.e. that").is_empty());
+        assert!(extracted("self.value and obj.method").is_empty());
+        assert!(extracted("version 1.2.3 and v2.0").is_empty());
+        assert!(extracted("config.rsx_thing").is_empty());
+        assert_eq!(extracted("in main.rs, and Cargo.lock"), ["main.rs", "Cargo.lock"]);
+        assert_eq!(extracted("(src/lib.rs:4-6)"), ["src/lib.rs:4-6"]);
+        // A `/` makes any extension plausible.
+        assert_eq!(extracted("docs/notes.weird"), ["docs/notes.weird"]);
+    }
+
+    #[test]
+    fn custom_rules_are_validated_and_applied() {
+        assert!(CitationRules::new(Some("(unclosed"), None, true).is_err());
+        assert!(CitationRules::new(Some(r"\w+\.rs"), None, true).is_err());
+
+        let only_py = CitationRules::new(None, Some(vec![".PY".into()]), true).unwrap();
+        let found: Vec<String> = extract_citations("main.rs and tool.py", &only_py)
+            .into_iter()
+            .map(|c| c.path)
+            .collect();
+        assert_eq!(found, ["tool.py"]);
+    }
+}
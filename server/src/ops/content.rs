@@ -1,28 +1,135 @@
+use std::collections::HashMap;
 use std::path::Path;
 use std::sync::Arc;
 
 use regex::Regex;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
+use crate::config::{PEEK_EXPAND_MAX_LINES, SUGGESTION_LIMIT, SUGGESTION_SCAN_LIMIT};
+use crate::index::encoding::{self, SourceEncoding};
 use crate::index::file_entry::Language;
-use crate::index::file_tree::FileTree;
+use crate::index::file_tree::{FileTree, MarkFilter};
+use crate::index::rel_path;
+use crate::index::text_index::TextIndexStats;
+use crate::ops::symbol_ops::{self, EnclosingSymbol};
+use crate::server::cancel;
 use crate::symbols::queries;
+use crate::symbols::symbol::{Symbol, SymbolKind};
+use crate::symbols::SymbolTable;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct PeekResponse {
     pub file: String,
     pub start_line: usize,
     pub end_line: usize,
     pub total_lines: usize,
     pub content: String,
+    /// True when the requested range was adjusted to fit the file.
+    pub clamped: bool,
+    /// 0-indexed start actually served (inclusive).
+    pub resolved_start: usize,
+    /// 0-indexed end actually served (exclusive).
+    pub resolved_end: usize,
+    /// 0-indexed range as requested, before clamping or expansion.
+    pub requested_start: usize,
+    pub requested_end: usize,
+    /// With symbol expansion, the symbols the requested range cut through
+    /// that were pulled in whole.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub expanded_by: Vec<String>,
+    /// With `include_columns`, the served lines with token positions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lines: Option<Vec<PeekLine>>,
+    /// When the peek was addressed by symbol, the symbol it resolved to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<PeekedSymbol>,
+    /// The range was taken from `symbol` rather than `start`/`end`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub symbol_resolved: bool,
+    /// With `symbol_resolved`, the symbol's own lines as `start`/`end`
+    /// would have requested them, before context or clamping.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved_lines: Option<ResolvedLines>,
+    /// Present when the file had a BOM, CRLF line endings, or non-UTF-8
+    /// bytes; `content` is the normalized text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<SourceEncoding>,
 }
 
+/// 0-indexed half-open line range, in the form of peek's `start`/`end`.
+#[derive(Debug, Clone, Copy, Serialize, ToSchema)]
+pub struct ResolvedLines {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Where a symbol-addressed peek's symbol sits in the file.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PeekedSymbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// 1-based, inclusive.
+    pub line_range: (usize, usize),
+    /// Byte offsets into the file, end exclusive.
+    pub byte_range: (usize, usize),
+    /// The requested name only matched through the case-insensitive
+    /// fallback of its language.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub matched_case_insensitively: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PeekLine {
+    /// 1-based line number.
+    pub number: usize,
+    pub content: String,
+    /// Empty for languages without a tree-sitter grammar.
+    pub tokens: Vec<LineToken>,
+}
+
+/// A token on one line. Columns count characters from 0; `col_end` is
+/// exclusive.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LineToken {
+    /// `identifier`, `type`, `string`, `number`, or `comment`.
+    pub kind: &'static str,
+    pub col_start: usize,
+    pub col_end: usize,
+    pub text: String,
+}
+
+/// Indexed paths with the same file name as `file`, to try when `file`
+/// isn't in the index. Only the first `SUGGESTION_SCAN_LIMIT` paths are
+/// examined.
+pub fn suggest_files(file_tree: &FileTree, file: &str) -> Vec<String> {
+    let name = file.rsplit('/').next().unwrap_or(file);
+    if name.is_empty() {
+        return Vec::new();
+    }
+    let mut paths: Vec<String> = file_tree
+        .files
+        .iter()
+        .take(SUGGESTION_SCAN_LIMIT)
+        .filter(|entry| entry.key() != file && entry.key().rsplit('/').next() == Some(name))
+        .map(|entry| entry.key().clone())
+        .collect();
+    paths.sort();
+    paths.truncate(SUGGESTION_LIMIT);
+    paths
+}
+
+/// Serve lines `[start, end)` of `file`. With `expand_symbols`, the range
+/// grows to cover whole any symbol it partially overlaps, as long as the
+/// total growth stays within `PEEK_EXPAND_MAX_LINES`.
 pub fn peek(
     root: &Path,
     file_tree: &Arc<FileTree>,
     file: &str,
     start: usize,
     end: usize,
+    expand_symbols: Option<&SymbolTable>,
+    include_columns: bool,
 ) -> Result<PeekResponse, String> {
     let entry = match file_tree.get(file) {
         Some(e) => e,
@@ -30,17 +137,25 @@ pub fn peek(
     };
 
     let abs_path = root.join(file);
-    let source = if entry.language == Language::Pdf {
-        crate::index::pdf::convert_pdf(root, file)
-            .map_err(|e| format!("PDF conversion failed for '{}': {}", file, e))?
+    let (source, source_encoding) = if entry.language == Language::Pdf {
+        let text = crate::index::pdf::convert_pdf(root, file, &cancel::current())
+            .map_err(|e| format!("PDF conversion failed for '{}': {}", file, e))?;
+        (text, SourceEncoding::default())
     } else {
-        std::fs::read_to_string(&abs_path).map_err(|e| format!("Failed to read '{}': {}", file, e))?
+        encoding::read_file(&abs_path).map_err(|e| format!("Failed to read '{}': {}", file, e))?
     };
 
+    let (requested_start, requested_end) = (start, end);
     let lines: Vec<&str> = source.lines().collect();
     let total_lines = lines.len();
-    let start = start.min(total_lines);
-    let end = end.min(total_lines);
+    let (clamped_start, clamped_end) = clamp_range(start, end, total_lines);
+    let clamped = (clamped_start, clamped_end) != (start, end);
+    let (start, end, expanded_by) = match expand_symbols {
+        Some(symbol_table) if clamped_start < clamped_end => {
+            expand_to_symbols(symbol_table, file, clamped_start, clamped_end, total_lines)
+        }
+        _ => (clamped_start, clamped_end, Vec::new()),
+    };
 
     let content: String = lines[start..end]
         .iter()
@@ -48,6 +163,7 @@ pub fn peek(
         .map(|(i, line)| format!("{:>6} │ {}", start + i + 1, line))
         .collect::<Vec<_>>()
         .join("\n");
+    let lines = include_columns.then(|| peek_lines(&source, entry.language, &lines, start, end));
 
     Ok(PeekResponse {
         file: file.to_string(),
@@ -55,24 +171,399 @@ pub fn peek(
         end_line: end,
         total_lines,
         content,
+        clamped,
+        resolved_start: start,
+        resolved_end: end,
+        requested_start,
+        requested_end,
+        expanded_by,
+        lines,
+        symbol: None,
+        symbol_resolved: false,
+        resolved_lines: None,
+        encoding: source_encoding.note(),
     })
 }
 
-#[derive(Debug, Serialize)]
+/// Lines `[start, end)` with their tokens. A token spanning several lines
+/// (a block comment, a multi-line string) is reported on its first line
+/// only, cut off at the end of that line.
+fn peek_lines(
+    source: &str,
+    language: Language,
+    lines: &[&str],
+    start: usize,
+    end: usize,
+) -> Vec<PeekLine> {
+    let mut peek_lines: Vec<PeekLine> = (start..end)
+        .map(|i| PeekLine {
+            number: i + 1,
+            content: lines[i].to_string(),
+            tokens: Vec::new(),
+        })
+        .collect();
+
+    let Some(config) = queries::get_language_config(language) else {
+        return peek_lines;
+    };
+    let mut parser = tree_sitter::Parser::new();
+    if parser.set_language(&config.language).is_err() {
+        return peek_lines;
+    }
+    let Some(tree) = parser.parse(source, None) else {
+        return peek_lines;
+    };
+    collect_tokens(tree.root_node(), lines, start, end, &mut peek_lines);
+    peek_lines
+}
+
+fn collect_tokens(
+    node: tree_sitter::Node,
+    lines: &[&str],
+    start: usize,
+    end: usize,
+    out: &mut [PeekLine],
+) {
+    let (first_row, last_row) = (node.start_position().row, node.end_position().row);
+    if last_row < start || first_row >= end {
+        return;
+    }
+    if let Some(kind) = token_kind(node.kind()) {
+        if first_row >= start
+            && let Some(line) = lines.get(first_row)
+        {
+            let byte_start = node.start_position().column.min(line.len());
+            let byte_end = if last_row == first_row {
+                node.end_position().column.min(line.len())
+            } else {
+                line.len()
+            };
+            if line.is_char_boundary(byte_start) && line.is_char_boundary(byte_end) {
+                let col_start = line[..byte_start].chars().count();
+                let text = &line[byte_start..byte_end];
+                out[first_row - start].tokens.push(LineToken {
+                    kind,
+                    col_start,
+                    col_end: col_start + text.chars().count(),
+                    text: text.to_string(),
+                });
+            }
+        }
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_tokens(child, lines, start, end, out);
+    }
+}
+
+/// Token class for a node kind, across the supported grammars. Strings and
+/// comments are taken whole rather than descended into.
+fn token_kind(kind: &str) -> Option<&'static str> {
+    match kind {
+        "type_identifier" | "primitive_type" | "predefined_type" => Some("type"),
+        k if k.ends_with("identifier") => Some("identifier"),
+        "string_literal" | "raw_string_literal" | "char_literal" | "string"
+        | "interpreted_string_literal" | "rune_literal" | "template_string" => Some("string"),
+        "integer_literal" | "float_literal" | "int_literal" | "imaginary_literal"
+        | "integer" | "float" | "number" => Some("number"),
+        "comment" | "line_comment" | "block_comment" => Some("comment"),
+        _ => None,
+    }
+}
+
+/// One file range in a `multi_peek` batch. The range defaults to the first
+/// 100 lines, as with a single peek.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PeekRequest {
+    #[serde(deserialize_with = "rel_path::deserialize")]
+    pub file: String,
+    pub start: Option<usize>,
+    pub end: Option<usize>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MultiPeekEntry {
+    #[serde(flatten)]
+    pub peek: PeekResponse,
+    pub lines_returned: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MultiPeekError {
+    pub file: String,
+    pub error: String,
+    /// Indexed paths with the same file name, when `file` isn't indexed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MultiPeekResult {
+    /// Successful peeks, in request order.
+    pub results: Vec<MultiPeekEntry>,
+    /// Files that could not be served; they don't fail the rest.
+    pub errors: Vec<MultiPeekError>,
+}
+
+/// Peek each requested range in turn, collecting per-file failures in
+/// `errors` instead of stopping.
+pub fn multi_peek(
+    root: &Path,
+    file_tree: &Arc<FileTree>,
+    requests: &[PeekRequest],
+) -> MultiPeekResult {
+    let mut results = Vec::new();
+    let mut errors = Vec::new();
+    for req in requests {
+        let start = req.start.unwrap_or(0);
+        let end = req.end.unwrap_or(100);
+        match peek(root, file_tree, &req.file, start, end, None, false) {
+            Ok(peek) => results.push(MultiPeekEntry {
+                lines_returned: peek.resolved_end - peek.resolved_start,
+                peek,
+            }),
+            Err(error) => {
+                let suggestions = if file_tree.get(&req.file).is_none() {
+                    suggest_files(file_tree, &req.file)
+                } else {
+                    Vec::new()
+                };
+                errors.push(MultiPeekError {
+                    file: req.file.clone(),
+                    error,
+                    suggestions,
+                });
+            }
+        }
+    }
+    MultiPeekResult { results, errors }
+}
+
+/// Grow the 0-indexed `[start, end)` to cover the symbols in `file` that it
+/// partially overlaps, in line order, skipping any that would push the total
+/// growth past `PEEK_EXPAND_MAX_LINES`.
+fn expand_to_symbols(
+    symbol_table: &SymbolTable,
+    file: &str,
+    start: usize,
+    end: usize,
+    total_lines: usize,
+) -> (usize, usize, Vec<String>) {
+    let mut symbols = symbol_table.list_by_file(file);
+    symbols.sort_by_key(|s| s.line_range);
+
+    let (mut lo, mut hi) = (start, end);
+    let mut expanded_by = Vec::new();
+    for sym in symbols {
+        // line_range is 1-indexed and inclusive.
+        let sym_start = sym.line_range.0.saturating_sub(1);
+        let sym_end = sym.line_range.1.min(total_lines);
+        let overlaps = sym_start < end && sym_end > start;
+        let cut = sym_start < start || sym_end > end;
+        if !overlaps || !cut {
+            continue;
+        }
+        let (new_lo, new_hi) = (lo.min(sym_start), hi.max(sym_end));
+        if (start - new_lo) + (new_hi - end) > PEEK_EXPAND_MAX_LINES {
+            continue;
+        }
+        (lo, hi) = (new_lo, new_hi);
+        expanded_by.push(sym.name);
+    }
+    (lo, hi, expanded_by)
+}
+
+/// Clamp a half-open `[start, end)` range to `[0, len]`, collapsing it to an
+/// empty range at `start` when `start > end`.
+pub fn clamp_range(start: usize, end: usize, len: usize) -> (usize, usize) {
+    let start = start.min(len);
+    let end = end.min(len).max(start);
+    (start, end)
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct GrepResponse {
     pub pattern: String,
     pub matches: Vec<GrepMatch>,
     pub total_matches: usize,
     pub truncated: bool,
+    /// Project-wide grep only: files left after `path_pattern` and mark
+    /// filtering, and files those filters excluded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub searched_files: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filtered_files: Option<usize>,
+    /// With `format=blocks`, matches grouped into merged context blocks per
+    /// file; `matches` is then empty.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<Vec<GrepFileBlocks>>,
+    /// With `group_by=file`, `matches` regrouped per file; `matches` is then
+    /// empty.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub groups: Option<Vec<GrepFileGroup>>,
+    /// With `files_only`, the files that matched (or, inverted, the files
+    /// where the pattern never appears); `matches` is then empty.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_paths: Option<Vec<String>>,
+    /// Matches are lines where the pattern does not appear.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub inverted: bool,
+    /// Matches found per file, including any left out of the response.
+    #[serde(skip)]
+    pub file_match_counts: Vec<(String, usize)>,
+    /// `code` when every match was checked against comments and strings,
+    /// `all` when none or only some were.
+    pub scope_used: &'static str,
+    /// Why `scope=code` could not be applied to every match.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope_fallback_reason: Option<String>,
+    /// The request was cancelled mid-scan; the matches are those found in
+    /// the files scanned before it stopped.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GrepFileGroup {
+    pub file: String,
+    /// Matches found in the file; `matches` may hold fewer.
+    pub match_count: usize,
+    pub matches: Vec<GrepMatch>,
+}
+
+/// Order of `group_by=file` groups.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupSort {
+    /// Most matches first, then by path.
+    Count,
+    Path,
 }
 
-#[derive(Debug, Serialize)]
+impl GroupSort {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "count" => Some(GroupSort::Count),
+            "path" => Some(GroupSort::Path),
+            _ => None,
+        }
+    }
+}
+
+impl GrepResponse {
+    /// Move `matches` into per-file groups, ordered by `sort`. Files with no
+    /// returned matches are left out.
+    pub fn group_by_file(&mut self, sort: GroupSort) {
+        let counts: HashMap<&str, usize> = self
+            .file_match_counts
+            .iter()
+            .map(|(file, count)| (file.as_str(), *count))
+            .collect();
+        let mut groups: Vec<GrepFileGroup> = Vec::new();
+        for m in std::mem::take(&mut self.matches) {
+            match groups.last_mut() {
+                Some(group) if group.file == m.file => group.matches.push(m),
+                _ => groups.push(GrepFileGroup {
+                    file: m.file.clone(),
+                    match_count: counts.get(m.file.as_str()).copied().unwrap_or(1),
+                    matches: vec![m],
+                }),
+            }
+        }
+        match sort {
+            GroupSort::Count => groups.sort_by(|a, b| {
+                b.match_count.cmp(&a.match_count).then_with(|| a.file.cmp(&b.file))
+            }),
+            GroupSort::Path => groups.sort_by(|a, b| a.file.cmp(&b.file)),
+        }
+        self.groups = Some(groups);
+    }
+
+    /// Attach the innermost enclosing definition to each match, from the
+    /// symbols already extracted for its file. Files without a grammar or
+    /// without symbols are skipped. Call before `group_by_file`.
+    pub fn resolve_enclosing_symbols(&mut self, file_tree: &FileTree, symbol_table: &SymbolTable) {
+        let mut by_file: HashMap<String, Vec<Symbol>> = HashMap::new();
+        for m in &mut self.matches {
+            let symbols = by_file.entry(m.file.clone()).or_insert_with(|| {
+                let supported = file_tree
+                    .get(&m.file)
+                    .is_some_and(|entry| entry.language.has_tree_sitter_support());
+                if supported {
+                    symbol_table.list_by_file(&m.file)
+                } else {
+                    Vec::new()
+                }
+            });
+            m.enclosing_symbol =
+                symbol_ops::enclosing_symbol(symbols, m.line).map(|s| EnclosingSymbol {
+                    name: s.name.clone(),
+                    kind: s.kind,
+                    file: s.file.clone(),
+                });
+        }
+    }
+
+    /// File and line of every returned match, whatever the layout.
+    pub fn match_locations(&self) -> Vec<(&str, usize)> {
+        if let Some(files) = &self.files {
+            return files
+                .iter()
+                .flat_map(|f| {
+                    f.blocks
+                        .iter()
+                        .flat_map(|b| b.match_lines.iter().map(|line| (f.file.as_str(), *line)))
+                })
+                .collect();
+        }
+        let matches: Box<dyn Iterator<Item = &GrepMatch>> = match &self.groups {
+            Some(groups) => Box::new(groups.iter().flat_map(|g| g.matches.iter())),
+            None => Box::new(self.matches.iter()),
+        };
+        matches.map(|m| (m.file.as_str(), m.line)).collect()
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct GrepMatch {
     pub file: String,
     pub line: usize,
     pub text: String,
     pub context_before: Vec<String>,
     pub context_after: Vec<String>,
+    /// Innermost definition containing the match, with `resolve_symbols`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enclosing_symbol: Option<EnclosingSymbol>,
+}
+
+/// A run of lines covering one or more matches and their context. Matches
+/// whose context windows overlap or touch share a block.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GrepBlock {
+    /// 1-indexed, inclusive.
+    pub start_line: usize,
+    pub end_line: usize,
+    /// 1-indexed lines in the block that matched.
+    pub match_lines: Vec<usize>,
+    pub lines: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GrepFileBlocks {
+    pub file: String,
+    pub blocks: Vec<GrepBlock>,
+    /// Matches left out because the file hit `max_blocks_per_file`.
+    pub omitted_matches: usize,
+}
+
+/// How grep lays out matches: one entry per matching line, ripgrep-style
+/// merged blocks capped at `max_per_file` per file, or just the paths of
+/// matching files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrepFormat {
+    Flat,
+    Blocks { max_per_file: usize },
+    FilesOnly,
 }
 
 /// Scope filter for grep: restrict matches to code only (skip comments/strings).
@@ -103,9 +594,80 @@ pub fn grep(
     max_matches: usize,
     context_lines: usize,
 ) -> Result<GrepResponse, String> {
-    grep_with_scope(root, file_tree, pattern, max_matches, context_lines, GrepScope::All)
+    grep_with_scope(
+        root,
+        file_tree,
+        pattern,
+        max_matches,
+        context_lines,
+        GrepScope::All,
+        &[],
+        GrepFormat::Flat,
+        None,
+        &MarkFilter::default(),
+        false,
+    )
+}
+
+/// Like `grep`, but a match is a line that does not contain `pattern`, as
+/// with `grep -v`.
+#[allow(dead_code)]
+pub fn grep_inverted(
+    root: &Path,
+    file_tree: &Arc<FileTree>,
+    pattern: &str,
+    max_matches: usize,
+    context_lines: usize,
+) -> Result<GrepResponse, String> {
+    grep_with_scope(
+        root,
+        file_tree,
+        pattern,
+        max_matches,
+        context_lines,
+        GrepScope::All,
+        &[],
+        GrepFormat::Flat,
+        None,
+        &MarkFilter::default(),
+        true,
+    )
+}
+
+/// Build the project's trigram index from scratch, attaching one first when
+/// the server runs without `--text-index`. Grep scans every file while the
+/// build runs, then prefilters with the index; file changes keep it current
+/// from then on.
+pub fn build_trigram_index(root: &Path, file_tree: &FileTree) -> TextIndexStats {
+    file_tree.enable_text_index().clear();
+    file_tree
+        .build_text_index(root)
+        .expect("text index was just enabled")
+}
+
+/// Compile `path_pattern` globs. They match paths relative to the project
+/// root; `*` stays within one directory and `**` spans any number.
+pub fn compile_path_patterns(patterns: &[String]) -> Result<Vec<glob::Pattern>, String> {
+    patterns
+        .iter()
+        .map(|p| {
+            glob::Pattern::new(p).map_err(|e| format!("Invalid path_pattern '{}': {}", p, e))
+        })
+        .collect()
+}
+
+fn matches_path_patterns(patterns: &[glob::Pattern], rel_path: &str) -> bool {
+    let options = glob::MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    };
+    patterns.is_empty() || patterns.iter().any(|p| p.matches_with(rel_path, options))
 }
 
+/// Grep the indexed files in path order. A cancelled request stops the scan
+/// between files and gets the matches found so far, flagged `cancelled`.
+#[allow(clippy::too_many_arguments)]
 pub fn grep_with_scope(
     root: &Path,
     file_tree: &Arc<FileTree>,
@@ -113,101 +675,360 @@ pub fn grep_with_scope(
     max_matches: usize,
     context_lines: usize,
     scope: GrepScope,
+    path_patterns: &[glob::Pattern],
+    format: GrepFormat,
+    max_matches_per_file: Option<usize>,
+    marks: &MarkFilter,
+    invert: bool,
 ) -> Result<GrepResponse, String> {
     let re = Regex::new(pattern).map_err(|e| format!("Invalid regex: {}", e))?;
+    let mut matcher = LineMatcher::new(&re, scope, context_lines, max_matches, format);
+    matcher.max_per_file = max_matches_per_file;
+    matcher.invert = invert;
 
-    let mut matches = Vec::new();
-    let mut total = 0;
-
+    let mut filtered_files = 0;
     let mut paths: Vec<(String, Language)> = file_tree
         .files
         .iter()
+        .filter(|e| {
+            let keep = matches_path_patterns(path_patterns, e.key()) && marks.allows(e.value());
+            filtered_files += usize::from(!keep);
+            keep
+        })
         .map(|e| (e.key().clone(), e.value().language))
         .collect();
     paths.sort_by(|a, b| a.0.cmp(&b.0));
 
+    let total_files = paths.len();
+    // Inverted grep wants exactly the files the index would drop.
+    if !invert
+        && let Some(index) = file_tree.text_index()
+        && index.retain_candidates(pattern, &mut paths, |(path, _)| path)
+    {
+        tracing::debug!(
+            "grep '{}': text index kept {} of {} files",
+            pattern,
+            paths.len(),
+            total_files
+        );
+    }
+
+    let cancel = cancel::current();
+    let mut cancelled = false;
     for (rel_path, language) in &paths {
+        if cancel.is_cancelled() {
+            cancelled = true;
+            break;
+        }
         let abs_path = root.join(rel_path);
         let source = if *language == Language::Pdf {
-            match crate::index::pdf::convert_pdf(root, rel_path) {
+            match crate::index::pdf::convert_pdf(root, rel_path, &cancel) {
                 Ok(s) => s,
                 Err(_) => continue,
             }
         } else {
-            match std::fs::read_to_string(&abs_path) {
+            match encoding::read_to_string(&abs_path) {
                 Ok(s) => s,
                 Err(_) => continue,
             }
         };
+        matcher.scan(rel_path, &source, *language, None);
+    }
 
-        // For scope=code, build a set of byte ranges that are inside comments/strings
-        let excluded_ranges = if scope == GrepScope::Code && language.has_tree_sitter_support() {
-            compute_non_code_ranges(&source, *language)
-        } else {
-            Vec::new()
+    let mut response = matcher.finish(pattern);
+    response.searched_files = Some(total_files);
+    response.filtered_files = Some(filtered_files);
+    response.cancelled = cancelled;
+    Ok(response)
+}
+
+/// Grep one already-loaded file, restricted to the 0-indexed half-open line
+/// range `within`. Line numbers stay absolute within the file.
+#[allow(clippy::too_many_arguments)]
+pub fn grep_lines(
+    rel_path: &str,
+    source: &str,
+    language: Language,
+    within: (usize, usize),
+    pattern: &str,
+    max_matches: usize,
+    context_lines: usize,
+    scope: GrepScope,
+) -> Result<GrepResponse, String> {
+    let re = Regex::new(pattern).map_err(|e| format!("Invalid regex: {}", e))?;
+    let mut matcher = LineMatcher::new(&re, scope, context_lines, max_matches, GrepFormat::Flat);
+    matcher.scan(rel_path, source, language, Some(within));
+    Ok(matcher.finish(pattern))
+}
+
+/// Lines in `lo..hi` (0-indexed) whose text matches `re`, as the line index
+/// and the byte offset of the first match within `source`. With scope=code,
+/// a line is skipped when that first match sits in a comment or string.
+pub fn matching_lines(
+    re: &Regex,
+    scope: GrepScope,
+    source: &str,
+    language: Language,
+    lines: &[&str],
+    lo: usize,
+    hi: usize,
+) -> Vec<(usize, usize)> {
+    // For scope=code, build a set of byte ranges that are inside comments/strings
+    let excluded_ranges = if scope == GrepScope::Code && language.has_tree_sitter_support() {
+        compute_non_code_ranges(source, language)
+    } else {
+        Vec::new()
+    };
+
+    let mut line_offsets = Vec::with_capacity(lines.len());
+    let mut offset = 0;
+    for line in lines {
+        line_offsets.push(offset);
+        offset += line.len() + 1; // +1 for newline
+    }
+
+    let mut hits = Vec::new();
+    for i in lo..hi.min(lines.len()) {
+        // Find where in the line the regex matched
+        let Some(m) = re.find(lines[i]) else {
+            continue;
         };
+        let match_byte = line_offsets[i] + m.start();
+        if is_in_excluded_range(match_byte, &excluded_ranges) {
+            continue;
+        }
+        hits.push((i, match_byte));
+    }
+    hits
+}
 
-        let lines: Vec<&str> = source.lines().collect();
+/// Line-matching core shared by project-wide and per-symbol grep.
+struct LineMatcher<'a> {
+    re: &'a Regex,
+    scope: GrepScope,
+    context_lines: usize,
+    max_matches: usize,
+    format: GrepFormat,
+    /// Matches returned per file before the rest of the file is skipped
+    /// without using up `max_matches`.
+    max_per_file: Option<usize>,
+    /// Report the lines `re` does not match instead.
+    invert: bool,
+    matches: Vec<GrepMatch>,
+    files: Vec<GrepFileBlocks>,
+    file_paths: Vec<String>,
+    file_match_counts: Vec<(String, usize)>,
+    /// With scope=code, languages of files with matches that have no
+    /// grammar, so their matches were taken unfiltered.
+    fallback_languages: Vec<Language>,
+    /// Matches included in the response, in either format.
+    returned: usize,
+    total: usize,
+}
 
-        // Pre-compute line byte offsets for scope filtering
-        let line_offsets: Vec<usize> = if scope == GrepScope::Code {
-            let mut offsets = Vec::with_capacity(lines.len());
-            let mut offset = 0;
-            for line in &lines {
-                offsets.push(offset);
-                offset += line.len() + 1; // +1 for newline
+impl<'a> LineMatcher<'a> {
+    fn new(
+        re: &'a Regex,
+        scope: GrepScope,
+        context_lines: usize,
+        max_matches: usize,
+        format: GrepFormat,
+    ) -> Self {
+        Self {
+            re,
+            scope,
+            context_lines,
+            max_matches,
+            format,
+            max_per_file: None,
+            invert: false,
+            matches: Vec::new(),
+            files: Vec::new(),
+            file_paths: Vec::new(),
+            file_match_counts: Vec::new(),
+            fallback_languages: Vec::new(),
+            returned: 0,
+            total: 0,
+        }
+    }
+
+    /// Match every line of `source`, or only the 0-indexed half-open line
+    /// range `within` (context is clipped to it as well).
+    fn scan(
+        &mut self,
+        rel_path: &str,
+        source: &str,
+        language: Language,
+        within: Option<(usize, usize)>,
+    ) {
+        let lines: Vec<&str> = source.lines().collect();
+        let (lo, hi) = within.map_or((0, lines.len()), |(lo, hi)| {
+            (lo.min(lines.len()), hi.min(lines.len()))
+        });
+        let matched: Vec<usize> =
+            matching_lines(self.re, self.scope, source, language, &lines, lo, hi)
+                .into_iter()
+                .map(|(i, _)| i)
+                .collect();
+        if self.format == GrepFormat::FilesOnly {
+            // Inverted, a file counts only when the pattern never appears.
+            if matched.is_empty() == self.invert {
+                self.total += 1;
+                if self.file_paths.len() < self.max_matches {
+                    self.file_paths.push(rel_path.to_string());
+                    self.returned += 1;
+                }
             }
-            offsets
+            return;
+        }
+        let mut hits = if self.invert {
+            let mut matched = matched.into_iter().peekable();
+            (lo..hi)
+                .filter(|&i| matched.next_if_eq(&i).is_none())
+                .collect()
         } else {
-            Vec::new()
+            matched
         };
+        if hits.is_empty() {
+            return;
+        }
+        if self.scope == GrepScope::Code
+            && !language.has_tree_sitter_support()
+            && !self.fallback_languages.contains(&language)
+        {
+            self.fallback_languages.push(language);
+        }
+        self.file_match_counts.push((rel_path.to_string(), hits.len()));
+        if let Some(cap) = self.max_per_file
+            && hits.len() > cap
+        {
+            self.total += hits.len() - cap;
+            hits.truncate(cap);
+        }
+        if let GrepFormat::Blocks { max_per_file } = self.format {
+            self.scan_blocks(rel_path, &lines, (lo, hi), &hits, max_per_file);
+            return;
+        }
 
-        for (i, line) in lines.iter().enumerate() {
-            if re.is_match(line) {
-                // If scope=code, check that the match byte offset is not inside an excluded range
-                if scope == GrepScope::Code && !excluded_ranges.is_empty() {
-                    let line_start = line_offsets[i];
-                    // Find where in the line the regex matched
-                    if let Some(m) = re.find(line) {
-                        let match_byte = line_start + m.start();
-                        if is_in_excluded_range(match_byte, &excluded_ranges) {
-                            continue;
-                        }
-                    }
-                }
+        for i in hits {
+            let line = lines[i];
+            self.total += 1;
+            if self.matches.len() < self.max_matches {
+                let ctx_start = i.saturating_sub(self.context_lines).max(lo);
+                let ctx_end = (i + self.context_lines + 1).min(hi);
 
-                total += 1;
-                if matches.len() < max_matches {
-                    let ctx_start = i.saturating_sub(context_lines);
-                    let ctx_end = (i + context_lines + 1).min(lines.len());
+                let context_before: Vec<String> = lines[ctx_start..i]
+                    .iter()
+                    .map(|l| l.to_string())
+                    .collect();
+                let context_after: Vec<String> = lines[(i + 1)..ctx_end]
+                    .iter()
+                    .map(|l| l.to_string())
+                    .collect();
 
-                    let context_before: Vec<String> = lines[ctx_start..i]
-                        .iter()
-                        .map(|l| l.to_string())
-                        .collect();
-                    let context_after: Vec<String> = lines[(i + 1)..ctx_end]
-                        .iter()
-                        .map(|l| l.to_string())
-                        .collect();
-
-                    matches.push(GrepMatch {
-                        file: rel_path.clone(),
-                        line: i + 1,
-                        text: line.to_string(),
-                        context_before,
-                        context_after,
-                    });
+                self.matches.push(GrepMatch {
+                    file: rel_path.to_string(),
+                    line: i + 1,
+                    text: line.to_string(),
+                    context_before,
+                    context_after,
+                    enclosing_symbol: None,
+                });
+                self.returned += 1;
+            }
+        }
+    }
+
+    /// Merge the context windows of `hits` into blocks, the way `rg -C`
+    /// does. Once the file has `max_per_file` blocks, its later matches are
+    /// counted in `omitted_matches` without using up `max_matches`.
+    fn scan_blocks(
+        &mut self,
+        rel_path: &str,
+        lines: &[&str],
+        (lo, hi): (usize, usize),
+        hits: &[usize],
+        max_per_file: usize,
+    ) {
+        // 0-indexed half-open windows with their match lines.
+        let mut windows: Vec<(usize, usize, Vec<usize>)> = Vec::new();
+        let mut omitted_matches = 0;
+        for &i in hits {
+            self.total += 1;
+            if self.returned >= self.max_matches {
+                continue;
+            }
+            let start = i.saturating_sub(self.context_lines).max(lo);
+            let end = (i + self.context_lines + 1).min(hi);
+            let full = windows.len() >= max_per_file;
+            match windows.last_mut() {
+                Some(last) if start <= last.1 => {
+                    last.1 = last.1.max(end);
+                    last.2.push(i + 1);
+                }
+                _ if full => {
+                    omitted_matches += 1;
+                    continue;
                 }
+                _ => windows.push((start, end, vec![i + 1])),
             }
+            self.returned += 1;
         }
+        if windows.is_empty() && omitted_matches == 0 {
+            return;
+        }
+        let blocks = windows
+            .into_iter()
+            .map(|(start, end, match_lines)| GrepBlock {
+                start_line: start + 1,
+                end_line: end,
+                match_lines,
+                lines: lines[start..end].iter().map(|l| l.to_string()).collect(),
+            })
+            .collect();
+        self.files.push(GrepFileBlocks {
+            file: rel_path.to_string(),
+            blocks,
+            omitted_matches,
+        });
     }
 
-    Ok(GrepResponse {
-        pattern: pattern.to_string(),
-        matches,
-        total_matches: total,
-        truncated: total > max_matches,
-    })
+    fn finish(self, pattern: &str) -> GrepResponse {
+        let scope_fallback_reason = (!self.fallback_languages.is_empty()).then(|| {
+            let names: Vec<String> = self
+                .fallback_languages
+                .iter()
+                .map(|l| format!("{:?}", l).to_lowercase())
+                .collect();
+            format!(
+                "no tree-sitter support for language {}; matches there were not filtered",
+                names.join(", ")
+            )
+        });
+        if let Some(reason) = &scope_fallback_reason {
+            tracing::warn!("grep '{}' with scope=code fell back to all: {}", pattern, reason);
+        }
+        let scope_used = match self.scope {
+            GrepScope::Code if scope_fallback_reason.is_none() => "code",
+            _ => "all",
+        };
+        GrepResponse {
+            scope_used,
+            scope_fallback_reason,
+            pattern: pattern.to_string(),
+            matches: self.matches,
+            total_matches: self.total,
+            truncated: self.returned < self.total,
+            searched_files: None,
+            filtered_files: None,
+            files: matches!(self.format, GrepFormat::Blocks { .. }).then_some(self.files),
+            groups: None,
+            file_paths: (self.format == GrepFormat::FilesOnly).then_some(self.file_paths),
+            inverted: self.invert,
+            file_match_counts: self.file_match_counts,
+            cancelled: false,
+        }
+    }
 }
 
 /// Compute byte ranges of comment and string nodes using tree-sitter.
@@ -293,20 +1114,47 @@ fn is_in_excluded_range(byte_offset: usize, ranges: &[(usize, usize)]) -> bool {
         .is_ok()
 }
 
-#[derive(Debug, Serialize)]
+/// How `chunk_indices` places chunk ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkAlign {
+    /// Cut exactly every `size` bytes, possibly mid-line; overlap in bytes.
+    Byte,
+    /// End each chunk on a line boundary; overlap in lines.
+    Line,
+}
+
+impl ChunkAlign {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "byte" => Some(ChunkAlign::Byte),
+            "line" => Some(ChunkAlign::Line),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ChunkIndicesResponse {
     pub file: String,
     pub total_bytes: usize,
+    pub total_lines: usize,
     pub chunk_size: usize,
+    pub align: ChunkAlign,
+    /// Overlap in bytes (`align=byte`) or lines (`align=line`).
     pub overlap: usize,
     pub chunks: Vec<ChunkInfo>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ChunkInfo {
     pub index: usize,
+    /// Byte offsets, end exclusive.
     pub start: usize,
     pub end: usize,
+    /// 1-indexed lines containing the first and last byte of the chunk.
+    pub line_start: usize,
+    pub line_end: usize,
 }
 
 pub fn chunk_indices(
@@ -315,11 +1163,12 @@ pub fn chunk_indices(
     file: &str,
     size: usize,
     overlap: usize,
+    align: ChunkAlign,
 ) -> Result<ChunkIndicesResponse, String> {
     if size == 0 {
         return Err("Chunk size must be > 0".to_string());
     }
-    if overlap >= size {
+    if align == ChunkAlign::Byte && overlap >= size {
         return Err("Overlap must be < chunk size".to_string());
     }
     let entry = match file_tree.get(file) {
@@ -329,33 +1178,205 @@ pub fn chunk_indices(
 
     let abs_path = root.join(file);
     let source = if entry.language == Language::Pdf {
-        crate::index::pdf::convert_pdf(root, file)
+        crate::index::pdf::convert_pdf(root, file, &cancel::current())
             .map_err(|e| format!("PDF conversion failed for '{}': {}", file, e))?
     } else {
-        std::fs::read_to_string(&abs_path).map_err(|e| format!("Failed to read '{}': {}", file, e))?
+        encoding::read_to_string(&abs_path).map_err(|e| format!("Failed to read '{}': {}", file, e))?
     };
 
     let total_bytes = source.len();
+    // Byte offset at which each line begins.
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+        .filter(|&i| i < total_bytes)
+        .collect();
+    let line_of = |byte: usize| line_starts.partition_point(|&s| s <= byte).max(1);
+
+    let ranges = match align {
+        ChunkAlign::Byte => byte_chunks(total_bytes, size, overlap),
+        ChunkAlign::Line => line_chunks(&source, &line_starts, size, overlap),
+    };
+    let chunks = ranges
+        .into_iter()
+        .enumerate()
+        .map(|(index, (start, end))| ChunkInfo {
+            index,
+            start,
+            end,
+            line_start: line_of(start),
+            line_end: line_of(end.saturating_sub(1).max(start)),
+        })
+        .collect();
+
+    Ok(ChunkIndicesResponse {
+        file: file.to_string(),
+        total_bytes,
+        total_lines: line_starts.len(),
+        chunk_size: size,
+        align,
+        overlap,
+        chunks,
+    })
+}
+
+fn byte_chunks(total_bytes: usize, size: usize, overlap: usize) -> Vec<(usize, usize)> {
     let step = size - overlap;
     let mut chunks = Vec::new();
     let mut start = 0;
-    let mut index = 0;
 
     while start < total_bytes {
         let end = (start + size).min(total_bytes);
-        chunks.push(ChunkInfo { index, start, end });
-        index += 1;
+        chunks.push((start, end));
         start += step;
         if end >= total_bytes {
             break;
         }
     }
+    chunks
+}
 
-    Ok(ChunkIndicesResponse {
-        file: file.to_string(),
-        total_bytes,
-        chunk_size: size,
-        overlap,
-        chunks,
-    })
+/// Chunks of at most `size` bytes that end just after a newline. A line
+/// longer than `size` becomes a chunk of its own. Each chunk after the first
+/// starts `overlap_lines` lines before the previous one ended, as long as
+/// that still moves forward.
+fn line_chunks(
+    source: &str,
+    line_starts: &[usize],
+    size: usize,
+    overlap_lines: usize,
+) -> Vec<(usize, usize)> {
+    let total_bytes = source.len();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+
+    while start < total_bytes {
+        let limit = (start + size).min(total_bytes);
+        let end = if limit == total_bytes {
+            total_bytes
+        } else {
+            // Back off to the last line break inside the chunk; if there is
+            // none, run on to the end of this line.
+            match source[start..limit].rfind('\n') {
+                Some(i) => start + i + 1,
+                None => source[limit..]
+                    .find('\n')
+                    .map_or(total_bytes, |i| limit + i + 1),
+            }
+        };
+        chunks.push((start, end));
+        if end >= total_bytes {
+            break;
+        }
+
+        // `end` is a line start; step back `overlap_lines` lines from it.
+        let end_line = line_starts.partition_point(|&s| s < end);
+        let next = line_starts[end_line.saturating_sub(overlap_lines)];
+        start = if next > start { next } else { end };
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::config::DEFAULT_MAX_FILE_SIZE;
+    use crate::index::walker;
+    use crate::server::cancel::CancelToken;
+    use crate::server::state::temp_project;
+
+    fn indexed(files: &[(&str, &str)]) -> (std::path::PathBuf, Arc<FileTree>) {
+        let dir = temp_project(files);
+        let file_tree = Arc::new(FileTree::new());
+        walker::scan_directory(&dir, &file_tree, DEFAULT_MAX_FILE_SIZE).unwrap();
+        (dir, file_tree)
+    }
+
+    fn grep(root: &Path, file_tree: &Arc<FileTree>, pattern: &str) -> GrepResponse {
+        grep_with_scope(
+            root,
+            file_tree,
+            pattern,
+            100,
+            0,
+            GrepScope::All,
+            &[],
+            GrepFormat::Flat,
+            None,
+            &MarkFilter::default(),
+            false,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn cancelled_grep_returns_promptly() {
+        let files: Vec<(String, String)> = (0..500)
+            .map(|i| (format!("src/f{:03}.rs", i), "fn needle() {}\n".repeat(200)))
+            .collect();
+        let files: Vec<(&str, &str)> = files.iter().map(|(p, c)| (p.as_str(), c.as_str())).collect();
+        let (dir, file_tree) = indexed(&files);
+
+        let token = CancelToken::default();
+        token.cancel();
+        let started = Instant::now();
+        let response = cancel::with_token(token, || grep(&dir, &file_tree, "needle"));
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(response.cancelled);
+        assert!(response.matches.is_empty());
+
+        // Uncancelled, the same grep finds matches.
+        let response = grep(&dir, &file_tree, "needle");
+        assert!(!response.cancelled);
+        assert!(!response.matches.is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn clamp_range_start_past_eof_is_empty_at_eof() {
+        assert_eq!(clamp_range(50, 60, 10), (10, 10));
+        assert_eq!(clamp_range(10, 20, 10), (10, 10));
+    }
+
+    #[test]
+    fn clamp_range_end_before_start_collapses_at_start() {
+        assert_eq!(clamp_range(5, 2, 10), (5, 5));
+        assert_eq!(clamp_range(12, 2, 10), (10, 10));
+    }
+
+    #[test]
+    fn clamp_range_empty_range_stays_empty() {
+        assert_eq!(clamp_range(4, 4, 10), (4, 4));
+        assert_eq!(clamp_range(0, 0, 0), (0, 0));
+    }
+
+    #[test]
+    fn clamp_range_huge_end_stops_at_len() {
+        assert_eq!(clamp_range(3, usize::MAX, 10), (3, 10));
+        assert_eq!(clamp_range(usize::MAX, usize::MAX, 10), (10, 10));
+        assert_eq!(clamp_range(0, 7, 10), (0, 7));
+    }
+
+    #[test]
+    fn matching_lines_reports_the_first_match_byte() {
+        let source = "fn a() {}\nlet x = a(); a();\nfn b() {}\n";
+        let lines: Vec<&str> = source.lines().collect();
+        let re = Regex::new(r"a\(").unwrap();
+        let hits = matching_lines(&re, GrepScope::All, source, Language::Rust, &lines, 0, lines.len());
+        assert_eq!(hits, [(0, 3), (1, 18)]);
+        // `lo..hi` restricts the lines searched.
+        let hits = matching_lines(&re, GrepScope::All, source, Language::Rust, &lines, 1, 2);
+        assert_eq!(hits, [(1, 18)]);
+    }
+
+    #[test]
+    fn matching_lines_code_scope_skips_comments() {
+        let source = "// needle here\nlet needle = 1;\n";
+        let lines: Vec<&str> = source.lines().collect();
+        let re = Regex::new("needle").unwrap();
+        let hits = matching_lines(&re, GrepScope::Code, source, Language::Rust, &lines, 0, lines.len());
+        assert_eq!(hits, [(1, 19)]);
+    }
 }
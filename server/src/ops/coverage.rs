@@ -0,0 +1,180 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::config;
+use crate::index::file_entry::Language;
+use crate::index::file_tree::FileTree;
+use crate::index::walker::{self, IgnoredList, OversizedList};
+use crate::symbols::parser::ExtractionErrors;
+use crate::symbols::SymbolTable;
+
+/// Where an indexed file stands with symbol extraction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CoverageStatus {
+    /// Extracted, with at least one symbol.
+    Extracted,
+    /// Extracted, but no symbols were found.
+    Empty,
+    /// No grammar for the file's language; it is searchable but has no
+    /// symbols.
+    Unsupported,
+    /// The last extraction failed; see `error`.
+    Failed,
+    /// Not extracted yet, e.g. while the project is still indexing.
+    Pending,
+}
+
+impl CoverageStatus {
+    pub const NAMES: &'static [&'static str] =
+        &["extracted", "empty", "unsupported", "failed", "pending"];
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "extracted" => Some(CoverageStatus::Extracted),
+            "empty" => Some(CoverageStatus::Empty),
+            "unsupported" => Some(CoverageStatus::Unsupported),
+            "failed" => Some(CoverageStatus::Failed),
+            "pending" => Some(CoverageStatus::Pending),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct CoverageCounts {
+    pub files: usize,
+    pub extracted: usize,
+    pub empty: usize,
+    pub unsupported: usize,
+    pub failed: usize,
+    pub pending: usize,
+    /// Symbols across the extracted files.
+    pub symbols: usize,
+}
+
+impl CoverageCounts {
+    fn add(&mut self, status: CoverageStatus, symbols: usize) {
+        self.files += 1;
+        self.symbols += symbols;
+        match status {
+            CoverageStatus::Extracted => self.extracted += 1,
+            CoverageStatus::Empty => self.empty += 1,
+            CoverageStatus::Unsupported => self.unsupported += 1,
+            CoverageStatus::Failed => self.failed += 1,
+            CoverageStatus::Pending => self.pending += 1,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LanguageCoverage {
+    pub language: Language,
+    #[serde(flatten)]
+    pub counts: CoverageCounts,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CoverageFile {
+    pub path: String,
+    pub language: Language,
+    pub status: CoverageStatus,
+    pub symbols: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Files the walk found but left out of the index.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SkippedFiles {
+    /// Files under `ignored.paths`; more were left out when `ignored` is
+    /// truncated.
+    pub ignored_files: usize,
+    pub ignored: IgnoredList,
+    pub oversized: OversizedList,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CoverageReport {
+    pub totals: CoverageCounts,
+    /// Most files first.
+    pub by_language: Vec<LanguageCoverage>,
+    /// Files with one of the requested statuses, by path.
+    pub files: Vec<CoverageFile>,
+    /// More files matched than `limit`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    pub skipped: SkippedFiles,
+}
+
+/// Tally every indexed file by extraction status, overall and per language,
+/// and list the files whose status is in `statuses` (up to `limit`). Also
+/// walks `root` for files kept out of the index.
+pub fn coverage_report(
+    root: &Path,
+    file_tree: &FileTree,
+    symbol_table: &SymbolTable,
+    errors: &ExtractionErrors,
+    max_file_size: u64,
+    statuses: &HashSet<CoverageStatus>,
+    limit: usize,
+) -> CoverageReport {
+    let mut totals = CoverageCounts::default();
+    let mut by_language: HashMap<Language, CoverageCounts> = HashMap::new();
+    let mut files = Vec::new();
+
+    for entry in file_tree.files.iter() {
+        let (path, file) = (entry.key(), entry.value());
+        let symbols = symbol_table.by_file.get(path).map_or(0, |keys| keys.len());
+        let error = errors.get(path).map(|e| e.error.clone());
+        let status = if error.is_some() {
+            CoverageStatus::Failed
+        } else if !file.language.has_tree_sitter_support() {
+            CoverageStatus::Unsupported
+        } else if !file.symbols_extracted {
+            CoverageStatus::Pending
+        } else if symbols == 0 {
+            CoverageStatus::Empty
+        } else {
+            CoverageStatus::Extracted
+        };
+        totals.add(status, symbols);
+        by_language.entry(file.language).or_default().add(status, symbols);
+        if statuses.contains(&status) {
+            files.push(CoverageFile {
+                path: path.clone(),
+                language: file.language,
+                status,
+                symbols,
+                error,
+            });
+        }
+    }
+
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    let truncated = files.len() > limit;
+    files.truncate(limit);
+
+    let mut by_language: Vec<LanguageCoverage> = by_language
+        .into_iter()
+        .map(|(language, counts)| LanguageCoverage { language, counts })
+        .collect();
+    by_language.sort_by_key(|l| (std::cmp::Reverse(l.counts.files), format!("{:?}", l.language)));
+
+    let ignored = walker::list_ignored(root, file_tree, config::STRUCTURE_IGNORED_LIMIT);
+    let oversized = walker::list_oversized(root, file_tree, max_file_size, config::COVERAGE_LIST_LIMIT);
+    CoverageReport {
+        totals,
+        by_language,
+        files,
+        truncated,
+        skipped: SkippedFiles {
+            ignored_files: ignored.paths.iter().map(|p| p.files).sum(),
+            ignored,
+            oversized,
+        },
+    }
+}
@@ -0,0 +1,705 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::process::Command;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::config::GIT_DIFF_MAX_BYTES;
+use crate::index::file_tree::FileTree;
+use crate::symbols::symbol::SymbolKind;
+use crate::symbols::SymbolTable;
+
+/// Why a git query failed.
+#[derive(Debug)]
+pub enum GitError {
+    /// The project is not inside a git work tree.
+    NotARepository(String),
+    /// Git ran but rejected the request (unknown ref, bad path).
+    Failed(String),
+    /// The git binary could not be run.
+    Unavailable(String),
+}
+
+/// Read-only access to the git repository containing a project, by running
+/// the configured git binary in the project root. Nothing here stages,
+/// commits, or otherwise writes to the repository.
+pub struct Git<'a> {
+    binary: &'a Path,
+    root: &'a Path,
+}
+
+impl<'a> Git<'a> {
+    /// Check that `root` is inside a work tree before running anything else.
+    pub fn open(binary: &'a Path, root: &'a Path) -> Result<Self, GitError> {
+        let git = Self { binary, root };
+        let inside = git.run(&["rev-parse", "--is-inside-work-tree"]).map_err(|e| match e {
+            GitError::Failed(msg) if msg.contains("not a git repository") => {
+                GitError::NotARepository(format!("'{}' is not a git repository", root.display()))
+            }
+            other => other,
+        })?;
+        if inside.trim() != "true" {
+            return Err(GitError::NotARepository(format!(
+                "'{}' is not inside a git work tree",
+                root.display()
+            )));
+        }
+        Ok(git)
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(self.binary);
+        command
+            .current_dir(self.root)
+            // Don't refresh the index (which takes index.lock) or prompt.
+            .env("GIT_OPTIONAL_LOCKS", "0")
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .stdin(std::process::Stdio::null());
+        command
+    }
+
+    /// Run git with `args`, returning stdout. A non-zero exit is `Failed`
+    /// with git's stderr.
+    fn run(&self, args: &[&str]) -> Result<String, GitError> {
+        let output = self.command().args(args).output().map_err(|e| {
+            GitError::Unavailable(format!(
+                "Failed to run git binary '{}': {}",
+                self.binary.display(),
+                e
+            ))
+        })?;
+        if !output.status.success() {
+            return Err(GitError::Failed(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Working tree changes under the project root. Paths are relative to
+    /// the project root, like file tree paths. Changes to files the index
+    /// doesn't hold (ignored by coderlm, too large) are counted in
+    /// `not_indexed` instead of listed; deleted files are always listed.
+    pub fn status(&self, file_tree: &FileTree) -> Result<GitStatus, GitError> {
+        let branch = self
+            .run(&["rev-parse", "--abbrev-ref", "HEAD"])
+            .ok()
+            .map(|s| s.trim().to_string());
+        let head = self
+            .run(&["rev-parse", "--short", "HEAD"])
+            .ok()
+            .map(|s| s.trim().to_string());
+        // Porcelain paths are relative to the top level, not the cwd.
+        let prefix = self.run(&["rev-parse", "--show-prefix"])?.trim().to_string();
+        let raw = self.run(&[
+            "status",
+            "--porcelain=v1",
+            "-z",
+            "--untracked-files=all",
+            "--",
+            ".",
+        ])?;
+
+        let mut files = Vec::new();
+        let mut not_indexed = 0;
+        let mut fields = raw.split('\0').filter(|f| !f.is_empty());
+        while let Some(field) = fields.next() {
+            if field.len() < 4 {
+                continue;
+            }
+            let (code, path) = field.split_at(3);
+            let (x, y) = (code.as_bytes()[0], code.as_bytes()[1]);
+            // Renames and copies carry the original path as the next field.
+            let from = if matches!(x, b'R' | b'C') {
+                fields.next().map(|f| strip_prefix(f, &prefix))
+            } else {
+                None
+            };
+            let path = strip_prefix(path, &prefix);
+            let status = classify(x, y);
+            if status != "deleted" && file_tree.get(&path).is_none() {
+                not_indexed += 1;
+                continue;
+            }
+            files.push(GitFileStatus {
+                path,
+                status: status.to_string(),
+                staged: !matches!(x, b' ' | b'?'),
+                from,
+            });
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(GitStatus {
+            branch,
+            head,
+            count: files.len(),
+            files,
+            not_indexed,
+        })
+    }
+
+    /// Unified diff of one file, working tree against `base`. Untracked
+    /// files are shown as entirely added.
+    pub fn diff_file(&self, base: &str, path: &str) -> Result<GitFileDiff, GitError> {
+        check_base(base)?;
+        check_path(path)?;
+        let untracked = self
+            .run(&["status", "--porcelain=v1", "--", path])?
+            .starts_with("??");
+        let mut diff = if untracked {
+            // `--no-index` exits 1 when the files differ, which they always do.
+            let output = self
+                .command()
+                .args(["diff", "--no-index", "--", "/dev/null", path])
+                .output()
+                .map_err(|e| GitError::Unavailable(e.to_string()))?;
+            String::from_utf8_lossy(&output.stdout).into_owned()
+        } else {
+            self.run(&["diff", "--relative", base, "--", path])?
+        };
+
+        let truncated = diff.len() > GIT_DIFF_MAX_BYTES;
+        if truncated {
+            diff.truncate(diff.floor_char_boundary(GIT_DIFF_MAX_BYTES));
+        }
+        Ok(GitFileDiff {
+            base: base.to_string(),
+            path: path.to_string(),
+            untracked,
+            diff,
+            truncated,
+        })
+    }
+
+    /// Per-file line counts of working tree changes against `base`, limited
+    /// to the project root.
+    pub fn diff_stat(&self, base: &str) -> Result<GitDiffStat, GitError> {
+        check_base(base)?;
+        let raw = self.run(&["diff", "--numstat", "-z", "--relative", base, "--", "."])?;
+        let mut files = Vec::new();
+        let mut fields = raw.split('\0');
+        while let Some(field) = fields.next() {
+            let mut parts = field.splitn(3, '\t');
+            let (Some(added), Some(removed), Some(path)) = (parts.next(), parts.next(), parts.next())
+            else {
+                continue;
+            };
+            // A rename leaves the path empty and puts old and new paths in
+            // the next two fields.
+            let (path, from) = if path.is_empty() {
+                let from = fields.next().unwrap_or_default().to_string();
+                (fields.next().unwrap_or_default().to_string(), Some(from))
+            } else {
+                (path.to_string(), None)
+            };
+            // Binary files report "-" for both counts.
+            let binary = added == "-";
+            files.push(GitDiffStatFile {
+                path,
+                from,
+                added: added.parse().unwrap_or(0),
+                removed: removed.parse().unwrap_or(0),
+                binary,
+            });
+        }
+        Ok(GitDiffStat {
+            base: base.to_string(),
+            files_changed: files.len(),
+            insertions: files.iter().map(|f| f.added).sum(),
+            deletions: files.iter().map(|f| f.removed).sum(),
+            files,
+        })
+    }
+
+    /// Symbols in the current index whose lines intersect a hunk of the
+    /// diff from `base` to `head` (the working tree when `None`), with the
+    /// lines each hunk added and removed. Renamed files are matched under
+    /// their new path. The index reflects the working tree, so with a `head`
+    /// that differs from it, line numbers may not line up; `note` says so.
+    pub fn changed_symbols(
+        &self,
+        base: &str,
+        head: Option<&str>,
+        symbol_table: &SymbolTable,
+    ) -> Result<ChangedSymbols, GitError> {
+        check_base(base)?;
+        let mut args = vec![
+            "diff",
+            "--unified=0",
+            "--find-renames",
+            "--no-color",
+            "--no-ext-diff",
+            "--src-prefix=a/",
+            "--dst-prefix=b/",
+            "--relative",
+            base,
+        ];
+        if let Some(head) = head {
+            check_base(head)?;
+            args.push(head);
+        }
+        args.extend(["--", "."]);
+        let raw = self.run(&args)?;
+
+        let mut symbols = Vec::new();
+        let mut files = Vec::new();
+        for file in parse_hunks(&raw) {
+            let mut changed: Vec<ChangedSymbol> = Vec::new();
+            if file.status != "deleted" {
+                for sym in symbol_table.list_by_file(&file.path) {
+                    let (start, end) = sym.line_range;
+                    let mut entry = ChangedSymbol {
+                        name: sym.name.clone(),
+                        kind: sym.kind,
+                        file: sym.file.clone(),
+                        line_range: sym.line_range,
+                        old_file: file.old_path.clone(),
+                        added: 0,
+                        removed: 0,
+                        hunks: 0,
+                    };
+                    for hunk in &file.hunks {
+                        if hunk.new_lines > 0 {
+                            let hunk_end = hunk.new_start + hunk.new_lines - 1;
+                            if hunk.new_start > end || hunk_end < start {
+                                continue;
+                            }
+                            entry.added += hunk_end.min(end) + 1 - hunk.new_start.max(start);
+                        } else if !(start <= hunk.new_start && hunk.new_start < end) {
+                            // A pure deletion sits after line `new_start`; it
+                            // only touches symbols that span both sides.
+                            continue;
+                        }
+                        entry.removed += hunk.old_lines;
+                        entry.hunks += 1;
+                    }
+                    if entry.hunks > 0 {
+                        changed.push(entry);
+                    }
+                }
+            }
+            changed.sort_by_key(|s| s.line_range);
+            files.push(ChangedFile {
+                file: file.path,
+                old_file: file.old_path,
+                status: file.status.to_string(),
+                added: file.hunks.iter().map(|h| h.new_lines).sum(),
+                removed: file.hunks.iter().map(|h| h.old_lines).sum(),
+                symbols: changed.len(),
+            });
+            symbols.extend(changed);
+        }
+        files.sort_by(|a, b| a.file.cmp(&b.file));
+        symbols.sort_by(|a, b| a.file.cmp(&b.file).then(a.line_range.cmp(&b.line_range)));
+
+        let note = match head {
+            Some(head) if !self.run(&["diff", "--name-only", head, "--", "."])?.is_empty() => {
+                Some(format!(
+                    "The working tree differs from '{}'; symbol line ranges come from the \
+                     working tree and may not match its hunks",
+                    head
+                ))
+            }
+            _ => None,
+        };
+        Ok(ChangedSymbols {
+            base: base.to_string(),
+            head: head.map(str::to_string),
+            count: symbols.len(),
+            symbols,
+            files,
+            note,
+        })
+    }
+
+    /// Full hash of `HEAD`, or `None` before the first commit.
+    pub fn head(&self) -> Option<String> {
+        self.run(&["rev-parse", "--verify", "--quiet", "HEAD"])
+            .ok()
+            .map(|s| s.trim().to_string())
+    }
+
+    /// Whether `path` matches `HEAD` in both the index and the working tree.
+    pub fn is_clean(&self, path: &str) -> Result<bool, GitError> {
+        check_path(path)?;
+        Ok(self.run(&["status", "--porcelain=v1", "--", path])?.trim().is_empty())
+    }
+
+    /// Summarise `git blame` over 1-indexed, inclusive lines `start..=end` of
+    /// `path` in the working tree. Uncommitted edits don't fail the blame:
+    /// their lines are counted in `uncommitted_lines` and `dirty` is set.
+    pub fn blame(&self, path: &str, start: usize, end: usize) -> Result<GitBlame, GitError> {
+        check_path(path)?;
+        let status = self.run(&["status", "--porcelain=v1", "--", path])?;
+        let lines = end.saturating_sub(start) + 1;
+        if status.starts_with("??") {
+            return Ok(GitBlame {
+                lines,
+                uncommitted_lines: lines,
+                dirty: true,
+                note: Some(format!("'{}' is not tracked by git", path)),
+                ..GitBlame::default()
+            });
+        }
+        let dirty = !status.trim().is_empty();
+        let range = format!("{},{}", start, end);
+        let raw = self.run(&["blame", "--porcelain", "-L", &range, "--", path])?;
+        let mut blame = parse_blame(&raw);
+        blame.dirty = dirty;
+        if blame.uncommitted_lines > 0 {
+            blame.note = Some(format!(
+                "{} of {} lines have uncommitted changes",
+                blame.uncommitted_lines, blame.lines
+            ));
+        } else if dirty {
+            blame.note = Some(format!("'{}' has uncommitted changes elsewhere", path));
+        }
+        Ok(blame)
+    }
+}
+
+/// Commit fields from `git blame --porcelain`, given once per commit.
+#[derive(Default)]
+struct BlameCommitInfo {
+    author: String,
+    email: String,
+    time: Option<DateTime<Utc>>,
+    summary: String,
+}
+
+/// Aggregate porcelain blame output: each line starts with a header
+/// `<hash> <orig> <final> [<count>]`, the first header for a commit is
+/// followed by its fields, and the line content follows a tab.
+fn parse_blame(raw: &str) -> GitBlame {
+    let mut commits: HashMap<String, BlameCommitInfo> = HashMap::new();
+    let mut line_commits: Vec<String> = Vec::new();
+    let mut current: Option<String> = None;
+    for line in raw.lines() {
+        if line.starts_with('\t') {
+            if let Some(hash) = current.take() {
+                line_commits.push(hash);
+            }
+            continue;
+        }
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        if current.is_none() {
+            if key.len() == 40 && key.bytes().all(|b| b.is_ascii_hexdigit()) {
+                commits.entry(key.to_string()).or_default();
+                current = Some(key.to_string());
+            }
+            continue;
+        }
+        let Some(info) = current.as_ref().and_then(|h| commits.get_mut(h)) else {
+            continue;
+        };
+        match key {
+            "author" => info.author = value.to_string(),
+            "author-mail" => {
+                info.email = value.trim_matches(|c| c == '<' || c == '>').to_string();
+            }
+            "author-time" => {
+                info.time = value.parse().ok().and_then(|t| DateTime::from_timestamp(t, 0));
+            }
+            "summary" => info.summary = value.to_string(),
+            _ => {}
+        }
+    }
+
+    let mut blame = GitBlame {
+        lines: line_commits.len(),
+        ..GitBlame::default()
+    };
+    let mut authors: HashMap<(String, String), usize> = HashMap::new();
+    let mut seen: HashSet<&str> = HashSet::new();
+    for hash in &line_commits {
+        // Lines not committed yet are blamed on the all-zero hash.
+        if hash.bytes().all(|b| b == b'0') {
+            blame.uncommitted_lines += 1;
+            continue;
+        }
+        let info = &commits[hash];
+        *authors.entry((info.author.clone(), info.email.clone())).or_default() += 1;
+        seen.insert(hash);
+        if let Some(time) = info.time {
+            if blame.oldest_line_date.is_none_or(|oldest| time < oldest) {
+                blame.oldest_line_date = Some(time);
+            }
+            if blame.latest.as_ref().is_none_or(|latest| Some(time) > latest.date) {
+                blame.latest = Some(BlameCommit {
+                    hash: hash.clone(),
+                    author: info.author.clone(),
+                    date: Some(time),
+                    subject: info.summary.clone(),
+                });
+            }
+        }
+    }
+    blame.commits = seen.len();
+    blame.authors = authors
+        .into_iter()
+        .map(|((name, email), lines)| BlameAuthor { name, email, lines })
+        .collect();
+    blame
+        .authors
+        .sort_by(|a, b| b.lines.cmp(&a.lines).then_with(|| a.name.cmp(&b.name)));
+    blame
+}
+
+/// One `@@ -a,b +c,d @@` hunk of a zero-context diff.
+struct Hunk {
+    old_lines: usize,
+    new_start: usize,
+    new_lines: usize,
+}
+
+struct FileHunks {
+    path: String,
+    old_path: Option<String>,
+    status: &'static str,
+    hunks: Vec<Hunk>,
+}
+
+/// Split `git diff --unified=0` output into files and hunks.
+fn parse_hunks(raw: &str) -> Vec<FileHunks> {
+    let mut files: Vec<FileHunks> = Vec::new();
+    for line in raw.lines() {
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            // Fallback path for diffs without `+++` lines (binary files,
+            // pure renames); the header is ambiguous when paths have spaces.
+            let path = rest.rsplit_once(" b/").map(|(_, p)| p).unwrap_or(rest);
+            files.push(FileHunks {
+                path: unquote(path),
+                old_path: None,
+                status: "modified",
+                hunks: Vec::new(),
+            });
+            continue;
+        }
+        let Some(file) = files.last_mut() else {
+            continue;
+        };
+        if line.starts_with("new file mode") {
+            file.status = "added";
+        } else if line.starts_with("deleted file mode") {
+            file.status = "deleted";
+        } else if let Some(from) = line.strip_prefix("rename from ") {
+            file.status = "renamed";
+            file.old_path = Some(unquote(from));
+        } else if let Some(to) = line.strip_prefix("rename to ") {
+            file.path = unquote(to);
+        } else if let Some(to) = line.strip_prefix("+++ b/") {
+            file.path = unquote(to);
+        } else if let Some(from) = line.strip_prefix("--- a/") {
+            if file.status == "deleted" {
+                file.path = unquote(from);
+            }
+        } else if let Some(header) = line.strip_prefix("@@ -") {
+            let mut ranges = header.split_whitespace();
+            let (Some(old), Some(new)) = (ranges.next(), ranges.next()) else {
+                continue;
+            };
+            let (_, old_lines) = parse_range(old);
+            let (new_start, new_lines) = parse_range(new.trim_start_matches('+'));
+            file.hunks.push(Hunk {
+                old_lines,
+                new_start,
+                new_lines,
+            });
+        }
+    }
+    files
+}
+
+/// `start,count` from a hunk header; a missing count means 1.
+fn parse_range(range: &str) -> (usize, usize) {
+    match range.split_once(',') {
+        Some((start, count)) => (start.parse().unwrap_or(0), count.parse().unwrap_or(0)),
+        None => (range.parse().unwrap_or(0), 1),
+    }
+}
+
+/// Strip the quotes and trailing tab git adds around unusual file names.
+/// Escapes inside quoted names are left as they are.
+fn unquote(path: &str) -> String {
+    let path = path.trim_end_matches('\t');
+    path.strip_prefix('"')
+        .and_then(|p| p.strip_suffix('"'))
+        .unwrap_or(path)
+        .to_string()
+}
+
+/// Paths are passed after `--`, but must stay inside the project root.
+fn check_path(path: &str) -> Result<(), GitError> {
+    if Path::new(path).is_absolute()
+        || Path::new(path).components().any(|c| c == std::path::Component::ParentDir)
+    {
+        return Err(GitError::Failed(format!(
+            "Path '{}' must be relative to the project root",
+            path
+        )));
+    }
+    Ok(())
+}
+
+/// Refs go before `--`, so one starting with `-` would be read as an option.
+fn check_base(base: &str) -> Result<(), GitError> {
+    if base.is_empty() || base.starts_with('-') {
+        return Err(GitError::Failed(format!("Invalid ref '{}'", base)));
+    }
+    Ok(())
+}
+
+fn strip_prefix(path: &str, prefix: &str) -> String {
+    path.strip_prefix(prefix).unwrap_or(path).to_string()
+}
+
+/// Name for a porcelain `XY` status pair.
+fn classify(x: u8, y: u8) -> &'static str {
+    match (x, y) {
+        (b'?', b'?') => "untracked",
+        (b'U', _) | (_, b'U') | (b'A', b'A') | (b'D', b'D') => "conflicted",
+        (b'D', _) | (_, b'D') => "deleted",
+        (b'R', _) => "renamed",
+        (b'C', _) => "copied",
+        (b'A', _) => "added",
+        _ => "modified",
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GitFileStatus {
+    pub path: String,
+    /// `modified`, `added`, `deleted`, `renamed`, `copied`, `untracked`, or
+    /// `conflicted`.
+    pub status: String,
+    /// Whether the change is staged in the git index.
+    pub staged: bool,
+    /// Original path of a rename or copy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GitStatus {
+    /// Current branch, or `HEAD` when detached. `None` before the first commit.
+    pub branch: Option<String>,
+    pub head: Option<String>,
+    pub files: Vec<GitFileStatus>,
+    pub count: usize,
+    /// Changed files under the root that the index doesn't hold.
+    pub not_indexed: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GitFileDiff {
+    pub base: String,
+    pub path: String,
+    pub untracked: bool,
+    /// Unified diff; empty when the file matches `base`.
+    pub diff: String,
+    pub truncated: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GitDiffStatFile {
+    pub path: String,
+    /// Previous path of a renamed file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    pub added: usize,
+    pub removed: usize,
+    pub binary: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GitDiffStat {
+    pub base: String,
+    pub files: Vec<GitDiffStatFile>,
+    pub files_changed: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BlameAuthor {
+    pub name: String,
+    pub email: String,
+    /// Lines in the range last changed by this author.
+    pub lines: usize,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BlameCommit {
+    pub hash: String,
+    pub author: String,
+    pub date: Option<DateTime<Utc>>,
+    pub subject: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct GitBlame {
+    pub lines: usize,
+    /// Authors by number of lines, most first.
+    pub authors: Vec<BlameAuthor>,
+    /// Distinct commits the committed lines come from.
+    pub commits: usize,
+    /// Most recent commit touching the range.
+    pub latest: Option<BlameCommit>,
+    /// Author date of the oldest committed line.
+    pub oldest_line_date: Option<DateTime<Utc>>,
+    pub oldest_line_age_days: Option<i64>,
+    pub uncommitted_lines: usize,
+    /// Whether the file differs from `HEAD` in the working tree or index.
+    pub dirty: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// Blame summaries keyed by file, symbol, line range, and `HEAD`. Entries
+/// are only stored and served while the file is clean, since edits in the
+/// working tree change neither `HEAD` nor, necessarily, the range.
+pub type BlameCache = HashMap<(String, String, (usize, usize), String), GitBlame>;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChangedSymbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub file: String,
+    pub line_range: (usize, usize),
+    /// Path before a rename.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_file: Option<String>,
+    /// Added lines inside the symbol.
+    pub added: usize,
+    /// Removed lines from the hunks that touch the symbol.
+    pub removed: usize,
+    pub hunks: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChangedFile {
+    pub file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_file: Option<String>,
+    /// `added`, `modified`, `deleted`, or `renamed`.
+    pub status: String,
+    pub added: usize,
+    pub removed: usize,
+    /// Indexed symbols in the file that the changes touch.
+    pub symbols: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChangedSymbols {
+    pub base: String,
+    /// Ref compared against `base`; `None` for the working tree.
+    pub head: Option<String>,
+    pub symbols: Vec<ChangedSymbol>,
+    pub count: usize,
+    pub files: Vec<ChangedFile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
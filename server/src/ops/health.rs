@@ -0,0 +1,167 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use serde::Serialize;
+
+use crate::index::file_entry::Language;
+use crate::symbols::queries;
+
+/// Languages with a tree-sitter grammar. Pdf reuses the Markdown config.
+const GRAMMAR_LANGUAGES: [Language; 7] = [
+    Language::Rust,
+    Language::Python,
+    Language::TypeScript,
+    Language::JavaScript,
+    Language::Go,
+    Language::Lean,
+    Language::Markdown,
+];
+
+#[derive(Debug, Serialize)]
+pub struct DeepHealth {
+    /// `ok`, `degraded` (an optional backend is missing or a query falls
+    /// back to regex), or `unhealthy` (a grammar fails to load or
+    /// annotations can't be persisted).
+    pub status: &'static str,
+    /// Check name to `ok`, `unavailable`, or `error`.
+    pub checks: BTreeMap<&'static str, &'static str>,
+    /// Failure messages for checks that are not `ok`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub details: BTreeMap<&'static str, String>,
+}
+
+/// Run the checks behind `/health?deep=true`. Spawns a python3 process, so
+/// call it off the async runtime.
+pub fn deep_check(project_roots: &[PathBuf]) -> DeepHealth {
+    let mut health = DeepHealth {
+        status: "ok",
+        checks: BTreeMap::new(),
+        details: BTreeMap::new(),
+    };
+
+    let mut record = |name: &'static str, result: Result<(), String>, failure: &'static str| {
+        match result {
+            Ok(()) => {
+                health.checks.insert(name, "ok");
+            }
+            Err(e) => {
+                health.checks.insert(name, failure);
+                health.details.insert(name, e);
+            }
+        }
+    };
+
+    let grammars = check_grammars();
+    let grammar_load_failed = grammars.load_failed;
+    record("tree_sitter", grammars.into_result(), "error");
+    record("coderlm_dir", check_writable(project_roots), "error");
+    record("pdf_backend", check_pdf_backend(), "unavailable");
+
+    let critical_failed =
+        grammar_load_failed || health.checks.get("coderlm_dir") != Some(&"ok");
+    health.status = if critical_failed {
+        "unhealthy"
+    } else if health.checks.values().any(|s| *s != "ok") {
+        "degraded"
+    } else {
+        "ok"
+    };
+    health
+}
+
+struct GrammarCheck {
+    /// A grammar could not be loaded at all (ABI mismatch), so its files get
+    /// no symbols. Query failures alone only drop that query to the regex
+    /// fallback.
+    load_failed: bool,
+    failures: Vec<String>,
+}
+
+impl GrammarCheck {
+    fn into_result(self) -> Result<(), String> {
+        if self.failures.is_empty() {
+            Ok(())
+        } else {
+            Err(self.failures.join("; "))
+        }
+    }
+}
+
+/// Load every grammar into a parser and compile each of its queries, so an
+/// ABI mismatch or a query referencing a renamed node shows up here rather
+/// than as silently empty or regex-only results.
+fn check_grammars() -> GrammarCheck {
+    let mut load_failed = false;
+    let mut failures = Vec::new();
+    for lang in GRAMMAR_LANGUAGES {
+        let Some(config) = queries::get_language_config(lang) else {
+            continue;
+        };
+        let name = format!("{:?}", lang).to_lowercase();
+
+        let mut parser = tree_sitter::Parser::new();
+        if let Err(e) = parser.set_language(&config.language) {
+            load_failed = true;
+            failures.push(format!("{}: {}", name, e));
+            continue;
+        }
+
+        let named_queries = [
+            ("symbols_query", config.symbols_query),
+            ("callers_query", config.callers_query),
+            ("variables_query", config.variables_query),
+            ("nested_variables_query", config.nested_variables_query),
+        ];
+        for (query_name, source) in named_queries {
+            if source.is_empty() {
+                continue;
+            }
+            if let Err(e) = tree_sitter::Query::new(&config.language, source) {
+                failures.push(format!("{} {}: {}", name, query_name, e));
+            }
+        }
+    }
+
+    GrammarCheck {
+        load_failed,
+        failures,
+    }
+}
+
+/// Each project's `.coderlm/` must accept writes for annotations and the PDF
+/// cache.
+fn check_writable(project_roots: &[PathBuf]) -> Result<(), String> {
+    let mut failures = Vec::new();
+    for root in project_roots {
+        let dir = root.join(".coderlm");
+        let probe = dir.join(".health_probe");
+        let result = std::fs::create_dir_all(&dir)
+            .and_then(|_| std::fs::write(&probe, b"ok"))
+            .and_then(|_| std::fs::remove_file(&probe));
+        if let Err(e) = result {
+            failures.push(format!("{}: {}", dir.display(), e));
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures.join("; "))
+    }
+}
+
+fn check_pdf_backend() -> Result<(), String> {
+    let output = Command::new("python3")
+        .args(["-c", "import pymupdf4llm"])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("python3 not runnable: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let last = stderr.lines().last().unwrap_or("").trim();
+        Err(format!("pymupdf4llm not importable: {}", last))
+    }
+}
@@ -1,6 +1,7 @@
 use crate::server::session::HistoryEntry;
 use crate::server::state::AppState;
 use serde::Serialize;
+use utoipa::ToSchema;
 
 pub fn get_history(state: &AppState, session_id: &str, limit: usize) -> Result<Vec<HistoryEntry>, String> {
     let session = state
@@ -14,7 +15,7 @@ pub fn get_history(state: &AppState, session_id: &str, limit: usize) -> Result<V
     Ok(history[start..].to_vec())
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct SessionHistoryBlock {
     pub session_id: String,
     pub project: String,
@@ -50,7 +51,8 @@ pub fn get_all_history(state: &AppState, limit: usize) -> Vec<SessionHistoryBloc
 }
 
 /// Compact history: group consecutive same-path operations into summaries,
-/// keeping the most recent `keep_recent` entries uncompacted.
+/// keeping the most recent `keep_recent` entries uncompacted. Also resets the
+/// session's running response-token total, clearing any budget warning.
 pub fn compact_history(
     state: &AppState,
     session_id: &str,
@@ -61,6 +63,7 @@ pub fn compact_history(
         .sessions
         .get_mut(session_id)
         .ok_or_else(|| format!("Session '{}' not found", session_id))?;
+    session.response_tokens = 0;
 
     let total = session.history.len();
     if total <= keep_recent {
@@ -94,6 +97,10 @@ pub fn compact_history(
                 method: current.method.clone(),
                 path: current.path.clone(),
                 response_preview: format!("[{} calls compacted]", count),
+                estimated_tokens: to_compact[i..i + count]
+                    .iter()
+                    .filter_map(|e| e.estimated_tokens)
+                    .reduce(|a, b| a + b),
             });
         } else {
             compacted.push(current.clone());
@@ -114,7 +121,7 @@ pub fn compact_history(
     })
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct CompactResult {
     pub original_count: usize,
     pub compacted_count: usize,
@@ -0,0 +1,395 @@
+use std::collections::{BTreeMap, HashSet};
+use std::mem::size_of;
+use std::path::Path;
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::index::file_entry::{FileEntry, FileMark};
+use crate::server::session::{
+    Buffer, BufferSource, HistoryEntry, ReplState, Session, SubcallResult,
+};
+use crate::server::state::{AppState, Project};
+use crate::symbols::symbol::Symbol;
+use crate::symbols::{CallerRef, SymbolTable};
+
+// Estimates below are deliberately rough: struct size plus owned string
+// bytes, walked in place without cloning. Map overhead (buckets, hashes) is
+// not counted, so real usage runs somewhat higher. Each report labels which
+// of its byte figures are exact and which are estimates.
+
+/// How a byte figure in a memory report was obtained.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Accuracy {
+    /// Counted from the owned allocations themselves.
+    Exact,
+    /// Approximated from lengths and struct sizes.
+    Estimated,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProjectMemory {
+    pub root: String,
+    pub files: usize,
+    pub symbols: usize,
+    /// Distinct callee names in the reverse call graph.
+    pub caller_names: usize,
+    /// Total call sites across all callee names.
+    pub caller_refs: usize,
+    pub file_tree_bytes: usize,
+    pub symbol_bytes: usize,
+    pub caller_bytes: usize,
+    /// Symbol fingerprint snapshots kept for `/symbols/diff`.
+    pub snapshot_bytes: usize,
+    pub estimated_bytes: usize,
+    /// On-disk size of converted PDF markdown under `.coderlm/converted`.
+    pub pdf_cache_disk_bytes: u64,
+    /// Accuracy of each `*_bytes` field above, keyed by field name.
+    pub accuracy: BTreeMap<String, Accuracy>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionMemory {
+    pub session_id: String,
+    pub project: String,
+    pub buffers: usize,
+    pub buffer_bytes: usize,
+    pub variables: usize,
+    pub variable_bytes: usize,
+    pub subcall_results: usize,
+    pub history_entries: usize,
+    pub estimated_bytes: usize,
+    /// Accuracy of each `*_bytes` field above, keyed by field name.
+    pub accuracy: BTreeMap<String, Accuracy>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MemoryReport {
+    pub projects: Vec<ProjectMemory>,
+    pub sessions: Vec<SessionMemory>,
+    pub total_estimated_bytes: usize,
+}
+
+/// Per-project and per-session memory estimates, largest first.
+pub fn memory_report(state: &AppState) -> MemoryReport {
+    let mut projects: Vec<ProjectMemory> = state
+        .inner
+        .projects
+        .iter()
+        .map(|entry| project_memory(entry.value()))
+        .collect();
+    projects.sort_by_key(|p| std::cmp::Reverse(p.estimated_bytes));
+
+    let mut sessions: Vec<SessionMemory> = state
+        .inner
+        .sessions
+        .iter()
+        .map(|entry| session_memory(entry.value()))
+        .collect();
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.estimated_bytes));
+
+    let total_estimated_bytes = projects.iter().map(|p| p.estimated_bytes).sum::<usize>()
+        + sessions.iter().map(|s| s.estimated_bytes).sum::<usize>();
+
+    MemoryReport {
+        projects,
+        sessions,
+        total_estimated_bytes,
+    }
+}
+
+pub fn project_memory(project: &Project) -> ProjectMemory {
+    let file_tree_bytes: usize = project
+        .file_tree
+        .files
+        .iter()
+        .map(|e| e.key().len() + file_entry_bytes(e.value()))
+        .sum();
+
+    let table = &project.symbol_table;
+    let symbol_bytes = symbol_table_bytes(table);
+
+    let mut caller_refs = 0;
+    let caller_bytes: usize = table
+        .reverse_call_graph
+        .iter()
+        .map(|e| {
+            caller_refs += e.value().len();
+            e.key().len()
+                + size_of::<Vec<CallerRef>>()
+                + e.value().capacity() * size_of::<CallerRef>()
+                + e.value().iter().map(caller_ref_heap_bytes).sum::<usize>()
+        })
+        .sum::<usize>()
+        + table
+            .callees_by_file
+            .iter()
+            .map(|e| {
+                e.key().len()
+                    + size_of::<HashSet<String>>()
+                    + e.value().iter().map(|name| name.len() + size_of::<String>()).sum::<usize>()
+            })
+            .sum::<usize>();
+
+    let snapshot_bytes = project.symbol_snapshots.lock().estimated_bytes();
+
+    ProjectMemory {
+        root: project.root.display().to_string(),
+        files: project.file_tree.len(),
+        symbols: table.len(),
+        caller_names: table.reverse_call_graph.len(),
+        caller_refs,
+        file_tree_bytes,
+        symbol_bytes,
+        caller_bytes,
+        snapshot_bytes,
+        estimated_bytes: file_tree_bytes + symbol_bytes + caller_bytes + snapshot_bytes,
+        pdf_cache_disk_bytes: dir_size(&project.root.join(".coderlm").join("converted")),
+        accuracy: accuracy_labels(&[
+            ("file_tree_bytes", Accuracy::Estimated),
+            ("symbol_bytes", Accuracy::Estimated),
+            ("caller_bytes", Accuracy::Estimated),
+            ("snapshot_bytes", Accuracy::Estimated),
+            ("estimated_bytes", Accuracy::Estimated),
+            ("pdf_cache_disk_bytes", Accuracy::Exact),
+        ]),
+    }
+}
+
+pub fn session_memory(session: &Session) -> SessionMemory {
+    let repl: &ReplState = &session.repl_state;
+
+    let buffer_bytes: usize = repl
+        .buffers
+        .iter()
+        .map(|e| e.key().capacity() + buffer_bytes(e.value()))
+        .sum();
+    let variable_bytes: usize = repl
+        .variables
+        .iter()
+        .map(|e| e.key().len() + json_len(e.value()))
+        .sum();
+    let (subcall_results, subcall_bytes) = {
+        let results = repl.subcall_results.lock();
+        let bytes: usize = results
+            .iter()
+            .map(|r| size_of::<SubcallResult>() + json_len(r))
+            .sum();
+        (results.len(), bytes)
+    };
+    let history_bytes: usize = session
+        .history
+        .iter()
+        .map(|h| {
+            size_of::<HistoryEntry>() + h.method.len() + h.path.len() + h.response_preview.len()
+        })
+        .sum();
+
+    SessionMemory {
+        session_id: session.id.clone(),
+        project: session.project_path.display().to_string(),
+        buffers: repl.buffers.len(),
+        buffer_bytes,
+        variables: repl.variables.len(),
+        variable_bytes,
+        subcall_results,
+        history_entries: session.history.len(),
+        estimated_bytes: buffer_bytes + variable_bytes + subcall_bytes + history_bytes,
+        accuracy: accuracy_labels(&[
+            ("buffer_bytes", Accuracy::Exact),
+            ("variable_bytes", Accuracy::Estimated),
+            ("estimated_bytes", Accuracy::Estimated),
+        ]),
+    }
+}
+
+fn accuracy_labels(labels: &[(&str, Accuracy)]) -> BTreeMap<String, Accuracy> {
+    labels
+        .iter()
+        .map(|(field, accuracy)| (field.to_string(), *accuracy))
+        .collect()
+}
+
+/// Struct size plus the allocated capacity of every string a buffer owns.
+fn buffer_bytes(buffer: &Buffer) -> usize {
+    let source = match &buffer.source {
+        BufferSource::File { path, .. } => path.capacity(),
+        BufferSource::Symbol { name, file } => name.capacity() + file.capacity(),
+        BufferSource::Directory {
+            path,
+            include_globs,
+            exclude_globs,
+            ..
+        } => {
+            path.capacity()
+                + include_globs
+                    .iter()
+                    .chain(exclude_globs)
+                    .map(|g| g.capacity())
+                    .sum::<usize>()
+        }
+        BufferSource::Grep { pattern } => pattern.capacity(),
+        BufferSource::SubLmResult { query } => query.capacity(),
+        BufferSource::Computed { description } => description.capacity(),
+    };
+    size_of::<Buffer>() + buffer.name.capacity() + buffer.content.capacity() + source
+}
+
+fn file_entry_bytes(entry: &FileEntry) -> usize {
+    size_of::<FileEntry>()
+        + entry.rel_path.len()
+        + entry.definition.as_ref().map_or(0, |d| d.len())
+        + entry.marks.capacity() * size_of::<FileMark>()
+}
+
+fn symbol_table_bytes(table: &SymbolTable) -> usize {
+    let symbols: usize = table
+        .symbols
+        .iter()
+        .map(|e| e.key().len() + symbol_bytes(e.value()))
+        .sum();
+    let by_name: usize = table
+        .by_name
+        .iter()
+        .map(|e| e.key().len() + e.value().iter().map(|k| size_of::<String>() + k.len()).sum::<usize>())
+        .sum();
+    let by_file: usize = table
+        .by_file
+        .iter()
+        .map(|e| e.key().len() + e.value().iter().map(|k| size_of::<String>() + k.len()).sum::<usize>())
+        .sum();
+    let by_signature_hash: usize = table
+        .by_signature_hash
+        .iter()
+        .map(|e| e.key().len() + e.value().iter().map(|k| size_of::<String>() + k.len()).sum::<usize>())
+        .sum();
+    let by_parent: usize = table
+        .by_parent
+        .iter()
+        .map(|e| e.key().len() + e.value().iter().map(|k| size_of::<String>() + k.len()).sum::<usize>())
+        .sum();
+    let sorted_names: usize = table
+        .sorted_names
+        .read()
+        .iter()
+        .map(|(lower, name)| 2 * size_of::<String>() + lower.len() + name.len())
+        .sum();
+    symbols + by_name + by_file + by_signature_hash + by_parent + sorted_names
+}
+
+fn symbol_bytes(sym: &Symbol) -> usize {
+    size_of::<Symbol>()
+        + sym.name.len()
+        + sym.file.len()
+        + sym.signature.len()
+        + sym.signature_hash.len()
+        + sym.return_type.as_ref().map_or(0, |s| s.len())
+        + sym.definition.as_ref().map_or(0, |s| s.len())
+        + sym.parent.as_ref().map_or(0, |s| s.len())
+}
+
+fn caller_ref_heap_bytes(r: &CallerRef) -> usize {
+    r.file.len() + r.text.len() + r.context.as_ref().map_or(0, |c| c.len())
+}
+
+/// Serialized length of a value, counted without building the string.
+fn json_len<T: Serialize>(value: &T) -> usize {
+    struct Counter(usize);
+    impl std::io::Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let mut counter = Counter(0);
+    let _ = serde_json::to_writer(&mut counter, value);
+    counter.0
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|e| match e.file_type() {
+            Ok(t) if t.is_dir() => dir_size(&e.path()),
+            Ok(_) => e.metadata().map(|m| m.len()).unwrap_or(0),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+// ── Trimming ─────────────────────────────────────────────────────────
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct TrimReport {
+    pub sessions_evicted: Vec<String>,
+    pub pdf_cache_bytes_removed: u64,
+    pub estimated_bytes_before: usize,
+    pub estimated_bytes_after: usize,
+}
+
+/// Release memory held by caches and spare index capacity. Query results
+/// are unaffected:
+/// - shrink the call graph and the symbol and file indexes to fit;
+/// - if `evict_idle_secs` is set, drop sessions idle for longer than that;
+/// - if `drop_pdf_cache` is set, delete converted PDF markdown on disk.
+pub fn trim(
+    state: &AppState,
+    evict_idle_secs: Option<u64>,
+    drop_pdf_cache: bool,
+) -> TrimReport {
+    let mut report = TrimReport {
+        estimated_bytes_before: memory_report(state).total_estimated_bytes,
+        ..Default::default()
+    };
+
+    for entry in state.inner.projects.iter() {
+        let project = entry.value();
+        let table = &project.symbol_table;
+        for mut callers in table.reverse_call_graph.iter_mut() {
+            callers.shrink_to_fit();
+        }
+        table.reverse_call_graph.shrink_to_fit();
+        table.callees_by_file.shrink_to_fit();
+        table.symbols.shrink_to_fit();
+        table.by_name.shrink_to_fit();
+        table.by_file.shrink_to_fit();
+        table.by_signature_hash.shrink_to_fit();
+        table.by_parent.shrink_to_fit();
+        project.file_tree.files.shrink_to_fit();
+
+        if drop_pdf_cache {
+            let cache = project.root.join(".coderlm").join("converted");
+            let bytes = dir_size(&cache);
+            if bytes > 0 && std::fs::remove_dir_all(&cache).is_ok() {
+                report.pdf_cache_bytes_removed += bytes;
+            }
+        }
+    }
+
+    if let Some(secs) = evict_idle_secs {
+        let cutoff = chrono::Utc::now() - chrono::Duration::seconds(secs as i64);
+        let idle: Vec<String> = state
+            .inner
+            .sessions
+            .iter()
+            .filter(|s| s.value().last_active < cutoff)
+            .map(|s| s.key().clone())
+            .collect();
+        for id in idle {
+            if state.inner.sessions.remove(&id).is_some() {
+                state.inner.rate_limiter.forget(&id);
+                report.sessions_evicted.push(id);
+            }
+        }
+    }
+
+    report.estimated_bytes_after = memory_report(state).total_estimated_bytes;
+    report
+}
@@ -1,6 +1,16 @@
 pub mod annotations;
+pub mod citations;
 pub mod content;
+pub mod coverage;
+pub mod git;
+pub mod health;
 pub mod history;
+pub mod memory;
+pub mod project_vars;
+pub mod raw_query;
 pub mod repl;
+pub mod session_ops;
 pub mod structure;
+pub mod symbol_context;
 pub mod symbol_ops;
+pub mod watch;
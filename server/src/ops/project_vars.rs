@@ -0,0 +1,40 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use dashmap::DashMap;
+use serde_json::Value;
+use tracing::debug;
+
+const PROJECT_VARS_FILE: &str = ".coderlm/project_vars.json";
+
+/// Read the project variables saved under `root`. A missing file means no
+/// variables yet.
+pub fn load_project_vars(root: &Path) -> Result<DashMap<String, Value>, String> {
+    let path = root.join(PROJECT_VARS_FILE);
+    if !path.exists() {
+        return Ok(DashMap::new());
+    }
+    let json = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read project variables: {}", e))?;
+    let vars: BTreeMap<String, Value> = serde_json::from_str(&json)
+        .map_err(|e| format!("Failed to parse project variables: {}", e))?;
+    debug!("Loaded {} project variables for {}", vars.len(), root.display());
+    Ok(vars.into_iter().collect())
+}
+
+/// Write every project variable to `.coderlm/project_vars.json`, sorted by
+/// name so the file diffs cleanly.
+pub fn save_project_vars(root: &Path, vars: &DashMap<String, Value>) -> Result<(), String> {
+    let path = root.join(PROJECT_VARS_FILE);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create project variables dir: {}", e))?;
+    }
+    let sorted: BTreeMap<String, Value> = vars
+        .iter()
+        .map(|entry| (entry.key().clone(), entry.value().clone()))
+        .collect();
+    let json = serde_json::to_string_pretty(&sorted)
+        .map_err(|e| format!("Failed to serialize project variables: {}", e))?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write project variables: {}", e))
+}
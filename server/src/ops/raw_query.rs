@@ -0,0 +1,405 @@
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use rayon::prelude::*;
+use serde::Serialize;
+use tree_sitter::{QueryCursor, QueryCursorOptions, QueryCursorState, StreamingIterator};
+use utoipa::ToSchema;
+
+use crate::config;
+use crate::index::encoding;
+use crate::index::file_entry::Language;
+use crate::index::file_tree::FileTree;
+use crate::server::cancel::{self, CancelToken};
+use crate::symbols::queries;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QueryCapture {
+    /// The capture name without its `@`.
+    pub name: String,
+    /// Which pattern in the query produced the match.
+    pub pattern_index: usize,
+    /// Tree-sitter node kind, e.g. `unsafe_block`.
+    pub kind: String,
+    /// 1-based, inclusive.
+    pub start_line: usize,
+    pub end_line: usize,
+    pub start_byte: usize,
+    pub end_byte: usize,
+    pub text: String,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub text_truncated: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RawQueryResult {
+    pub file: String,
+    pub language: Language,
+    pub count: usize,
+    pub captures: Vec<QueryCapture>,
+    /// More captures exist past `limit`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// The time budget ran out; `captures` holds what was found before.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub timed_out: bool,
+    /// The cursor dropped in-progress matches past `RAW_QUERY_MATCH_LIMIT`,
+    /// so some matches may be missing.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub match_limit_exceeded: bool,
+    /// The request was cancelled; `captures` holds what was found before.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub cancelled: bool,
+}
+
+/// One file's captures in a `/query/scan` page.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FileCaptures {
+    pub file: String,
+    pub count: usize,
+    pub captures: Vec<QueryCapture>,
+    /// The file had more captures than fit in one page, or the time budget
+    /// ran out in it.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ScanResult {
+    pub language: Language,
+    /// Indexed files of `language` matching the globs, before the
+    /// `RAW_QUERY_SCAN_MAX_FILES` cap.
+    pub total_files: usize,
+    /// Files run in this page, starting at `offset`.
+    pub files_scanned: usize,
+    /// Captures across every file in this page.
+    pub count: usize,
+    /// Files with at least one capture, in path order.
+    pub files: Vec<FileCaptures>,
+    /// Pass as `offset` to continue; absent once every file was scanned.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_offset: Option<usize>,
+    /// The time budget ran out before the page was full.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub timed_out: bool,
+    /// More than `RAW_QUERY_SCAN_MAX_FILES` files matched; only the first
+    /// ones (by path) are scanned.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub files_capped: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<ScanError>,
+    /// Some file's cursor dropped in-progress matches.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub match_limit_exceeded: bool,
+    /// The request was cancelled; `next_offset` resumes from the first
+    /// file not finished.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ScanError {
+    pub file: String,
+    pub error: String,
+}
+
+/// Run a caller-supplied tree-sitter `query` against `file`, returning up to
+/// `limit` captures in document order. Stops early once
+/// `RAW_QUERY_TIMEOUT_MS` has passed. The file must be in the index.
+pub fn run_query(
+    root: &Path,
+    file_tree: &FileTree,
+    file: &str,
+    query: &str,
+    limit: usize,
+) -> Result<RawQueryResult, String> {
+    let entry = file_tree
+        .get(file)
+        .ok_or_else(|| format!("File '{}' not found in index", file))?;
+    if queries::get_language_config(entry.language).is_none() {
+        return Err(format!(
+            "No tree-sitter grammar for '{}' ({:?})",
+            file, entry.language
+        ));
+    }
+    let (language, query) = compile_query(entry.language, query)?;
+    let deadline = Instant::now() + Duration::from_millis(config::RAW_QUERY_TIMEOUT_MS);
+    let cancel = cancel::current();
+    let run = run_on_file(root, file, entry.language, &language, &query, limit, deadline, &cancel)?;
+    Ok(RawQueryResult {
+        file: file.to_string(),
+        language: entry.language,
+        count: run.captures.len(),
+        captures: run.captures,
+        truncated: run.truncated,
+        timed_out: run.timed_out,
+        match_limit_exceeded: run.match_limit_exceeded,
+        cancelled: cancel.is_cancelled(),
+    })
+}
+
+/// Captures of one query over one file.
+struct FileRun {
+    captures: Vec<QueryCapture>,
+    truncated: bool,
+    timed_out: bool,
+    match_limit_exceeded: bool,
+}
+
+fn compile_query(
+    language: Language,
+    query: &str,
+) -> Result<(tree_sitter::Language, tree_sitter::Query), String> {
+    let grammar = queries::get_language_config(language)
+        .ok_or_else(|| format!("No tree-sitter grammar for {:?}", language))?
+        .language;
+    let query = tree_sitter::Query::new(&grammar, query)
+        .map_err(|e| format!("Invalid query (offset {}): {}", e.offset, e))?;
+    Ok((grammar, query))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_on_file(
+    root: &Path,
+    file: &str,
+    language: Language,
+    grammar: &tree_sitter::Language,
+    query: &tree_sitter::Query,
+    limit: usize,
+    deadline: Instant,
+    cancel: &CancelToken,
+) -> Result<FileRun, String> {
+    let source = if language == Language::Pdf {
+        crate::index::pdf::convert_pdf(root, file, cancel)
+            .map_err(|e| format!("PDF conversion failed for '{}': {}", file, e))?
+    } else {
+        encoding::read_to_string(root.join(file))
+            .map_err(|e| format!("Failed to read '{}': {}", file, e))?
+    };
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(grammar)
+        .map_err(|e| format!("Failed to load grammar: {}", e))?;
+    let tree = parser
+        .parse(&source, None)
+        .ok_or_else(|| format!("Failed to parse '{}'", file))?;
+
+    let mut check_deadline = |_: &QueryCursorState| {
+        if Instant::now() >= deadline || cancel.is_cancelled() {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    };
+    let mut cursor = QueryCursor::new();
+    cursor.set_match_limit(config::RAW_QUERY_MATCH_LIMIT);
+
+    let capture_names = query.capture_names();
+    let mut captures = Vec::new();
+    let mut truncated = false;
+    let mut timed_out = false;
+    {
+        let options = QueryCursorOptions::new().progress_callback(&mut check_deadline);
+        let mut matches =
+            cursor.captures_with_options(query, tree.root_node(), source.as_bytes(), options);
+        while let Some((m, index)) = matches.next() {
+            if Instant::now() >= deadline {
+                timed_out = true;
+                break;
+            }
+            if captures.len() >= limit {
+                truncated = true;
+                break;
+            }
+            let cap = m.captures[*index];
+            let node = cap.node;
+            let full = &source[node.byte_range()];
+            let text_truncated = full.len() > config::RAW_QUERY_TEXT_MAX_BYTES;
+            let text = if text_truncated {
+                &full[..full.floor_char_boundary(config::RAW_QUERY_TEXT_MAX_BYTES)]
+            } else {
+                full
+            };
+            captures.push(QueryCapture {
+                name: capture_names[cap.index as usize].to_string(),
+                pattern_index: m.pattern_index,
+                kind: node.kind().to_string(),
+                start_line: node.start_position().row + 1,
+                end_line: node.end_position().row + 1,
+                start_byte: node.start_byte(),
+                end_byte: node.end_byte(),
+                text: text.to_string(),
+                text_truncated,
+            });
+        }
+    }
+    // The progress callback ends iteration silently; tell a budget cut-off
+    // apart from a query that simply ran out of matches.
+    timed_out |= !truncated && Instant::now() >= deadline;
+
+    Ok(FileRun {
+        captures,
+        truncated,
+        timed_out,
+        match_limit_exceeded: cursor.did_exceed_match_limit(),
+    })
+}
+
+/// Run `query` over the indexed files of `language` whose paths match
+/// `include` (all when empty), in path order from `offset`. Files are run
+/// in parallel batches and merged in order, so a page is the same whatever
+/// the thread timing. The page ends before the first file that doesn't
+/// fit in `limit` or that the time budget cut off; `next_offset` resumes
+/// from there. A file that overflows an empty page is returned cut short.
+pub fn scan_query(
+    root: &Path,
+    file_tree: &FileTree,
+    language: Language,
+    query: &str,
+    include: &[glob::Pattern],
+    offset: usize,
+    limit: usize,
+) -> Result<ScanResult, String> {
+    let (grammar, query) = compile_query(language, query)?;
+    let options = glob::MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    };
+    let mut files: Vec<String> = file_tree
+        .files
+        .iter()
+        .filter(|entry| entry.value().language == language)
+        .filter(|entry| {
+            include.is_empty() || include.iter().any(|p| p.matches_with(entry.key(), options))
+        })
+        .map(|entry| entry.key().clone())
+        .collect();
+    files.sort();
+    let total_files = files.len();
+    let files_capped = total_files > config::RAW_QUERY_SCAN_MAX_FILES;
+    files.truncate(config::RAW_QUERY_SCAN_MAX_FILES);
+
+    let deadline = Instant::now() + Duration::from_millis(config::RAW_QUERY_SCAN_TIMEOUT_MS);
+    // Rayon workers don't see the request's token, so hand it to them.
+    let cancel = cancel::current();
+    let batch_size = rayon::current_num_threads() * 4;
+    let mut result = ScanResult {
+        language,
+        total_files,
+        files_scanned: 0,
+        count: 0,
+        files: Vec::new(),
+        next_offset: None,
+        timed_out: false,
+        files_capped,
+        errors: Vec::new(),
+        match_limit_exceeded: false,
+        cancelled: false,
+    };
+    let mut next = offset.min(files.len());
+    'batches: while next < files.len() {
+        if Instant::now() >= deadline {
+            result.timed_out = true;
+            break;
+        }
+        let remaining = limit - result.count;
+        let batch = &files[next..(next + batch_size).min(files.len())];
+        let runs: Vec<Result<FileRun, String>> = batch
+            .par_iter()
+            .map(|file| {
+                run_on_file(root, file, language, &grammar, &query, remaining, deadline, &cancel)
+            })
+            .collect();
+        for (file, run) in batch.iter().zip(runs) {
+            // A run cut short by cancellation is incomplete; leave it for
+            // the next page.
+            if cancel.is_cancelled() {
+                result.cancelled = true;
+                break 'batches;
+            }
+            let run = match run {
+                Ok(run) => run,
+                Err(error) => {
+                    result.errors.push(ScanError {
+                        file: file.clone(),
+                        error,
+                    });
+                    next += 1;
+                    result.files_scanned += 1;
+                    continue;
+                }
+            };
+            let take = run.captures.len().min(limit - result.count);
+            let cut_short = take < run.captures.len() || run.truncated || run.timed_out;
+            // A file that doesn't fit waits for the next page, unless it
+            // alone overflows a page.
+            if cut_short && !result.files.is_empty() {
+                if run.timed_out {
+                    result.timed_out = true;
+                }
+                break 'batches;
+            }
+            next += 1;
+            result.files_scanned += 1;
+            result.match_limit_exceeded |= run.match_limit_exceeded;
+            if !run.captures.is_empty() {
+                let mut captures = run.captures;
+                captures.truncate(take);
+                result.count += take;
+                result.files.push(FileCaptures {
+                    file: file.clone(),
+                    count: captures.len(),
+                    captures,
+                    truncated: cut_short,
+                });
+            }
+            if run.timed_out {
+                result.timed_out = true;
+                break 'batches;
+            }
+            if result.count >= limit {
+                break 'batches;
+            }
+        }
+    }
+    if next < files.len() {
+        result.next_offset = Some(next);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::config::DEFAULT_MAX_FILE_SIZE;
+    use crate::index::walker;
+    use crate::server::state::temp_project;
+
+    #[test]
+    fn cancelled_scan_stops_before_the_first_file() {
+        let dir = temp_project(&[("a.rs", "fn a() {}\n"), ("b.rs", "fn b() {}\n")]);
+        let file_tree = Arc::new(FileTree::new());
+        walker::scan_directory(&dir, &file_tree, DEFAULT_MAX_FILE_SIZE).unwrap();
+        let query = "(function_item name: (identifier) @name)";
+
+        let token = CancelToken::default();
+        token.cancel();
+        let result = cancel::with_token(token, || {
+            scan_query(&dir, &file_tree, Language::Rust, query, &[], 0, 10)
+        })
+        .unwrap();
+        assert!(result.cancelled);
+        assert_eq!(result.files_scanned, 0);
+        assert_eq!(result.next_offset, Some(0));
+
+        let result = scan_query(&dir, &file_tree, Language::Rust, query, &[], 0, 10).unwrap();
+        assert!(!result.cancelled);
+        assert_eq!(result.count, 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,12 +1,26 @@
 use std::path::Path;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use chrono::Utc;
+use dashmap::DashMap;
+use regex::Regex;
 use serde::Serialize;
+use utoipa::ToSchema;
 
+use crate::index::encoding;
 use crate::index::file_entry::Language;
 use crate::index::file_tree::FileTree;
-use crate::server::session::{Buffer, BufferInfo, BufferSource, ReplState, SubcallResult};
+use crate::config;
+use crate::ops::content::{self, GrepScope};
+use crate::ops::symbol_ops;
+use crate::server::budget;
+use crate::server::cancel;
+use crate::server::session::{
+    Bookmark, Buffer, BufferAccess, BufferInfo, BufferSource, ReplState, SubcallResult,
+};
+use crate::server::state::Project;
+use crate::symbols::symbol::SymbolKind;
 use crate::symbols::SymbolTable;
 
 // ── Buffer operations ────────────────────────────────────────────────
@@ -24,6 +38,7 @@ pub fn buffer_create(
             description: description.to_string(),
         },
         created_at: Utc::now(),
+        access: BufferAccess::default(),
     };
     let info = BufferInfo::from_buffer(&buf);
     repl.buffers.insert(name.to_string(), buf);
@@ -45,17 +60,15 @@ pub fn buffer_from_file(
 
     let abs_path = root.join(file);
     let source = if entry.language == Language::Pdf {
-        crate::index::pdf::convert_pdf(root, file)
+        crate::index::pdf::convert_pdf(root, file, &cancel::current())
             .map_err(|e| format!("PDF conversion failed for '{}': {}", file, e))?
     } else {
-        std::fs::read_to_string(&abs_path)
+        encoding::read_to_string(&abs_path)
             .map_err(|e| format!("Failed to read '{}': {}", file, e))?
     };
 
     let lines: Vec<&str> = source.lines().collect();
-    let total_lines = lines.len();
-    let start = start.min(total_lines);
-    let end = end.min(total_lines);
+    let (start, end) = crate::ops::content::clamp_range(start, end, lines.len());
 
     let content: String = lines[start..end].join("\n");
 
@@ -68,6 +81,7 @@ pub fn buffer_from_file(
             end_line: end,
         },
         created_at: Utc::now(),
+        access: BufferAccess::default(),
     };
     let info = BufferInfo::from_buffer(&buf);
     repl.buffers.insert(name.to_string(), buf);
@@ -76,28 +90,16 @@ pub fn buffer_from_file(
 
 pub fn buffer_from_symbol(
     repl: &Arc<ReplState>,
-    root: &Path,
-    symbol_table: &Arc<SymbolTable>,
+    project: &Project,
     name: &str,
     symbol_name: &str,
     file: &str,
 ) -> Result<BufferInfo, String> {
-    let sym = symbol_table
+    let sym = project
+        .symbol_table
         .get(file, symbol_name)
         .ok_or_else(|| format!("Symbol '{}' not found in '{}'", symbol_name, file))?;
-
-    let abs_path = root.join(&sym.file);
-    let source = if sym.language == Language::Pdf {
-        crate::index::pdf::convert_pdf(root, &sym.file)
-            .map_err(|e| format!("PDF conversion failed for '{}': {}", sym.file, e))?
-    } else {
-        std::fs::read_to_string(&abs_path)
-            .map_err(|e| format!("Failed to read '{}': {}", sym.file, e))?
-    };
-
-    let start = sym.byte_range.0;
-    let end = sym.byte_range.1.min(source.len());
-    let content = source[start..end].to_string();
+    let (_, content) = symbol_ops::symbol_text(project, sym)?;
 
     let buf = Buffer {
         name: name.to_string(),
@@ -107,34 +109,232 @@ pub fn buffer_from_symbol(
             file: file.to_string(),
         },
         created_at: Utc::now(),
+        access: BufferAccess::default(),
     };
     let info = BufferInfo::from_buffer(&buf);
     repl.buffers.insert(name.to_string(), buf);
     Ok(info)
 }
 
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SkippedFile {
+    pub file: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DirectoryBufferInfo {
+    #[serde(flatten)]
+    pub buffer: BufferInfo,
+    /// Files concatenated into the buffer, in order.
+    pub included: Vec<String>,
+    /// Files that matched but were left out: unreadable, or past the cap.
+    pub skipped: Vec<SkippedFile>,
+    /// Files under the directory that the globs filtered out.
+    pub filtered_files: usize,
+    /// The byte cap was hit before every matching file was added.
+    pub truncated: bool,
+}
+
+/// Concatenate the indexed files under `dir`, sorted by path, each preceded
+/// by a `=== path ===` line. Globs match paths relative to `dir`. Files are
+/// added whole until the next one would push the buffer past
+/// `max_total_bytes`; it and the rest are reported as skipped.
+#[allow(clippy::too_many_arguments)]
+pub fn buffer_from_dir(
+    repl: &Arc<ReplState>,
+    root: &Path,
+    file_tree: &Arc<FileTree>,
+    name: &str,
+    dir: &str,
+    max_total_bytes: usize,
+    include: &[glob::Pattern],
+    exclude: &[glob::Pattern],
+) -> Result<DirectoryBufferInfo, String> {
+    let options = glob::MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    };
+
+    let dir = dir.trim_start_matches("./").trim_end_matches('/');
+    let dir = if dir == "." { "" } else { dir };
+    let mut files: Vec<String> = file_tree
+        .all_paths()
+        .into_iter()
+        .filter(|path| {
+            dir.is_empty()
+                || path
+                    .strip_prefix(dir)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        })
+        .collect();
+    if files.is_empty() {
+        return Err(format!("No indexed files under '{}'", dir));
+    }
+    files.sort();
+
+    let under_dir = files.len();
+    files.retain(|path| {
+        let relative = path[dir.len()..].trim_start_matches('/');
+        (include.is_empty() || include.iter().any(|p| p.matches_with(relative, options)))
+            && !exclude.iter().any(|p| p.matches_with(relative, options))
+    });
+    let filtered_files = under_dir - files.len();
+
+    let mut content = String::new();
+    let mut included = Vec::new();
+    let mut skipped = Vec::new();
+    let mut truncated = false;
+    for file in files {
+        if truncated {
+            skipped.push(SkippedFile {
+                file,
+                reason: "max_total_bytes reached".to_string(),
+            });
+            continue;
+        }
+        let language = file_tree.get(&file).map_or(Language::Other, |e| e.language);
+        let text = if language == Language::Pdf {
+            crate::index::pdf::convert_pdf(root, &file, &cancel::current())
+                .map_err(|e| format!("PDF conversion failed: {}", e))
+        } else {
+            encoding::read_to_string(root.join(&file)).map_err(|e| e.to_string())
+        };
+        let text = match text {
+            Ok(text) => text,
+            Err(reason) => {
+                skipped.push(SkippedFile { file, reason });
+                continue;
+            }
+        };
+        let section = format!("=== {} ===\n{}", file, text);
+        let separator = if content.is_empty() { 0 } else { 1 };
+        if content.len() + separator + section.len() > max_total_bytes {
+            truncated = true;
+            skipped.push(SkippedFile {
+                file,
+                reason: "max_total_bytes reached".to_string(),
+            });
+            continue;
+        }
+        if separator == 1 {
+            content.push('\n');
+        }
+        content.push_str(&section);
+        included.push(file);
+    }
+
+    let buf = Buffer {
+        name: name.to_string(),
+        content,
+        source: BufferSource::Directory {
+            path: dir.to_string(),
+            include_globs: include.iter().map(|p| p.as_str().to_string()).collect(),
+            exclude_globs: exclude.iter().map(|p| p.as_str().to_string()).collect(),
+            max_total_bytes,
+        },
+        created_at: Utc::now(),
+        access: BufferAccess::default(),
+    };
+    let info = BufferInfo::from_buffer(&buf);
+    repl.buffers.insert(name.to_string(), buf);
+    Ok(DirectoryBufferInfo {
+        buffer: info,
+        included,
+        skipped,
+        filtered_files,
+        truncated,
+    })
+}
+
+/// A byte slice of a buffer and the range it actually covers.
+#[derive(Debug)]
+pub struct BufferPeek {
+    pub content: String,
+    /// True when the requested range was adjusted to fit the buffer or to
+    /// land on character boundaries.
+    pub clamped: bool,
+    /// Byte offset actually served from (inclusive).
+    pub resolved_start: usize,
+    /// Byte offset actually served to (exclusive).
+    pub resolved_end: usize,
+    pub total_bytes: usize,
+}
+
 pub fn buffer_peek(
     repl: &Arc<ReplState>,
     name: &str,
     start: usize,
     end: usize,
-) -> Result<String, String> {
+) -> Result<BufferPeek, String> {
     let buf = repl
         .buffers
         .get(name)
         .ok_or_else(|| format!("Buffer '{}' not found", name))?;
+    buf.access.touch();
 
     let content = &buf.content;
-    let start = content.floor_char_boundary(start.min(content.len()));
-    let end = content.floor_char_boundary(end.min(content.len()));
-    Ok(content[start..end].to_string())
+    let (resolved_start, resolved_end) = content::clamp_range(start, end, content.len());
+    let resolved_start = content.floor_char_boundary(resolved_start);
+    let resolved_end = content.floor_char_boundary(resolved_end);
+    Ok(BufferPeek {
+        content: content[resolved_start..resolved_end].to_string(),
+        clamped: (resolved_start, resolved_end) != (start, end),
+        resolved_start,
+        resolved_end,
+        total_bytes: content.len(),
+    })
 }
 
-pub fn buffer_list(repl: &Arc<ReplState>) -> Vec<BufferInfo> {
-    repl.buffers
+/// Order of `GET /buffers`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BufferSort {
+    /// Alphabetical by name.
+    #[default]
+    Name,
+    /// Least recently read first; never-read buffers count from creation.
+    Lru,
+    /// Largest first, ties by name.
+    Size,
+    /// Oldest first.
+    Created,
+}
+
+impl BufferSort {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "name" => Some(BufferSort::Name),
+            "lru" => Some(BufferSort::Lru),
+            "size" => Some(BufferSort::Size),
+            "created" => Some(BufferSort::Created),
+            _ => None,
+        }
+    }
+}
+
+/// Every buffer's metadata. Listing doesn't count as reading them.
+pub fn buffer_list(repl: &Arc<ReplState>, sort: BufferSort) -> Vec<BufferInfo> {
+    let mut buffers: Vec<BufferInfo> = repl
+        .buffers
         .iter()
         .map(|entry| BufferInfo::from_buffer(entry.value()))
-        .collect()
+        .collect();
+    match sort {
+        BufferSort::Name => buffers.sort_by(|a, b| a.name.cmp(&b.name)),
+        BufferSort::Lru => buffers.sort_by(|a, b| {
+            let a_at = a.last_accessed_at.unwrap_or(a.created_at);
+            let b_at = b.last_accessed_at.unwrap_or(b.created_at);
+            a_at.cmp(&b_at).then_with(|| a.name.cmp(&b.name))
+        }),
+        BufferSort::Size => buffers.sort_by(|a, b| {
+            b.size_bytes.cmp(&a.size_bytes).then_with(|| a.name.cmp(&b.name))
+        }),
+        BufferSort::Created => buffers.sort_by(|a, b| {
+            a.created_at.cmp(&b.created_at).then_with(|| a.name.cmp(&b.name))
+        }),
+    }
+    buffers
 }
 
 pub fn buffer_info(repl: &Arc<ReplState>, name: &str) -> Result<BufferInfo, String> {
@@ -142,6 +342,7 @@ pub fn buffer_info(repl: &Arc<ReplState>, name: &str) -> Result<BufferInfo, Stri
         .buffers
         .get(name)
         .ok_or_else(|| format!("Buffer '{}' not found", name))?;
+    buf.access.touch();
     Ok(BufferInfo::from_buffer(buf.value()))
 }
 
@@ -158,6 +359,28 @@ pub fn var_set(repl: &Arc<ReplState>, name: &str, value: serde_json::Value) {
     repl.variables.insert(name.to_string(), value);
 }
 
+/// Evaluate the JMESPath `expr` against all variables, presented as one
+/// object keyed by variable name, and store the result as `name`.
+pub fn var_compute(
+    repl: &Arc<ReplState>,
+    name: &str,
+    expr: &str,
+) -> Result<serde_json::Value, String> {
+    let compiled = jmespath::compile(expr)
+        .map_err(|e| format!("Invalid expression '{}': {}", expr, e))?;
+    let scope: serde_json::Map<String, serde_json::Value> = repl
+        .variables
+        .iter()
+        .map(|entry| (entry.key().clone(), entry.value().clone()))
+        .collect();
+    let result = compiled
+        .search(serde_json::Value::Object(scope))
+        .map_err(|e| format!("Evaluating '{}' failed: {}", expr, e))?;
+    let value = serde_json::to_value(&*result).map_err(|e| e.to_string())?;
+    var_set(repl, name, value.clone());
+    Ok(value)
+}
+
 pub fn var_get(repl: &Arc<ReplState>, name: &str) -> Result<serde_json::Value, String> {
     repl.variables
         .get(name)
@@ -165,11 +388,198 @@ pub fn var_get(repl: &Arc<ReplState>, name: &str) -> Result<serde_json::Value, S
         .ok_or_else(|| format!("Variable '{}' not found", name))
 }
 
-pub fn var_list(repl: &Arc<ReplState>) -> Vec<(String, serde_json::Value)> {
-    repl.variables
+/// Which variables `var_list` returns and how much of each value.
+#[derive(Debug, Default)]
+pub struct VarSelection {
+    /// Exact names to include.
+    pub names: Option<Vec<String>>,
+    /// Include every variable whose name starts with this.
+    pub prefix: Option<String>,
+    /// Values whose JSON encoding exceeds this are cut and flagged.
+    pub max_value_bytes: Option<usize>,
+    pub sort: VarSort,
+    /// Number of matching variables to skip.
+    pub offset: usize,
+    /// Return at most this many variables after `offset`.
+    pub limit: Option<usize>,
+}
+
+/// Order of `var_list` results.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum VarSort {
+    /// Alphabetical by name (default behavior).
+    #[default]
+    Name,
+    /// Largest JSON-encoded value first, ties by name.
+    Size,
+}
+
+impl VarSort {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "name" => Some(VarSort::Name),
+            "size" => Some(VarSort::Size),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct VarEntry {
+    pub name: String,
+    pub value: serde_json::Value,
+    /// Set when `value` was cut to `max_value_bytes`. Truncated values are
+    /// returned as a string: the original string, or the JSON encoding for
+    /// any other type, followed by `...`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// Size of the full JSON-encoded value, present when truncated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_bytes: Option<usize>,
+    /// Where the value comes from, in listings that include project
+    /// variables.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub origin: Option<VarOrigin>,
+    /// A session variable hiding a project variable of the same name.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub shadows_project: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum VarOrigin {
+    Session,
+    Project,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct VarListing {
+    pub variables: Vec<VarEntry>,
+    /// Number of variables in this page.
+    pub count: usize,
+    /// Number of variables matching the selection, before paging.
+    pub total: usize,
+    /// More matching variables follow this page.
+    pub has_more: bool,
+    /// Requested `names` that don't exist.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<String>,
+}
+
+/// List variables, by default sorted by name. With no `names` or `prefix`
+/// every variable is returned; otherwise a variable is included if it matches
+/// either. `offset` and `limit` page through the sorted matches.
+pub fn var_list(repl: &Arc<ReplState>, selection: &VarSelection) -> VarListing {
+    list_vars_in(&repl.variables, selection)
+}
+
+/// `var_list` over the session's variables laid on top of `project_vars`:
+/// a session variable shadows a project variable of the same name. Each
+/// entry is labeled with its origin.
+pub fn var_list_with_project(
+    repl: &Arc<ReplState>,
+    project_vars: &DashMap<String, serde_json::Value>,
+    selection: &VarSelection,
+) -> VarListing {
+    let merged: DashMap<String, serde_json::Value> = project_vars
         .iter()
+        .chain(repl.variables.iter())
         .map(|entry| (entry.key().clone(), entry.value().clone()))
-        .collect()
+        .collect();
+    let mut listing = list_vars_in(&merged, selection);
+    for var in &mut listing.variables {
+        let in_session = repl.variables.contains_key(&var.name);
+        var.origin = Some(if in_session { VarOrigin::Session } else { VarOrigin::Project });
+        var.shadows_project = in_session && project_vars.contains_key(&var.name);
+    }
+    listing
+}
+
+/// List the variables in `vars`, as described on `var_list`.
+pub fn list_vars_in(
+    vars: &DashMap<String, serde_json::Value>,
+    selection: &VarSelection,
+) -> VarListing {
+    let select_all = selection.names.is_none() && selection.prefix.is_none();
+    let wanted = |name: &str| {
+        select_all
+            || selection
+                .names
+                .as_ref()
+                .is_some_and(|names| names.iter().any(|n| n == name))
+            || selection
+                .prefix
+                .as_ref()
+                .is_some_and(|prefix| name.starts_with(prefix.as_str()))
+    };
+
+    let mut matched: Vec<(String, serde_json::Value)> = vars
+        .iter()
+        .filter(|entry| wanted(entry.key()))
+        .map(|entry| (entry.key().clone(), entry.value().clone()))
+        .collect();
+    match selection.sort {
+        VarSort::Name => matched.sort_by(|a, b| a.0.cmp(&b.0)),
+        VarSort::Size => matched.sort_by_cached_key(|(name, value)| {
+            (std::cmp::Reverse(value.to_string().len()), name.clone())
+        }),
+    }
+
+    let total = matched.len();
+    let variables: Vec<VarEntry> = matched
+        .iter()
+        .skip(selection.offset)
+        .take(selection.limit.unwrap_or(usize::MAX))
+        .map(|(name, value)| var_entry(name, value, selection.max_value_bytes))
+        .collect();
+
+    let missing = selection
+        .names
+        .iter()
+        .flatten()
+        .filter(|n| !vars.contains_key(n.as_str()))
+        .cloned()
+        .collect();
+
+    VarListing {
+        count: variables.len(),
+        total,
+        has_more: selection.offset.saturating_add(variables.len()) < total,
+        variables,
+        missing,
+    }
+}
+
+fn var_entry(name: &str, value: &serde_json::Value, max_bytes: Option<usize>) -> VarEntry {
+    let full = |value: serde_json::Value| VarEntry {
+        name: name.to_string(),
+        value,
+        truncated: false,
+        value_bytes: None,
+        origin: None,
+        shadows_project: false,
+    };
+    let Some(max) = max_bytes else {
+        return full(value.clone());
+    };
+    let encoded = value.to_string();
+    if encoded.len() <= max {
+        return full(value.clone());
+    }
+
+    let text = match value {
+        serde_json::Value::String(s) => s.as_str(),
+        _ => encoded.as_str(),
+    };
+    let cut = &text[..text.floor_char_boundary(max)];
+    VarEntry {
+        name: name.to_string(),
+        value: serde_json::Value::String(format!("{}...", cut)),
+        truncated: true,
+        value_bytes: Some(encoded.len()),
+        origin: None,
+        shadows_project: false,
+    }
 }
 
 pub fn var_delete(repl: &Arc<ReplState>, name: &str) -> Result<(), String> {
@@ -183,6 +593,23 @@ pub fn check_final(repl: &Arc<ReplState>) -> Option<serde_json::Value> {
     repl.variables.get("Final").map(|v| v.value().clone())
 }
 
+/// Poll for `Final` every 100 ms until it is set or `timeout` elapses.
+pub async fn wait_for_final(
+    repl: &Arc<ReplState>,
+    timeout: std::time::Duration,
+) -> Option<serde_json::Value> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        if let Some(value) = check_final(repl) {
+            return Some(value);
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return None;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+}
+
 // ── Subcall results ──────────────────────────────────────────────────
 
 pub fn add_subcall_result(repl: &Arc<ReplState>, result: SubcallResult) {
@@ -193,13 +620,309 @@ pub fn list_subcall_results(repl: &Arc<ReplState>) -> Vec<SubcallResult> {
     repl.subcall_results.lock().clone()
 }
 
-pub fn clear_subcall_results(repl: &Arc<ReplState>) {
-    repl.subcall_results.lock().clear();
+/// Drop every stored result. With `delete_buffers`, the buffers their text
+/// was materialized into go too. Returns the results and buffers removed.
+pub fn clear_subcall_results(repl: &Arc<ReplState>, delete_buffers: bool) -> (usize, usize) {
+    let removed: Vec<SubcallResult> = std::mem::take(&mut *repl.subcall_results.lock());
+    let buffers = if delete_buffers { drop_materialized(repl, &removed) } else { 0 };
+    (removed.len(), buffers)
+}
+
+/// Move the findings and answer of `result` into a `subcall/<chunk_id>`
+/// buffer when together they exceed `SUBCALL_MATERIALIZE_MIN_BYTES`,
+/// leaving previews and the buffer name behind. The buffer holds them as
+/// JSON. A name already taken by an earlier result gets a `/2`, `/3`...
+/// suffix. Returns the buffer created, if any.
+pub fn materialize_subcall_result(
+    repl: &Arc<ReplState>,
+    result: &mut SubcallResult,
+) -> Option<BufferInfo> {
+    let size: usize = result
+        .findings
+        .iter()
+        .map(|f| f.point.len() + f.evidence.len())
+        .sum::<usize>()
+        + result.answer_if_complete.as_ref().map_or(0, String::len);
+    if size <= config::SUBCALL_MATERIALIZE_MIN_BYTES {
+        return None;
+    }
+
+    let base = format!("subcall/{}", result.chunk_id);
+    let name = (1..)
+        .map(|n| if n == 1 { base.clone() } else { format!("{}/{}", base, n) })
+        .find(|name| !repl.buffers.contains_key(name))
+        .expect("unbounded range");
+    let full = serde_json::json!({
+        "chunk_id": result.chunk_id,
+        "query": result.query,
+        "findings": result.findings,
+        "answer_if_complete": result.answer_if_complete,
+    });
+    let buf = Buffer {
+        name: name.clone(),
+        content: serde_json::to_string_pretty(&full).unwrap_or_default(),
+        source: BufferSource::SubLmResult {
+            query: result.query.clone(),
+        },
+        created_at: Utc::now(),
+        access: BufferAccess::default(),
+    };
+    let info = BufferInfo::from_buffer(&buf);
+    repl.buffers.insert(name.clone(), buf);
+
+    for finding in &mut result.findings {
+        finding.point = subcall_preview(&finding.point);
+        finding.evidence = subcall_preview(&finding.evidence);
+    }
+    result.answer_if_complete = result.answer_if_complete.as_deref().map(subcall_preview);
+    result.buffer = Some(name);
+    Some(info)
+}
+
+fn subcall_preview(text: &str) -> String {
+    if text.len() > config::SUBCALL_PREVIEW_BYTES {
+        let boundary = text.floor_char_boundary(config::SUBCALL_PREVIEW_BYTES);
+        format!("{}...", &text[..boundary])
+    } else {
+        text.to_string()
+    }
+}
+
+/// Remove the materialized buffers of `results`. Returns how many existed.
+fn drop_materialized(repl: &Arc<ReplState>, results: &[SubcallResult]) -> usize {
+    results
+        .iter()
+        .filter_map(|r| r.buffer.as_deref())
+        .filter(|name| repl.buffers.remove(*name).is_some())
+        .count()
+}
+
+/// Results stored for `chunk_id`, oldest first. Empty when the chunk hasn't
+/// been analyzed yet.
+pub fn get_subcall_results_for_chunk(repl: &Arc<ReplState>, chunk_id: &str) -> Vec<SubcallResult> {
+    repl.subcall_results
+        .lock()
+        .iter()
+        .filter(|r| r.chunk_id == chunk_id)
+        .cloned()
+        .collect()
+}
+
+/// Drop the results stored for `chunk_id`, and with `delete_buffers` their
+/// materialized buffers. Returns the results and buffers removed.
+pub fn clear_subcall_results_for_chunk(
+    repl: &Arc<ReplState>,
+    chunk_id: &str,
+    delete_buffers: bool,
+) -> (usize, usize) {
+    let removed: Vec<SubcallResult> = {
+        let mut results = repl.subcall_results.lock();
+        let (removed, kept) = std::mem::take(&mut *results)
+            .into_iter()
+            .partition(|r| r.chunk_id == chunk_id);
+        *results = kept;
+        removed
+    };
+    let buffers = if delete_buffers { drop_materialized(repl, &removed) } else { 0 };
+    (removed.len(), buffers)
+}
+
+/// One finding point reported by any of a chunk's results.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AggregatedFinding {
+    pub point: String,
+    /// Distinct evidence given for the point, in the order reported.
+    pub evidence: Vec<String>,
+    /// Highest confidence any result gave it.
+    pub confidence: String,
+    /// How many results reported it.
+    pub occurrences: usize,
+}
+
+/// Every result stored for one chunk, merged.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChunkAggregate {
+    pub chunk_id: String,
+    pub result_count: usize,
+    /// Distinct queries the chunk was analyzed for.
+    pub queries: Vec<String>,
+    /// Findings merged by point (case and surrounding whitespace ignored),
+    /// most reported first.
+    pub findings: Vec<AggregatedFinding>,
+    pub suggested_queries: Vec<String>,
+    /// Every `answer_if_complete` given, oldest first.
+    pub answers: Vec<String>,
+}
+
+pub fn aggregate_subcall_results(chunk_id: &str, results: &[SubcallResult]) -> ChunkAggregate {
+    fn push_unique(list: &mut Vec<String>, item: &str) {
+        if !item.is_empty() && !list.iter().any(|x| x == item) {
+            list.push(item.to_string());
+        }
+    }
+    fn rank(confidence: &str) -> u8 {
+        match confidence {
+            "high" => 3,
+            "medium" => 2,
+            "low" => 1,
+            _ => 0,
+        }
+    }
+
+    let mut queries = Vec::new();
+    let mut suggested_queries = Vec::new();
+    let mut answers = Vec::new();
+    let mut findings: Vec<AggregatedFinding> = Vec::new();
+    for result in results {
+        push_unique(&mut queries, &result.query);
+        for q in &result.suggested_queries {
+            push_unique(&mut suggested_queries, q);
+        }
+        if let Some(answer) = &result.answer_if_complete {
+            answers.push(answer.clone());
+        }
+        for finding in &result.findings {
+            let key = finding.point.trim().to_lowercase();
+            match findings
+                .iter_mut()
+                .find(|f| f.point.trim().to_lowercase() == key)
+            {
+                Some(merged) => {
+                    merged.occurrences += 1;
+                    push_unique(&mut merged.evidence, &finding.evidence);
+                    if rank(&finding.confidence) > rank(&merged.confidence) {
+                        merged.confidence = finding.confidence.clone();
+                    }
+                }
+                None => {
+                    let mut evidence = Vec::new();
+                    push_unique(&mut evidence, &finding.evidence);
+                    findings.push(AggregatedFinding {
+                        point: finding.point.trim().to_string(),
+                        evidence,
+                        confidence: finding.confidence.clone(),
+                        occurrences: 1,
+                    });
+                }
+            }
+        }
+    }
+    // Stable, so ties keep the order they were first reported in.
+    findings.sort_by_key(|f| std::cmp::Reverse(f.occurrences));
+
+    ChunkAggregate {
+        chunk_id: chunk_id.to_string(),
+        result_count: results.len(),
+        queries,
+        findings,
+        suggested_queries,
+        answers,
+    }
+}
+
+// ── Bookmarks ────────────────────────────────────────────────────────
+
+/// A bookmark with its location resolved against the current file content.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BookmarkView {
+    #[serde(flatten)]
+    pub bookmark: Bookmark,
+    /// 1-based line the preview was taken from: the symbol's current start
+    /// line if it still exists, else the stored line, else 1.
+    pub resolved_line: usize,
+    /// Trimmed source line, `None` if the file or line is gone.
+    pub preview: Option<String>,
+}
+
+pub fn bookmark_add(
+    repl: &Arc<ReplState>,
+    file_tree: &Arc<FileTree>,
+    symbol_table: &Arc<SymbolTable>,
+    file: &str,
+    line: Option<usize>,
+    symbol: Option<&str>,
+    note: &str,
+) -> Result<Bookmark, String> {
+    if file_tree.get(file).is_none() {
+        return Err(format!("File '{}' not found in index", file));
+    }
+    if line == Some(0) {
+        return Err("line is 1-based".to_string());
+    }
+    if let Some(name) = symbol
+        && symbol_table.get(file, name).is_none()
+    {
+        return Err(format!("Symbol '{}' not found in '{}'", name, file));
+    }
+
+    let bookmark = Bookmark {
+        id: repl.next_bookmark_id.fetch_add(1, Ordering::SeqCst) + 1,
+        file: file.to_string(),
+        line,
+        symbol: symbol.map(|s| s.to_string()),
+        note: note.to_string(),
+        created_at: Utc::now(),
+    };
+    repl.bookmarks.lock().push(bookmark.clone());
+    Ok(bookmark)
+}
+
+/// List bookmarks in creation order, each with a fresh one-line preview.
+pub fn bookmark_list(
+    repl: &Arc<ReplState>,
+    root: &Path,
+    file_tree: &Arc<FileTree>,
+    symbol_table: &Arc<SymbolTable>,
+) -> Vec<BookmarkView> {
+    let bookmarks = repl.bookmarks.lock().clone();
+    bookmarks
+        .into_iter()
+        .map(|bookmark| {
+            let resolved_line = bookmark
+                .symbol
+                .as_deref()
+                .and_then(|name| symbol_table.get(&bookmark.file, name))
+                .map(|sym| sym.line_range.0)
+                .or(bookmark.line)
+                .unwrap_or(1);
+            let preview = read_line(root, file_tree, &bookmark.file, resolved_line);
+            BookmarkView {
+                bookmark,
+                resolved_line,
+                preview,
+            }
+        })
+        .collect()
+}
+
+pub fn bookmark_delete(repl: &Arc<ReplState>, id: u64) -> Result<(), String> {
+    let mut bookmarks = repl.bookmarks.lock();
+    let before = bookmarks.len();
+    bookmarks.retain(|b| b.id != id);
+    if bookmarks.len() == before {
+        return Err(format!("Bookmark {} not found", id));
+    }
+    Ok(())
+}
+
+fn read_line(root: &Path, file_tree: &Arc<FileTree>, file: &str, line: usize) -> Option<String> {
+    let language = file_tree.get(file)?.language;
+    let source = if language == Language::Pdf {
+        crate::index::pdf::convert_pdf(root, file, &cancel::current()).ok()?
+    } else {
+        encoding::read_to_string(root.join(file)).ok()?
+    };
+    let text = source.lines().nth(line.checked_sub(1)?)?.trim();
+    Some(if text.len() > 200 {
+        format!("{}...", &text[..text.floor_char_boundary(200)])
+    } else {
+        text.to_string()
+    })
 }
 
 // ── Semantic chunking ────────────────────────────────────────────────
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct SemanticChunk {
     pub index: usize,
     pub byte_start: usize,
@@ -208,6 +931,11 @@ pub struct SemanticChunk {
     pub line_end: usize,
     pub symbols: Vec<String>,
     pub preview: String,
+    /// Short label for the chunk, when requested: the name of a symbol
+    /// covering over 80% of its bytes, else the first three symbol names,
+    /// else `misc`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
 }
 
 pub fn semantic_chunks(
@@ -216,33 +944,58 @@ pub fn semantic_chunks(
     symbol_table: &Arc<SymbolTable>,
     file: &str,
     max_chunk_bytes: usize,
+    include_topic: bool,
 ) -> Result<Vec<SemanticChunk>, String> {
+    let (source, _) = read_indexed(root, file_tree, file)?;
+    let mut chunks = chunk_source(&source, symbol_table, file, max_chunk_bytes);
+    if !include_topic {
+        for chunk in &mut chunks {
+            chunk.topic = None;
+        }
+    }
+    Ok(chunks)
+}
+
+fn read_indexed(
+    root: &Path,
+    file_tree: &Arc<FileTree>,
+    file: &str,
+) -> Result<(String, Language), String> {
     let entry = file_tree
         .get(file)
         .ok_or_else(|| format!("File '{}' not found in index", file))?;
 
     let abs_path = root.join(file);
     let source = if entry.language == Language::Pdf {
-        crate::index::pdf::convert_pdf(root, file)
+        crate::index::pdf::convert_pdf(root, file, &cancel::current())
             .map_err(|e| format!("PDF conversion failed for '{}': {}", file, e))?
     } else {
-        std::fs::read_to_string(&abs_path)
+        encoding::read_to_string(&abs_path)
             .map_err(|e| format!("Failed to read '{}': {}", file, e))?
     };
+    Ok((source, entry.language))
+}
 
+fn chunk_source(
+    source: &str,
+    symbol_table: &Arc<SymbolTable>,
+    file: &str,
+    max_chunk_bytes: usize,
+) -> Vec<SemanticChunk> {
     // Get all symbols in this file, sorted by byte range start
     let mut file_symbols = symbol_table.list_by_file(file);
     file_symbols.sort_by_key(|s| s.byte_range.0);
 
     if file_symbols.is_empty() {
         // No symbols: fall back to byte-boundary chunks
-        return Ok(simple_chunks(&source, max_chunk_bytes));
+        return simple_chunks(source, max_chunk_bytes);
     }
 
     // Build chunks aligned to symbol boundaries
     let mut chunks = Vec::new();
     let mut chunk_start = 0usize;
-    let mut chunk_symbols: Vec<String> = Vec::new();
+    // Names with byte ranges, for the topic label.
+    let mut chunk_symbols: Vec<(String, (usize, usize))> = Vec::new();
     let mut chunk_index = 0usize;
 
     for sym in &file_symbols {
@@ -259,7 +1012,7 @@ pub fn semantic_chunks(
             // Close chunk at the start of this symbol
             let chunk_end = sym_start;
             chunks.push(make_chunk(
-                &source,
+                source,
                 chunk_index,
                 chunk_start,
                 chunk_end,
@@ -272,9 +1025,9 @@ pub fn semantic_chunks(
 
         // If a single symbol exceeds the budget, it gets its own chunk
         if sym_size > max_chunk_bytes && chunk_symbols.is_empty() {
-            chunk_symbols.push(sym.name.clone());
+            chunk_symbols.push((sym.name.clone(), (sym_start, sym_end)));
             chunks.push(make_chunk(
-                &source,
+                source,
                 chunk_index,
                 sym_start,
                 sym_end,
@@ -286,13 +1039,13 @@ pub fn semantic_chunks(
             continue;
         }
 
-        chunk_symbols.push(sym.name.clone());
+        chunk_symbols.push((sym.name.clone(), (sym_start, sym_end)));
     }
 
     // Final chunk: from chunk_start to end of file
     if chunk_start < source.len() {
         chunks.push(make_chunk(
-            &source,
+            source,
             chunk_index,
             chunk_start,
             source.len(),
@@ -300,7 +1053,206 @@ pub fn semantic_chunks(
         ));
     }
 
-    Ok(chunks)
+    chunks
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChunkGrepHit {
+    pub index: usize,
+    pub byte_start: usize,
+    pub byte_end: usize,
+    pub line_start: usize,
+    pub line_end: usize,
+    pub symbols: Vec<String>,
+    /// Matching lines whose first match starts in this chunk.
+    pub match_count: usize,
+    /// The first few of those lines.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub matches: Vec<ChunkGrepLine>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChunkGrepLine {
+    /// 1-based line in the file.
+    pub line: usize,
+    pub text: String,
+}
+
+/// Chunk `file` as `semantic_chunks` does and grep it, reporting per chunk
+/// how many lines match and the first `lines_per_chunk` of them. A line
+/// that straddles a chunk boundary counts toward the chunk where its first
+/// match starts.
+#[allow(clippy::too_many_arguments)]
+pub fn semantic_chunk_grep(
+    root: &Path,
+    file_tree: &Arc<FileTree>,
+    symbol_table: &Arc<SymbolTable>,
+    file: &str,
+    pattern: &str,
+    max_chunk_bytes: usize,
+    lines_per_chunk: usize,
+    scope: GrepScope,
+) -> Result<Vec<ChunkGrepHit>, String> {
+    let re = Regex::new(pattern).map_err(|e| format!("Invalid regex: {}", e))?;
+    let (source, language) = read_indexed(root, file_tree, file)?;
+    let chunks = chunk_source(&source, symbol_table, file, max_chunk_bytes);
+
+    let mut hits: Vec<ChunkGrepHit> = chunks
+        .into_iter()
+        .map(|c| ChunkGrepHit {
+            index: c.index,
+            byte_start: c.byte_start,
+            byte_end: c.byte_end,
+            line_start: c.line_start,
+            line_end: c.line_end,
+            symbols: c.symbols,
+            match_count: 0,
+            matches: Vec::new(),
+        })
+        .collect();
+
+    let lines: Vec<&str> = source.lines().collect();
+    let matched = content::matching_lines(&re, scope, &source, language, &lines, 0, lines.len());
+    for (i, byte) in matched {
+        // Chunks are contiguous and sorted, so the owner is the last one
+        // starting at or before the match.
+        let owner = hits.partition_point(|h| h.byte_start <= byte);
+        let Some(hit) = owner.checked_sub(1).and_then(|k| hits.get_mut(k)) else {
+            continue;
+        };
+        hit.match_count += 1;
+        if hit.matches.len() < lines_per_chunk {
+            hit.matches.push(ChunkGrepLine {
+                line: i + 1,
+                text: lines[i].to_string(),
+            });
+        }
+    }
+
+    Ok(hits)
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChunkPackage {
+    pub file: String,
+    /// The chunk's `index` in `/semantic_chunks` with the same
+    /// `max_chunk_bytes`.
+    pub chunk_id: usize,
+    pub chunk_count: usize,
+    /// As on `SemanticChunk`; the header in `text` gives 1-based lines.
+    pub line_start: usize,
+    pub line_end: usize,
+    /// Ready-to-send prompt: header, adjacent signatures, chunk body, query.
+    pub text: String,
+    /// Estimated tokens of `text`, at most the budget.
+    pub tokens: usize,
+    pub budget_tokens: usize,
+    /// The chunk body was cut to fit the budget; `text` says so too.
+    pub truncated: bool,
+}
+
+/// Assemble the prompt a sub-LM call over one chunk of `file` needs: file
+/// and chunk metadata, the signatures of up to `CHUNK_PACKAGE_NEIGHBORS`
+/// symbols on each side of the chunk, the chunk body and `query`. When the
+/// whole text would exceed `budget_tokens`, the body is cut at a line
+/// boundary to fit and a note says how much was kept. Fails when the budget
+/// can't hold the text around the body.
+#[allow(clippy::too_many_arguments)]
+pub fn package_chunk(
+    root: &Path,
+    file_tree: &Arc<FileTree>,
+    symbol_table: &Arc<SymbolTable>,
+    file: &str,
+    chunk_id: usize,
+    max_chunk_bytes: usize,
+    query: &str,
+    budget_tokens: usize,
+) -> Result<ChunkPackage, String> {
+    let (source, language) = read_indexed(root, file_tree, file)?;
+    let chunks = chunk_source(&source, symbol_table, file, max_chunk_bytes);
+    let chunk = chunks.get(chunk_id).ok_or_else(|| {
+        format!("Chunk {} out of range: '{}' has {} chunks", chunk_id, file, chunks.len())
+    })?;
+
+    let mut symbols = symbol_table.list_by_file(file);
+    symbols.retain(|s| !matches!(s.kind, SymbolKind::Variable | SymbolKind::Import));
+    symbols.sort_by_key(|s| s.byte_range);
+    let before = symbols.iter().filter(|s| s.byte_range.1 <= chunk.byte_start);
+    let after = symbols.iter().filter(|s| s.byte_range.0 >= chunk.byte_end);
+    let mut neighbors: Vec<_> = before.rev().take(config::CHUNK_PACKAGE_NEIGHBORS).collect();
+    neighbors.reverse();
+    neighbors.extend(after.take(config::CHUNK_PACKAGE_NEIGHBORS));
+
+    let mut header = format!(
+        "File: {} ({})\nChunk: {} of {}, lines {}-{}\n",
+        file,
+        format!("{:?}", language).to_lowercase(),
+        chunk_id + 1,
+        chunks.len(),
+        chunk.line_start + 1,
+        chunk.line_end,
+    );
+    if !chunk.symbols.is_empty() {
+        header.push_str(&format!("Symbols: {}\n", chunk.symbols.join(", ")));
+    }
+    if !neighbors.is_empty() {
+        header.push_str("\nAdjacent signatures:\n");
+        for sym in neighbors {
+            let signature = sym.signature.lines().next().unwrap_or_default();
+            header.push_str(&format!("  L{}: {}\n", sym.line_range.0, signature));
+        }
+    }
+    let body = &source[chunk.byte_start..chunk.byte_end];
+    let assemble = |body: &str, note: Option<&str>| {
+        let mut text = format!("{}\n--- chunk ---\n{}", header, body);
+        if !body.is_empty() && !body.ends_with('\n') {
+            text.push('\n');
+        }
+        if let Some(note) = note {
+            text.push_str(note);
+            text.push('\n');
+        }
+        text.push_str(&format!("--- end chunk ---\n\nQuery: {}\n", query));
+        text
+    };
+    let truncation_note = |kept: usize| {
+        format!(
+            "[truncated: {} of {} bytes shown to fit {} tokens]",
+            kept,
+            body.len(),
+            budget_tokens
+        )
+    };
+
+    let full = assemble(body, None);
+    let (text, truncated) = if budget::estimate_tokens(full.len()) <= budget_tokens {
+        (full, false)
+    } else {
+        // Reserve room for the longest note, then keep whole lines.
+        let overhead = assemble("", Some(&truncation_note(body.len()))).len();
+        let room = (budget_tokens * 4).checked_sub(overhead + 1).ok_or_else(|| {
+            format!(
+                "budget_tokens {} can't hold the chunk header and query ({} tokens)",
+                budget_tokens,
+                budget::estimate_tokens(overhead)
+            )
+        })?;
+        let cut = body.floor_char_boundary(room);
+        let cut = body[..cut].rfind('\n').map_or(cut, |i| i + 1);
+        let kept = &body[..cut];
+        (assemble(kept, Some(&truncation_note(kept.len()))), true)
+    };
+    Ok(ChunkPackage {
+        file: file.to_string(),
+        chunk_id,
+        chunk_count: chunks.len(),
+        line_start: chunk.line_start,
+        line_end: chunk.line_end,
+        tokens: budget::estimate_tokens(text.len()),
+        text,
+        budget_tokens,
+        truncated,
+    })
 }
 
 fn make_chunk(
@@ -308,7 +1260,7 @@ fn make_chunk(
     index: usize,
     byte_start: usize,
     byte_end: usize,
-    symbols: &[String],
+    symbols: &[(String, (usize, usize))],
 ) -> SemanticChunk {
     let line_start = source[..byte_start].lines().count();
     let line_end = source[..byte_end].lines().count();
@@ -326,11 +1278,33 @@ fn make_chunk(
         byte_end,
         line_start,
         line_end,
-        symbols: symbols.to_vec(),
+        symbols: symbols.iter().map(|(name, _)| name.clone()).collect(),
         preview,
+        topic: Some(chunk_topic(byte_start, byte_end, symbols)),
     }
 }
 
+/// Label a chunk by its symbols, as described on `SemanticChunk::topic`.
+fn chunk_topic(byte_start: usize, byte_end: usize, symbols: &[(String, (usize, usize))]) -> String {
+    let chunk_bytes = byte_end.saturating_sub(byte_start);
+    let dominant = symbols.iter().find(|(_, (start, end))| {
+        let covered = (*end).min(byte_end).saturating_sub((*start).max(byte_start));
+        chunk_bytes > 0 && covered * 5 > chunk_bytes * 4
+    });
+    if let Some((name, _)) = dominant {
+        return name.clone();
+    }
+    if symbols.is_empty() {
+        return "misc".to_string();
+    }
+    symbols
+        .iter()
+        .take(3)
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 fn simple_chunks(source: &str, max_chunk_bytes: usize) -> Vec<SemanticChunk> {
     let mut chunks = Vec::new();
     let mut start = 0;
@@ -351,3 +1325,112 @@ fn simple_chunks(source: &str, max_chunk_bytes: usize) -> Vec<SemanticChunk> {
 
     chunks
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DEFAULT_MAX_FILE_SIZE;
+    use crate::index::walker;
+    use crate::server::state::temp_project;
+
+    fn chunk_grep(source: &str, pattern: &str, max_chunk_bytes: usize) -> Vec<ChunkGrepHit> {
+        let dir = temp_project(&[("notes.txt", source)]);
+        let file_tree = Arc::new(FileTree::new());
+        walker::scan_directory(&dir, &file_tree, DEFAULT_MAX_FILE_SIZE).unwrap();
+        let symbol_table = Arc::new(SymbolTable::new());
+        let hits = semantic_chunk_grep(
+            &dir,
+            &file_tree,
+            &symbol_table,
+            "notes.txt",
+            pattern,
+            max_chunk_bytes,
+            3,
+            GrepScope::All,
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+        hits.unwrap()
+    }
+
+    fn counts(hits: &[ChunkGrepHit]) -> Vec<usize> {
+        hits.iter().map(|h| h.match_count).collect()
+    }
+
+    #[test]
+    fn matches_are_counted_per_chunk() {
+        // Three 20-byte lines; 25-byte chunks break after each line.
+        let source = "alpha needle 000000\nbeta 0000000000000\ngamma needle needle\n";
+        let hits = chunk_grep(source, "needle", 25);
+        assert_eq!(counts(&hits), [1, 0, 1]);
+        assert_eq!(hits[0].matches[0].line, 1);
+        assert_eq!(hits[2].matches[0].line, 3);
+        assert!(hits[1].matches.is_empty());
+    }
+
+    #[test]
+    fn straddling_match_counts_in_the_chunk_holding_its_start() {
+        // One long line, so chunks split mid-line at 40 bytes; `needle`
+        // spans bytes 37..43.
+        let source = format!("{}needle{}\n", "x".repeat(37), "y".repeat(60));
+        let hits = chunk_grep(&source, "needle", 40);
+        assert!(hits.len() >= 2);
+        assert_eq!(hits[0].byte_end, 40);
+        assert_eq!(hits[0].match_count, 1);
+        assert_eq!(hits[0].matches[0].line, 1);
+        assert!(hits[1..].iter().all(|h| h.match_count == 0));
+    }
+
+    #[test]
+    fn match_starting_after_a_boundary_belongs_to_the_next_chunk() {
+        let source = format!("{}needle{}\n", "x".repeat(40), "y".repeat(20));
+        let hits = chunk_grep(&source, "needle", 40);
+        assert_eq!(hits[0].match_count, 0);
+        assert_eq!(hits[1].match_count, 1);
+    }
+
+    #[test]
+    fn lines_reported_per_chunk_are_capped() {
+        let source = "needle\n".repeat(10);
+        let hits = chunk_grep(&source, "needle", 1000);
+        assert_eq!(counts(&hits), [10]);
+        assert_eq!(hits[0].matches.len(), 3);
+    }
+
+    #[test]
+    fn invalid_pattern_is_an_error() {
+        let dir = temp_project(&[("notes.txt", "text\n")]);
+        let file_tree = Arc::new(FileTree::new());
+        walker::scan_directory(&dir, &file_tree, DEFAULT_MAX_FILE_SIZE).unwrap();
+        let result = semantic_chunk_grep(
+            &dir,
+            &file_tree,
+            &Arc::new(SymbolTable::new()),
+            "notes.txt",
+            "(",
+            100,
+            3,
+            GrepScope::All,
+        );
+        assert!(result.unwrap_err().starts_with("Invalid regex"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn buffer_peek_reports_the_range_served() {
+        let repl = Arc::new(ReplState::default());
+        buffer_create(&repl, "b", "héllo".to_string(), "test");
+
+        let peek = buffer_peek(&repl, "b", 0, 3).unwrap();
+        assert_eq!((peek.content.as_str(), peek.clamped), ("hé", false));
+
+        // Past the end, and ending inside `é`: both pulled back.
+        let peek = buffer_peek(&repl, "b", 2, 500).unwrap();
+        assert_eq!((peek.resolved_start, peek.resolved_end, peek.clamped), (1, 6, true));
+        assert_eq!(peek.content, "éllo");
+        assert_eq!(peek.total_bytes, 6);
+
+        let peek = buffer_peek(&repl, "b", 4, 2).unwrap();
+        assert_eq!((peek.content.as_str(), peek.resolved_start, peek.resolved_end), ("", 4, 4));
+        assert!(peek.clamped);
+    }
+}
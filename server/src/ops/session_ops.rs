@@ -0,0 +1,398 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use crate::server::session::{
+    AnnotationAction, AnnotationChange, BufferSource, HistoryEntry, Session,
+};
+use crate::server::state::AppState;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionInfo {
+    pub session_id: String,
+    pub project: String,
+    pub created_at: String,
+    pub last_active: String,
+    pub history_count: usize,
+}
+
+/// List sessions, most recently active first. Each filter is optional:
+/// `project` matches the session's project root (canonicalized when the path
+/// exists), `active_since` keeps sessions active at or after that time, and
+/// `has_var` keeps sessions whose REPL state has that variable set.
+pub fn list_sessions_filtered(
+    state: &AppState,
+    project: Option<&Path>,
+    active_since: Option<DateTime<Utc>>,
+    has_var: Option<&str>,
+) -> Vec<SessionInfo> {
+    let project: Option<PathBuf> =
+        project.map(|p| p.canonicalize().unwrap_or_else(|_| p.to_path_buf()));
+
+    let mut matched: Vec<(DateTime<Utc>, SessionInfo)> = state
+        .inner
+        .sessions
+        .iter()
+        .filter(|entry| {
+            let session = entry.value();
+            project.as_ref().is_none_or(|p| session.project_path == *p)
+                && active_since.is_none_or(|t| session.last_active >= t)
+                && has_var.is_none_or(|name| session.repl_state.variables.contains_key(name))
+        })
+        .map(|entry| {
+            let session = entry.value();
+            (
+                session.last_active,
+                SessionInfo {
+                    session_id: session.id.clone(),
+                    project: session.project_path.display().to_string(),
+                    created_at: session.created_at.to_rfc3339(),
+                    last_active: session.last_active.to_rfc3339(),
+                    history_count: session.history.len(),
+                },
+            )
+        })
+        .collect();
+
+    matched.sort_by_key(|(last_active, _)| std::cmp::Reverse(*last_active));
+    matched.into_iter().map(|(_, info)| info).collect()
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkDeleteResult {
+    pub deleted_count: usize,
+    pub retained_count: usize,
+    /// IDs of the deleted sessions.
+    pub freed_symbols_sessions: Vec<String>,
+    /// Projects dropped because this call removed their last sessions.
+    pub evicted_projects: Vec<String>,
+}
+
+/// Delete every session matching both filters: idle since before
+/// `older_than` and attached to `project`. A project left without sessions
+/// by this call is evicted too, as `--max-projects` eviction would; projects
+/// that already had none are kept. Repeating the call deletes nothing more.
+pub fn bulk_delete_sessions(
+    state: &AppState,
+    older_than: Option<DateTime<Utc>>,
+    project: Option<&Path>,
+) -> BulkDeleteResult {
+    let project: Option<PathBuf> =
+        project.map(|p| p.canonicalize().unwrap_or_else(|_| p.to_path_buf()));
+
+    let mut deleted = Vec::new();
+    let mut touched: HashSet<PathBuf> = HashSet::new();
+    state.inner.sessions.retain(|id, session| {
+        let matches = older_than.is_none_or(|t| session.last_active < t)
+            && project.as_ref().is_none_or(|p| session.project_path == *p);
+        if matches {
+            deleted.push(id.clone());
+            touched.insert(session.project_path.clone());
+        }
+        !matches
+    });
+    for id in &deleted {
+        state.inner.rate_limiter.forget(id);
+    }
+
+    // Session creation takes the same lock to attach to a project, so no
+    // session can appear for a project between this snapshot and its
+    // eviction. Collected before removing anything so no session shard is
+    // locked while a project shard is.
+    let _attach = state.inner.session_attach.lock();
+    let still_used: HashSet<PathBuf> = state
+        .inner
+        .sessions
+        .iter()
+        .map(|entry| entry.value().project_path.clone())
+        .collect();
+    let mut evicted: Vec<String> = touched
+        .difference(&still_used)
+        .filter(|path| state.inner.projects.remove(*path).is_some())
+        .map(|path| {
+            tracing::info!("Evicting project with no sessions left: {}", path.display());
+            path.display().to_string()
+        })
+        .collect();
+    evicted.sort();
+    deleted.sort();
+
+    BulkDeleteResult {
+        deleted_count: deleted.len(),
+        retained_count: state.inner.sessions.len(),
+        freed_symbols_sessions: deleted,
+        evicted_projects: evicted,
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RecapBuffer {
+    pub name: String,
+    pub source: BufferSource,
+    pub size_bytes: usize,
+    pub line_count: usize,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RecapVariable {
+    pub name: String,
+    /// JSON type of the value: `null`, `bool`, `number`, `string`, `array`
+    /// or `object`.
+    pub value_type: &'static str,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RecapChunk {
+    pub chunk_id: String,
+    pub results: usize,
+    pub findings: usize,
+}
+
+/// What a session has accumulated, for handing it to another agent or
+/// picking it up after a break.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionRecap {
+    pub session_id: String,
+    pub project: String,
+    pub created_at: DateTime<Utc>,
+    pub last_active: DateTime<Utc>,
+    /// Sorted by creation time.
+    pub buffers: Vec<RecapBuffer>,
+    /// Sorted by name.
+    pub variables: Vec<RecapVariable>,
+    pub final_set: bool,
+    /// Definitions and marks written through this session, in the order
+    /// they were first made.
+    pub annotations: Vec<AnnotationChange>,
+    /// Chunks with stored subcall results, in the order first analyzed.
+    pub subcall_chunks: Vec<RecapChunk>,
+    pub bookmark_count: usize,
+    pub history_count: usize,
+    /// The last `history` entries, oldest first.
+    pub recent_history: Vec<HistoryEntry>,
+    /// The same recap as compact markdown.
+    pub markdown: String,
+}
+
+/// Assemble `session`'s recap, with its last `history_limit` history
+/// entries.
+pub fn session_recap(session: &Session, history_limit: usize) -> SessionRecap {
+    let repl = &session.repl_state;
+
+    let mut buffers: Vec<RecapBuffer> = repl
+        .buffers
+        .iter()
+        .map(|entry| {
+            let buf = entry.value();
+            RecapBuffer {
+                name: buf.name.clone(),
+                source: buf.source.clone(),
+                size_bytes: buf.content.len(),
+                line_count: buf.content.lines().count(),
+                created_at: buf.created_at,
+            }
+        })
+        .collect();
+    buffers.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.name.cmp(&b.name)));
+
+    let mut variables: Vec<RecapVariable> = repl
+        .variables
+        .iter()
+        .map(|entry| RecapVariable {
+            name: entry.key().clone(),
+            value_type: json_type(entry.value()),
+        })
+        .collect();
+    variables.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut subcall_chunks: Vec<RecapChunk> = Vec::new();
+    for result in repl.subcall_results.lock().iter() {
+        match subcall_chunks.iter_mut().find(|c| c.chunk_id == result.chunk_id) {
+            Some(chunk) => {
+                chunk.results += 1;
+                chunk.findings += result.findings.len();
+            }
+            None => subcall_chunks.push(RecapChunk {
+                chunk_id: result.chunk_id.clone(),
+                results: 1,
+                findings: result.findings.len(),
+            }),
+        }
+    }
+
+    let skip = session.history.len().saturating_sub(history_limit);
+    let mut recap = SessionRecap {
+        session_id: session.id.clone(),
+        project: session.project_path.display().to_string(),
+        created_at: session.created_at,
+        last_active: session.last_active,
+        buffers,
+        variables,
+        final_set: repl.variables.contains_key("Final"),
+        annotations: repl.annotations.lock().list(),
+        subcall_chunks,
+        bookmark_count: repl.bookmarks.lock().len(),
+        history_count: session.history.len(),
+        recent_history: session.history[skip..].to_vec(),
+        markdown: String::new(),
+    };
+    recap.markdown = render_recap(&recap);
+    recap
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// One line saying where a buffer's content came from.
+fn describe_source(source: &BufferSource) -> String {
+    match source {
+        BufferSource::File {
+            path,
+            start_line,
+            end_line,
+        } => format!("file {}:{}-{}", path, start_line, end_line),
+        BufferSource::Symbol { name, file } => format!("symbol {} in {}", name, file),
+        BufferSource::Directory { path, .. } => format!("directory {}", path),
+        BufferSource::Grep { pattern } => format!("grep `{}`", pattern),
+        BufferSource::SubLmResult { query } => format!("sub-LM result for \"{}\"", query),
+        BufferSource::Computed { description } if description.is_empty() => "computed".into(),
+        BufferSource::Computed { description } => format!("computed: {}", description),
+    }
+}
+
+/// Markdown recap; sections with nothing in them are left out.
+fn render_recap(recap: &SessionRecap) -> String {
+    let mut out = format!(
+        "# Session {}\n\nProject `{}`, created {}, last active {}.\n",
+        recap.session_id,
+        recap.project,
+        recap.created_at.format("%Y-%m-%d %H:%M:%S UTC"),
+        recap.last_active.format("%Y-%m-%d %H:%M:%S UTC"),
+    );
+    if recap.final_set {
+        out.push_str("`Final` is set.\n");
+    }
+    if !recap.buffers.is_empty() {
+        out.push_str(&format!("\n## Buffers ({})\n", recap.buffers.len()));
+        for b in &recap.buffers {
+            out.push_str(&format!(
+                "- `{}`: {} bytes, {} lines, from {}\n",
+                b.name,
+                b.size_bytes,
+                b.line_count,
+                describe_source(&b.source)
+            ));
+        }
+    }
+    if !recap.variables.is_empty() {
+        out.push_str(&format!("\n## Variables ({})\n", recap.variables.len()));
+        for v in &recap.variables {
+            out.push_str(&format!("- `{}`: {}\n", v.name, v.value_type));
+        }
+    }
+    if !recap.annotations.is_empty() {
+        out.push_str(&format!("\n## Annotations ({})\n", recap.annotations.len()));
+        for a in &recap.annotations {
+            let target = match &a.symbol {
+                Some(symbol) => format!("{}::{}", a.file, symbol),
+                None => a.file.clone(),
+            };
+            let action = match a.action {
+                AnnotationAction::Define => "define",
+                AnnotationAction::Redefine => "redefine",
+                AnnotationAction::Mark => "mark",
+            };
+            let times = if a.count > 1 { format!(" (x{})", a.count) } else { String::new() };
+            out.push_str(&format!("- {} `{}`{}\n", action, target, times));
+        }
+    }
+    if !recap.subcall_chunks.is_empty() {
+        out.push_str(&format!("\n## Subcall chunks ({})\n", recap.subcall_chunks.len()));
+        for c in &recap.subcall_chunks {
+            out.push_str(&format!(
+                "- `{}`: {} results, {} findings\n",
+                c.chunk_id, c.results, c.findings
+            ));
+        }
+    }
+    if !recap.recent_history.is_empty() {
+        out.push_str(&format!(
+            "\n## Recent history (last {} of {})\n",
+            recap.recent_history.len(),
+            recap.history_count
+        ));
+        for h in &recap.recent_history {
+            out.push_str(&format!(
+                "- {} {} {}: {}\n",
+                h.timestamp.format("%H:%M:%S"),
+                h.method,
+                h.path,
+                h.response_preview.replace('\n', " ")
+            ));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::session::Session;
+    use crate::server::state::temp_project;
+
+    #[tokio::test]
+    async fn session_for_an_evicted_project_is_refused() {
+        let state = AppState::for_tests();
+        let dir = temp_project(&[("lib.rs", "fn f() {}\n")]);
+        let project = state.get_or_create_project(&dir, None, false).unwrap();
+        state.inner.projects.remove(&project.root);
+
+        let session = Session::new("s".to_string(), project.root.clone());
+        assert!(!state.insert_session(session));
+        assert!(state.inner.sessions.is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn bulk_delete_never_strands_a_new_session() {
+        let state = AppState::for_tests();
+        let dir = temp_project(&[("lib.rs", "fn f() {}\n")]);
+        let root = dir.canonicalize().unwrap();
+        let runtime = tokio::runtime::Handle::current();
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                let _runtime = runtime.enter();
+                for i in 0..50 {
+                    loop {
+                        state.get_or_create_project(&dir, None, false).unwrap();
+                        if state.insert_session(Session::new(i.to_string(), root.clone())) {
+                            break;
+                        }
+                    }
+                }
+            });
+            scope.spawn(|| {
+                for _ in 0..50 {
+                    bulk_delete_sessions(&state, None, Some(&root));
+                }
+            });
+        });
+        for entry in state.inner.sessions.iter() {
+            assert!(state.inner.projects.contains_key(&entry.value().project_path));
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
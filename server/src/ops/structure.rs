@@ -1,26 +1,99 @@
-use crate::index::file_entry::FileMark;
-use crate::index::file_tree::FileTree;
+use crate::index::encoding::{self, SourceEncoding};
+use crate::index::file_entry::{FileEntry, FileMark, Language};
+use crate::index::file_tree::{self, FileTree};
+use crate::index::walker::IgnoredList;
+use crate::index::watcher;
+use crate::ops::annotations::AnnotationData;
+use crate::ops::symbol_ops;
+use crate::server::cancel;
+use crate::symbols::parser::{ExtractionError, ExtractionErrors};
+use crate::symbols::symbol::SymbolKind;
+use crate::symbols::SymbolTable;
 use serde::Serialize;
+use utoipa::ToSchema;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
 use std::sync::Arc;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct StructureResponse {
     pub tree: String,
     pub file_count: usize,
     pub language_breakdown: Vec<LanguageCount>,
+    /// Files shown in `tree`, with annotation indicators.
+    pub files: Vec<FileNode>,
+    /// Files whose symbol extraction last failed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extraction_errors: Vec<ExtractionError>,
+    /// Paths left out by the default or configured ignore rules, with
+    /// `include_ignored=true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ignored: Option<IgnoredList>,
 }
 
-#[derive(Debug, Serialize)]
+/// Whether a file has a saved definition or marks. Both are `null` until
+/// the project's annotations have been loaded.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FileNode {
+    pub path: String,
+    pub has_annotation: Option<bool>,
+    pub has_mark: Option<bool>,
+    /// How the file's text was normalized, if it had to be.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<SourceEncoding>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct LanguageCount {
     pub language: String,
     pub count: usize,
 }
 
-pub fn get_structure(file_tree: &Arc<FileTree>, depth: usize) -> StructureResponse {
-    let tree = file_tree.render_tree(depth);
-    let file_count = file_tree.len();
+/// Indexed files at or below `scope` (a directory or file path; empty for
+/// the whole project), sorted.
+pub fn paths_under(file_tree: &FileTree, scope: &str) -> Vec<String> {
+    let mut paths: Vec<String> = file_tree
+        .all_paths()
+        .into_iter()
+        .filter(|p| in_scope(p, scope))
+        .collect();
+    paths.sort();
+    paths
+}
+
+fn in_scope(path: &str, scope: &str) -> bool {
+    scope.is_empty()
+        || path == scope
+        || path.strip_prefix(scope).is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// `path` relative to `scope`; a file scope keeps its own name.
+fn relative<'a>(path: &'a str, scope: &str) -> &'a str {
+    match path.strip_prefix(scope).and_then(|rest| rest.strip_prefix('/')) {
+        Some(rest) if !scope.is_empty() => rest,
+        _ if path == scope => path.rsplit('/').next().unwrap_or(path),
+        _ => path,
+    }
+}
+
+/// The tree and file list for `paths` (from `paths_under(scope)`), with
+/// `depth` counted from `scope`.
+pub fn get_structure(
+    file_tree: &Arc<FileTree>,
+    errors: &ExtractionErrors,
+    annotations: Option<&AnnotationData>,
+    paths: Vec<String>,
+    scope: &str,
+    depth: usize,
+) -> StructureResponse {
+    let relative_paths: Vec<String> = paths
+        .iter()
+        .map(|p| relative(p, scope).to_string())
+        .collect();
+    let tree = file_tree::render_paths(&relative_paths, depth);
+    let file_count = paths.len();
     let breakdown = file_tree
-        .language_breakdown()
+        .language_breakdown(&paths)
         .into_iter()
         .map(|b| LanguageCount {
             language: format!("{:?}", b.language).to_lowercase(),
@@ -28,47 +101,301 @@ pub fn get_structure(file_tree: &Arc<FileTree>, depth: usize) -> StructureRespon
         })
         .collect();
 
+    let files = paths
+        .into_iter()
+        .filter(|p| depth == 0 || relative(p, scope).split('/').count() <= depth)
+        .map(|path| FileNode {
+            has_annotation: annotations.map(|a| a.file_definitions.contains_key(&path)),
+            has_mark: annotations.map(|a| a.file_marks.get(&path).is_some_and(|m| !m.is_empty())),
+            encoding: file_tree.get(&path).and_then(|entry| entry.encoding),
+            path,
+        })
+        .collect();
+
     StructureResponse {
         tree,
         file_count,
         language_breakdown: breakdown,
+        files,
+        extraction_errors: list_extraction_errors(errors),
+        ignored: None,
+    }
+}
+
+/// What `render_compact_tree` appends after each file name.
+#[derive(Debug, Clone, Copy)]
+pub struct TreeSuffixes {
+    /// File size, e.g. `4.2K`.
+    pub sizes: bool,
+    /// Extracted symbols, e.g. `12sym`.
+    pub symbol_counts: bool,
+    /// `*` for a file with a definition, then its mark glyphs in brackets.
+    pub annotations: bool,
+}
+
+enum CompactNode<'a> {
+    File(&'a str),
+    Dir(CompactDir<'a>),
+}
+
+#[derive(Default)]
+struct CompactDir<'a> {
+    children: BTreeMap<&'a str, CompactNode<'a>>,
+    /// Files anywhere below this directory.
+    files: usize,
+}
+
+/// A plain-text tree of `paths` (from `paths_under(scope)`) for reading
+/// rather than parsing: two spaces of indent per level, directories as
+/// `name/ (files below)`, and a `tree`-style total at the end. Directories
+/// at the `depth` limit are listed with their count but not expanded.
+pub fn render_compact_tree(
+    file_tree: &FileTree,
+    symbol_table: &SymbolTable,
+    paths: &[String],
+    scope: &str,
+    depth: usize,
+    suffixes: TreeSuffixes,
+) -> String {
+    let mut root = CompactDir::default();
+    for path in paths {
+        let mut dir = &mut root;
+        dir.files += 1;
+        let mut parts = relative(path, scope).split('/').peekable();
+        while let Some(part) = parts.next() {
+            if parts.peek().is_none() {
+                dir.children.insert(part, CompactNode::File(path));
+                break;
+            }
+            let node = dir
+                .children
+                .entry(part)
+                .or_insert_with(|| CompactNode::Dir(CompactDir::default()));
+            let CompactNode::Dir(sub) = node else {
+                break;
+            };
+            sub.files += 1;
+            dir = sub;
+        }
+    }
+
+    let mut out = String::new();
+    let mut dirs = 0;
+    let mut glyphs_used = false;
+    let mut render = CompactRender {
+        file_tree,
+        symbol_table,
+        suffixes,
+        depth,
+        out: &mut out,
+        dirs: &mut dirs,
+        glyphs_used: &mut glyphs_used,
+    };
+    render.dir(&root, 0);
+
+    let noun = |n: usize, one: &str, many: &str| format!("{} {}", n, if n == 1 { one } else { many });
+    out.push_str(&format!(
+        "\n{}, {}\n",
+        noun(dirs, "directory", "directories"),
+        noun(paths.len(), "file", "files")
+    ));
+    if glyphs_used {
+        out.push_str(
+            "* defined; marks: d=documentation i=ignore t=test c=config g=generated u=custom\n",
+        );
+    }
+    out
+}
+
+struct CompactRender<'a, 'o> {
+    file_tree: &'a FileTree,
+    symbol_table: &'a SymbolTable,
+    suffixes: TreeSuffixes,
+    depth: usize,
+    out: &'o mut String,
+    dirs: &'o mut usize,
+    glyphs_used: &'o mut bool,
+}
+
+impl CompactRender<'_, '_> {
+    fn dir(&mut self, dir: &CompactDir, level: usize) {
+        let indent = "  ".repeat(level);
+        for (name, node) in &dir.children {
+            match node {
+                CompactNode::Dir(sub) => {
+                    *self.dirs += 1;
+                    self.out
+                        .push_str(&format!("{}{}/ ({})\n", indent, name, sub.files));
+                    if self.depth == 0 || level + 1 < self.depth {
+                        self.dir(sub, level + 1);
+                    } else {
+                        *self.dirs += count_dirs(sub);
+                    }
+                }
+                CompactNode::File(path) => {
+                    self.out.push_str(&indent);
+                    self.out.push_str(name);
+                    self.file_suffix(path);
+                    self.out.push('\n');
+                }
+            }
+        }
+    }
+
+    fn file_suffix(&mut self, path: &str) {
+        let Some(entry) = self.file_tree.get(path) else {
+            return;
+        };
+        if self.suffixes.sizes {
+            self.out.push(' ');
+            self.out.push_str(&human_size(entry.size));
+        }
+        if self.suffixes.symbol_counts && entry.symbols_extracted {
+            let count = self.symbol_table.by_file.get(path).map_or(0, |keys| keys.len());
+            self.out.push_str(&format!(" {}sym", count));
+        }
+        if self.suffixes.annotations {
+            if entry.definition.is_some() {
+                self.out.push_str(" *");
+                *self.glyphs_used = true;
+            }
+            if !entry.marks.is_empty() {
+                let glyphs: String = entry.marks.iter().map(|m| mark_glyph(*m)).collect();
+                self.out.push_str(&format!(" [{}]", glyphs));
+                *self.glyphs_used = true;
+            }
+        }
+    }
+}
+
+fn count_dirs(dir: &CompactDir) -> usize {
+    dir.children
+        .values()
+        .map(|node| match node {
+            CompactNode::Dir(sub) => 1 + count_dirs(sub),
+            CompactNode::File(_) => 0,
+        })
+        .sum()
+}
+
+fn mark_glyph(mark: FileMark) -> char {
+    match mark {
+        FileMark::Documentation => 'd',
+        FileMark::Ignore => 'i',
+        FileMark::Test => 't',
+        FileMark::Config => 'c',
+        FileMark::Generated => 'g',
+        FileMark::Custom => 'u',
+    }
+}
+
+/// `812`, `4.2K`, `1.3M`: bytes in the short form `tree -h` uses.
+fn human_size(bytes: u64) -> String {
+    const K: f64 = 1024.0;
+    let b = bytes as f64;
+    if b < K {
+        bytes.to_string()
+    } else if b < K * K {
+        format!("{:.1}K", b / K)
+    } else if b < K * K * K {
+        format!("{:.1}M", b / (K * K))
+    } else {
+        format!("{:.1}G", b / (K * K * K))
     }
 }
 
+/// Current per-file extraction failures, sorted by path.
+pub fn list_extraction_errors(errors: &ExtractionErrors) -> Vec<ExtractionError> {
+    let mut list: Vec<ExtractionError> = errors.iter().map(|e| e.value().clone()).collect();
+    list.sort_by(|a, b| a.file.cmp(&b.file));
+    list
+}
+
+/// Set a file's definition. An agent's definition (`auto` false) may
+/// replace a generated one; a generated one may only replace another. With
+/// `upsert`, an existing agent definition is replaced too. Returns the
+/// definition that was replaced, if any.
 pub fn define_file(
     file_tree: &Arc<FileTree>,
     file: &str,
     definition: &str,
-) -> Result<(), String> {
+    auto: bool,
+    upsert: bool,
+) -> Result<Option<String>, String> {
     if let Some(mut entry) = file_tree.files.get_mut(file) {
-        if entry.definition.is_some() {
+        if entry.definition.is_some() && !entry.definition_auto && !upsert {
             return Err(format!(
-                "File '{}' already has a definition. Use redefine to update it.",
+                "File '{}' already has a definition. Use redefine to update it, \
+                 or pass upsert=true.",
                 file
             ));
         }
-        entry.definition = Some(definition.to_string());
-        Ok(())
+        entry.definition_auto = auto;
+        Ok(entry.definition.replace(definition.to_string()))
     } else {
-        Err(format!("File '{}' not found in index", file))
+        Err(not_indexed(file))
     }
 }
 
+/// Replace a file's definition, returning the previous one if any.
 pub fn redefine_file(
     file_tree: &Arc<FileTree>,
     file: &str,
     definition: &str,
-) -> Result<(), String> {
+) -> Result<Option<String>, String> {
     if let Some(mut entry) = file_tree.files.get_mut(file) {
-        entry.definition = Some(definition.to_string());
-        Ok(())
+        entry.definition_auto = false;
+        Ok(entry.definition.replace(definition.to_string()))
     } else {
-        Err(format!("File '{}' not found in index", file))
+        Err(not_indexed(file))
     }
 }
 
+fn not_indexed(file: &str) -> String {
+    format!(
+        "File '{}' not found in index. If it was just created, pass \
+         allow_unindexed=true to index it from disk first.",
+        file
+    )
+}
+
+/// Index `file` from disk if it isn't in the tree yet, for files created
+/// since the last scan that the watcher hasn't picked up. The file must exist
+/// under `root` and pass the usual ignore and size filters.
+pub fn index_if_missing(
+    root: &Path,
+    file_tree: &FileTree,
+    symbol_table: &SymbolTable,
+    errors: &ExtractionErrors,
+    max_file_size: u64,
+    file: &str,
+) -> Result<(), String> {
+    if file_tree.get(file).is_some() {
+        return Ok(());
+    }
+    let abs = root
+        .join(file)
+        .canonicalize()
+        .map_err(|_| format!("File '{}' does not exist on disk", file))?;
+    if !abs.starts_with(root) || !abs.is_file() {
+        return Err(format!("'{}' is not a file inside the project", file));
+    }
+
+    watcher::re_index_file(root, file_tree, symbol_table, errors, max_file_size, file);
+    if file_tree.get(file).is_none() {
+        return Err(format!(
+            "File '{}' is excluded from the index (ignored extension or over the size limit)",
+            file
+        ));
+    }
+    Ok(())
+}
+
+/// Add a mark to `file`. The `generated` mark also hides the file's symbols
+/// from listings.
 pub fn mark_file(
     file_tree: &Arc<FileTree>,
+    symbol_table: &SymbolTable,
     file: &str,
     mark_str: &str,
 ) -> Result<(), String> {
@@ -79,8 +406,232 @@ pub fn mark_file(
         if !entry.marks.contains(&mark) {
             entry.marks.push(mark);
         }
+        drop(entry);
+        if mark == FileMark::Generated {
+            symbol_table.set_generated(file, true);
+        }
         Ok(())
     } else {
         Err(format!("File '{}' not found in index", file))
     }
 }
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AutoDefinition {
+    pub file: String,
+    pub definition: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AutoDefineReport {
+    /// Files given a definition, including regenerated ones.
+    pub defined: Vec<AutoDefinition>,
+    /// How many of `defined` replaced an earlier generated definition.
+    pub regenerated: usize,
+    /// Files left alone because an agent defined them.
+    pub kept: usize,
+    /// Files that could not be read.
+    pub unreadable: Vec<String>,
+}
+
+/// Give every file under `path_prefix` without an agent-written definition
+/// a summary built from the index alone. Earlier generated summaries are
+/// rebuilt; agent definitions are never touched.
+pub fn auto_define(
+    root: &Path,
+    file_tree: &Arc<FileTree>,
+    symbol_table: &SymbolTable,
+    path_prefix: &str,
+) -> AutoDefineReport {
+    let mut report = AutoDefineReport {
+        defined: Vec::new(),
+        regenerated: 0,
+        kept: 0,
+        unreadable: Vec::new(),
+    };
+    let mut paths: Vec<String> = file_tree
+        .all_paths()
+        .into_iter()
+        .filter(|p| p.starts_with(path_prefix))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let Some(entry) = file_tree.get(&path) else {
+            continue;
+        };
+        if entry.definition.is_some() && !entry.definition_auto {
+            report.kept += 1;
+            continue;
+        }
+        let source = if entry.language == Language::Pdf {
+            crate::index::pdf::convert_pdf(root, &path, &cancel::current()).ok()
+        } else {
+            encoding::read_to_string(root.join(&path)).ok()
+        };
+        let Some(source) = source else {
+            report.unreadable.push(path);
+            continue;
+        };
+        let definition = summarize_file(&entry, &source, symbol_table);
+        if define_file(file_tree, &path, &definition, true, false).is_ok() {
+            if entry.definition.is_some() {
+                report.regenerated += 1;
+            }
+            report.defined.push(AutoDefinition {
+                file: path,
+                definition,
+            });
+        }
+    }
+    report
+}
+
+/// e.g. `rust, 412 lines: structs Session, ReplState; 9 fns incl. record,
+/// new; imports chrono, serde; has tests; called from 3 files`.
+fn summarize_file(entry: &FileEntry, source: &str, symbol_table: &SymbolTable) -> String {
+    let mut symbols = symbol_table.list_by_file(&entry.rel_path);
+    symbols.sort_by_key(|s| s.byte_range.0);
+
+    let mut parts = Vec::new();
+    // (kind, singular, plural)
+    const KINDS: &[(SymbolKind, &str, &str)] = &[
+        (SymbolKind::Struct, "struct", "structs"),
+        (SymbolKind::Class, "class", "classes"),
+        (SymbolKind::Enum, "enum", "enums"),
+        (SymbolKind::Trait, "trait", "traits"),
+        (SymbolKind::Interface, "interface", "interfaces"),
+        (SymbolKind::Type, "type", "types"),
+        (SymbolKind::Function, "fn", "fns"),
+        (SymbolKind::Method, "method", "methods"),
+        (SymbolKind::Constant, "const", "consts"),
+        (SymbolKind::Module, "module", "modules"),
+    ];
+    for &(kind, singular, plural) in KINDS {
+        let names: Vec<&str> = symbols
+            .iter()
+            .filter(|s| s.kind == kind && (kind == SymbolKind::Method || s.parent.is_none()))
+            .map(|s| s.name.as_str())
+            .collect();
+        match names.len() {
+            0 => {}
+            1 => parts.push(format!("{} {}", singular, names[0])),
+            2 | 3 => parts.push(format!("{} {}", plural, names.join(", "))),
+            n => parts.push(format!("{} {} incl. {}", n, plural, names[..2].join(", "))),
+        }
+    }
+
+    let imports = import_targets(source, entry.language);
+    if !imports.is_empty() {
+        let shown: Vec<&str> = imports.iter().take(3).map(String::as_str).collect();
+        let more = if imports.len() > 3 { ", ..." } else { "" };
+        parts.push(format!("imports {}{}", shown.join(", "), more));
+    }
+    if entry.marks.contains(&FileMark::Test) || symbols.iter().any(symbol_ops::is_test_symbol) {
+        parts.push("has tests".to_string());
+    }
+    let callers: HashSet<String> = symbols
+        .iter()
+        .filter(|s| matches!(s.kind, SymbolKind::Function | SymbolKind::Method))
+        .filter_map(|s| symbol_table.get_callers(&s.name))
+        .flatten()
+        .filter(|c| c.file != entry.rel_path)
+        .map(|c| c.file)
+        .collect();
+    if !callers.is_empty() {
+        let noun = if callers.len() == 1 { "file" } else { "files" };
+        parts.push(format!("called from {} {}", callers.len(), noun));
+    }
+
+    let head = format!(
+        "{}, {} lines",
+        format!("{:?}", entry.language).to_lowercase(),
+        source.lines().count()
+    );
+    if parts.is_empty() {
+        head
+    } else {
+        format!("{}: {}", head, parts.join("; "))
+    }
+}
+
+/// Modules or packages a file imports, in order of first appearance. Rust
+/// and Go standard-library imports are left out. A line-based scan, not a
+/// parse.
+fn import_targets(source: &str, language: Language) -> Vec<String> {
+    let mut targets: Vec<String> = Vec::new();
+    let mut push = |target: &str| {
+        let target = target.trim();
+        if !target.is_empty() && !targets.iter().any(|t| t == target) {
+            targets.push(target.to_string());
+        }
+    };
+    let mut in_go_block = false;
+    for line in source.lines() {
+        let line = line.trim();
+        match language {
+            Language::Rust => {
+                let Some(path) = line
+                    .strip_prefix("pub use ")
+                    .or_else(|| line.strip_prefix("use "))
+                else {
+                    continue;
+                };
+                let path = path.split(['{', ';', ' ']).next().unwrap_or("");
+                let mut segments = path.split("::").filter(|s| !s.is_empty());
+                match segments.next() {
+                    Some("std" | "core" | "alloc" | "self" | "super") | None => {}
+                    Some("crate") => {
+                        if let Some(module) = segments.next() {
+                            push(&format!("crate::{}", module));
+                        }
+                    }
+                    Some(krate) => push(krate),
+                }
+            }
+            Language::Python => {
+                let module = if let Some(rest) = line.strip_prefix("from ") {
+                    rest.split_whitespace().next()
+                } else if let Some(rest) = line.strip_prefix("import ") {
+                    rest.split([',', ' ']).next()
+                } else {
+                    None
+                };
+                if let Some(module) = module {
+                    push(module);
+                }
+            }
+            Language::TypeScript | Language::JavaScript => {
+                if !(line.starts_with("import ") || line.contains("require(")) {
+                    continue;
+                }
+                let quoted = line.rsplit(['\'', '"']).nth(1);
+                if let Some(module) = quoted {
+                    push(module);
+                }
+            }
+            Language::Go => {
+                if line.starts_with("import (") {
+                    in_go_block = true;
+                    continue;
+                }
+                if in_go_block && line.starts_with(')') {
+                    in_go_block = false;
+                    continue;
+                }
+                let spec = if in_go_block {
+                    Some(line)
+                } else {
+                    line.strip_prefix("import ")
+                };
+                if let Some(module) = spec.and_then(|s| s.split('"').nth(1))
+                    && module.contains('.')
+                {
+                    push(module);
+                }
+            }
+            _ => return targets,
+        }
+    }
+    targets
+}
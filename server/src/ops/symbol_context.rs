@@ -0,0 +1,337 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::config;
+use crate::index::file_entry::Language;
+use crate::ops::symbol_ops;
+use crate::server::budget;
+use crate::server::state::Project;
+use crate::symbols::symbol::{Symbol, SymbolKind};
+
+/// How much of one lower-priority section made it into the text.
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct ContextSection {
+    pub included: usize,
+    /// Symbols left out to fit the budget.
+    pub omitted_count: usize,
+    /// `file::name` keys of the first `SYMBOL_CONTEXT_OMITTED_LIMIT`
+    /// omitted symbols, in priority order.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub omitted: Vec<String>,
+}
+
+impl ContextSection {
+    fn omit(&mut self, sym: &Symbol) {
+        self.omitted_count += 1;
+        if self.omitted.len() < config::SYMBOL_CONTEXT_OMITTED_LIMIT {
+            self.omitted.push(format!("{}::{}", sym.file, sym.name));
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SymbolContext {
+    pub symbol: String,
+    pub file: String,
+    pub kind: SymbolKind,
+    /// 1-based, inclusive.
+    pub line_start: usize,
+    pub line_end: usize,
+    /// Ready-to-send text: header, doc comment and implementation, then
+    /// callee signatures, caller signatures and referenced types.
+    pub text: String,
+    /// Estimated tokens of `text`, at most the budget.
+    pub tokens: usize,
+    pub budget_tokens: usize,
+    /// The implementation alone overflowed the budget and was cut; `text`
+    /// says so too, and every other section is omitted.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub implementation_truncated: bool,
+    pub callees: ContextSection,
+    pub callers: ContextSection,
+    /// Types named in the implementation. Each is given in full when it
+    /// fits, else by signature.
+    pub types: ContextSection,
+}
+
+/// Assemble what it takes to understand `symbol_name` in `file` within
+/// `budget_tokens`, in priority order: its doc comment and implementation,
+/// the signatures of the functions it calls, the signatures of the symbols
+/// calling it, then the types its implementation names. Calls are resolved
+/// by name as in `/symbols/callers`, preferring a definition in the same
+/// file. Each later entry goes in only if it still fits; the ones that
+/// don't are counted per section. Fails when the budget can't hold the
+/// header.
+pub fn symbol_context(
+    project: &Project,
+    symbol_name: &str,
+    file: &str,
+    budget_tokens: usize,
+) -> Result<SymbolContext, String> {
+    let sym = project
+        .symbol_table
+        .get(file, symbol_name)
+        .ok_or_else(|| format!("Symbol '{}' not found in '{}'", symbol_name, file))?;
+    let (sym, body) = symbol_ops::symbol_text(project, sym)?;
+    let doc = if sym.language == Language::Pdf {
+        String::new()
+    } else {
+        symbol_ops::read_source(&project.root, &sym.file, sym.language)
+            .map(|source| leading_comment(&source, sym.line_range.0))
+            .unwrap_or_default()
+    };
+
+    let header = format!(
+        "Symbol: {} ({}) in {}, lines {}-{}\n",
+        sym.name,
+        format!("{:?}", sym.kind).to_lowercase(),
+        sym.file,
+        sym.line_range.0,
+        sym.line_range.1,
+    );
+    let implementation = format!("{}{}", doc, body);
+    let assemble = |implementation: &str, note: Option<&str>| {
+        let mut text = format!("{}\n--- implementation ---\n{}", header, implementation);
+        if !implementation.is_empty() && !implementation.ends_with('\n') {
+            text.push('\n');
+        }
+        if let Some(note) = note {
+            text.push_str(note);
+            text.push('\n');
+        }
+        text.push_str("--- end implementation ---\n");
+        text
+    };
+    let truncation_note = |kept: usize| {
+        format!(
+            "[truncated: {} of {} bytes shown to fit {} tokens]",
+            kept,
+            implementation.len(),
+            budget_tokens
+        )
+    };
+
+    let full = assemble(&implementation, None);
+    let fits = |text: &str, extra: usize| {
+        budget::estimate_tokens(text.len() + extra) <= budget_tokens
+    };
+    let (mut text, implementation_truncated) = if fits(&full, 0) {
+        (full, false)
+    } else {
+        // Reserve room for the longest note, then keep whole lines.
+        let overhead = assemble("", Some(&truncation_note(implementation.len()))).len();
+        let room = (budget_tokens * 4).checked_sub(overhead + 1).ok_or_else(|| {
+            format!(
+                "budget_tokens {} can't hold the symbol header ({} tokens)",
+                budget_tokens,
+                budget::estimate_tokens(overhead)
+            )
+        })?;
+        let cut = implementation.floor_char_boundary(room);
+        let cut = implementation[..cut].rfind('\n').map_or(cut, |i| i + 1);
+        let kept = &implementation[..cut];
+        (assemble(kept, Some(&truncation_note(kept.len()))), true)
+    };
+
+    let mut context = SymbolContext {
+        symbol: sym.name.clone(),
+        file: sym.file.clone(),
+        kind: sym.kind,
+        line_start: sym.line_range.0,
+        line_end: sym.line_range.1,
+        text: String::new(),
+        tokens: 0,
+        budget_tokens,
+        implementation_truncated,
+        callees: ContextSection::default(),
+        callers: ContextSection::default(),
+        types: ContextSection::default(),
+    };
+
+    // Entries that don't fit are skipped, so a shorter one further down
+    // may still go in.
+    let add_signatures = |text: &mut String,
+                          title: &str,
+                          symbols: Vec<Symbol>,
+                          section: &mut ContextSection| {
+        let heading = format!("\n{}:\n", title);
+        for s in symbols {
+            let line = signature_line(&s);
+            let extra = line.len() + if section.included == 0 { heading.len() } else { 0 };
+            if implementation_truncated || !fits(text, extra) {
+                section.omit(&s);
+                continue;
+            }
+            if section.included == 0 {
+                text.push_str(&heading);
+            }
+            text.push_str(&line);
+            section.included += 1;
+        }
+    };
+    add_signatures(&mut text, "Calls", callees(project, &sym), &mut context.callees);
+    add_signatures(&mut text, "Called by", callers(project, &sym), &mut context.callers);
+
+    let heading = "\nTypes:\n";
+    for ty in referenced_types(project, &sym, &body) {
+        let first = if context.types.included == 0 { heading.len() } else { 0 };
+        let definition = symbol_ops::symbol_text(project, ty.clone())
+            .ok()
+            .map(|(_, definition)| {
+                let mut entry = format!("--- {}:{} ---\n{}", ty.file, ty.line_range.0, definition);
+                if !entry.ends_with('\n') {
+                    entry.push('\n');
+                }
+                entry
+            })
+            .filter(|entry| fits(&text, first + entry.len()));
+        let entry = definition.unwrap_or_else(|| signature_line(&ty));
+        if implementation_truncated || !fits(&text, first + entry.len()) {
+            context.types.omit(&ty);
+            continue;
+        }
+        if context.types.included == 0 {
+            text.push_str(heading);
+        }
+        text.push_str(&entry);
+        context.types.included += 1;
+    }
+
+    context.tokens = budget::estimate_tokens(text.len());
+    context.text = text;
+    Ok(context)
+}
+
+fn signature_line(sym: &Symbol) -> String {
+    let signature = sym.signature.lines().next().unwrap_or_default();
+    let signature = signature.trim_end().trim_end_matches('{').trim_end();
+    format!("  {}:{}: {}\n", sym.file, sym.line_range.0, signature)
+}
+
+/// The comment, attribute and decorator lines directly above `line`
+/// (1-based), up to the first blank or code line.
+fn leading_comment(source: &str, line: usize) -> String {
+    let lines: Vec<&str> = source.lines().take(line.saturating_sub(1)).collect();
+    let start = lines
+        .iter()
+        .rposition(|l| {
+            let l = l.trim_start();
+            !config::SYMBOL_CONTEXT_COMMENT_PREFIXES.iter().any(|p| l.starts_with(p))
+        })
+        .map_or(0, |i| i + 1);
+    let mut doc = String::new();
+    for l in &lines[start..] {
+        doc.push_str(l);
+        doc.push('\n');
+    }
+    doc
+}
+
+/// The symbols `name` may refer to when used in `file`: the definition in
+/// `file` when there is one, else every definition of one of `kinds`.
+fn resolve(project: &Project, name: &str, file: &str, kinds: &[SymbolKind]) -> Vec<Symbol> {
+    let mut candidates: Vec<Symbol> = project
+        .symbol_table
+        .list_by_name(name)
+        .into_iter()
+        .filter(|s| kinds.contains(&s.kind))
+        .collect();
+    if candidates.iter().any(|s| s.file == file) {
+        candidates.retain(|s| s.file == file);
+    }
+    candidates.sort_by(|a, b| a.file.cmp(&b.file).then(a.line_range.0.cmp(&b.line_range.0)));
+    candidates
+}
+
+/// Functions called inside `sym`, by first call.
+fn callees(project: &Project, sym: &Symbol) -> Vec<Symbol> {
+    let table = &project.symbol_table;
+    let names: Vec<String> = table
+        .callees_by_file
+        .get(&sym.file)
+        .map(|names| names.iter().cloned().collect())
+        .unwrap_or_default();
+    let mut first_calls: Vec<(usize, String)> = names
+        .into_iter()
+        .filter_map(|name| {
+            let sites = table.reverse_call_graph.get(&name)?;
+            let first = sites
+                .iter()
+                .filter(|c| c.file == sym.file)
+                .map(|c| c.line)
+                .filter(|line| sym.line_range.0 <= *line && *line <= sym.line_range.1)
+                .min()?;
+            Some((first, name))
+        })
+        .collect();
+    first_calls.sort();
+
+    let kinds = [
+        SymbolKind::Function,
+        SymbolKind::Method,
+        SymbolKind::Class,
+        SymbolKind::Struct,
+    ];
+    let mut seen = HashSet::new();
+    first_calls
+        .into_iter()
+        .flat_map(|(_, name)| resolve(project, &name, &sym.file, &kinds))
+        .filter(|s| !(s.file == sym.file && s.name == sym.name))
+        .filter(|s| seen.insert((s.file.clone(), s.name.clone())))
+        .collect()
+}
+
+/// Definitions enclosing a call to `sym`'s name, those in its own file
+/// first.
+fn callers(project: &Project, sym: &Symbol) -> Vec<Symbol> {
+    let table = &project.symbol_table;
+    let sites = table.get_callers(&sym.name).unwrap_or_default();
+    let mut file_symbols: HashMap<String, Vec<Symbol>> = HashMap::new();
+    let mut seen = HashSet::new();
+    let mut callers = Vec::new();
+    for site in sites {
+        let symbols = file_symbols
+            .entry(site.file.clone())
+            .or_insert_with(|| table.list_by_file(&site.file));
+        let Some(caller) = symbol_ops::enclosing_symbol(symbols, site.line) else {
+            continue;
+        };
+        if caller.file == sym.file && caller.name == sym.name {
+            continue;
+        }
+        if seen.insert((caller.file.clone(), caller.name.clone())) {
+            callers.push(caller.clone());
+        }
+    }
+    callers.sort_by_key(|c| (c.file != sym.file, c.file.clone(), c.line_range.0));
+    callers
+}
+
+/// Type definitions named in `body`, in order of first mention; the type
+/// `sym` belongs to comes first.
+fn referenced_types(project: &Project, sym: &Symbol, body: &str) -> Vec<Symbol> {
+    let kinds = [
+        SymbolKind::Struct,
+        SymbolKind::Class,
+        SymbolKind::Enum,
+        SymbolKind::Trait,
+        SymbolKind::Interface,
+        SymbolKind::Type,
+    ];
+    let mut names: Vec<&str> = sym.parent.as_deref().into_iter().collect();
+    let mut seen_names: HashSet<&str> = names.iter().copied().collect();
+    for word in body.split(|c: char| !c.is_alphanumeric() && c != '_') {
+        if !word.is_empty() && word != sym.name && seen_names.insert(word) {
+            names.push(word);
+        }
+    }
+    let mut seen = HashSet::new();
+    names
+        .into_iter()
+        .flat_map(|name| resolve(project, name, &sym.file, &kinds))
+        .filter(|s| !(s.file == sym.file && s.name == sym.name))
+        .filter(|s| seen.insert((s.file.clone(), s.name.clone())))
+        .collect()
+}
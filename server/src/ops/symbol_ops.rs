@@ -1,106 +1,1011 @@
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 use std::sync::Arc;
 
 use tree_sitter::StreamingIterator;
+use utoipa::ToSchema;
 
+use crate::config::{SUGGESTION_LIMIT, SUGGESTION_SCAN_LIMIT, SYMBOL_COMPLETE_SCAN_FACTOR};
+use crate::index::encoding;
 use crate::index::file_entry::Language;
-use crate::index::file_tree::FileTree;
+use crate::index::file_tree::{FileTree, MarkFilter};
+use crate::index::watcher;
+use crate::ops::content::{self, GrepResponse, GrepScope};
+use crate::server::cancel;
+use crate::server::session::ReplState;
+use crate::server::state::{AppState, Project};
+use crate::symbols::parser::{self, ExtractionErrors};
 use crate::symbols::queries;
-use crate::symbols::symbol::{Symbol, SymbolKind};
+use crate::symbols::symbol::{source_hash, Symbol, SymbolKind};
 use crate::symbols::SymbolTable;
 
 /// Read file source, converting PDFs to markdown on-demand.
-fn read_source(root: &Path, rel_path: &str, language: Language) -> Result<String, String> {
+pub fn read_source(root: &Path, rel_path: &str, language: Language) -> Result<String, String> {
     if language == Language::Pdf {
-        crate::index::pdf::convert_pdf(root, rel_path)
+        crate::index::pdf::convert_pdf(root, rel_path, &cancel::current())
             .map_err(|e| format!("PDF conversion failed for '{}': {}", rel_path, e))
     } else {
         let abs_path = root.join(rel_path);
-        std::fs::read_to_string(&abs_path)
+        encoding::read_to_string(&abs_path)
             .map_err(|e| format!("Failed to read '{}': {}", rel_path, e))
     }
 }
 
+/// One page of `list_symbols`, ordered by file, then position.
+#[derive(Debug)]
+pub struct SymbolPage {
+    pub symbols: Vec<Symbol>,
+    /// Matches before `offset` and `limit` were applied.
+    pub total_count: usize,
+}
+
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub struct KindCount {
+    pub kind: SymbolKind,
+    pub count: usize,
+}
+
+/// Which symbols `list_symbols` and `count_symbol_kinds` match: those in
+/// `file`, in the files in `files` (from a mark filter), of any of `kinds`
+/// and whose parent is `parent`, when set. Symbols from generated code are
+/// left out unless `include_generated`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ListFilter<'a> {
+    pub kinds: Option<&'a HashSet<SymbolKind>>,
+    pub file: Option<&'a str>,
+    pub files: Option<&'a HashSet<String>>,
+    pub parent: Option<&'a str>,
+    pub include_generated: bool,
+}
+
+/// List the symbols matching `filter`. Matches are sorted by key first and
+/// only the page is cloned.
 pub fn list_symbols(
     symbol_table: &Arc<SymbolTable>,
-    kind_filter: Option<SymbolKind>,
-    file_filter: Option<&str>,
+    filter: ListFilter,
+    offset: usize,
     limit: usize,
-) -> Vec<Symbol> {
-    let mut results: Vec<Symbol> = if let Some(file) = file_filter {
-        symbol_table.list_by_file(file)
+) -> SymbolPage {
+    // (file, start line, start byte, key): a total order, so pages never
+    // overlap or skip even when symbols share a line.
+    let mut matches: Vec<(String, usize, usize, String)> = Vec::new();
+    for_each_listed(symbol_table, filter, |key, sym| {
+        matches.push((sym.file.clone(), sym.line_range.0, sym.byte_range.0, key.to_string()));
+    });
+    matches.sort_unstable();
+    let total_count = matches.len();
+    let symbols = matches
+        .into_iter()
+        .skip(offset)
+        .take(limit)
+        .filter_map(|(.., key)| symbol_table.symbols.get(&key).map(|r| r.value().clone()))
+        .collect();
+    SymbolPage {
+        symbols,
+        total_count,
+    }
+}
+
+/// How many symbols of each kind `list_symbols` would match, most common
+/// first, without cloning any of them.
+pub fn count_symbol_kinds(symbol_table: &Arc<SymbolTable>, filter: ListFilter) -> Vec<KindCount> {
+    let mut counts: HashMap<SymbolKind, usize> = HashMap::new();
+    for_each_listed(symbol_table, filter, |_, sym| {
+        *counts.entry(sym.kind).or_insert(0) += 1;
+    });
+    let mut counts: Vec<KindCount> = counts
+        .into_iter()
+        .map(|(kind, count)| KindCount { kind, count })
+        .collect();
+    counts.sort_by_key(|k| (std::cmp::Reverse(k.count), k.kind as u8));
+    counts
+}
+
+/// What the index knows of a name used as a `parent` filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ParentStatus {
+    /// Neither a symbol of that name nor any symbol with it as parent.
+    Unknown,
+    /// A symbol of that name is indexed, but nothing has it as parent.
+    NoMembers,
+    /// Some symbols have it as parent (the filters may still drop them all).
+    HasMembers,
+}
+
+pub fn parent_status(symbol_table: &SymbolTable, parent: &str) -> ParentStatus {
+    if symbol_table.by_parent.contains_key(parent) {
+        ParentStatus::HasMembers
+    } else if symbol_table.by_name.contains_key(parent) {
+        ParentStatus::NoMembers
     } else {
-        symbol_table.all_symbols()
+        ParentStatus::Unknown
+    }
+}
+
+/// Call `f` with the key and symbol of every match for `filter`. A file or
+/// parent filter only visits that file's or parent's symbols.
+fn for_each_listed(symbol_table: &SymbolTable, filter: ListFilter, mut f: impl FnMut(&str, &Symbol)) {
+    let keep = |sym: &Symbol| {
+        filter.kinds.is_none_or(|kinds| kinds.contains(&sym.kind))
+            && filter.file.is_none_or(|file| sym.file == file)
+            && filter.files.is_none_or(|files| files.contains(&sym.file))
+            && filter.parent.is_none_or(|parent| sym.parent.as_deref() == Some(parent))
+            && (filter.include_generated || !sym.generated)
+    };
+    let index = match (filter.parent, filter.file) {
+        (Some(parent), _) => Some(symbol_table.by_parent.get(parent)),
+        (None, Some(file)) => Some(symbol_table.by_file.get(file)),
+        (None, None) => None,
     };
+    if let Some(keys) = index {
+        let Some(keys) = keys else {
+            return;
+        };
+        for key in keys.iter() {
+            if let Some(sym) = symbol_table.symbols.get(key)
+                && keep(&sym)
+            {
+                f(key, &sym);
+            }
+        }
+    } else {
+        for entry in symbol_table.symbols.iter() {
+            if keep(entry.value()) {
+                f(entry.key(), entry.value());
+            }
+        }
+    }
+}
+
+pub fn search_symbols(
+    symbol_table: &Arc<SymbolTable>,
+    query: &str,
+    kind_filter: Option<&HashSet<SymbolKind>>,
+    files: Option<&HashSet<String>>,
+    parent: Option<&str>,
+    include_generated: bool,
+    limit: usize,
+) -> Vec<Symbol> {
+    symbol_table.search(query, kind_filter, files, parent, limit, include_generated)
+}
 
-    if let Some(kind) = kind_filter {
-        results.retain(|s| s.kind == kind);
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub struct Completion {
+    pub name: String,
+    /// Kind and file of the best-ranked definition when several share the
+    /// name.
+    pub kind: SymbolKind,
+    pub file: String,
+    /// Definitions with this name across the project.
+    pub definitions: usize,
+    /// Call sites of the name in the reverse call graph.
+    pub references: usize,
+}
+
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub struct Completions {
+    pub prefix: String,
+    pub completions: Vec<Completion>,
+    /// Names matching the prefix, before `limit`. Counts at most the names
+    /// scanned; see `truncated`.
+    pub total_matches: usize,
+    /// More names match than were scanned, so a better-referenced name may
+    /// be missing. A longer prefix narrows it down.
+    pub truncated: bool,
+}
+
+/// Where a kind ranks in completions: types first, locals and imports last.
+fn completion_kind_rank(kind: SymbolKind) -> u8 {
+    match kind {
+        SymbolKind::Class
+        | SymbolKind::Struct
+        | SymbolKind::Enum
+        | SymbolKind::Trait
+        | SymbolKind::Interface
+        | SymbolKind::Type => 0,
+        SymbolKind::Function | SymbolKind::Method => 1,
+        SymbolKind::Module | SymbolKind::Constant | SymbolKind::Other => 2,
+        SymbolKind::Variable | SymbolKind::Import => 3,
+    }
+}
+
+/// Indexed names starting with `prefix` (ignoring case), most referenced
+/// first, then types before functions before locals, then shorter names.
+/// Reads the sorted name index and each name's keys, cloning no symbols.
+/// Only the first `limit * SYMBOL_COMPLETE_SCAN_FACTOR` names in
+/// alphabetical order are ranked, so a one-letter prefix on a huge project
+/// costs the same as a specific one.
+pub fn complete_symbols(symbol_table: &SymbolTable, prefix: &str, limit: usize) -> Completions {
+    let scan = limit.saturating_mul(SYMBOL_COMPLETE_SCAN_FACTOR);
+    let mut names = symbol_table.names_with_prefix(prefix, scan.saturating_add(1));
+    let truncated = names.len() > scan;
+    names.truncate(scan);
+    let total_matches = names.len();
+    let mut completions: Vec<(u8, Completion)> = names
+        .into_iter()
+        .filter_map(|name| {
+            let keys = symbol_table.by_name.get(&name)?;
+            let (rank, kind, file) = keys
+                .iter()
+                .filter_map(|key| {
+                    let sym = symbol_table.symbols.get(key)?;
+                    Some((completion_kind_rank(sym.kind), sym.kind, sym.file.clone()))
+                })
+                .min_by(|a, b| a.0.cmp(&b.0).then_with(|| a.2.cmp(&b.2)))?;
+            let definitions = keys.len();
+            drop(keys);
+            let references = symbol_table
+                .reverse_call_graph
+                .get(&name)
+                .map_or(0, |callers| callers.len());
+            Some((
+                rank,
+                Completion {
+                    name,
+                    kind,
+                    file,
+                    definitions,
+                    references,
+                },
+            ))
+        })
+        .collect();
+    completions.sort_by(|(rank_a, a), (rank_b, b)| {
+        b.references
+            .cmp(&a.references)
+            .then(rank_a.cmp(rank_b))
+            .then(a.name.len().cmp(&b.name.len()))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    completions.truncate(limit);
+    Completions {
+        prefix: prefix.to_string(),
+        completions: completions.into_iter().map(|(_, c)| c).collect(),
+        total_matches,
+        truncated,
+    }
+}
+
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub struct GlobalMatch {
+    pub project: String,
+    /// 1.0 exact name, 0.9 exact ignoring case, 0.7 prefix, 0.5 substring.
+    pub score: f64,
+    #[serde(flatten)]
+    pub symbol: Symbol,
+}
+
+/// How well `name` matches `query`; `None` when it doesn't contain it.
+fn match_score(name: &str, query: &str, query_lower: &str) -> Option<f64> {
+    let name_lower = name.to_lowercase();
+    if name == query {
+        Some(1.0)
+    } else if name_lower == query_lower {
+        Some(0.9)
+    } else if name_lower.starts_with(query_lower) {
+        Some(0.7)
+    } else if name_lower.contains(query_lower) {
+        Some(0.5)
+    } else {
+        None
+    }
+}
+
+/// What to match in every indexed project; shared by `/search/global` and
+/// session-less `/symbols/search`.
+pub struct ProjectSearch<'a> {
+    pub query: &'a str,
+    pub kind_filter: Option<&'a HashSet<SymbolKind>>,
+    pub parent: Option<&'a str>,
+    pub marks: &'a MarkFilter,
+    pub include_generated: bool,
+}
+
+/// Run `search` on every indexed project, in root order. Each project's
+/// matches are ranked by `match_score`, then file, line and name, and cut to
+/// its best `per_project`, so the result doesn't depend on index iteration
+/// order. Also returns whether any project had more.
+fn ranked_matches_per_project(
+    state: &AppState,
+    search: &ProjectSearch,
+    per_project: usize,
+) -> (Vec<Vec<GlobalMatch>>, bool) {
+    let mut projects: Vec<Arc<Project>> = state
+        .inner
+        .projects
+        .iter()
+        .map(|entry| entry.value().clone())
+        .collect();
+    projects.sort_by(|a, b| a.root.cmp(&b.root));
+
+    let query_lower = search.query.to_lowercase();
+    let mut truncated = false;
+    let ranked = projects
+        .into_iter()
+        .map(|project| {
+            let root = project.root.display().to_string();
+            let files = project.file_tree.paths_matching(search.marks);
+            let mut matches: Vec<GlobalMatch> = project
+                .symbol_table
+                .search(
+                    search.query,
+                    search.kind_filter,
+                    files.as_ref(),
+                    search.parent,
+                    usize::MAX,
+                    search.include_generated,
+                )
+                .into_iter()
+                .filter_map(|symbol| {
+                    let score = match_score(&symbol.name, search.query, &query_lower)?;
+                    Some(GlobalMatch {
+                        project: root.clone(),
+                        score,
+                        symbol,
+                    })
+                })
+                .collect();
+            matches.sort_by(|a, b| {
+                b.score
+                    .total_cmp(&a.score)
+                    .then_with(|| a.symbol.file.cmp(&b.symbol.file))
+                    .then_with(|| a.symbol.line_range.0.cmp(&b.symbol.line_range.0))
+                    .then_with(|| a.symbol.name.cmp(&b.symbol.name))
+            });
+            if matches.len() > per_project {
+                matches.truncate(per_project);
+                truncated = true;
+            }
+            matches
+        })
+        .collect();
+    (ranked, truncated)
+}
+
+/// Search every indexed project for `/search/global`. Each project gives at
+/// most `per_project` matches, best first; results are ordered by project
+/// root, then score, then file and line, and cut at `limit`. Generated code
+/// is skipped. Returns the matches and whether any were cut.
+pub fn search_global(
+    state: &AppState,
+    query: &str,
+    kind_filter: Option<&HashSet<SymbolKind>>,
+    per_project: usize,
+    limit: usize,
+) -> (Vec<GlobalMatch>, bool) {
+    let search = ProjectSearch {
+        query,
+        kind_filter,
+        parent: None,
+        marks: &MarkFilter::default(),
+        include_generated: false,
+    };
+    let (ranked, mut truncated) = ranked_matches_per_project(state, &search, per_project);
+    let mut results: Vec<GlobalMatch> = ranked.into_iter().flatten().collect();
+    if results.len() > limit {
+        results.truncate(limit);
+        truncated = true;
     }
+    (results, truncated)
+}
 
-    results.sort_by(|a, b| a.file.cmp(&b.file).then(a.line_range.0.cmp(&b.line_range.0)));
-    results.truncate(limit);
+/// Search every indexed project, for `/symbols/search` without a session.
+/// Projects take turns contributing their next best match, so one large
+/// project can't crowd out the rest of the `limit`.
+pub fn search_all_projects(
+    state: &AppState,
+    search: &ProjectSearch,
+    limit: usize,
+) -> Vec<GlobalMatch> {
+    let (ranked, _) = ranked_matches_per_project(state, search, limit);
+    let mut per_project: Vec<_> = ranked.into_iter().map(Vec::into_iter).collect();
+    let mut results = Vec::new();
+    while results.len() < limit {
+        let before = results.len();
+        for matches in per_project.iter_mut() {
+            if results.len() >= limit {
+                break;
+            }
+            if let Some(found) = matches.next() {
+                results.push(found);
+            }
+        }
+        if results.len() == before {
+            break;
+        }
+    }
     results
 }
 
-pub fn search_symbols(symbol_table: &Arc<SymbolTable>, query: &str, limit: usize) -> Vec<Symbol> {
-    symbol_table.search(query, limit)
+/// What a segment of a qualified name matched in `resolve_qualified_name`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SegmentSource {
+    /// The symbol's own name.
+    Name,
+    /// The symbol's recorded parent (enclosing type or class).
+    Parent,
+    /// A directory or the file stem of the defining file.
+    Path,
+}
+
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub struct SegmentMatch {
+    pub segment: String,
+    pub matched_by: SegmentSource,
+}
+
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub struct ResolvedCandidate {
+    /// Fraction of the name's segments accounted for, from 0 to 1.
+    pub score: f64,
+    /// The trailing part of the name that equals the symbol's name.
+    pub matched_name: String,
+    /// Segments that matched, and what they matched.
+    pub matched: Vec<SegmentMatch>,
+    /// Qualifier segments found in neither the parent nor the path.
+    pub unmatched: Vec<String>,
+    #[serde(flatten)]
+    pub symbol: Symbol,
+}
+
+/// Qualifier segments that say nothing about where a symbol lives.
+const RELATIVE_QUALIFIERS: &[&str] = &["crate", "self", "super", "Self"];
+
+/// Split a qualified name into segments: Rust and C++ `::` paths, dotted
+/// Python/JS/Go names, and Ruby-style `Class#method`. Call parentheses and
+/// generic arguments are dropped, as are `crate`, `self` and `super`.
+pub fn split_qualified_name(name: &str) -> Vec<String> {
+    let name = name.trim();
+    let name = name.split('(').next().unwrap_or(name);
+    let mut plain = String::with_capacity(name.len());
+    let mut depth = 0usize;
+    for c in name.chars() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth = depth.saturating_sub(1),
+            _ if depth == 0 => plain.push(c),
+            _ => {}
+        }
+    }
+    plain
+        .replace("::", ".")
+        .replace('#', ".")
+        .split('.')
+        .map(str::trim)
+        .filter(|seg| !seg.is_empty() && !RELATIVE_QUALIFIERS.contains(seg))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Symbols a qualified name like `crate::ops::repl::buffer_create`,
+/// `foo.bar.Baz` or `ns::Widget::draw` may refer to. Each trailing run of
+/// segments, longest first, is looked up as a bare name (joined with `.` or
+/// `::`, for languages that index qualified names); the segments before it
+/// are qualifiers, matched against the symbol's parent and the directories
+/// and stem of its file. A candidate's score is the fraction of all segments
+/// it accounts for. Imports are skipped. Best first, then by file and line.
+pub fn resolve_qualified_name(
+    symbol_table: &SymbolTable,
+    name: &str,
+    limit: usize,
+) -> Vec<ResolvedCandidate> {
+    let segments = split_qualified_name(name);
+    let mut seen = HashSet::new();
+    let mut candidates = Vec::new();
+    for split in 0..segments.len() {
+        let (qualifiers, tail) = segments.split_at(split);
+        let mut names = vec![tail.join(".")];
+        if tail.len() > 1 {
+            names.push(tail.join("::"));
+        }
+        for matched_name in names {
+            for sym in symbol_table.list_by_name(&matched_name) {
+                if sym.kind == SymbolKind::Import
+                    || !seen.insert(SymbolTable::make_key(&sym.file, &sym.name))
+                {
+                    continue;
+                }
+                candidates.push(score_candidate(sym, &matched_name, tail.len(), qualifiers));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.symbol.file.cmp(&b.symbol.file))
+            .then_with(|| a.symbol.line_range.0.cmp(&b.symbol.line_range.0))
+    });
+    candidates.truncate(limit);
+    candidates
+}
+
+fn score_candidate(
+    symbol: Symbol,
+    matched_name: &str,
+    name_segments: usize,
+    qualifiers: &[String],
+) -> ResolvedCandidate {
+    // Directories plus the file name up to its first dot.
+    let path_parts: Vec<String> = symbol
+        .file
+        .split('/')
+        .map(|part| part.split('.').next().unwrap_or(part).to_lowercase())
+        .collect();
+    let mut matched = vec![SegmentMatch {
+        segment: matched_name.to_string(),
+        matched_by: SegmentSource::Name,
+    }];
+    let mut unmatched = Vec::new();
+    for qualifier in qualifiers {
+        let matched_by = if symbol.parent.as_deref() == Some(qualifier.as_str()) {
+            SegmentSource::Parent
+        } else if path_parts.contains(&qualifier.to_lowercase()) {
+            SegmentSource::Path
+        } else {
+            unmatched.push(qualifier.clone());
+            continue;
+        };
+        matched.push(SegmentMatch {
+            segment: qualifier.clone(),
+            matched_by,
+        });
+    }
+    let total = name_segments + qualifiers.len();
+    let accounted = name_segments + qualifiers.len() - unmatched.len();
+    ResolvedCandidate {
+        score: accounted as f64 / total as f64,
+        matched_name: matched_name.to_string(),
+        matched,
+        unmatched,
+        symbol,
+    }
+}
+
+/// Note that the session read `symbol` in `file`, for `/symbols/recently_used`.
+pub fn record_access(repl: &ReplState, file: &str, symbol: &str) {
+    repl.recently_used.lock().touch(file, symbol);
 }
 
 pub fn get_implementation(
+    project: &Project,
+    symbol_name: &str,
+    file: &str,
+) -> Result<String, String> {
+    let sym = project
+        .symbol_table
+        .get(file, symbol_name)
+        .ok_or_else(|| format!("Symbol '{}' not found in '{}'", symbol_name, file))?;
+    Ok(symbol_text(project, sym)?.1)
+}
+
+/// The text `sym` spans, with `sym` as it was sliced. A converted
+/// document's text is checked against `sym.source_hash` first; if the
+/// conversion came out different, the file's symbols are re-extracted from
+/// the new text (keeping agent definitions) and `sym` is looked up again.
+pub fn symbol_text(project: &Project, sym: Symbol) -> Result<(Symbol, String), String> {
+    let source = read_source(&project.root, &sym.file, sym.language)?;
+    let sym = match sym.source_hash.as_deref() {
+        Some(hash) if hash != source_hash(&source) => re_extract_converted(project, &sym, &source)?,
+        _ => sym,
+    };
+    let end = sym.byte_range.1.min(source.len());
+    let text = source.get(sym.byte_range.0..end).ok_or_else(|| {
+        format!(
+            "Symbol '{}' no longer lines up with '{}'; re-index the file",
+            sym.name, sym.file
+        )
+    })?;
+    Ok((sym, text.to_string()))
+}
+
+fn re_extract_converted(project: &Project, stale: &Symbol, source: &str) -> Result<Symbol, String> {
+    let file = &stale.file;
+    let definitions: Vec<(String, String)> = project
+        .symbol_table
+        .list_by_file(file)
+        .into_iter()
+        .filter_map(|s| Some((s.name, s.definition?)))
+        .collect();
+    watcher::re_extract_symbols(
+        &project.root,
+        &project.file_tree,
+        &project.symbol_table,
+        &project.extraction_errors,
+        file,
+        stale.language,
+    )
+    .map_err(|e| {
+        format!(
+            "The converted text of '{}' changed and re-extracting its symbols failed: {:#}",
+            file, e
+        )
+    })?;
+    for (name, definition) in definitions {
+        let key = SymbolTable::make_key(file, &name);
+        if let Some(mut sym) = project.symbol_table.symbols.get_mut(&key) {
+            sym.definition = Some(definition);
+        }
+    }
+
+    let sym = project.symbol_table.get(file, &stale.name).ok_or_else(|| {
+        format!(
+            "Symbol '{}' is no longer in '{}' since its converted text changed",
+            stale.name, file
+        )
+    })?;
+    if sym.source_hash.as_deref() != Some(source_hash(source).as_str()) {
+        return Err(format!(
+            "The converted text of '{}' changed again while its symbols were re-extracted",
+            file
+        ));
+    }
+    Ok(sym)
+}
+
+/// Symbols to try when `symbol_name` isn't in `file`: the same name in other
+/// files as `file::name` keys, then indexed names within a small edit
+/// distance, then names containing it (or contained in it). Only the first
+/// `SUGGESTION_SCAN_LIMIT` names are examined.
+pub fn suggest_symbols(symbol_table: &SymbolTable, symbol_name: &str) -> Vec<String> {
+    let mut suggestions: Vec<String> = symbol_table
+        .by_name
+        .get(symbol_name)
+        .map(|keys| keys.iter().cloned().collect())
+        .unwrap_or_default();
+    suggestions.sort();
+    suggestions.truncate(SUGGESTION_LIMIT);
+    if suggestions.len() == SUGGESTION_LIMIT {
+        return suggestions;
+    }
+
+    let needle = symbol_name.to_lowercase();
+    let max_distance = (needle.chars().count() / 3).max(1);
+    let mut near: Vec<(usize, String)> = symbol_table
+        .by_name
+        .iter()
+        .take(SUGGESTION_SCAN_LIMIT)
+        .filter(|entry| entry.key() != symbol_name)
+        .filter_map(|entry| {
+            let candidate = entry.key().to_lowercase();
+            let score = bounded_edit_distance(&needle, &candidate, max_distance).or_else(|| {
+                let contains = candidate.contains(&needle)
+                    || (candidate.len() >= 3 && needle.contains(&candidate));
+                contains.then_some(max_distance + 1)
+            })?;
+            Some((score, entry.key().clone()))
+        })
+        .collect();
+    near.sort();
+    let room = SUGGESTION_LIMIT - suggestions.len();
+    suggestions.extend(near.into_iter().take(room).map(|(_, name)| name));
+    suggestions
+}
+
+/// Levenshtein distance between `a` and `b`, or `None` once it must exceed
+/// `max`.
+fn bounded_edit_distance(a: &str, b: &str, max: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut row = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = prev[j] + usize::from(ca != cb);
+            row[j + 1] = substitute.min(prev[j + 1] + 1).min(row[j] + 1);
+        }
+        if row.iter().min().is_some_and(|&m| m > max) {
+            return None;
+        }
+        prev = row;
+    }
+    Some(prev[b.len()]).filter(|&d| d <= max)
+}
+
+/// Grep within a symbol's implementation. Matches and their context are
+/// limited to the symbol's lines; line numbers are absolute within the file.
+#[allow(clippy::too_many_arguments)]
+pub fn grep_symbol(
     root: &Path,
     symbol_table: &Arc<SymbolTable>,
     symbol_name: &str,
     file: &str,
-) -> Result<String, String> {
+    pattern: &str,
+    max_matches: usize,
+    context_lines: usize,
+    scope: GrepScope,
+) -> Result<GrepResponse, String> {
     let sym = symbol_table
         .get(file, symbol_name)
         .ok_or_else(|| format!("Symbol '{}' not found in '{}'", symbol_name, file))?;
 
     let source = read_source(root, &sym.file, sym.language)?;
 
-    let start = sym.byte_range.0;
-    let end = sym.byte_range.1.min(source.len());
-    Ok(source[start..end].to_string())
+    let start = sym.byte_range.0.min(source.len());
+    let end = sym.byte_range.1.min(source.len()).max(start);
+    let first_line = source[..start].matches('\n').count();
+    let body_lines = source[start..end].trim_end_matches('\n').matches('\n').count() + 1;
+    content::grep_lines(
+        &sym.file,
+        &source,
+        sym.language,
+        (first_line, first_line + body_lines),
+        pattern,
+        max_matches,
+        context_lines,
+        scope,
+    )
+}
+
+/// A file `reindex_files` could not bring up to date.
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub struct ReindexError {
+    pub file: String,
+    pub error: String,
+}
+
+#[derive(Debug, Default, serde::Serialize, ToSchema)]
+pub struct ReindexResult {
+    pub files_reindexed: usize,
+    pub symbols_added: usize,
+    pub symbols_removed: usize,
+    pub errors: Vec<ReindexError>,
+    /// Fingerprint snapshot taken afterwards, usable as `/symbols/diff?since=`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot_id: Option<u64>,
+    /// The request was cancelled; files not extracted by then are listed in
+    /// `errors` and keep their previous symbols.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub cancelled: bool,
 }
 
+/// Re-index a batch of files from disk: refresh their tree entries, then
+/// re-extract symbols and call sites in parallel. Files deleted from disk are
+/// dropped from the index. Paths outside the project or excluded by the
+/// ignore and size filters are reported as errors. Once the request is
+/// cancelled, files not yet started are skipped.
+pub fn reindex_files(
+    root: &Path,
+    files: &[String],
+    file_tree: &FileTree,
+    symbol_table: &SymbolTable,
+    errors: &ExtractionErrors,
+    max_file_size: u64,
+) -> ReindexResult {
+    use rayon::prelude::*;
+
+    let mut result = ReindexResult::default();
+    let fail = |result: &mut ReindexResult, file: &str, error: String| {
+        result.errors.push(ReindexError {
+            file: file.to_string(),
+            error,
+        })
+    };
+
+    let mut seen = HashSet::new();
+    let mut to_extract: Vec<(&str, Language)> = Vec::new();
+    let mut deleted: Vec<&str> = Vec::new();
+    for file in files {
+        let file = file.as_str();
+        if !seen.insert(file) {
+            continue;
+        }
+        if let Some(reason) = file_tree.ignore_rules().reason(file, false) {
+            fail(
+                &mut result,
+                file,
+                format!("'{}' is ignored (matches '{}')", file, reason.pattern),
+            );
+            continue;
+        }
+        let abs = root.join(file);
+        if !abs.exists() {
+            if file_tree.get(file).is_some() {
+                deleted.push(file);
+            } else {
+                fail(&mut result, file, format!("File '{}' does not exist on disk", file));
+            }
+            continue;
+        }
+        match abs.canonicalize() {
+            Ok(canonical) if canonical.starts_with(root) && canonical.is_file() => {}
+            _ => {
+                fail(&mut result, file, format!("'{}' is not a file inside the project", file));
+                continue;
+            }
+        }
+        match watcher::refresh_file_entry(root, file_tree, max_file_size, file, &abs) {
+            Some(language) => to_extract.push((file, language)),
+            None => fail(
+                &mut result,
+                file,
+                format!(
+                    "File '{}' is excluded from the index (over the size limit)",
+                    file
+                ),
+            ),
+        }
+    }
+
+    // Rayon workers don't see the request's token, so hand it to them.
+    let cancel = cancel::current();
+    let extracted: Vec<_> = to_extract
+        .par_iter()
+        .filter(|_| !cancel.is_cancelled())
+        .map(|&(file, language)| {
+            let symbols = language
+                .has_tree_sitter_support()
+                .then(|| parser::extract_symbols_from_file(root, file, language, &cancel));
+            let call_sites = parser::extract_call_sites(root, file, language, &cancel);
+            (file, language, symbols, call_sites)
+        })
+        .collect();
+    if extracted.len() < to_extract.len() {
+        result.cancelled = true;
+        let done: HashSet<&str> = extracted.iter().map(|(file, ..)| *file).collect();
+        for &(file, _) in &to_extract {
+            if !done.contains(file) {
+                fail(&mut result, file, "Cancelled before extraction".to_string());
+            }
+        }
+    }
+
+    // Drop every changed file's call sites in one pass over the callee
+    // lists they touch; `remove_file` below then finds none left.
+    let changed: Vec<&str> = deleted
+        .iter()
+        .copied()
+        .chain(extracted.iter().map(|(file, ..)| *file))
+        .collect();
+    symbol_table.remove_callers_from_files(&changed);
+
+    for file in deleted {
+        errors.remove(file);
+        file_tree.remove(file);
+        result.symbols_removed += symbol_table.list_by_file(file).len();
+        symbol_table.remove_file(file);
+        result.files_reindexed += 1;
+    }
+    for (file, language, symbols, call_sites) in extracted {
+        result.symbols_removed += symbol_table.list_by_file(file).len();
+        symbol_table.remove_file(file);
+        if let Some(symbols) = symbols {
+            parser::record_extraction(errors, file, language, &symbols);
+            match symbols {
+                Ok((symbols, source)) => {
+                    result.symbols_added += symbols.len();
+                    parser::insert_file_symbols(file_tree, symbol_table, file, symbols, source);
+                }
+                Err(e) => {
+                    fail(&mut result, file, format!("Symbol extraction failed: {:#}", e));
+                    continue;
+                }
+            }
+        }
+        for (callee, line, text) in call_sites {
+            symbol_table.add_caller(&callee, file, line, &text);
+        }
+        result.files_reindexed += 1;
+    }
+
+    result
+}
+
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub struct AddFileResult {
+    pub file: String,
+    pub language: Language,
+    pub symbols_extracted: usize,
+    /// The file was already in the index and has been re-read.
+    pub already_indexed: bool,
+    /// Fingerprint snapshot taken afterwards, usable as `/symbols/diff?since=`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot_id: Option<u64>,
+}
+
+/// Add one file that exists on disk to the index: create its tree entry,
+/// then extract its symbols and call sites. A file already indexed is simply
+/// re-read. Fails if the file is excluded from the index or can't be parsed.
+pub fn add_file(
+    root: &Path,
+    file: &str,
+    file_tree: &FileTree,
+    symbol_table: &SymbolTable,
+    errors: &ExtractionErrors,
+    max_file_size: u64,
+) -> Result<AddFileResult, String> {
+    let already_indexed = file_tree.get(file).is_some();
+    let result = reindex_files(
+        root,
+        &[file.to_string()],
+        file_tree,
+        symbol_table,
+        errors,
+        max_file_size,
+    );
+    if let Some(error) = result.errors.into_iter().next() {
+        return Err(error.error);
+    }
+    let language = file_tree
+        .get(file)
+        .map(|entry| entry.language)
+        .ok_or_else(|| format!("File '{}' was not added to the index", file))?;
+    Ok(AddFileResult {
+        file: file.to_string(),
+        language,
+        symbols_extracted: result.symbols_added,
+        already_indexed,
+        snapshot_id: None,
+    })
+}
+
+/// Set a symbol's definition. Fails if it already has one, unless
+/// `upsert`. Returns the definition that was replaced, if any.
 pub fn define_symbol(
     symbol_table: &Arc<SymbolTable>,
     symbol_name: &str,
     file: &str,
     definition: &str,
-) -> Result<(), String> {
+    upsert: bool,
+) -> Result<Option<String>, String> {
     let key = SymbolTable::make_key(file, symbol_name);
     if let Some(mut sym) = symbol_table.symbols.get_mut(&key) {
-        if sym.definition.is_some() {
+        if sym.definition.is_some() && !upsert {
             return Err(format!(
-                "Symbol '{}' in '{}' already has a definition. Use redefine.",
+                "Symbol '{}' in '{}' already has a definition. Use redefine, or pass upsert=true.",
                 symbol_name, file
             ));
         }
-        sym.definition = Some(definition.to_string());
-        Ok(())
+        Ok(sym.definition.replace(definition.to_string()))
     } else {
         Err(format!("Symbol '{}' not found in '{}'", symbol_name, file))
     }
 }
 
+/// Replace a symbol's definition, returning the previous one if any.
 pub fn redefine_symbol(
     symbol_table: &Arc<SymbolTable>,
     symbol_name: &str,
     file: &str,
     definition: &str,
-) -> Result<(), String> {
+) -> Result<Option<String>, String> {
     let key = SymbolTable::make_key(file, symbol_name);
     if let Some(mut sym) = symbol_table.symbols.get_mut(&key) {
-        sym.definition = Some(definition.to_string());
-        Ok(())
+        Ok(sym.definition.replace(definition.to_string()))
     } else {
         Err(format!("Symbol '{}' not found in '{}'", symbol_name, file))
     }
 }
 
+/// Where `find_callers` got its answer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CallerSource {
+    /// The reverse call graph built during indexing.
+    Cached,
+    /// A scan of every file for this one symbol.
+    Scanned,
+}
+
+pub struct FoundCallers {
+    /// Ordered by file, then line.
+    pub callers: Vec<CallerInfo>,
+    pub warning: Option<String>,
+    pub source: CallerSource,
+}
+
 /// Find callers of a symbol. Uses the pre-built reverse call graph for O(1)
 /// lookup when available, falling back to per-file tree-sitter/regex scan.
+/// While the graph is still being built (`graph_complete` unset), the
+/// cached entries are only used when there are at least `limit` of them.
+/// When `context_lines` is set, each caller also carries that many lines of
+/// source before and after the call site. With `enrich`, each caller is
+/// matched to the innermost symbol whose line range contains the call.
+#[allow(clippy::too_many_arguments)]
 pub fn find_callers(
     root: &Path,
     file_tree: &Arc<FileTree>,
@@ -108,14 +1013,312 @@ pub fn find_callers(
     symbol_name: &str,
     file: &str,
     limit: usize,
-) -> Result<Vec<CallerInfo>, String> {
+    context_lines: Option<usize>,
+    enrich: bool,
+    filter: &CallerFilter,
+    graph_complete: bool,
+) -> Result<FoundCallers, String> {
+    let filtering = filter.callee_kind.is_some() || filter.caller_kind.is_some();
+    let fetch = if filtering { usize::MAX } else { limit };
+    let (mut callers, source) = find_callers_inner(
+        root,
+        file_tree,
+        symbol_table,
+        symbol_name,
+        file,
+        fetch,
+        graph_complete,
+    )?;
+
+    let mut warning = None;
+    if let Some(kind) = filter.callee_kind {
+        if let Some(sym) = symbol_table.get(file, symbol_name)
+            && sym.kind != kind
+        {
+            warning = Some(format!(
+                "'{}' in '{}' is a {}, not a {}; call sites were still filtered by call syntax",
+                symbol_name,
+                file,
+                format!("{:?}", sym.kind).to_lowercase(),
+                format!("{:?}", kind).to_lowercase()
+            ));
+        }
+        // A line calling the name twice yields two call sites, in order, so
+        // pair them with the calls found in the text when the counts agree.
+        let mut per_line: std::collections::HashMap<(String, usize), usize> =
+            std::collections::HashMap::new();
+        for c in &callers {
+            *per_line.entry((c.file.clone(), c.line)).or_default() += 1;
+        }
+        let mut seen: std::collections::HashMap<(String, usize), usize> =
+            std::collections::HashMap::new();
+        callers.retain(|c| {
+            let key = (c.file.clone(), c.line);
+            let kinds = call_site_kinds(&c.text, symbol_name);
+            let index = seen.entry(key.clone()).or_default();
+            let nth = *index;
+            *index += 1;
+            if kinds.len() == per_line[&key] {
+                kinds[nth] == kind
+            } else {
+                kinds.contains(&kind)
+            }
+        });
+    }
+    if enrich || filter.caller_kind.is_some() {
+        attach_enclosing_symbols(symbol_table, &mut callers);
+    }
+    if let Some(kind) = filter.caller_kind {
+        callers.retain(|c| c.enclosing_symbol.as_ref().is_some_and(|s| s.kind == kind));
+    }
+    callers.truncate(limit);
+
+    if let Some(n) = context_lines {
+        attach_caller_context(root, file_tree, &mut callers, n);
+    }
+    Ok(FoundCallers {
+        callers,
+        warning,
+        source,
+    })
+}
+
+/// Optional `find_callers` filters. `callee_kind` (function or method) keeps
+/// call sites whose syntax calls that kind; `caller_kind` keeps call sites
+/// inside a definition of that kind.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CallerFilter {
+    pub callee_kind: Option<SymbolKind>,
+    pub caller_kind: Option<SymbolKind>,
+}
+
+/// How each call of `name` in `text` calls it, in order: as a method
+/// (`x.name(`, `Self::name(`, `Type::name(`) or as a function (`name(`,
+/// `module::name(`).
+fn call_site_kinds(text: &str, name: &str) -> Vec<SymbolKind> {
+    let call = format!("{}(", name);
+    let mut kinds = Vec::new();
+    for (at, _) in text.match_indices(&call) {
+        let before = &text[..at];
+        if before
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_')
+        {
+            continue;
+        }
+        let kind = if before.ends_with('.') {
+            SymbolKind::Method
+        } else if let Some(path) = before.strip_suffix("::") {
+            let owner = path
+                .rsplit(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .next()
+                .unwrap_or("");
+            if owner.starts_with(char::is_uppercase) {
+                SymbolKind::Method
+            } else {
+                SymbolKind::Function
+            }
+        } else {
+            SymbolKind::Function
+        };
+        kinds.push(kind);
+    }
+    kinds
+}
+
+fn attach_enclosing_symbols(symbol_table: &SymbolTable, callers: &mut [CallerInfo]) {
+    let mut by_file: std::collections::HashMap<String, Vec<Symbol>> =
+        std::collections::HashMap::new();
+
+    for caller in callers.iter_mut() {
+        let symbols = by_file
+            .entry(caller.file.clone())
+            .or_insert_with(|| symbol_table.list_by_file(&caller.file));
+        caller.enclosing_symbol = enclosing_symbol(symbols, caller.line).map(|s| EnclosingSymbol {
+            name: s.name.clone(),
+            kind: s.kind,
+            file: s.file.clone(),
+        });
+    }
+}
+
+/// Innermost definition in `symbols` whose line range contains `line`.
+pub fn enclosing_symbol(symbols: &[Symbol], line: usize) -> Option<&Symbol> {
+    // Variables and imports have line ranges too, but the caller wants the
+    // named definition the call sits in.
+    symbols
+        .iter()
+        .filter(|s| !matches!(s.kind, SymbolKind::Variable | SymbolKind::Import))
+        .filter(|s| s.line_range.0 <= line && line <= s.line_range.1)
+        .min_by_key(|s| s.line_range.1 - s.line_range.0)
+}
+
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub struct GraphNeighbor {
+    pub symbol: Symbol,
+    /// Call-graph hops from the center.
+    pub distance: usize,
+}
+
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub struct GraphNeighborhood {
+    pub center: Symbol,
+    pub radius: usize,
+    pub callers: Vec<GraphNeighbor>,
+    pub callees: Vec<GraphNeighbor>,
+}
+
+/// Symbols within `radius` call-graph hops of `symbol_name`, walking callers
+/// and callees separately. Calls are resolved by name, so a callee name
+/// shared by several symbols yields all of them. A symbol reachable in both
+/// directions is listed once, under the direction where it is closer
+/// (callers on a tie). `radius` is clamped to 1..=3.
+pub fn graph_neighbors(
+    symbol_table: &Arc<SymbolTable>,
+    symbol_name: &str,
+    file: &str,
+    radius: usize,
+) -> Result<GraphNeighborhood, String> {
+    let center = symbol_table
+        .get(file, symbol_name)
+        .ok_or_else(|| format!("Symbol '{}' not found in '{}'", symbol_name, file))?;
+    let radius = radius.clamp(1, crate::config::GRAPH_NEIGHBORS_MAX_RADIUS);
+
+    // Resolve every call site to its enclosing definition once, giving
+    // caller key -> callee names and callee name -> caller keys.
+    let mut calls: std::collections::HashMap<String, HashSet<String>> =
+        std::collections::HashMap::new();
+    let mut called_by: std::collections::HashMap<String, HashSet<String>> =
+        std::collections::HashMap::new();
+    let mut file_symbols: std::collections::HashMap<String, Vec<Symbol>> =
+        std::collections::HashMap::new();
+    for entry in symbol_table.reverse_call_graph.iter() {
+        for site in entry.value() {
+            let symbols = file_symbols
+                .entry(site.file.clone())
+                .or_insert_with(|| symbol_table.list_by_file(&site.file));
+            let Some(caller) = enclosing_symbol(symbols, site.line) else {
+                continue;
+            };
+            let key = SymbolTable::make_key(&caller.file, &caller.name);
+            calls.entry(key.clone()).or_default().insert(entry.key().clone());
+            called_by.entry(entry.key().clone()).or_default().insert(key);
+        }
+    }
+
+    let resolve_name = |name: &str| -> Vec<String> {
+        let mut keys: Vec<String> = symbol_table
+            .by_name
+            .get(name)
+            .map(|keys| keys.iter().cloned().collect())
+            .unwrap_or_default();
+        keys.sort();
+        keys
+    };
+    let callers_of = |key: &str| -> Vec<String> {
+        let Some(sym) = symbol_table.symbols.get(key) else {
+            return Vec::new();
+        };
+        let mut keys: Vec<String> = called_by
+            .get(&sym.name)
+            .map(|keys| keys.iter().cloned().collect())
+            .unwrap_or_default();
+        keys.sort();
+        keys
+    };
+    let callees_of = |key: &str| -> Vec<String> {
+        let mut names: Vec<&String> =
+            calls.get(key).map(|n| n.iter().collect()).unwrap_or_default();
+        names.sort();
+        names.into_iter().flat_map(|n| resolve_name(n)).collect()
+    };
+
+    let center_key = SymbolTable::make_key(&center.file, &center.name);
+    let caller_distances = walk_call_graph(&center_key, radius, callers_of);
+    let mut callee_distances = walk_call_graph(&center_key, radius, callees_of);
+    callee_distances.retain(|key, distance| {
+        caller_distances.get(key).is_none_or(|caller_distance| *distance < *caller_distance)
+    });
+    let caller_distances: std::collections::HashMap<String, usize> = caller_distances
+        .into_iter()
+        .filter(|(key, _)| !callee_distances.contains_key(key))
+        .collect();
+
+    Ok(GraphNeighborhood {
+        callers: into_neighbors(symbol_table, caller_distances),
+        callees: into_neighbors(symbol_table, callee_distances),
+        center,
+        radius,
+    })
+}
+
+/// Breadth-first walk from `start` up to `radius` hops, returning each
+/// reached key (other than `start`) with its shortest distance.
+fn walk_call_graph(
+    start: &str,
+    radius: usize,
+    next: impl Fn(&str) -> Vec<String>,
+) -> std::collections::HashMap<String, usize> {
+    let mut distances = std::collections::HashMap::new();
+    let mut seen: HashSet<String> = HashSet::from([start.to_string()]);
+    let mut frontier = vec![start.to_string()];
+    for distance in 1..=radius {
+        let mut next_frontier = Vec::new();
+        for key in &frontier {
+            for neighbor in next(key) {
+                if seen.insert(neighbor.clone()) {
+                    distances.insert(neighbor.clone(), distance);
+                    next_frontier.push(neighbor);
+                }
+            }
+        }
+        if next_frontier.is_empty() {
+            break;
+        }
+        frontier = next_frontier;
+    }
+    distances
+}
+
+/// Look up each key's symbol, ordered by distance then key.
+fn into_neighbors(
+    symbol_table: &SymbolTable,
+    distances: std::collections::HashMap<String, usize>,
+) -> Vec<GraphNeighbor> {
+    let mut distances: Vec<(String, usize)> = distances.into_iter().collect();
+    distances.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+    distances
+        .into_iter()
+        .filter_map(|(key, distance)| {
+            let symbol = symbol_table.symbols.get(&key)?.value().clone();
+            Some(GraphNeighbor { symbol, distance })
+        })
+        .collect()
+}
+
+fn find_callers_inner(
+    root: &Path,
+    file_tree: &Arc<FileTree>,
+    symbol_table: &Arc<SymbolTable>,
+    symbol_name: &str,
+    file: &str,
+    limit: usize,
+    graph_complete: bool,
+) -> Result<(Vec<CallerInfo>, CallerSource), String> {
     // Verify symbol exists
     let _sym = symbol_table
         .get(file, symbol_name)
         .ok_or_else(|| format!("Symbol '{}' not found in '{}'", symbol_name, file))?;
 
-    // Fast path: use cached reverse call graph
-    if let Some(cached) = symbol_table.get_callers(symbol_name) {
+    // Fast path: use cached reverse call graph. A graph still being built
+    // may be missing call sites, so only trust it then if it already has
+    // enough to fill the response.
+    if let Some(mut cached) = symbol_table.get_callers(symbol_name)
+        && (graph_complete || cached.len() >= limit)
+    {
+        // Stable, so calls on one line keep their order.
+        cached.sort_by(|a, b| a.file.cmp(&b.file).then(a.line.cmp(&b.line)));
         let callers: Vec<CallerInfo> = cached
             .into_iter()
             .take(limit)
@@ -123,38 +1326,44 @@ pub fn find_callers(
                 file: c.file,
                 line: c.line,
                 text: c.text,
+                context: c.context,
+                enclosing_symbol: None,
             })
             .collect();
-        return Ok(callers);
+        return Ok((callers, CallerSource::Cached));
     }
 
-    // Slow path: scan all files (call graph not yet built)
+    // Slow path: scan all files (call graph not yet built), in path order
+    let mut paths: Vec<(String, Language)> = file_tree
+        .files
+        .iter()
+        .map(|entry| (entry.key().clone(), entry.value().language))
+        .collect();
+    paths.sort_by(|a, b| a.0.cmp(&b.0));
     let mut callers = Vec::new();
 
-    for entry in file_tree.files.iter() {
-        let rel_path = entry.key().clone();
-        let language = entry.value().language;
-
+    for (rel_path, language) in paths {
         let source = match read_source(root, &rel_path, language) {
             Ok(s) => s,
             Err(_) => continue,
         };
 
-        let file_callers = if language.has_tree_sitter_support() {
+        let mut file_callers = if language.has_tree_sitter_support() {
             find_callers_ast(&source, &rel_path, language, symbol_name, file)
         } else {
             find_callers_regex(&source, &rel_path, symbol_name, file)
         };
+        file_callers.sort_by_key(|c| c.line);
 
         for caller in file_callers {
             callers.push(caller);
             if callers.len() >= limit {
-                return Ok(callers);
+                return Ok((callers, CallerSource::Scanned));
             }
         }
     }
 
-    Ok(callers)
+    Ok((callers, CallerSource::Scanned))
 }
 
 /// AST-aware caller detection: parse the file, run the callers query,
@@ -218,6 +1427,8 @@ fn find_callers_ast(
                         file: rel_path.to_string(),
                         line: line_num,
                         text: line_text,
+                        context: None,
+                        enclosing_symbol: None,
                     });
                 }
             }
@@ -257,6 +1468,8 @@ fn find_callers_regex(
                 file: rel_path.to_string(),
                 line: line_num + 1,
                 text: line.trim().to_string(),
+                context: None,
+                enclosing_symbol: None,
             });
         }
     }
@@ -288,23 +1501,222 @@ fn is_definition_line(line: &str, name: &str, language: Language) -> bool {
     }
 }
 
-#[derive(Debug, serde::Serialize)]
+/// Fill in `context` on each caller with `n` lines either side of the call
+/// site. Each file is read once no matter how many callers it contains.
+fn attach_caller_context(
+    root: &Path,
+    file_tree: &Arc<FileTree>,
+    callers: &mut [CallerInfo],
+    n: usize,
+) {
+    let mut sources: std::collections::HashMap<String, Option<String>> =
+        std::collections::HashMap::new();
+
+    for caller in callers.iter_mut() {
+        let source = sources.entry(caller.file.clone()).or_insert_with(|| {
+            let language = file_tree.get(&caller.file)?.language;
+            read_source(root, &caller.file, language).ok()
+        });
+        if let Some(source) = source {
+            caller.context = Some(line_context(source, caller.line, n));
+        }
+    }
+}
+
+/// Lines `line - n ..= line + n` (1-indexed `line`), clamped to the file.
+fn line_context(source: &str, line: usize, n: usize) -> String {
+    let lines: Vec<&str> = source.lines().collect();
+    let idx = line.saturating_sub(1).min(lines.len());
+    let start = idx.saturating_sub(n);
+    let end = (idx + n + 1).min(lines.len());
+    lines[start..end].join("\n")
+}
+
+#[derive(Debug, serde::Serialize, ToSchema)]
 pub struct CallerInfo {
     pub file: String,
     pub line: usize,
     pub text: String,
+    /// Source lines around the call site, present when `context_lines` is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    /// Definition containing the call site, present when `enrich` is set and
+    /// a match is found.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enclosing_symbol: Option<EnclosingSymbol>,
 }
 
-/// Find test functions that reference a given symbol.
-pub fn find_tests(
-    root: &Path,
-    _file_tree: &Arc<FileTree>,
-    symbol_table: &Arc<SymbolTable>,
-    symbol_name: &str,
-    file: &str,
-    limit: usize,
-) -> Result<Vec<TestInfo>, String> {
-    let _sym = symbol_table
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub struct EnclosingSymbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub file: String,
+}
+
+/// A line that mentions a symbol being renamed.
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub struct RenameSite {
+    pub file: String,
+    pub line: usize,
+    pub text: String,
+}
+
+/// Every place a rename of one symbol would touch, by kind of reference.
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub struct RenameCandidates {
+    pub call_sites: Vec<RenameSite>,
+    /// `pub use` and `export ... from` lines that re-export the name.
+    pub reexports: Vec<RenameSite>,
+    /// `///` and `//!` doc comment lines naming the symbol.
+    pub doc_refs: Vec<RenameSite>,
+    /// `use`, `import`, and `from ... import` lines bringing the name in.
+    pub use_stmts: Vec<RenameSite>,
+    /// Sites listed above, not counting the definition itself.
+    pub total_edits_needed: usize,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum RenameRef {
+    Reexport,
+    Doc,
+    Use,
+}
+
+/// Collect the call sites, re-exports, doc comment references, and import
+/// statements that name `symbol_name`, so an agent can see what a rename
+/// would need to update. Call sites come from the call graph; the rest from
+/// a whole-word line scan of every parseable file.
+pub fn rename_candidates(
+    root: &Path,
+    file_tree: &Arc<FileTree>,
+    symbol_table: &Arc<SymbolTable>,
+    symbol_name: &str,
+    file: &str,
+    graph_complete: bool,
+) -> Result<RenameCandidates, String> {
+    let (callers, _) = find_callers_inner(
+        root,
+        file_tree,
+        symbol_table,
+        symbol_name,
+        file,
+        usize::MAX,
+        graph_complete,
+    )?;
+
+    let mut paths: Vec<(String, Language)> = file_tree
+        .files
+        .iter()
+        .filter(|entry| entry.value().language.has_tree_sitter_support())
+        .map(|entry| (entry.key().clone(), entry.value().language))
+        .collect();
+    paths.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut reexports = Vec::new();
+    let mut doc_refs = Vec::new();
+    let mut use_stmts = Vec::new();
+    let mut other_refs: HashSet<(String, usize)> = HashSet::new();
+    for (rel_path, language) in paths {
+        let Ok(source) = read_source(root, &rel_path, language) else {
+            continue;
+        };
+        if !source.contains(symbol_name) {
+            continue;
+        }
+        // A braced `use a::{...};`, `import {...} from`, or parenthesized
+        // `from a import (...)` spanning lines keeps its kind until it closes.
+        let mut open_block: Option<RenameRef> = None;
+        for (idx, line) in source.lines().enumerate() {
+            let kind = open_block.or_else(|| rename_ref_kind(line));
+            if let Some(kind) = kind.filter(|k| *k != RenameRef::Doc) {
+                if line.contains(['}', ')']) {
+                    open_block = None;
+                } else if line.contains(['{', '(']) {
+                    open_block = Some(kind);
+                }
+            }
+            let Some(kind) = kind else {
+                continue;
+            };
+            if !contains_word(line, symbol_name) {
+                continue;
+            }
+            let site = RenameSite {
+                file: rel_path.clone(),
+                line: idx + 1,
+                text: line.trim().to_string(),
+            };
+            other_refs.insert((site.file.clone(), site.line));
+            match kind {
+                RenameRef::Reexport => reexports.push(site),
+                RenameRef::Doc => doc_refs.push(site),
+                RenameRef::Use => use_stmts.push(site),
+            }
+        }
+    }
+
+    // The regex fallback matches any mention, so drop call sites the scan
+    // already placed elsewhere.
+    let call_sites: Vec<RenameSite> = callers
+        .into_iter()
+        .filter(|c| !other_refs.contains(&(c.file.clone(), c.line)))
+        .map(|c| RenameSite {
+            file: c.file,
+            line: c.line,
+            text: c.text,
+        })
+        .collect();
+
+    let total_edits_needed = call_sites.len() + reexports.len() + doc_refs.len() + use_stmts.len();
+    Ok(RenameCandidates {
+        call_sites,
+        reexports,
+        doc_refs,
+        use_stmts,
+        total_edits_needed,
+    })
+}
+
+/// What kind of reference a line can hold, judged by how it starts.
+fn rename_ref_kind(line: &str) -> Option<RenameRef> {
+    let line = line.trim_start();
+    if line.starts_with("///") || line.starts_with("//!") {
+        Some(RenameRef::Doc)
+    } else if (line.starts_with("pub use ") || line.starts_with("pub(") && line.contains(") use "))
+        || (line.starts_with("export ") && (line.contains(" from ") || line.starts_with("export {")))
+    {
+        Some(RenameRef::Reexport)
+    } else if line.starts_with("use ")
+        || line.starts_with("import ")
+        || (line.starts_with("from ") && line.contains(" import "))
+    {
+        Some(RenameRef::Use)
+    } else {
+        None
+    }
+}
+
+/// Whether `name` occurs in `line` as a whole identifier.
+fn contains_word(line: &str, name: &str) -> bool {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    line.match_indices(name).any(|(at, _)| {
+        !line[..at].chars().next_back().is_some_and(is_ident)
+            && !line[at + name.len()..].chars().next().is_some_and(is_ident)
+    })
+}
+
+/// Find test functions that reference `symbol_name`. With `source_max_bytes`
+/// set, each result also carries the test's source, cut to that many bytes.
+pub fn find_tests(
+    root: &Path,
+    _file_tree: &Arc<FileTree>,
+    symbol_table: &Arc<SymbolTable>,
+    symbol_name: &str,
+    file: &str,
+    limit: usize,
+    source_max_bytes: Option<usize>,
+) -> Result<Vec<TestInfo>, String> {
+    let _sym = symbol_table
         .get(file, symbol_name)
         .ok_or_else(|| format!("Symbol '{}' not found in '{}'", symbol_name, file))?;
 
@@ -328,11 +1740,20 @@ pub fn find_tests(
         let body = &source[start..end];
 
         if body.contains(symbol_name) {
+            let source = source_max_bytes.map(|max| {
+                if body.len() > max {
+                    let boundary = body.floor_char_boundary(max);
+                    format!("{}...", &body[..boundary])
+                } else {
+                    body.to_string()
+                }
+            });
             tests.push(TestInfo {
                 name: sym.name.clone(),
                 file: sym.file.clone(),
                 line: sym.line_range.0,
                 signature: sym.signature.clone(),
+                source,
             });
 
             if tests.len() >= limit {
@@ -344,7 +1765,470 @@ pub fn find_tests(
     Ok(tests)
 }
 
-fn is_test_symbol(sym: &Symbol) -> bool {
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub struct CoverageHint {
+    pub has_test_callers: bool,
+    /// Distinct test files containing a call to the symbol's name.
+    pub test_file_count: usize,
+}
+
+/// Cheap "is there probably a test?" check: look for call sites of the
+/// symbol's name in test-looking files, using only the reverse call graph.
+/// Calls are matched by name, so a same-named symbol elsewhere also counts.
+pub fn test_coverage_hint(
+    symbol_table: &Arc<SymbolTable>,
+    symbol_name: &str,
+    file: &str,
+) -> Result<CoverageHint, String> {
+    if symbol_table.get(file, symbol_name).is_none() {
+        return Err(format!("Symbol '{}' not found in '{}'", symbol_name, file));
+    }
+
+    let test_files: HashSet<String> = symbol_table
+        .reverse_call_graph
+        .get(symbol_name)
+        .map(|callers| {
+            callers
+                .iter()
+                .filter(|c| is_test_path(&c.file))
+                .map(|c| c.file.clone())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(CoverageHint {
+        has_test_callers: !test_files.is_empty(),
+        test_file_count: test_files.len(),
+    })
+}
+
+/// Bucket layout for `call_frequency_histogram`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistogramBuckets {
+    /// Fixed, roughly logarithmic edges: 0, 1-2, 3-5, 6-10, 11-20, 21-50,
+    /// 51-100, 101+ (default).
+    Log,
+    /// This many equal-width buckets from 0 up to the highest caller count.
+    Linear(usize),
+}
+
+impl HistogramBuckets {
+    /// `"log"` or a bucket count for linear buckets.
+    pub fn from_str(s: &str) -> Option<Self> {
+        if s.eq_ignore_ascii_case("log") {
+            return Some(HistogramBuckets::Log);
+        }
+        match s.parse::<usize>() {
+            Ok(n) if (1..=crate::config::HISTOGRAM_MAX_BUCKETS).contains(&n) => {
+                Some(HistogramBuckets::Linear(n))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub struct HistogramBucket {
+    pub label: String,
+    pub min: usize,
+    /// Inclusive upper bound; `None` for the open-ended last log bucket.
+    pub max: Option<usize>,
+    pub count: usize,
+}
+
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub struct CallFrequencyHistogram {
+    /// Symbols counted: everything except variables and imports.
+    pub symbols: usize,
+    pub buckets: Vec<HistogramBucket>,
+    pub mean: f64,
+    pub median: usize,
+    pub p95: usize,
+}
+
+/// Distribution of caller counts over all definitions. A symbol's count is
+/// the number of call sites of its name in the reverse call graph, so
+/// same-named symbols share their callers, as in `test_coverage_hint`.
+pub fn call_frequency_histogram(
+    symbol_table: &SymbolTable,
+    buckets: HistogramBuckets,
+) -> CallFrequencyHistogram {
+    let mut counts: Vec<usize> = symbol_table
+        .symbols
+        .iter()
+        .filter(|entry| !matches!(entry.kind, SymbolKind::Variable | SymbolKind::Import))
+        .map(|entry| {
+            symbol_table
+                .reverse_call_graph
+                .get(&entry.name)
+                .map_or(0, |callers| callers.len())
+        })
+        .collect();
+    counts.sort_unstable();
+
+    let edges: Vec<(usize, Option<usize>)> = match buckets {
+        HistogramBuckets::Log => vec![
+            (0, Some(0)),
+            (1, Some(2)),
+            (3, Some(5)),
+            (6, Some(10)),
+            (11, Some(20)),
+            (21, Some(50)),
+            (51, Some(100)),
+            (101, None),
+        ],
+        HistogramBuckets::Linear(n) => {
+            let highest = counts.last().copied().unwrap_or(0);
+            let width = (highest + 1).div_ceil(n).max(1);
+            (0..n)
+                .map(|i| (i * width, Some((i + 1) * width - 1)))
+                .collect()
+        }
+    };
+    let buckets = edges
+        .into_iter()
+        .map(|(min, max)| {
+            // `counts` is sorted, so each bucket is a contiguous run.
+            let start = counts.partition_point(|&c| c < min);
+            let end = match max {
+                Some(max) => counts.partition_point(|&c| c <= max),
+                None => counts.len(),
+            };
+            let label = match max {
+                Some(max) if max == min => min.to_string(),
+                Some(max) => format!("{}-{}", min, max),
+                None => format!("{}+", min),
+            };
+            HistogramBucket {
+                label,
+                min,
+                max,
+                count: end - start,
+            }
+        })
+        .collect();
+
+    let total: usize = counts.iter().sum();
+    let percentile = |p: usize| -> usize {
+        if counts.is_empty() {
+            return 0;
+        }
+        // Nearest-rank percentile.
+        let rank = (counts.len() * p).div_ceil(100).max(1);
+        counts[rank - 1]
+    };
+    CallFrequencyHistogram {
+        symbols: counts.len(),
+        buckets,
+        mean: if counts.is_empty() {
+            0.0
+        } else {
+            total as f64 / counts.len() as f64
+        },
+        median: percentile(50),
+        p95: percentile(95),
+    }
+}
+
+/// What `hotspot_files` ranks files by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotspotMetric {
+    /// Sum of the cyclomatic complexity of the file's functions and methods.
+    Complexity,
+    /// Sum of the caller counts of the file's symbols.
+    Callers,
+}
+
+impl HotspotMetric {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "complexity" => Some(HotspotMetric::Complexity),
+            "callers" => Some(HotspotMetric::Callers),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub struct FileHotspot {
+    pub file: String,
+    pub total_score: f64,
+    /// Symbols that contributed to `total_score`.
+    pub symbol_count: usize,
+    /// The symbol with the highest individual score.
+    pub top_symbol: String,
+}
+
+/// Files ranked by the summed per-symbol `metric`, highest first. Caller
+/// counts come from the reverse call graph, matched by name as in
+/// `call_frequency_histogram`; complexity needs the file source, so files
+/// that can't be read or parsed are left out. Generated code is skipped.
+pub fn hotspot_files(
+    root: &Path,
+    symbol_table: &SymbolTable,
+    metric: HotspotMetric,
+    limit: usize,
+) -> Vec<FileHotspot> {
+    let files: Vec<String> = symbol_table
+        .by_file
+        .iter()
+        .map(|entry| entry.key().clone())
+        .collect();
+
+    let mut hotspots: Vec<FileHotspot> = files
+        .into_iter()
+        .filter_map(|file| {
+            let symbols: Vec<Symbol> = symbol_table
+                .list_by_file(&file)
+                .into_iter()
+                .filter(|s| !s.generated)
+                .collect();
+            let scores: Vec<(String, f64)> = match metric {
+                HotspotMetric::Callers => symbols
+                    .iter()
+                    .filter(|s| !matches!(s.kind, SymbolKind::Variable | SymbolKind::Import))
+                    .map(|s| {
+                        let callers = symbol_table
+                            .reverse_call_graph
+                            .get(&s.name)
+                            .map_or(0, |callers| callers.len());
+                        (s.name.clone(), callers as f64)
+                    })
+                    .collect(),
+                HotspotMetric::Complexity => file_complexity(root, &symbols)?,
+            };
+            let (top_symbol, _) = scores
+                .iter()
+                .max_by(|a, b| a.1.total_cmp(&b.1).then_with(|| b.0.cmp(&a.0)))?;
+            let total_score: f64 = scores.iter().map(|(_, score)| score).sum();
+            (total_score > 0.0).then(|| FileHotspot {
+                top_symbol: top_symbol.clone(),
+                file,
+                total_score,
+                symbol_count: scores.len(),
+            })
+        })
+        .collect();
+
+    hotspots.sort_by(|a, b| {
+        b.total_score
+            .total_cmp(&a.total_score)
+            .then_with(|| a.file.cmp(&b.file))
+    });
+    hotspots.truncate(limit);
+    hotspots
+}
+
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub struct DependencyMatrix {
+    pub files: Vec<String>,
+    /// `matrix[i][j]`: calls made from `files[i]` to symbols defined in
+    /// `files[j]`. The diagonal is always 0.
+    pub matrix: Vec<Vec<usize>>,
+}
+
+/// Cross-file call counts between `files`, from the reverse call graph.
+/// Calls are matched by name as in `graph_neighbors`, so a call to a name
+/// defined in several of the files counts towards each. Variables and
+/// imports aren't treated as definitions.
+pub fn dependency_matrix(symbol_table: &SymbolTable, files: &[String]) -> DependencyMatrix {
+    let index: std::collections::HashMap<&str, usize> = files
+        .iter()
+        .enumerate()
+        .map(|(i, file)| (file.as_str(), i))
+        .collect();
+    let mut matrix = vec![vec![0; files.len()]; files.len()];
+
+    for entry in symbol_table.reverse_call_graph.iter() {
+        let Some(keys) = symbol_table.by_name.get(entry.key()) else {
+            continue;
+        };
+        let mut defined_in: Vec<usize> = keys
+            .iter()
+            .filter_map(|key| symbol_table.symbols.get(key))
+            .filter(|s| !matches!(s.kind, SymbolKind::Variable | SymbolKind::Import))
+            .filter_map(|s| index.get(s.file.as_str()).copied())
+            .collect();
+        defined_in.sort_unstable();
+        defined_in.dedup();
+        if defined_in.is_empty() {
+            continue;
+        }
+        for site in entry.value() {
+            let Some(&from) = index.get(site.file.as_str()) else {
+                continue;
+            };
+            for &to in &defined_in {
+                if to != from {
+                    matrix[from][to] += 1;
+                }
+            }
+        }
+    }
+
+    DependencyMatrix {
+        files: files.to_vec(),
+        matrix,
+    }
+}
+
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub struct LocalSymbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub line_range: (usize, usize),
+    /// Recorded call sites of the name, all in the defining file.
+    pub call_sites: usize,
+}
+
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub struct LocalSymbolGroup {
+    pub file: String,
+    pub count: usize,
+    /// Ordered by line.
+    pub symbols: Vec<LocalSymbol>,
+}
+
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub struct LocalOnlyReport {
+    /// Symbols called only from their own file, per file.
+    pub local_only: Vec<LocalSymbolGroup>,
+    pub local_only_count: usize,
+    /// Symbols with no recorded call sites at all, per file.
+    pub uncalled: Vec<LocalSymbolGroup>,
+    pub uncalled_count: usize,
+}
+
+/// Definitions under `path_prefix` whose recorded call sites are all in the
+/// file defining them: candidates for making private. Call sites are matched
+/// by name as in `call_frequency_histogram`, so a name also called from
+/// another file, whichever definition that call meant, is not local. Names
+/// with no call sites go in `uncalled` instead. Only functions and methods
+/// are considered, since the call graph records calls; generated code, tests
+/// and entry points (`main`, Python dunder methods) are left out.
+pub fn local_only_symbols(symbol_table: &SymbolTable, path_prefix: &str) -> LocalOnlyReport {
+    let mut local_only: std::collections::BTreeMap<String, Vec<LocalSymbol>> = Default::default();
+    let mut uncalled: std::collections::BTreeMap<String, Vec<LocalSymbol>> = Default::default();
+
+    for entry in symbol_table.symbols.iter() {
+        let sym = entry.value();
+        if !sym.file.starts_with(path_prefix)
+            || sym.generated
+            || !matches!(sym.kind, SymbolKind::Function | SymbolKind::Method)
+            || is_entry_point(sym)
+            || is_test_symbol(sym)
+            || queries::is_test_name(sym.language, &sym.name)
+        {
+            continue;
+        }
+        let call_sites = match symbol_table.reverse_call_graph.get(&sym.name) {
+            Some(sites) if sites.iter().any(|site| site.file != sym.file) => continue,
+            Some(sites) => sites.len(),
+            None => 0,
+        };
+        let bucket = if call_sites == 0 { &mut uncalled } else { &mut local_only };
+        bucket.entry(sym.file.clone()).or_default().push(LocalSymbol {
+            name: sym.name.clone(),
+            kind: sym.kind,
+            line_range: sym.line_range,
+            call_sites,
+        });
+    }
+
+    let into_groups = |by_file: std::collections::BTreeMap<String, Vec<LocalSymbol>>| {
+        by_file
+            .into_iter()
+            .map(|(file, mut symbols)| {
+                symbols.sort_by(|a, b| {
+                    a.line_range.cmp(&b.line_range).then_with(|| a.name.cmp(&b.name))
+                });
+                LocalSymbolGroup {
+                    file,
+                    count: symbols.len(),
+                    symbols,
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+    let local_only = into_groups(local_only);
+    let uncalled = into_groups(uncalled);
+    LocalOnlyReport {
+        local_only_count: local_only.iter().map(|g| g.count).sum(),
+        uncalled_count: uncalled.iter().map(|g| g.count).sum(),
+        local_only,
+        uncalled,
+    }
+}
+
+/// Called by the runtime rather than from indexed code.
+fn is_entry_point(sym: &Symbol) -> bool {
+    sym.name == "main"
+        || (sym.language == Language::Python
+            && sym.name.len() > 4
+            && sym.name.starts_with("__")
+            && sym.name.ends_with("__"))
+}
+
+/// Cyclomatic complexity of each function and method in `symbols` (all from
+/// one file): one plus the branch points inside its byte range. A nested
+/// function's branches count towards its parent too.
+fn file_complexity(root: &Path, symbols: &[Symbol]) -> Option<Vec<(String, f64)>> {
+    let functions: Vec<&Symbol> = symbols
+        .iter()
+        .filter(|s| matches!(s.kind, SymbolKind::Function | SymbolKind::Method))
+        .collect();
+    let first = functions.first()?;
+    let config = queries::get_language_config(first.language)?;
+    let source = read_source(root, &first.file, first.language).ok()?;
+    let mut parser = tree_sitter::Parser::new();
+    parser.set_language(&config.language).ok()?;
+    let tree = parser.parse(&source, None)?;
+
+    let mut branches = Vec::new();
+    collect_branch_points(tree.root_node(), source.as_bytes(), &mut branches);
+    Some(
+        functions
+            .iter()
+            .map(|f| {
+                let (start, end) = f.byte_range;
+                let inside = branches.iter().filter(|&&at| start <= at && at < end).count();
+                (f.name.clone(), (1 + inside) as f64)
+            })
+            .collect(),
+    )
+}
+
+/// Start bytes of the nodes that add a path through a function: conditionals,
+/// loops, match/switch arms, catch clauses, and short-circuit operators. The
+/// node kinds cover the Rust, Python, JS/TS, and Go grammars.
+fn collect_branch_points(node: tree_sitter::Node, source: &[u8], out: &mut Vec<usize>) {
+    let is_branch = match node.kind() {
+        "if_expression" | "if_statement" | "elif_clause" | "if_clause" | "while_expression"
+        | "while_statement" | "do_statement" | "for_expression" | "for_statement"
+        | "for_in_statement" | "for_in_clause" | "match_arm" | "case_clause"
+        | "switch_case" | "expression_case" | "type_case" | "communication_case"
+        | "catch_clause" | "except_clause" | "conditional_expression"
+        | "ternary_expression" | "boolean_operator" => true,
+        "binary_expression" => node
+            .child_by_field_name("operator")
+            .and_then(|op| op.utf8_text(source).ok())
+            .is_some_and(|op| matches!(op, "&&" | "||" | "??")),
+        _ => false,
+    };
+    if is_branch {
+        out.push(node.start_byte());
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_branch_points(child, source, out);
+    }
+}
+
+fn is_test_path(path: &str) -> bool {
+    path.contains("test") || path.ends_with("_tests.rs")
+}
+
+pub fn is_test_symbol(sym: &Symbol) -> bool {
     match sym.language {
         Language::Rust => {
             sym.name.starts_with("test") || sym.file.contains("/tests/")
@@ -369,12 +2253,266 @@ fn is_test_symbol(sym: &Symbol) -> bool {
     }
 }
 
-#[derive(Debug, serde::Serialize)]
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub struct TraitCompliance {
+    /// Trait as written in the impl header, e.g. `From<u8>`.
+    #[serde(rename = "trait")]
+    pub trait_name: String,
+    pub impl_file: String,
+    pub impl_line: usize,
+    /// Whether the trait is defined in the project. Without its definition
+    /// (e.g. `Iterator`) the required methods are unknown and `missing` is
+    /// empty.
+    pub trait_found: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trait_file: Option<String>,
+    /// Trait methods without a default body.
+    pub required_methods: Vec<String>,
+    /// Trait methods with a default body.
+    pub provided_methods: Vec<String>,
+    /// Methods defined in the impl block.
+    pub implemented: Vec<String>,
+    /// Required methods the impl block doesn't define.
+    pub missing: Vec<String>,
+}
+
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub struct ComplianceReport {
+    #[serde(rename = "type")]
+    pub type_name: String,
+    pub file: String,
+    /// One entry per `impl Trait for Type` block, by file and line.
+    pub traits: Vec<TraitCompliance>,
+    /// True when no trait with a known definition is missing a method.
+    pub compliant: bool,
+    /// Rust files mentioning the type that were parsed for impl blocks.
+    pub files_scanned: usize,
+}
+
+/// Methods a trait declares, split by whether they have a default body.
+#[derive(Debug, Clone)]
+pub struct TraitMethods {
+    pub file: String,
+    pub required: Vec<String>,
+    pub provided: Vec<String>,
+}
+
+/// Every trait a Rust type implements, checked against the trait's
+/// definition. Impl blocks are found by parsing each Rust file that mentions
+/// the type; generics and paths are ignored when matching, so `impl<T> Tr for
+/// Foo<T>` counts for `Foo`. Impls of a same-named type elsewhere also match.
+pub fn interface_compliance(
+    root: &Path,
+    file_tree: &Arc<FileTree>,
+    symbol_table: &Arc<SymbolTable>,
+    type_name: &str,
+    file: &str,
+) -> Result<ComplianceReport, String> {
+    let sym = symbol_table
+        .get(file, type_name)
+        .ok_or_else(|| format!("Symbol '{}' not found in '{}'", type_name, file))?;
+    if sym.language != Language::Rust {
+        return Err(format!(
+            "Interface compliance is only supported for Rust, not {:?}",
+            sym.language
+        ));
+    }
+    let config = queries::get_language_config(Language::Rust)
+        .ok_or_else(|| "No Rust grammar available".to_string())?;
+
+    let mut paths: Vec<String> = file_tree
+        .files
+        .iter()
+        .filter(|e| e.value().language == Language::Rust)
+        .map(|e| e.key().clone())
+        .collect();
+    paths.sort();
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&config.language)
+        .map_err(|e| format!("Failed to load Rust grammar: {}", e))?;
+
+    let mut trait_cache: std::collections::HashMap<String, Option<TraitMethods>> =
+        std::collections::HashMap::new();
+    let mut traits = Vec::new();
+    let mut files_scanned = 0;
+    for rel_path in &paths {
+        let Ok(source) = encoding::read_to_string(root.join(rel_path)) else {
+            continue;
+        };
+        if !source.contains(type_name) {
+            continue;
+        }
+        let Some(tree) = parser.parse(&source, None) else {
+            continue;
+        };
+        files_scanned += 1;
+
+        let mut impls = Vec::new();
+        collect_nodes(tree.root_node(), "impl_item", &mut impls);
+        for node in impls {
+            let (Some(trait_node), Some(type_node)) =
+                (node.child_by_field_name("trait"), node.child_by_field_name("type"))
+            else {
+                continue;
+            };
+            if base_type_name(type_node, &source) != Some(type_name) {
+                continue;
+            }
+            let Some(trait_base) = base_type_name(trait_node, &source) else {
+                continue;
+            };
+
+            let mut implemented: Vec<String> = node
+                .child_by_field_name("body")
+                .map(|body| function_names(body, "function_item", &source))
+                .unwrap_or_default();
+            implemented.sort();
+
+            let methods = trait_cache
+                .entry(trait_base.to_string())
+                .or_insert_with(|| list_trait_methods(root, symbol_table, trait_base))
+                .clone();
+            let (trait_file, required, provided) = match methods {
+                Some(m) => (Some(m.file), m.required, m.provided),
+                None => (None, Vec::new(), Vec::new()),
+            };
+            let missing = required
+                .iter()
+                .filter(|m| !implemented.contains(m))
+                .cloned()
+                .collect();
+            traits.push(TraitCompliance {
+                trait_name: trait_node.utf8_text(source.as_bytes()).unwrap_or("").to_string(),
+                impl_file: rel_path.clone(),
+                impl_line: node.start_position().row + 1,
+                trait_found: trait_file.is_some(),
+                trait_file,
+                required_methods: required,
+                provided_methods: provided,
+                implemented,
+                missing,
+            });
+        }
+    }
+
+    Ok(ComplianceReport {
+        type_name: type_name.to_string(),
+        file: file.to_string(),
+        compliant: traits.iter().all(|t| t.missing.is_empty()),
+        traits,
+        files_scanned,
+    })
+}
+
+/// Methods declared by the Rust trait `trait_name`, read from its definition.
+/// With several same-named traits, the one in the first file by path wins.
+/// `None` when the trait is not defined in the project.
+pub fn list_trait_methods(
+    root: &Path,
+    symbol_table: &SymbolTable,
+    trait_name: &str,
+) -> Option<TraitMethods> {
+    let mut candidates: Vec<Symbol> = symbol_table
+        .by_name
+        .get(trait_name)?
+        .iter()
+        .filter_map(|key| symbol_table.symbols.get(key).map(|s| s.clone()))
+        .filter(|s| s.kind == SymbolKind::Trait && s.language == Language::Rust)
+        .collect();
+    candidates.sort_by(|a, b| a.file.cmp(&b.file));
+    let sym = candidates.into_iter().next()?;
+
+    let config = queries::get_language_config(Language::Rust)?;
+    let source = encoding::read_to_string(root.join(&sym.file)).ok()?;
+    let mut parser = tree_sitter::Parser::new();
+    parser.set_language(&config.language).ok()?;
+    let tree = parser.parse(&source, None)?;
+
+    let mut trait_items = Vec::new();
+    collect_nodes(tree.root_node(), "trait_item", &mut trait_items);
+    let node = trait_items
+        .into_iter()
+        .find(|n| n.start_byte() == sym.byte_range.0)?;
+    let body = node.child_by_field_name("body")?;
+    Some(TraitMethods {
+        file: sym.file,
+        required: function_names(body, "function_signature_item", &source),
+        provided: function_names(body, "function_item", &source),
+    })
+}
+
+/// All nodes of `kind` under `node`, not descending into matches.
+fn collect_nodes<'t>(
+    node: tree_sitter::Node<'t>,
+    kind: &str,
+    out: &mut Vec<tree_sitter::Node<'t>>,
+) {
+    if node.kind() == kind {
+        out.push(node);
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_nodes(child, kind, out);
+    }
+}
+
+/// Names of the direct `kind` children of a declaration list.
+fn function_names(body: tree_sitter::Node, kind: &str, source: &str) -> Vec<String> {
+    let mut cursor = body.walk();
+    body.named_children(&mut cursor)
+        .filter(|n| n.kind() == kind)
+        .filter_map(|n| n.child_by_field_name("name"))
+        .filter_map(|n| n.utf8_text(source.as_bytes()).ok())
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// Bare name of a type or trait node: `a::Foo<T>` and `&'a Foo` give `Foo`.
+fn base_type_name<'s>(node: tree_sitter::Node, source: &'s str) -> Option<&'s str> {
+    match node.kind() {
+        "type_identifier" => node.utf8_text(source.as_bytes()).ok(),
+        "generic_type" | "reference_type" => {
+            base_type_name(node.child_by_field_name("type")?, source)
+        }
+        "scoped_type_identifier" => base_type_name(node.child_by_field_name("name")?, source),
+        _ => None,
+    }
+}
+
+#[derive(Debug, serde::Serialize, ToSchema)]
 pub struct TestInfo {
     pub name: String,
     pub file: String,
     pub line: usize,
     pub signature: String,
+    /// Test source, present when requested with `include_source=true`.
+    /// Cut at `max_bytes` with a trailing `...`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+/// Which bindings `list_variables` reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariableScope {
+    /// Bindings from the language's variables query, one entry per name
+    /// (default behavior).
+    TopLevel,
+    /// Also closure/lambda parameters, and every binding in a nested scope
+    /// reported separately with its `scope_path`.
+    All,
+}
+
+impl VariableScope {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "top_level" => Some(VariableScope::TopLevel),
+            "all" => Some(VariableScope::All),
+            _ => None,
+        }
+    }
 }
 
 /// List local variables within a function using tree-sitter queries.
@@ -384,6 +2522,7 @@ pub fn list_variables(
     symbol_table: &Arc<SymbolTable>,
     function_name: &str,
     file: &str,
+    scope: VariableScope,
 ) -> Result<Vec<VariableInfo>, String> {
     let sym = symbol_table
         .get(file, function_name)
@@ -395,7 +2534,7 @@ pub fn list_variables(
     let end = sym.byte_range.1.min(source.len());
 
     let variables = if sym.language.has_tree_sitter_support() {
-        list_variables_ast(&source, sym.language, start, end, function_name)
+        list_variables_ast(&source, sym.language, start, end, function_name, scope)
     } else {
         list_variables_regex(&source[start..end], sym.language, function_name)
     };
@@ -403,6 +2542,109 @@ pub fn list_variables(
     Ok(variables)
 }
 
+/// Result of a file-level variable listing.
+pub enum FileVariables {
+    Supported(Vec<VariableInfo>),
+    /// The language has no usable variables query; the reason says why.
+    Unsupported(String),
+}
+
+/// List bindings at file scope: run the language's variables query over the
+/// whole file and drop captures that fall inside a function, type, or module
+/// body. Useful for scripts and module-level Python where there is no
+/// enclosing function to name.
+pub fn list_file_variables(
+    root: &Path,
+    file_tree: &Arc<FileTree>,
+    symbol_table: &Arc<SymbolTable>,
+    file: &str,
+) -> Result<FileVariables, String> {
+    let language = file_tree
+        .get(file)
+        .ok_or_else(|| format!("File '{}' not found", file))?
+        .language;
+
+    let config = match queries::get_language_config(language) {
+        Some(c) if !c.variables_query.trim().is_empty() => c,
+        _ => {
+            return Ok(FileVariables::Unsupported(format!(
+                "no variables query for {:?}",
+                language
+            )));
+        }
+    };
+    let query = match tree_sitter::Query::new(&config.language, config.variables_query) {
+        Ok(q) => q,
+        Err(e) => {
+            return Ok(FileVariables::Unsupported(format!(
+                "variables query for {:?} does not compile: {}",
+                language, e
+            )));
+        }
+    };
+
+    let source = read_source(root, file, language)?;
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&config.language)
+        .map_err(|e| format!("Failed to load grammar for {:?}: {}", language, e))?;
+    let tree = parser
+        .parse(&source, None)
+        .ok_or_else(|| format!("Failed to parse '{}'", file))?;
+
+    // Bodies whose bindings are not file-level. Variable and constant
+    // symbols are left out: their ranges cover the very bindings we want.
+    let bodies: Vec<(usize, usize)> = symbol_table
+        .list_by_file(file)
+        .into_iter()
+        .filter(|s| {
+            matches!(
+                s.kind,
+                SymbolKind::Function
+                    | SymbolKind::Method
+                    | SymbolKind::Class
+                    | SymbolKind::Struct
+                    | SymbolKind::Enum
+                    | SymbolKind::Trait
+                    | SymbolKind::Interface
+                    | SymbolKind::Module
+            )
+        })
+        .map(|s| s.byte_range)
+        .collect();
+
+    let var_name_idx = query.capture_names().iter().position(|n| *n == "var.name");
+    let mut cursor = tree_sitter::QueryCursor::new();
+    let mut matches = cursor.matches(&query, tree.root_node(), source.as_bytes());
+    let mut seen = std::collections::HashSet::new();
+    let mut variables = Vec::new();
+
+    while let Some(m) = matches.next() {
+        for cap in m.captures {
+            if Some(cap.index as usize) != var_name_idx {
+                continue;
+            }
+            let at = cap.node.start_byte();
+            if bodies.iter().any(|&(start, end)| start <= at && at < end) {
+                continue;
+            }
+            let text = cap.node.utf8_text(source.as_bytes()).unwrap_or("");
+            if text.is_empty() || text == "_" {
+                continue;
+            }
+            if seen.insert(text.to_string()) {
+                variables.push(VariableInfo {
+                    name: text.to_string(),
+                    function: String::new(),
+                    scope_path: None,
+                });
+            }
+        }
+    }
+
+    Ok(FileVariables::Supported(variables))
+}
+
 /// AST-aware variable extraction: parse the function body slice, run the
 /// variables query, and collect all @var.name captures within the byte range.
 fn list_variables_ast(
@@ -411,6 +2653,7 @@ fn list_variables_ast(
     fn_start: usize,
     fn_end: usize,
     function_name: &str,
+    scope: VariableScope,
 ) -> Vec<VariableInfo> {
     let config = match queries::get_language_config(language) {
         Some(c) => c,
@@ -431,25 +2674,49 @@ fn list_variables_ast(
         Ok(q) => q,
         Err(_) => return list_variables_regex(&source[fn_start..fn_end], language, function_name),
     };
+    let mut queries = vec![query];
+    if scope == VariableScope::All && !config.nested_variables_query.is_empty() {
+        match tree_sitter::Query::new(&config.language, config.nested_variables_query) {
+            Ok(q) => queries.push(q),
+            Err(e) => tracing::debug!("Invalid nested variables query for {:?}: {}", language, e),
+        }
+    }
 
-    let capture_names: Vec<String> = query.capture_names().iter().map(|s| s.to_string()).collect();
-    let var_name_idx = capture_names.iter().position(|n| n == "var.name");
-
-    let mut cursor = tree_sitter::QueryCursor::new();
-    // Restrict matches to the function's byte range
-    cursor.set_byte_range(fn_start..fn_end);
-    let mut matches = cursor.matches(&query, tree.root_node(), source.as_bytes());
     let mut variables = Vec::new();
     let mut seen = std::collections::HashSet::new();
 
-    while let Some(m) = matches.next() {
-        for cap in m.captures {
-            if Some(cap.index as usize) == var_name_idx {
+    for query in &queries {
+        let capture_names: Vec<String> =
+            query.capture_names().iter().map(|s| s.to_string()).collect();
+        let var_name_idx = capture_names.iter().position(|n| n == "var.name");
+
+        let mut cursor = tree_sitter::QueryCursor::new();
+        // Restrict matches to the function's byte range
+        cursor.set_byte_range(fn_start..fn_end);
+        let mut matches = cursor.matches(query, tree.root_node(), source.as_bytes());
+
+        while let Some(m) = matches.next() {
+            for cap in m.captures {
+                if Some(cap.index as usize) != var_name_idx {
+                    continue;
+                }
                 let text = cap.node.utf8_text(source.as_bytes()).unwrap_or("");
-                if !text.is_empty() && text != "self" && text != "_" && seen.insert(text.to_string()) {
+                if text.is_empty() || text == "self" || text == "_" {
+                    continue;
+                }
+                let scope_path = match scope {
+                    VariableScope::TopLevel => None,
+                    VariableScope::All => {
+                        Some(scope_path(cap.node, fn_start, fn_end, function_name))
+                    }
+                };
+                // Top-level mode keeps one entry per name; `all` keeps
+                // shadowed bindings in different scopes apart.
+                if seen.insert((text.to_string(), scope_path.clone())) {
                     variables.push(VariableInfo {
                         name: text.to_string(),
                         function: function_name.to_string(),
+                        scope_path,
                     });
                 }
             }
@@ -459,6 +2726,45 @@ fn list_variables_ast(
     variables
 }
 
+/// Describe the lexical nesting of `node` within the function spanning
+/// `fn_start..fn_end`, e.g. `"process > closure > for"`.
+fn scope_path(node: tree_sitter::Node, fn_start: usize, fn_end: usize, function_name: &str) -> String {
+    let mut labels = Vec::new();
+    let mut current = node.parent();
+    while let Some(n) = current {
+        if n.start_byte() <= fn_start && n.end_byte() >= fn_end {
+            break;
+        }
+        let label = match n.kind() {
+            "closure_expression" | "lambda" | "arrow_function" | "func_literal"
+            | "function_expression" => Some("closure"),
+            "function_item" | "function_definition" | "function_declaration"
+            | "method_definition" => Some("fn"),
+            "for_expression" | "for_statement" | "for_in_statement" => Some("for"),
+            "while_expression" | "while_statement" | "loop_expression" => Some("loop"),
+            "if_expression" | "if_statement" | "if_let_expression" => Some("if"),
+            "match_arm" | "match_expression" | "switch_statement" => Some("match"),
+            // Bare `{ ... }` blocks; blocks owned by if/for/closures are
+            // already named by their parent.
+            "block" | "statement_block"
+                if n
+                    .parent()
+                    .is_some_and(|p| matches!(p.kind(), "block" | "expression_statement" | "statement_block")) =>
+            {
+                Some("block")
+            }
+            _ => None,
+        };
+        if let Some(label) = label {
+            labels.push(label);
+        }
+        current = n.parent();
+    }
+    labels.push(function_name);
+    labels.reverse();
+    labels.join(" > ")
+}
+
 /// Regex fallback for variable extraction.
 fn list_variables_regex(
     body: &str,
@@ -474,6 +2780,7 @@ fn list_variables_regex(
                 variables.push(VariableInfo {
                     name: cap[2].to_string(),
                     function: function_name.to_string(),
+                    scope_path: None,
                 });
             }
         }
@@ -485,6 +2792,7 @@ fn list_variables_regex(
                     variables.push(VariableInfo {
                         name,
                         function: function_name.to_string(),
+                        scope_path: None,
                     });
                 }
             }
@@ -495,6 +2803,7 @@ fn list_variables_regex(
                 variables.push(VariableInfo {
                     name: cap[1].to_string(),
                     function: function_name.to_string(),
+                    scope_path: None,
                 });
             }
         }
@@ -504,6 +2813,7 @@ fn list_variables_regex(
                 variables.push(VariableInfo {
                     name: cap[1].to_string(),
                     function: function_name.to_string(),
+                    scope_path: None,
                 });
             }
             let var_re = regex::Regex::new(r"var\s+(\w+)").unwrap();
@@ -511,6 +2821,7 @@ fn list_variables_regex(
                 variables.push(VariableInfo {
                     name: cap[1].to_string(),
                     function: function_name.to_string(),
+                    scope_path: None,
                 });
             }
         }
@@ -520,6 +2831,7 @@ fn list_variables_regex(
                 variables.push(VariableInfo {
                     name: cap[1].to_string(),
                     function: function_name.to_string(),
+                    scope_path: None,
                 });
             }
         }
@@ -533,8 +2845,263 @@ fn list_variables_regex(
     variables
 }
 
-#[derive(Debug, serde::Serialize)]
+#[derive(Debug, serde::Serialize, ToSchema)]
 pub struct VariableInfo {
     pub name: String,
+    /// Enclosing function; omitted for file-level bindings.
+    #[serde(skip_serializing_if = "String::is_empty")]
     pub function: String,
+    /// Lexical nesting within the function, e.g. `"outer > closure > for"`.
+    /// Only set for `scope=all`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope_path: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DEFAULT_MAX_FILE_SIZE;
+    use crate::index::walker;
+    use crate::server::state::temp_project;
+    use crate::symbols::symbol::{Symbol, SymbolKind};
+
+    #[test]
+    fn completion_ranks_by_references_then_kind() {
+        let table = SymbolTable::new();
+        table.insert(Symbol::for_tests("parse_args", SymbolKind::Function, "a.rs", 1));
+        table.insert(Symbol::for_tests("Parser", SymbolKind::Struct, "a.rs", 5));
+        table.insert(Symbol::for_tests("parse", SymbolKind::Function, "b.rs", 1));
+        table.insert(Symbol::for_tests("render", SymbolKind::Function, "b.rs", 9));
+        table.add_caller("parse_args", "main.rs", 3, "parse_args()");
+
+        let result = complete_symbols(&table, "PAR", 10);
+        let names: Vec<&str> = result.completions.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["parse_args", "Parser", "parse"]);
+        assert_eq!(result.total_matches, 3);
+        assert!(!result.truncated);
+    }
+
+    #[test]
+    fn completion_scan_is_capped() {
+        let table = SymbolTable::new();
+        let limit = 2;
+        let names = limit * SYMBOL_COMPLETE_SCAN_FACTOR + 5;
+        for i in 0..names {
+            table.insert(Symbol::for_tests(&format!("item_{:04}", i), SymbolKind::Function, "a.rs", i));
+        }
+
+        let result = complete_symbols(&table, "item", limit);
+        assert_eq!(result.completions.len(), limit);
+        assert_eq!(result.total_matches, limit * SYMBOL_COMPLETE_SCAN_FACTOR);
+        assert!(result.truncated);
+
+        let exact = complete_symbols(&table, "item_0000", limit);
+        assert_eq!(exact.total_matches, 1);
+        assert!(!exact.truncated);
+    }
+
+    /// `target` in src/lib.rs, called from src/a.rs (lines 2 and 4) and
+    /// src/b.rs (line 2). Only b.rs's call is in the reverse call graph, as
+    /// if indexing were still running.
+    fn partial_graph() -> (std::path::PathBuf, Arc<FileTree>, Arc<SymbolTable>) {
+        let dir = temp_project(&[
+            ("src/lib.rs", "pub fn target() {}\n"),
+            ("src/a.rs", "fn one() {\n    target();\n}\nfn two() { target(); }\n"),
+            ("src/b.rs", "fn three() {\n    target();\n}\n"),
+        ]);
+        let file_tree = Arc::new(FileTree::new());
+        walker::scan_directory(&dir, &file_tree, DEFAULT_MAX_FILE_SIZE).unwrap();
+        let symbol_table = target_only();
+        symbol_table.add_caller("target", "src/b.rs", 2, "    target();");
+        (dir, file_tree, symbol_table)
+    }
+
+    fn target_only() -> Arc<SymbolTable> {
+        let table = SymbolTable::new();
+        table.insert(Symbol::for_tests("target", SymbolKind::Function, "src/lib.rs", 1));
+        Arc::new(table)
+    }
+
+    fn callers(
+        dir: &Path,
+        file_tree: &Arc<FileTree>,
+        symbol_table: &Arc<SymbolTable>,
+        limit: usize,
+        graph_complete: bool,
+    ) -> (Vec<(String, usize)>, CallerSource) {
+        let found = find_callers(
+            dir,
+            file_tree,
+            symbol_table,
+            "target",
+            "src/lib.rs",
+            limit,
+            None,
+            false,
+            &CallerFilter::default(),
+            graph_complete,
+        )
+        .unwrap();
+        let sites = found.callers.into_iter().map(|c| (c.file, c.line)).collect();
+        (sites, found.source)
+    }
+
+    fn site(file: &str, line: usize) -> (String, usize) {
+        (file.to_string(), line)
+    }
+
+    #[test]
+    fn partial_graph_falls_back_to_a_scan() {
+        let (dir, file_tree, symbol_table) = partial_graph();
+        let (sites, source) = callers(&dir, &file_tree, &symbol_table, 10, false);
+        assert_eq!(source, CallerSource::Scanned);
+        assert_eq!(sites, [site("src/a.rs", 2), site("src/a.rs", 4), site("src/b.rs", 2)]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn partial_graph_is_used_when_it_fills_the_limit() {
+        let (dir, file_tree, symbol_table) = partial_graph();
+        let (sites, source) = callers(&dir, &file_tree, &symbol_table, 1, false);
+        assert_eq!(source, CallerSource::Cached);
+        assert_eq!(sites, [site("src/b.rs", 2)]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn complete_graph_is_trusted() {
+        let (dir, file_tree, symbol_table) = partial_graph();
+        let (sites, source) = callers(&dir, &file_tree, &symbol_table, 10, true);
+        assert_eq!(source, CallerSource::Cached);
+        assert_eq!(sites, [site("src/b.rs", 2)]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cached_callers_are_ordered_by_file_then_line() {
+        let (dir, file_tree, symbol_table) = partial_graph();
+        symbol_table.add_caller("target", "src/a.rs", 4, "fn two() { target(); }");
+        symbol_table.add_caller("target", "src/a.rs", 2, "    target();");
+        let (sites, source) = callers(&dir, &file_tree, &symbol_table, 10, true);
+        assert_eq!(source, CallerSource::Cached);
+        assert_eq!(sites, [site("src/a.rs", 2), site("src/a.rs", 4), site("src/b.rs", 2)]);
+        // Both paths agree once the graph has every call site.
+        let (scanned, _) = callers(&dir, &file_tree, &target_only(), 10, false);
+        assert_eq!(scanned, sites);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn split(name: &str) -> Vec<String> {
+        split_qualified_name(name)
+    }
+
+    #[test]
+    fn qualified_names_split_per_language() {
+        assert_eq!(split("crate::ops::repl::buffer_create"), ["ops", "repl", "buffer_create"]);
+        assert_eq!(split("super::self::Foo"), ["Foo"]);
+        assert_eq!(split("pkg.models.User.save"), ["pkg", "models", "User", "save"]);
+        assert_eq!(split("ns::Widget::draw"), ["ns", "Widget", "draw"]);
+        assert_eq!(split("Widget#render"), ["Widget", "render"]);
+        assert_eq!(split(" Vec<String>::new(x, y) "), ["Vec", "new"]);
+        assert_eq!(split("HashMap<K, Vec<V>>::insert"), ["HashMap", "insert"]);
+        assert_eq!(split("plain"), ["plain"]);
+        assert!(split("::").is_empty());
+    }
+
+    fn symbol_in(name: &str, kind: SymbolKind, file: &str, line: usize, parent: Option<&str>) -> Symbol {
+        let mut sym = Symbol::for_tests(name, kind, file, line);
+        sym.parent = parent.map(str::to_string);
+        sym
+    }
+
+    fn ranked(table: &SymbolTable, name: &str) -> Vec<(String, f64)> {
+        resolve_qualified_name(table, name, 10)
+            .into_iter()
+            .map(|c| (c.symbol.file, c.score))
+            .collect()
+    }
+
+    #[test]
+    fn rust_path_prefers_the_module_it_names() {
+        let table = SymbolTable::new();
+        table.insert(symbol_in("buffer_create", SymbolKind::Function, "src/other/buf.rs", 3, None));
+        table.insert(symbol_in("buffer_create", SymbolKind::Function, "src/ops/repl.rs", 40, None));
+
+        let candidates = resolve_qualified_name(&table, "crate::ops::repl::buffer_create", 10);
+        assert_eq!(candidates.len(), 2);
+        let best = &candidates[0];
+        assert_eq!(best.symbol.file, "src/ops/repl.rs");
+        assert_eq!(best.score, 1.0);
+        assert_eq!(best.matched_name, "buffer_create");
+        let sources: Vec<_> = best.matched.iter().map(|m| (m.segment.as_str(), m.matched_by)).collect();
+        assert_eq!(
+            sources,
+            [
+                ("buffer_create", SegmentSource::Name),
+                ("ops", SegmentSource::Path),
+                ("repl", SegmentSource::Path),
+            ]
+        );
+        assert!(best.unmatched.is_empty());
+        assert_eq!(candidates[1].unmatched, ["ops", "repl"]);
+        assert!((candidates[1].score - 1.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn python_dotted_name_matches_parent_and_module() {
+        let table = SymbolTable::new();
+        table.insert(symbol_in("save", SymbolKind::Method, "pkg/models.py", 10, Some("User")));
+        table.insert(symbol_in("save", SymbolKind::Method, "pkg/orders.py", 5, Some("Order")));
+
+        let candidates = resolve_qualified_name(&table, "pkg.models.User.save", 10);
+        assert_eq!(candidates[0].symbol.file, "pkg/models.py");
+        assert_eq!(candidates[0].score, 1.0);
+        assert!(candidates[0]
+            .matched
+            .iter()
+            .any(|m| m.segment == "User" && m.matched_by == SegmentSource::Parent));
+        // "pkg" is in the other file's path too.
+        assert_eq!(candidates[1].symbol.file, "pkg/orders.py");
+        assert_eq!(candidates[1].unmatched, ["models", "User"]);
+        assert_eq!(candidates[1].score, 0.5);
+    }
+
+    #[test]
+    fn cpp_scoped_name_uses_namespace_directory_and_class() {
+        let table = SymbolTable::new();
+        table.insert(symbol_in("draw", SymbolKind::Method, "src/ns/widget.cpp", 7, Some("Widget")));
+        table.insert(symbol_in("draw", SymbolKind::Method, "src/gfx/canvas.cpp", 2, Some("Canvas")));
+
+        assert_eq!(
+            ranked(&table, "ns::Widget::draw"),
+            [("src/ns/widget.cpp".to_string(), 1.0), ("src/gfx/canvas.cpp".to_string(), 1.0 / 3.0)]
+        );
+    }
+
+    #[test]
+    fn names_indexed_qualified_match_the_longer_tail() {
+        let table = SymbolTable::new();
+        table.insert(symbol_in("Widget::draw", SymbolKind::Method, "src/widget.cpp", 1, None));
+
+        let candidates = resolve_qualified_name(&table, "ns::Widget::draw", 10);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].matched_name, "Widget::draw");
+        assert_eq!(candidates[0].unmatched, ["ns"]);
+        assert!((candidates[0].score - 2.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn imports_are_skipped_and_ties_go_by_file_then_line() {
+        let table = SymbolTable::new();
+        table.insert(symbol_in("Config", SymbolKind::Import, "src/main.rs", 1, None));
+        table.insert(symbol_in("Config", SymbolKind::Struct, "src/b.rs", 9, None));
+        table.insert(symbol_in("Config", SymbolKind::Struct, "src/a.rs", 4, None));
+
+        assert_eq!(
+            ranked(&table, "Config"),
+            [("src/a.rs".to_string(), 1.0), ("src/b.rs".to_string(), 1.0)]
+        );
+        assert!(ranked(&table, "missing::Thing").is_empty());
+        assert_eq!(resolve_qualified_name(&table, "Config", 1).len(), 1);
+    }
 }
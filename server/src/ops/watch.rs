@@ -0,0 +1,143 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use notify_debouncer_mini::{new_debouncer, DebouncedEventKind};
+use tracing::{debug, warn};
+
+use crate::index::file_tree::FileTree;
+use crate::index::watcher;
+use crate::symbols::parser::ExtractionErrors;
+use crate::symbols::SymbolTable;
+
+/// Debounce window for per-file watches. Shorter than the project watcher so
+/// an agent editing one file sees its symbols refresh quickly.
+const FILE_WATCH_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Keeps a single-file watch alive. Drop it to stop watching.
+pub struct FileWatch {
+    _debouncer: notify_debouncer_mini::Debouncer<notify::RecommendedWatcher>,
+    _slot: WatchSlot,
+}
+
+/// A place under the server-wide per-file watch cap, given back on drop, so
+/// unwatching and project eviction both free it.
+pub struct WatchSlot(Arc<AtomicUsize>);
+
+impl WatchSlot {
+    /// Take a slot from `count` unless `max` are already taken. The check
+    /// and the increment are one atomic step, so concurrent requests
+    /// cannot overshoot the cap.
+    pub fn reserve(count: &Arc<AtomicUsize>, max: usize) -> Option<Self> {
+        count
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| (n < max).then_some(n + 1))
+            .ok()
+            .map(|_| WatchSlot(count.clone()))
+    }
+}
+
+impl Drop for WatchSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Watch one file and re-index only that file when it changes.
+///
+/// The watch is placed on the file's parent directory (non-recursively) and
+/// filtered to the file itself: editors commonly save by writing a temp file
+/// and renaming it over the original, which would orphan a watch on the
+/// file's own inode.
+pub fn watch_single_file(
+    root: &Path,
+    rel_path: &str,
+    symbol_table: Arc<SymbolTable>,
+    file_tree: Arc<FileTree>,
+    errors: Arc<ExtractionErrors>,
+    max_file_size: u64,
+    slot: WatchSlot,
+) -> Result<FileWatch, String> {
+    let abs_path = root.join(rel_path);
+    if !abs_path.is_file() {
+        return Err(format!("File '{}' not found", rel_path));
+    }
+    let parent = abs_path
+        .parent()
+        .ok_or_else(|| format!("File '{}' has no parent directory", rel_path))?
+        .to_path_buf();
+
+    let root_buf = root.to_path_buf();
+    let rel = rel_path.to_string();
+    let target = abs_path.clone();
+
+    let mut debouncer = new_debouncer(
+        FILE_WATCH_DEBOUNCE,
+        move |result: Result<Vec<notify_debouncer_mini::DebouncedEvent>, notify::Error>| {
+            match result {
+                Ok(events) => {
+                    let touched = events
+                        .iter()
+                        .any(|e| e.kind == DebouncedEventKind::Any && e.path == target);
+                    if touched {
+                        debug!("Per-file watch: re-indexing {}", rel);
+                        watcher::re_index_file(
+                            &root_buf,
+                            &file_tree,
+                            &symbol_table,
+                            &errors,
+                            max_file_size,
+                            &rel,
+                        );
+                    }
+                }
+                Err(e) => {
+                    warn!("Per-file watcher error for {}: {}", rel, e);
+                }
+            }
+        },
+    )
+    .map_err(|e| format!("Failed to create watcher: {}", e))?;
+
+    debouncer
+        .watcher()
+        .watch(&parent, notify::RecursiveMode::NonRecursive)
+        .map_err(|e| format!("Failed to watch '{}': {}", rel_path, e))?;
+
+    Ok(FileWatch {
+        _debouncer: debouncer,
+        _slot: slot,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slots_stop_at_the_cap_and_return_on_drop() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let first = WatchSlot::reserve(&count, 2).unwrap();
+        let _second = WatchSlot::reserve(&count, 2).unwrap();
+        assert!(WatchSlot::reserve(&count, 2).is_none());
+        assert_eq!(count.load(Ordering::Acquire), 2);
+
+        drop(first);
+        assert_eq!(count.load(Ordering::Acquire), 1);
+        assert!(WatchSlot::reserve(&count, 2).is_some());
+        assert_eq!(count.load(Ordering::Acquire), 1);
+    }
+
+    #[test]
+    fn concurrent_reservations_never_exceed_the_cap() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let held: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..16)
+                .map(|_| scope.spawn(|| WatchSlot::reserve(&count, 5)))
+                .collect();
+            handles.into_iter().filter_map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(held.len(), 5);
+        assert_eq!(count.load(Ordering::Acquire), 5);
+    }
+}
@@ -0,0 +1,92 @@
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderValue};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde_json::{json, Value};
+
+use crate::server::errors::AppError;
+use crate::server::state::AppState;
+
+/// Rough token estimate for `bytes` of response text (~4 chars per token),
+/// the same ratio `/context_budget` uses for stored state.
+pub fn estimate_tokens(bytes: usize) -> usize {
+    bytes.div_ceil(4)
+}
+
+/// Middleware counting what each session has been sent. JSON responses to a
+/// known session add their estimated tokens to the session's running total
+/// and to the history entry the handler recorded. Once the total reaches the
+/// configured budget, JSON object responses carry a `budget_warning`.
+/// Requests without a session, and non-JSON responses, pass through.
+pub async fn track_response_tokens(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let sid = req
+        .headers()
+        .get("x-session-id")
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string());
+    let Some((sid, history_len)) = sid.and_then(|sid| {
+        let len = state.inner.sessions.get(&sid)?.history.len();
+        Some((sid, len))
+    }) else {
+        return next.run(req).await;
+    };
+
+    let response = next.run(req).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"application/json"));
+    if !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => return AppError::Internal(e.to_string()).into_response(),
+    };
+    let tokens = estimate_tokens(bytes.len());
+    let Some(total) = state.inner.sessions.get_mut(&sid).map(|mut session| {
+        session.response_tokens += tokens;
+        // Attribute the estimate to the entry this request recorded, if any.
+        if session.history.len() > history_len
+            && let Some(entry) = session.history.last_mut()
+        {
+            entry.estimated_tokens = Some(tokens);
+        }
+        session.response_tokens
+    }) else {
+        // Session ended by this request.
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+
+    let budget = state.inner.response_token_budget;
+    if budget == 0 || total < budget {
+        return Response::from_parts(parts, Body::from(bytes));
+    }
+    let Ok(Value::Object(mut body)) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    body.insert(
+        "budget_warning".to_string(),
+        json!({
+            "estimated_response_tokens": total,
+            "response_token_budget": budget,
+            "message": format!(
+                "This session has been sent about {} tokens, over its budget of {}; \
+                 compact or summarize context, then POST /history/compact to reset",
+                total, budget
+            ),
+        }),
+    );
+    let bytes = serde_json::to_vec(&body).unwrap_or_default();
+    parts
+        .headers
+        .insert(header::CONTENT_LENGTH, HeaderValue::from(bytes.len()));
+    Response::from_parts(parts, Body::from(bytes))
+}
@@ -0,0 +1,128 @@
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use dashmap::DashMap;
+use tracing::info;
+
+use crate::server::request_log::RequestId;
+use crate::server::state::AppState;
+
+/// Flag a long-running operation polls to stop early. Clones share it.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+tokio::task_local! {
+    static REQUEST_TOKEN: CancelToken;
+}
+
+thread_local! {
+    static THREAD_TOKEN: RefCell<Option<CancelToken>> = const { RefCell::new(None) };
+}
+
+/// The token of the request being served: the request task's own in async
+/// code, or the one `with_token` installed on a blocking thread. Outside a
+/// request it is never cancelled.
+pub fn current() -> CancelToken {
+    REQUEST_TOKEN
+        .try_with(|token| token.clone())
+        .ok()
+        .or_else(|| THREAD_TOKEN.with(|token| token.borrow().clone()))
+        .unwrap_or_default()
+}
+
+/// Run `f` with `token` as this thread's `current` token.
+pub fn with_token<T>(token: CancelToken, f: impl FnOnce() -> T) -> T {
+    let previous = THREAD_TOKEN.with(|t| t.replace(Some(token)));
+    let result = f();
+    THREAD_TOKEN.with(|t| *t.borrow_mut() = previous);
+    result
+}
+
+/// Tokens of the requests in flight, by request id.
+#[derive(Default)]
+pub struct CancelRegistry {
+    in_flight: DashMap<String, CancelToken>,
+}
+
+impl CancelRegistry {
+    fn register(&self, request_id: &str) -> CancelToken {
+        let token = CancelToken::default();
+        self.in_flight.insert(request_id.to_string(), token.clone());
+        token
+    }
+
+    /// Forget `request_id`, unless a later request reused the id.
+    fn remove(&self, request_id: &str, token: &CancelToken) {
+        self.in_flight
+            .remove_if(request_id, |_, t| Arc::ptr_eq(&t.0, &token.0));
+    }
+
+    /// Cancel the in-flight request `request_id`. Returns false when no
+    /// such request is running.
+    pub fn cancel(&self, request_id: &str) -> bool {
+        match self.in_flight.get(request_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Unregisters a request when its handler finishes, and cancels it when the
+/// handler future is dropped first (the client disconnected).
+struct InFlight<'a> {
+    registry: &'a CancelRegistry,
+    request_id: String,
+    token: CancelToken,
+    completed: bool,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        if !self.completed {
+            self.token.cancel();
+            info!(request_id = %self.request_id, "client went away; request cancelled");
+        }
+        self.registry.remove(&self.request_id, &self.token);
+    }
+}
+
+/// Middleware giving each request a cancellation token, registered under
+/// its request id for `/admin/cancel/{request_id}` and made `current` for
+/// the handler. Must run inside `log_requests`, which assigns the id.
+pub async fn track_cancellation(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(request_id) = req.extensions().get::<RequestId>().map(|id| id.0.clone()) else {
+        return next.run(req).await;
+    };
+    let registry = &state.inner.cancellations;
+    let token = registry.register(&request_id);
+    let mut guard = InFlight {
+        registry,
+        request_id,
+        token: token.clone(),
+        completed: false,
+    };
+    let response = REQUEST_TOKEN.scope(token, next.run(req)).await;
+    guard.completed = true;
+    response
+}
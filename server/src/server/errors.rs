@@ -1,42 +1,135 @@
+use std::collections::BTreeMap;
+
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use axum::Json;
-use serde_json::json;
+use serde::Serialize;
 use thiserror::Error;
+use utoipa::openapi::{ContentBuilder, Ref, RefOr, ResponseBuilder};
+use utoipa::{IntoResponses, ToSchema};
 
 #[derive(Debug, Error)]
 pub enum AppError {
     #[error("Not found: {0}")]
     NotFound(String),
 
+    /// A 404 that also lists close matches for what was asked for.
+    #[error("Not found: {message}")]
+    NotFoundWithSuggestions {
+        message: String,
+        suggestions: Vec<String>,
+    },
+
     #[error("Bad request: {0}")]
     BadRequest(String),
 
+    /// A 400 for a name that matched several symbols, listing them.
+    #[error("Bad request: {message}")]
+    Ambiguous {
+        message: String,
+        candidates: Vec<Candidate>,
+    },
+
+    /// The operation is turned off by the server's configuration.
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
     #[error("Gone: {0}")]
     Gone(String),
 
+    #[error("Timed out: {0}")]
+    Timeout(String),
+
     #[error("Internal error: {0}")]
     Internal(String),
 }
 
+/// JSON body returned for every error response.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorBody {
+    pub error: String,
+    pub status: u16,
+    /// Close matches for a missing symbol or file, to try next.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<String>,
+    /// Symbols an ambiguous name could mean; repeat the request with one.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub candidates: Vec<Candidate>,
+}
+
+/// One of the symbols an ambiguous name matched.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Candidate {
+    pub name: String,
+    pub line_range: (usize, usize),
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, message) = match &self {
-            AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg.clone()),
-            AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
-            AppError::Gone(msg) => (StatusCode::GONE, msg.clone()),
-            AppError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg.clone()),
+        let mut candidates = Vec::new();
+        let (status, message, suggestions) = match self {
+            AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg, Vec::new()),
+            AppError::NotFoundWithSuggestions {
+                message,
+                suggestions,
+            } => (StatusCode::NOT_FOUND, message, suggestions),
+            AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg, Vec::new()),
+            AppError::Ambiguous {
+                message,
+                candidates: matched,
+            } => {
+                candidates = matched;
+                (StatusCode::BAD_REQUEST, message, Vec::new())
+            }
+            AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg, Vec::new()),
+            AppError::Gone(msg) => (StatusCode::GONE, msg, Vec::new()),
+            AppError::Timeout(msg) => (StatusCode::REQUEST_TIMEOUT, msg, Vec::new()),
+            AppError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg, Vec::new()),
         };
 
-        let body = json!({
-            "error": message,
-            "status": status.as_u16(),
-        });
+        // Emitted inside the request span, so it carries request_id/session.
+        tracing::warn!(status = status.as_u16(), error = %message, "request failed");
+
+        let body = ErrorBody {
+            error: message,
+            status: status.as_u16(),
+            suggestions,
+            candidates,
+        };
 
         (status, Json(body)).into_response()
     }
 }
 
+/// Documents every status `AppError` can map to, so handlers can list
+/// `AppError` in their `responses(...)` instead of repeating each code.
+impl IntoResponses for AppError {
+    fn responses() -> BTreeMap<String, RefOr<utoipa::openapi::Response>> {
+        [
+            (StatusCode::BAD_REQUEST, "Bad request"),
+            (StatusCode::FORBIDDEN, "Disabled by server configuration"),
+            (StatusCode::NOT_FOUND, "Not found"),
+            (StatusCode::REQUEST_TIMEOUT, "Timed out waiting"),
+            (StatusCode::GONE, "Project evicted; start a new session"),
+            (StatusCode::INTERNAL_SERVER_ERROR, "Internal error"),
+        ]
+        .into_iter()
+        .map(|(status, description)| {
+            let response = ResponseBuilder::new()
+                .description(description)
+                .content(
+                    "application/json",
+                    ContentBuilder::new()
+                        .schema(Some(Ref::from_schema_name("ErrorBody")))
+                        .build(),
+                )
+                .build();
+            (status.as_u16().to_string(), response.into())
+        })
+        .collect()
+    }
+}
+
 impl From<anyhow::Error> for AppError {
     fn from(err: anyhow::Error) -> Self {
         AppError::Internal(err.to_string())
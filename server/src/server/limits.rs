@@ -0,0 +1,166 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use dashmap::DashMap;
+use tokio::sync::Semaphore;
+
+use crate::server::cancel;
+use crate::server::errors::{AppError, ErrorBody};
+use crate::server::state::AppState;
+
+/// Bounds how much blocking work (grep, indexing, PDF conversion during
+/// indexing) runs at once, so one session can't starve the blocking pool.
+pub struct BlockingPool {
+    permits: Arc<Semaphore>,
+    max: usize,
+}
+
+impl BlockingPool {
+    pub fn new(max: usize) -> Self {
+        let max = max.max(1);
+        Self {
+            permits: Arc::new(Semaphore::new(max)),
+            max,
+        }
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    pub fn in_use(&self) -> usize {
+        self.max - self.permits.available_permits()
+    }
+
+    /// Wait for a permit, then run `f` on the blocking pool, where
+    /// `cancel::current` still returns the calling request's token.
+    pub async fn run<F, T>(&self, f: F) -> Result<T, AppError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        let token = cancel::current();
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            cancel::with_token(token, f)
+        })
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))
+    }
+
+    /// Hold a permit for work that manages its own blocking thread.
+    pub async fn acquire(&self) -> Option<tokio::sync::OwnedSemaphorePermit> {
+        self.permits.clone().acquire_owned().await.ok()
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+/// Per-session token bucket. `rate` tokens refill per second up to `burst`;
+/// each request costs one. A rate of zero disables limiting.
+pub struct RateLimiter {
+    buckets: DashMap<String, Bucket>,
+    rate: f64,
+    burst: f64,
+}
+
+impl RateLimiter {
+    pub fn new(rate: f64, burst: f64) -> Self {
+        Self {
+            buckets: DashMap::new(),
+            rate,
+            burst: burst.max(1.0),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.rate > 0.0
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    pub fn burst(&self) -> f64 {
+        self.burst
+    }
+
+    /// Take one token for `key`. On failure returns how long until one is
+    /// available.
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        if !self.enabled() {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let mut bucket = self.buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: self.burst,
+            last: now,
+        });
+        let elapsed = now.duration_since(bucket.last).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.last = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate))
+        }
+    }
+
+    /// Drop buckets whose session no longer exists.
+    pub fn retain(&self, keep: impl Fn(&str) -> bool) {
+        self.buckets.retain(|key, _| keep(key));
+    }
+
+    pub fn forget(&self, key: &str) {
+        self.buckets.remove(key);
+    }
+}
+
+/// Middleware enforcing the per-session rate limit. Requests without a
+/// session, or for unknown sessions, pass through to the handler.
+pub async fn rate_limit(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let sid = req
+        .headers()
+        .get("x-session-id")
+        .and_then(|v| v.to_str().ok())
+        .filter(|sid| state.inner.sessions.contains_key(*sid))
+        .map(|s| s.to_string());
+
+    if let Some(sid) = sid
+        && let Err(wait) = state.inner.rate_limiter.check(&sid)
+    {
+        let retry_after = (wait.as_secs_f64().ceil() as u64).max(1);
+        let body = ErrorBody {
+            error: format!(
+                "Rate limit exceeded for session '{}'; retry in {}s",
+                sid, retry_after
+            ),
+            status: StatusCode::TOO_MANY_REQUESTS.as_u16(),
+            suggestions: Vec::new(),
+            candidates: Vec::new(),
+        };
+        let mut response = (StatusCode::TOO_MANY_REQUESTS, Json(body)).into_response();
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        return response;
+    }
+
+    next.run(req).await
+}
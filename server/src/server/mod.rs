@@ -1,11 +1,19 @@
+pub mod budget;
+pub mod cancel;
 pub mod errors;
+pub mod limits;
+pub mod openapi;
+pub mod request_log;
 pub mod routes;
 pub mod session;
+pub mod shutdown;
 pub mod state;
 
 use axum::Router;
 use state::AppState;
 
-pub fn build_router(state: AppState) -> Router {
-    routes::build_routes(state)
+use crate::config::CompressionConfig;
+
+pub fn build_router(state: AppState, compression: &CompressionConfig) -> Router {
+    routes::build_routes(state, compression)
 }
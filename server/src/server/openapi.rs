@@ -0,0 +1,165 @@
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+use crate::index::file_entry::Language;
+use crate::ops::annotations::AnnotationData;
+use crate::ops::git::{GitDiffStat, GitFileDiff};
+use crate::ops::repl::BookmarkView;
+use crate::ops::session_ops::SessionInfo;
+use crate::ops::symbol_ops::{
+    CallerInfo, CoverageHint, EnclosingSymbol, GraphNeighbor, TestInfo, VariableInfo,
+};
+use crate::server::errors::ErrorBody;
+use crate::symbols::symbol::{Symbol, SymbolKind};
+
+/// Root OpenAPI document. Paths are registered by the router itself
+/// (see `routes::build_routes`), so only shared metadata lives here.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "coderlm",
+        description = "CoderLM REPL server: indexed structure, symbols, and content for code-aware agents."
+    ),
+    modifiers(&SessionHeader),
+    components(schemas(
+        ErrorBody,
+        Symbol,
+        SymbolKind,
+        Language,
+        CallerInfo,
+        EnclosingSymbol,
+        TestInfo,
+        CoverageHint,
+        GraphNeighbor,
+        VariableInfo,
+        AnnotationData,
+        SessionInfo,
+        BookmarkView,
+        GitFileDiff,
+        GitDiffStat,
+    )),
+    tags(
+        (name = "admin", description = "Health and server-wide views; no session required"),
+        (name = "sessions", description = "Session lifecycle"),
+        (name = "structure", description = "File tree and file annotations"),
+        (name = "index", description = "Index maintenance and per-file watches"),
+        (name = "symbols", description = "Symbol listing, search, and AST-aware lookups"),
+        (name = "content", description = "Peek, grep, and chunking"),
+        (name = "git", description = "Read-only git status and diffs for the project"),
+        (name = "history", description = "Command history and context budget"),
+        (name = "annotations", description = "Annotation persistence"),
+        (name = "repl", description = "Session buffers, bookmarks, variables, chunks, and subcall results"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Registers the `X-Session-Id` header as the `session` security scheme that
+/// session-scoped paths reference.
+struct SessionHeader;
+
+impl Modify for SessionHeader {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "session",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-Session-Id"))),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use axum::body::Body;
+    use axum::http::{Method, Request, StatusCode};
+    use axum::Router;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    use crate::config::CompressionConfig;
+    use crate::server::build_router;
+    use crate::server::state::AppState;
+
+    fn collect_refs<'a>(value: &'a Value, refs: &mut Vec<&'a str>) {
+        match value {
+            Value::Object(map) => {
+                if let Some(Value::String(reference)) = map.get("$ref") {
+                    refs.push(reference);
+                }
+                map.values().for_each(|v| collect_refs(v, refs));
+            }
+            Value::Array(items) => items.iter().for_each(|v| collect_refs(v, refs)),
+            _ => {}
+        }
+    }
+
+    fn router() -> Router {
+        let compression = CompressionConfig {
+            enabled: false,
+            min_bytes: 0,
+        };
+        build_router(AppState::for_tests(), &compression)
+    }
+
+    #[tokio::test]
+    async fn spec_parses_and_lists_every_route() {
+        let router = router();
+        let request = Request::get("/api/v1/openapi.json").body(Body::empty()).unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let spec: Value = serde_json::from_slice(&bytes).expect("served spec should parse");
+        assert!(spec["openapi"].as_str().unwrap().starts_with("3."));
+        assert_eq!(spec["info"]["title"], "coderlm");
+        let paths = spec["paths"].as_object().expect("spec should have paths");
+        for (path, item) in paths {
+            for (method, operation) in item.as_object().unwrap() {
+                assert!(operation["responses"].is_object(), "{} {} has no responses", method, path);
+            }
+        }
+        let mut refs = Vec::new();
+        collect_refs(&spec, &mut refs);
+        for reference in refs {
+            let name = reference
+                .strip_prefix("#/components/schemas/")
+                .unwrap_or_else(|| panic!("unexpected $ref {}", reference));
+            assert!(spec["components"]["schemas"][name].is_object(), "dangling $ref {}", reference);
+        }
+        let documented: BTreeSet<String> = paths.keys().cloned().collect();
+
+        // Every annotated handler made it into `build_routes`.
+        let annotated: BTreeSet<String> = include_str!("routes.rs")
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("path = \""))
+            .filter_map(|rest| rest.split('"').next())
+            .map(String::from)
+            .collect();
+        assert_eq!(documented, annotated);
+
+        // Every documented path is routed. Nothing serves TRACE, so a routed
+        // path answers 405 without running its handler; an unrouted one 404.
+        for path in &documented {
+            let uri = path.replace(['{', '}'], "");
+            let request = Request::builder()
+                .method(Method::TRACE)
+                .uri(&uri)
+                .body(Body::empty())
+                .unwrap();
+            let status = router.clone().oneshot(request).await.unwrap().status();
+            assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED, "{} is not routed", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn docs_are_served_without_a_cdn() {
+        let router = router();
+        for uri in ["/api/v1/docs/", "/api/v1/docs/swagger-ui-bundle.js"] {
+            let request = Request::get(uri).body(Body::empty()).unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            assert!(!String::from_utf8_lossy(&bytes).contains("unpkg.com"));
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use std::time::Instant;
+
+use axum::extract::Request;
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use tracing::{field, info, info_span, warn, Instrument};
+
+pub static X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// The request's id, set as an extension for the layers inside
+/// `log_requests`.
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+/// Longest client-supplied request id we will echo back and log.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Wrap each request in a `request` span carrying its id, session, method,
+/// and path, then log status and duration when it completes. An incoming
+/// `X-Request-Id` is reused so ids line up with the caller's transcript;
+/// otherwise one is generated. The id is echoed in the response header.
+pub async fn log_requests(mut req: Request, next: Next) -> Response {
+    let request_id = req
+        .headers()
+        .get(&X_REQUEST_ID)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+        .map(|id| id.to_string())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let session = req
+        .headers()
+        .get("x-session-id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("-")
+        .to_string();
+
+    let span = info_span!(
+        "request",
+        request_id = %request_id,
+        session = %session,
+        method = %req.method(),
+        path = %req.uri().path(),
+        status = field::Empty,
+        duration_ms = field::Empty,
+    );
+
+    req.extensions_mut().insert(RequestId(request_id.clone()));
+    let start = Instant::now();
+    let mut response = next.run(req).instrument(span.clone()).await;
+    let status = response.status();
+    let duration_ms = start.elapsed().as_millis() as u64;
+
+    span.record("status", status.as_u16());
+    span.record("duration_ms", duration_ms);
+    span.in_scope(|| {
+        if status.is_server_error() {
+            warn!("request completed");
+        } else {
+            info!("request completed");
+        }
+    });
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(X_REQUEST_ID.clone(), value);
+    }
+    response
+}
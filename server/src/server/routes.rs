@@ -1,18 +1,50 @@
+use std::collections::HashSet;
 use std::path::PathBuf;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use axum::extract::{Query, State};
-use axum::http::HeaderMap;
-use axum::routing::{delete, get, post};
+use axum::http::{header, HeaderMap};
+use axum::middleware;
+use axum::response::{IntoResponse, Redirect, Response};
 use axum::{Json, Router};
+use tower_http::compression::predicate::{NotForContentType, Predicate, SizeAbove};
+use tower_http::compression::CompressionLayer;
 use serde::Deserialize;
 use serde_json::{json, Value};
-
-use crate::ops::{annotations, content, history, repl, structure, symbol_ops};
-use crate::server::errors::AppError;
-use crate::server::session::{Finding, ReplState, Session, SubcallResult};
-use crate::server::state::{AppState, Project};
-use crate::symbols::symbol::SymbolKind;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_swagger_ui::SwaggerUi;
+use utoipa_axum::routes;
+
+use crate::config::{self, CompressionConfig};
+use crate::index::file_entry::{FileMark, Language};
+use crate::index::file_tree::MarkFilter;
+use crate::index::{pdf, rel_path, walker};
+use crate::ops::content::{
+    ChunkIndicesResponse, GrepResponse, MultiPeekResult, PeekRequest, PeekResponse,
+};
+use crate::ops::history::CompactResult;
+use crate::ops::repl::{ChunkAggregate, DirectoryBufferInfo, VarListing};
+use crate::ops::structure::StructureResponse;
+use crate::ops::symbol_context::SymbolContext;
+use crate::ops::symbol_ops::{
+    CallFrequencyHistogram, ComplianceReport, CoverageHint, DependencyMatrix, GraphNeighborhood,
+};
+use crate::ops::git::{self, ChangedSymbols, GitBlame, GitStatus};
+use crate::ops::{
+    annotations, citations, content, coverage, health, history, memory, project_vars, raw_query,
+    repl, session_ops, structure, symbol_context, symbol_ops, watch,
+};
+use crate::server::errors::{AppError, Candidate};
+use crate::server::{budget, cancel, limits, request_log};
+use crate::server::openapi::ApiDoc;
+use crate::server::session::{
+    AnnotationAction, Bookmark, BufferInfo, Finding, ReplState, Session, SubcallResult,
+};
+use crate::server::state::{project_root_warning, AppState, Project};
+use crate::symbols::fingerprint::SymbolDiff;
+use crate::symbols::symbol::{Symbol, SymbolKind};
 
 // ---------------------------------------------------------------------------
 // Helper: extract session ID from headers
@@ -41,6 +73,90 @@ fn require_project(state: &AppState, headers: &HeaderMap) -> Result<Arc<Project>
     Ok(project)
 }
 
+/// Resolve the project a file-addressed request targets: the attached
+/// project named by `project` (alias or root), else the one named by an
+/// `alias:` prefix on `file`, else the primary project. Returns the project
+/// and the path within it.
+fn require_project_for(
+    state: &AppState,
+    headers: &HeaderMap,
+    project: Option<&str>,
+    file: &str,
+) -> Result<(Arc<Project>, String), AppError> {
+    let primary = require_project(state, headers)?;
+    let sid = require_session(headers)?;
+    let session = state
+        .inner
+        .sessions
+        .get(&sid)
+        .ok_or_else(|| AppError::NotFound(format!("Session '{}' not found", sid)))?;
+    let (root, path) = match project {
+        Some(name) => {
+            let root = session.resolve_project(name).ok_or_else(|| {
+                let aliases: Vec<String> =
+                    session.projects().into_iter().map(|(alias, _)| alias).collect();
+                AppError::BadRequest(format!(
+                    "Project '{}' is not attached to this session. Attached: {}",
+                    name,
+                    aliases.join(", ")
+                ))
+            })?;
+            (root, file.to_string())
+        }
+        None => match file
+            .split_once(':')
+            .and_then(|(alias, rest)| Some((session.resolve_project(alias)?, rest)))
+        {
+            Some((root, rest)) => (root, rest.to_string()),
+            None => return Ok((primary, file.to_string())),
+        },
+    };
+    drop(session);
+    if root == primary.root {
+        return Ok((primary, path));
+    }
+    let project = state
+        .inner
+        .projects
+        .get(&root)
+        .map(|entry| entry.value().clone())
+        .ok_or_else(|| {
+            AppError::Gone(format!(
+                "Project at '{}' was evicted due to capacity limits. \
+                 Attach it again to re-index, or increase --max-projects.",
+                root.display()
+            ))
+        })?;
+    state.touch_project(&root);
+    Ok((project, path))
+}
+
+/// The session's projects that are still indexed, as `(alias, project)`,
+/// primary first.
+fn require_session_projects(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<Vec<(String, Arc<Project>)>, AppError> {
+    let primary = require_project(state, headers)?;
+    let sid = require_session(headers)?;
+    let projects = state
+        .inner
+        .sessions
+        .get(&sid)
+        .map(|session| session.projects())
+        .unwrap_or_default();
+    Ok(projects
+        .into_iter()
+        .filter_map(|(alias, root)| {
+            if root == primary.root {
+                return Some((alias, primary.clone()));
+            }
+            let project = state.inner.projects.get(&root)?.value().clone();
+            Some((alias, project))
+        })
+        .collect())
+}
+
 /// Get the REPL state for the current session.
 fn require_repl(state: &AppState, headers: &HeaderMap) -> Result<Arc<ReplState>, AppError> {
     let sid = require_session(headers)?;
@@ -52,6 +168,76 @@ fn require_repl(state: &AppState, headers: &HeaderMap) -> Result<Arc<ReplState>,
     Ok(session.repl_state.clone())
 }
 
+/// 404 for a lookup of `file`, listing same-named indexed paths when `file`
+/// itself isn't indexed.
+fn file_not_found(project: &Project, file: &str, message: String) -> AppError {
+    if project.file_tree.get(file).is_some() {
+        return AppError::NotFound(message);
+    }
+    AppError::NotFoundWithSuggestions {
+        message,
+        suggestions: content::suggest_files(&project.file_tree, file),
+    }
+}
+
+/// 404 for a lookup of `symbol` in `file`, listing close matches for whichever
+/// of the two is missing.
+fn symbol_not_found(project: &Project, symbol: &str, file: &str, message: String) -> AppError {
+    if project.file_tree.get(file).is_none() {
+        return file_not_found(project, file, message);
+    }
+    if project.symbol_table.get(file, symbol).is_some() {
+        return AppError::NotFound(message);
+    }
+    AppError::NotFoundWithSuggestions {
+        message,
+        suggestions: symbol_ops::suggest_symbols(&project.symbol_table, symbol),
+    }
+}
+
+/// `symbol` in `file` via `get_case_aware`, and whether only the
+/// case-insensitive fallback found it. A name that folds to several
+/// symbols is a 400 listing them.
+fn lookup_symbol(project: &Project, file: &str, symbol: &str) -> Result<(Symbol, bool), AppError> {
+    if let Some(found) = project.symbol_table.get_case_aware(file, symbol) {
+        return Ok(found);
+    }
+    let matches = project.symbol_table.folded_matches(file, symbol);
+    if matches.len() > 1 {
+        return Err(AppError::Ambiguous {
+            message: format!(
+                "Symbol '{}' is ambiguous in '{}': it matches {} symbols case-insensitively",
+                symbol,
+                file,
+                matches.len()
+            ),
+            candidates: matches
+                .into_iter()
+                .map(|sym| Candidate { name: sym.name, line_range: sym.line_range })
+                .collect(),
+        });
+    }
+    let message = format!("Symbol '{}' not found in '{}'", symbol, file);
+    Err(symbol_not_found(project, symbol, file, message))
+}
+
+/// `symbol` as indexed in `file`, and whether it was only found by the
+/// case-insensitive fallback. Unknown names come back unchanged.
+fn resolve_symbol_name(project: &Project, file: &str, symbol: &str) -> (String, bool) {
+    match project.symbol_table.get_case_aware(file, symbol) {
+        Some((sym, folded)) => (sym.name, folded),
+        None => (symbol.to_string(), false),
+    }
+}
+
+/// Flag `body` when its symbol was matched through the case-insensitive
+/// fallback; absent otherwise.
+fn mark_case_insensitive(body: &mut Value, folded: bool) {
+    if folded {
+        body["matched_case_insensitively"] = json!(true);
+    }
+}
+
 fn record_history(state: &AppState, session_id: Option<&str>, method: &str, path: &str, preview: &str) {
     if let Some(id) = session_id {
         if let Some(mut session) = state.inner.sessions.get_mut(id) {
@@ -60,69 +246,154 @@ fn record_history(state: &AppState, session_id: Option<&str>, method: &str, path
     }
 }
 
+/// Note an annotation in the calling session's state, for its summary.
+fn record_annotation(
+    state: &AppState,
+    headers: &HeaderMap,
+    action: AnnotationAction,
+    file: &str,
+    symbol: Option<&str>,
+) {
+    if let Ok(repl) = require_repl(state, headers) {
+        repl.annotations.lock().record(action, file, symbol);
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Router construction
 // ---------------------------------------------------------------------------
 
-pub fn build_routes(state: AppState) -> Router {
-    Router::new()
+pub fn build_routes(state: AppState, compression: &CompressionConfig) -> Router {
+    let (router, api) = OpenApiRouter::with_openapi(ApiDoc::openapi())
         // Health
-        .route("/api/v1/health", get(health))
+        .routes(routes!(health))
+        .routes(routes!(health_detail))
         // Admin
-        .route("/api/v1/roots", get(list_roots))
+        .routes(routes!(list_roots))
+        .routes(routes!(admin_shutdown))
+        .routes(routes!(admin_cancel))
+        .routes(routes!(admin_memory))
+        .routes(routes!(admin_memory_usage))
+        .routes(routes!(admin_trim))
+        .routes(routes!(admin_build_trigram_index))
         // Sessions
-        .route("/api/v1/sessions", get(list_sessions).post(create_session))
-        .route("/api/v1/sessions/{id}", get(get_session))
-        .route("/api/v1/sessions/{id}", delete(delete_session))
+        .routes(routes!(list_sessions, create_session, bulk_delete_sessions))
+        .routes(routes!(get_session, delete_session))
+        .routes(routes!(link_session_project))
+        .routes(routes!(session_summary))
         // Structure
-        .route("/api/v1/structure", get(get_structure))
-        .route("/api/v1/structure/define", post(define_file))
-        .route("/api/v1/structure/redefine", post(redefine_file))
-        .route("/api/v1/structure/mark", post(mark_file))
+        .routes(routes!(get_structure))
+        .routes(routes!(define_file))
+        .routes(routes!(redefine_file))
+        .routes(routes!(auto_define))
+        .routes(routes!(mark_file))
+        // Index
+        .routes(routes!(index_errors))
+        .routes(routes!(index_coverage))
+        .routes(routes!(reindex_partial))
+        .routes(routes!(add_file))
+        .routes(routes!(watch_file, unwatch_file))
         // Symbols
-        .route("/api/v1/symbols", get(list_symbols))
-        .route("/api/v1/symbols/search", get(search_symbols))
-        .route("/api/v1/symbols/define", post(define_symbol))
-        .route("/api/v1/symbols/redefine", post(redefine_symbol))
-        .route("/api/v1/symbols/implementation", get(get_implementation))
-        .route("/api/v1/symbols/tests", get(find_tests))
-        .route("/api/v1/symbols/callers", get(find_callers))
-        .route("/api/v1/symbols/variables", get(list_variables))
+        .routes(routes!(list_symbols))
+        .routes(routes!(search_symbols))
+        .routes(routes!(complete_symbols))
+        .routes(routes!(resolve_symbol))
+        .routes(routes!(search_global))
+        .routes(routes!(define_symbol))
+        .routes(routes!(redefine_symbol))
+        .routes(routes!(get_signature))
+        .routes(routes!(get_symbol_by_hash))
+        .routes(routes!(get_implementation))
+        .routes(routes!(grep_symbol))
+        .routes(routes!(find_tests))
+        .routes(routes!(test_coverage_hint))
+        .routes(routes!(find_callers))
+        .routes(routes!(rename_candidates))
+        .routes(routes!(graph_neighbors))
+        .routes(routes!(symbol_context))
+        .routes(routes!(call_frequency_histogram))
+        .routes(routes!(hotspot_files))
+        .routes(routes!(dependency_matrix))
+        .routes(routes!(local_only_symbols))
+        .routes(routes!(symbol_diff))
+        .routes(routes!(interface_compliance))
+        .routes(routes!(symbol_blame))
+        .routes(routes!(recently_used))
+        .routes(routes!(list_variables))
         // Content
-        .route("/api/v1/peek", get(peek))
-        .route("/api/v1/grep", get(grep_handler))
-        .route("/api/v1/chunk_indices", get(chunk_indices))
+        .routes(routes!(peek))
+        .routes(routes!(multi_peek))
+        .routes(routes!(grep_handler))
+        .routes(routes!(chunk_indices))
+        .routes(routes!(raw_query_handler))
+        .routes(routes!(raw_query_scan))
+        // Git
+        .routes(routes!(git_status))
+        .routes(routes!(git_diff))
+        .routes(routes!(changed_symbols))
         // History
-        .route("/api/v1/history", get(get_history))
-        .route("/api/v1/history/compact", post(compact_history))
+        .routes(routes!(get_history))
+        .routes(routes!(compact_history))
         // Context budget
-        .route("/api/v1/context_budget", get(context_budget))
+        .routes(routes!(context_budget))
         // Annotations
-        .route("/api/v1/annotations/save", post(save_annotations))
-        .route("/api/v1/annotations/load", post(load_annotations))
+        .routes(routes!(save_annotations))
+        .routes(routes!(load_annotations))
         // Buffers
-        .route("/api/v1/buffers", get(list_buffers).post(create_buffer))
-        .route("/api/v1/buffers/from-file", post(buffer_from_file))
-        .route("/api/v1/buffers/from-symbol", post(buffer_from_symbol))
-        .route(
-            "/api/v1/buffers/{name}",
-            get(get_buffer_info).delete(delete_buffer),
-        )
-        .route("/api/v1/buffers/{name}/peek", get(peek_buffer))
+        .routes(routes!(list_buffers, create_buffer))
+        .routes(routes!(buffer_from_file))
+        .routes(routes!(buffer_from_symbol))
+        .routes(routes!(buffer_from_dir))
+        .routes(routes!(get_buffer_info, delete_buffer))
+        .routes(routes!(peek_buffer))
+        // Bookmarks
+        .routes(routes!(list_bookmarks, create_bookmark))
+        .routes(routes!(delete_bookmark))
         // Variables
-        .route("/api/v1/vars", get(list_vars).post(set_var))
-        .route("/api/v1/vars/final", get(check_final))
-        .route(
-            "/api/v1/vars/{name}",
-            get(get_var).delete(delete_var),
-        )
+        .routes(routes!(list_vars, set_var))
+        .routes(routes!(compute_var))
+        .routes(routes!(check_final))
+        .routes(routes!(wait_final))
+        .routes(routes!(validate_final))
+        .routes(routes!(get_var, delete_var))
+        .routes(routes!(list_project_vars, set_project_var))
+        .routes(routes!(get_project_var, delete_project_var))
         // Semantic chunks
-        .route("/api/v1/semantic_chunks", get(semantic_chunks))
+        .routes(routes!(semantic_chunks))
+        .routes(routes!(package_chunk))
+        .routes(routes!(semantic_chunk_grep))
         // Subcall results
-        .route(
-            "/api/v1/subcall_results",
-            get(list_subcall_results).post(store_subcall_result).delete(clear_subcall_results),
-        )
+        .routes(routes!(
+            list_subcall_results,
+            store_subcall_result,
+            clear_subcall_results
+        ))
+        .routes(routes!(get_chunk_subcall_results, clear_chunk_subcall_results))
+        .routes(routes!(aggregate_chunk_subcall_results))
+        .split_for_parts();
+
+    // OpenAPI document generated from the handler annotations above, and a
+    // Swagger UI for it whose assets are compiled into the binary.
+    let router = router
+        .merge(SwaggerUi::new("/api/v1/docs").url("/api/v1/openapi.json", api))
+        .layer(middleware::from_fn_with_state(state.clone(), budget::track_response_tokens))
+        .layer(middleware::from_fn_with_state(state.clone(), limits::rate_limit))
+        .layer(middleware::from_fn_with_state(state.clone(), cancel::track_cancellation));
+
+    // Compress large JSON bodies for clients that ask for it. Event streams
+    // are excluded so they keep flushing incrementally.
+    let router = if compression.enabled {
+        let predicate = SizeAbove::new(compression.min_bytes)
+            .and(NotForContentType::GRPC)
+            .and(NotForContentType::IMAGES)
+            .and(NotForContentType::SSE);
+        router.layer(CompressionLayer::new().compress_when(predicate))
+    } else {
+        router
+    };
+
+    router
+        .layer(middleware::from_fn(request_log::log_requests))
         .with_state(state)
 }
 
@@ -130,15 +401,111 @@ pub fn build_routes(state: AppState) -> Router {
 // Health
 // ---------------------------------------------------------------------------
 
-async fn health(State(state): State<AppState>) -> Json<Value> {
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct HealthQuery {
+    /// Also check grammars, `.coderlm/` writability, and the PDF backend.
+    deep: Option<bool>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/health",
+    tag = "admin",
+    params(HealthQuery),
+    responses((status = 200, description = "Server status", body = Value), AppError),
+)]
+async fn health(
+    State(state): State<AppState>,
+    Query(params): Query<HealthQuery>,
+) -> Result<Json<Value>, AppError> {
     let project_count = state.inner.projects.len();
     let session_count = state.inner.sessions.len();
 
-    Json(json!({
+    let file_watches: Vec<Value> = state
+        .inner
+        .projects
+        .iter()
+        .flat_map(|entry| {
+            let root = entry.key().display().to_string();
+            entry
+                .value()
+                .file_watches
+                .iter()
+                .map(|w| json!({ "project": root, "file": w.key() }))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    let mut body = json!({
         "status": "ok",
         "projects": project_count,
         "active_sessions": session_count,
         "max_projects": state.inner.max_projects,
+        "file_watches": file_watches,
+        "blocking_permits": {
+            "in_use": state.inner.blocking.in_use(),
+            "max": state.inner.blocking.max(),
+        },
+        "rate_limit": {
+            "enabled": state.inner.rate_limiter.enabled(),
+            "per_second": state.inner.rate_limiter.rate(),
+            "burst": state.inner.rate_limiter.burst(),
+        },
+    });
+
+    if params.deep.unwrap_or(false) {
+        let roots: Vec<PathBuf> = state.inner.projects.iter().map(|e| e.key().clone()).collect();
+        let deep = state
+            .inner
+            .blocking
+            .run(move || health::deep_check(&roots))
+            .await?;
+        body["status"] = json!(deep.status);
+        body["checks"] = json!(deep.checks);
+        if !deep.details.is_empty() {
+            body["details"] = json!(deep.details);
+        }
+    }
+
+    Ok(Json(body))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/health/detail",
+    tag = "admin",
+    responses((status = 200, description = "Per-project indexing and error state", body = Value), AppError),
+)]
+async fn health_detail(State(state): State<AppState>) -> Json<Value> {
+    let projects: Vec<Value> = state
+        .inner
+        .projects
+        .iter()
+        .map(|entry| {
+            let project = entry.value();
+            let status = project.index_status.lock().clone();
+            json!({
+                "root": project.root.display().to_string(),
+                "indexing": status.state,
+                "last_index_duration_ms": status.last_index_duration_ms,
+                "files": project.file_tree.len(),
+                "symbols": project.symbol_table.len(),
+                "failed_files": status.failed_files,
+                "errors": status.errors,
+                "fatal_error": status.fatal_error,
+                "annotations_loaded": status.annotations_loaded,
+                "annotations_error": status.annotations_error,
+                "warm_start_files": status.warm_start_files,
+                "warm_start_error": status.warm_start_error,
+                "pdf_conversion_failures": pdf::failure_count(&project.root),
+            })
+        })
+        .collect();
+
+    Json(json!({
+        "status": "ok",
+        "projects": projects,
     }))
 }
 
@@ -146,6 +513,12 @@ async fn health(State(state): State<AppState>) -> Json<Value> {
 // Admin: list registered projects
 // ---------------------------------------------------------------------------
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/roots",
+    tag = "admin",
+    responses((status = 200, description = "Registered projects", body = Value), AppError),
+)]
 async fn list_roots(State(state): State<AppState>) -> Json<Value> {
     let roots: Vec<Value> = state
         .inner
@@ -172,15 +545,212 @@ async fn list_roots(State(state): State<AppState>) -> Json<Value> {
     Json(json!({ "roots": roots, "count": roots.len() }))
 }
 
+// ---------------------------------------------------------------------------
+// Admin: orchestrated shutdown
+// ---------------------------------------------------------------------------
+
+/// Check `X-Admin-Token` against the configured admin token. A custom
+/// header can't be sent cross-origin without a preflight, so a web page
+/// can't forge the request either.
+fn require_admin_token(state: &AppState, headers: &HeaderMap) -> Result<(), AppError> {
+    let Some(expected) = state.inner.admin_token.as_deref() else {
+        return Err(AppError::Forbidden(
+            "Disabled: start the server with --admin-token to enable it".into(),
+        ));
+    };
+    let given = headers
+        .get("x-admin-token")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    // Compare every byte so the time taken doesn't reveal a matching prefix.
+    let matches = given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0;
+    if matches {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden("Missing or wrong X-Admin-Token".into()))
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/shutdown",
+    tag = "admin",
+    params(("X-Admin-Token" = String, Header, description = "The server's `--admin-token`")),
+    responses((status = 200, description = "Shutdown started; in-flight requests drain, then state is flushed", body = Value), AppError),
+)]
+async fn admin_shutdown(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    require_admin_token(&state, &headers)?;
+    tracing::info!("Shutdown requested via /admin/shutdown");
+    state.request_shutdown();
+    Ok(Json(json!({ "ok": true, "drain_secs": config::SHUTDOWN_DRAIN_SECS })))
+}
+
+// ---------------------------------------------------------------------------
+// Admin: request cancellation
+// ---------------------------------------------------------------------------
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+struct CancelPath {
+    /// The request's `X-Request-Id`
+    request_id: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/cancel/{request_id}",
+    tag = "admin",
+    params(CancelPath),
+    responses((status = 200, description = "`{ request_id, cancelled: true }`; the request stops at its next check and returns what it has, flagged `cancelled`", body = Value), AppError),
+)]
+async fn admin_cancel(
+    State(state): State<AppState>,
+    axum::extract::Path(CancelPath { request_id }): axum::extract::Path<CancelPath>,
+) -> Result<Json<Value>, AppError> {
+    if !state.inner.cancellations.cancel(&request_id) {
+        return Err(AppError::NotFound(format!(
+            "No request '{}' is in flight",
+            request_id
+        )));
+    }
+    tracing::info!(request_id = %request_id, "request cancelled via /admin/cancel");
+    Ok(Json(json!({ "request_id": request_id, "cancelled": true })))
+}
+
+// ---------------------------------------------------------------------------
+// Admin: memory inspection and trimming
+// ---------------------------------------------------------------------------
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/memory",
+    tag = "admin",
+    responses((status = 200, description = "Estimated memory per project and session, largest first", body = memory::MemoryReport), AppError),
+)]
+async fn admin_memory(State(state): State<AppState>) -> Json<memory::MemoryReport> {
+    Json(memory::memory_report(&state))
+}
+
+/// `/admin/memory` under the name some clients ask for; redirected so the
+/// report has a single handler.
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/memory_usage",
+    tag = "admin",
+    responses((status = 308, description = "Permanent redirect to `/api/v1/admin/memory`")),
+)]
+async fn admin_memory_usage() -> Redirect {
+    Redirect::permanent("/api/v1/admin/memory")
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct TrimQuery {
+    /// Also evict sessions idle for longer than this many seconds
+    evict_idle_secs: Option<u64>,
+    /// Also delete converted PDF markdown caches on disk
+    drop_pdf_cache: Option<bool>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/trim",
+    tag = "admin",
+    params(TrimQuery),
+    responses((status = 200, description = "What was released", body = memory::TrimReport), AppError),
+)]
+async fn admin_trim(
+    State(state): State<AppState>,
+    Query(q): Query<TrimQuery>,
+) -> Json<memory::TrimReport> {
+    let report = memory::trim(
+        &state,
+        q.evict_idle_secs,
+        q.drop_pdf_cache.unwrap_or(false),
+    );
+    Json(report)
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct TrigramIndexQuery {
+    /// Project root to index; all projects when omitted
+    project: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/build_trigram_index",
+    tag = "admin",
+    params(TrigramIndexQuery),
+    responses((status = 200, description = "`{ projects: [{ root, files, trigrams, duration_ms }], count }`", body = Value), AppError),
+)]
+async fn admin_build_trigram_index(
+    State(state): State<AppState>,
+    Query(q): Query<TrigramIndexQuery>,
+) -> Result<Json<Value>, AppError> {
+    let projects: Vec<Arc<Project>> = match &q.project {
+        Some(path) => {
+            let canonical = std::fs::canonicalize(path).unwrap_or_else(|_| path.into());
+            let project = state
+                .inner
+                .projects
+                .get(&canonical)
+                .map(|entry| entry.value().clone())
+                .ok_or_else(|| AppError::NotFound(format!("Project '{}' is not indexed", path)))?;
+            vec![project]
+        }
+        None => state.inner.projects.iter().map(|e| e.value().clone()).collect(),
+    };
+
+    let mut built = Vec::new();
+    for project in projects {
+        let started = std::time::Instant::now();
+        let indexed = project.clone();
+        let stats = state
+            .inner
+            .blocking
+            .run(move || content::build_trigram_index(&indexed.root, &indexed.file_tree))
+            .await?;
+        built.push(json!({
+            "root": project.root.display().to_string(),
+            "files": stats.files,
+            "trigrams": stats.trigrams,
+            "duration_ms": started.elapsed().as_millis() as u64,
+        }));
+    }
+    Ok(Json(json!({ "projects": built, "count": built.len() })))
+}
+
 // ---------------------------------------------------------------------------
 // Sessions
 // ---------------------------------------------------------------------------
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct CreateSessionBody {
     cwd: String,
+    /// Index snapshot (or the `.coderlm` directory holding one) from another
+    /// checkout of the same repository to warm-start a new project from.
+    snapshot_from: Option<String>,
+    /// Allow rooting a new project at `/` or the home directory.
+    allow_large_root: Option<bool>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/sessions",
+    tag = "sessions",
+    request_body = CreateSessionBody,
+    responses((status = 200, description = "Session created; project indexed if new", body = Value), AppError),
+)]
 async fn create_session(
     State(state): State<AppState>,
     Json(body): Json<CreateSessionBody>,
@@ -188,35 +758,47 @@ async fn create_session(
     let cwd_path = PathBuf::from(&body.cwd);
 
     // Index the project (or return existing)
-    let project = state.get_or_create_project(&cwd_path)?;
-
+    let snapshot_from = body.snapshot_from.as_ref().map(PathBuf::from);
     let id = uuid::Uuid::new_v4().to_string();
-    let session = Session::new(id.clone(), project.root.clone());
-    let created_at = session.created_at;
-    state.inner.sessions.insert(id.clone(), session);
-
-    // Load annotations from disk after project is indexed
-    let ft = project.file_tree.clone();
-    let st = project.symbol_table.clone();
-    let root = project.root.clone();
-    tokio::spawn(async move {
-        // Small delay to let symbol extraction start first
-        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
-        let _ = annotations::load_annotations(&root, &ft, &st);
-    });
+    let (project, created_at) = loop {
+        let project = state.get_or_create_project(
+            &cwd_path,
+            snapshot_from.as_deref(),
+            body.allow_large_root.unwrap_or(false),
+        )?;
+        let session = Session::new(id.clone(), project.root.clone());
+        let created_at = session.created_at;
+        // Evicted since the lookup: index it again rather than hand out a
+        // session that would only get 410 Gone.
+        if state.insert_session(session) {
+            break (project, created_at);
+        }
+    };
 
-    Ok(Json(json!({
+    let mut response = json!({
         "session_id": id,
         "created_at": created_at.to_rfc3339(),
         "project": project.root.display().to_string(),
-    })))
+    });
+    if let Some(warning) = project_root_warning(&project.root) {
+        response["warning"] = json!(warning);
+    }
+    Ok(Json(response))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
 struct SessionPath {
     id: String,
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/sessions/{id}",
+    tag = "sessions",
+    params(SessionPath),
+    responses((status = 200, description = "Session info", body = Value), AppError),
+)]
 async fn get_session(
     State(state): State<AppState>,
     axum::extract::Path(params): axum::extract::Path<SessionPath>,
@@ -233,9 +815,115 @@ async fn get_session(
         "created_at": session.created_at.to_rfc3339(),
         "last_active": session.last_active.to_rfc3339(),
         "history_count": session.history.len(),
+        "bookmarks": *session.repl_state.bookmarks.lock(),
+        "projects": project_list(&session),
+        "repl": session.repl_summary(),
     })))
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SessionSummaryQuery {
+    /// Recent history entries to include (default 10, max 100)
+    history: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/sessions/{id}/summary",
+    tag = "sessions",
+    params(SessionPath, SessionSummaryQuery),
+    responses((status = 200, description = "What the session has accumulated, as JSON and markdown", body = session_ops::SessionRecap), AppError),
+)]
+async fn session_summary(
+    State(state): State<AppState>,
+    axum::extract::Path(params): axum::extract::Path<SessionPath>,
+    Query(q): Query<SessionSummaryQuery>,
+) -> Result<Json<session_ops::SessionRecap>, AppError> {
+    let session = state
+        .inner
+        .sessions
+        .get(&params.id)
+        .ok_or_else(|| AppError::NotFound(format!("Session '{}' not found", params.id)))?;
+    let history = q
+        .history
+        .unwrap_or(config::SESSION_RECAP_HISTORY)
+        .min(config::SESSION_RECAP_MAX_HISTORY);
+    Ok(Json(session_ops::session_recap(&session, history)))
+}
+
+/// Attached projects as `[{ alias, root, primary }]`, primary first.
+fn project_list(session: &Session) -> Vec<Value> {
+    session
+        .projects()
+        .into_iter()
+        .map(|(alias, root)| {
+            json!({
+                "alias": alias,
+                "root": root.display().to_string(),
+                "primary": root == session.project_path,
+            })
+        })
+        .collect()
+}
+
+#[derive(Deserialize, ToSchema)]
+struct LinkProjectBody {
+    /// Directory of the project to attach; indexed if not already
+    cwd: String,
+    /// Name used in `project=` and `alias:path`; defaults to the directory name
+    alias: Option<String>,
+    /// Allow rooting a new project at `/` or the home directory.
+    allow_large_root: Option<bool>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/sessions/{id}/projects",
+    tag = "sessions",
+    params(SessionPath),
+    request_body = LinkProjectBody,
+    responses((status = 200, description = "`{ session_id, alias, projects: [{ alias, root, primary }] }`", body = Value), AppError),
+)]
+async fn link_session_project(
+    State(state): State<AppState>,
+    axum::extract::Path(params): axum::extract::Path<SessionPath>,
+    Json(body): Json<LinkProjectBody>,
+) -> Result<Json<Value>, AppError> {
+    if !state.inner.sessions.contains_key(&params.id) {
+        return Err(AppError::NotFound(format!("Session '{}' not found", params.id)));
+    }
+    let project = state.get_or_create_project(
+        &PathBuf::from(&body.cwd),
+        None,
+        body.allow_large_root.unwrap_or(false),
+    )?;
+    let mut session = state
+        .inner
+        .sessions
+        .get_mut(&params.id)
+        .ok_or_else(|| AppError::NotFound(format!("Session '{}' not found", params.id)))?;
+    let alias = session
+        .link_project(project.root.clone(), body.alias.as_deref())
+        .map_err(AppError::BadRequest)?;
+    let mut response = json!({
+        "session_id": session.id,
+        "alias": alias,
+        "projects": project_list(&session),
+    });
+    if let Some(warning) = project_root_warning(&project.root) {
+        response["warning"] = json!(warning);
+    }
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/sessions/{id}",
+    tag = "sessions",
+    params(SessionPath),
+    responses((status = 200, description = "Session deleted", body = Value), AppError),
+)]
 async fn delete_session(
     State(state): State<AppState>,
     axum::extract::Path(params): axum::extract::Path<SessionPath>,
@@ -245,54 +933,225 @@ async fn delete_session(
         .sessions
         .remove(&params.id)
         .ok_or_else(|| AppError::NotFound(format!("Session '{}' not found", params.id)))?;
+    state.inner.rate_limiter.forget(&params.id);
 
     Ok(Json(json!({ "deleted": true })))
 }
 
-async fn list_sessions(State(state): State<AppState>) -> Json<Value> {
-    let mut sessions: Vec<Value> = state
-        .inner
-        .sessions
-        .iter()
-        .map(|entry| {
-            let session = entry.value();
-            json!({
-                "session_id": session.id,
-                "project": session.project_path.display().to_string(),
-                "created_at": session.created_at.to_rfc3339(),
-                "last_active": session.last_active.to_rfc3339(),
-                "history_count": session.history.len(),
-            })
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct BulkDeleteQuery {
+    /// Delete sessions idle for longer than this many hours
+    older_than_hours: Option<f64>,
+    /// Delete sessions attached to this project root
+    project: Option<String>,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/sessions",
+    tag = "sessions",
+    params(BulkDeleteQuery),
+    responses((status = 200, description = "Sessions deleted", body = session_ops::BulkDeleteResult), AppError),
+)]
+async fn bulk_delete_sessions(
+    State(state): State<AppState>,
+    Query(q): Query<BulkDeleteQuery>,
+) -> Result<Json<session_ops::BulkDeleteResult>, AppError> {
+    if q.older_than_hours.is_none() && q.project.is_none() {
+        return Err(AppError::BadRequest(
+            "Pass older_than_hours, project, or both".to_string(),
+        ));
+    }
+    let older_than = q
+        .older_than_hours
+        .map(|hours| {
+            if !hours.is_finite() || hours < 0.0 {
+                return Err(AppError::BadRequest(format!(
+                    "Invalid older_than_hours '{}': expected a non-negative number",
+                    hours
+                )));
+            }
+            chrono::TimeDelta::try_milliseconds((hours * 3_600_000.0) as i64)
+                .and_then(|age| chrono::Utc::now().checked_sub_signed(age))
+                .ok_or_else(|| {
+                    AppError::BadRequest(format!("older_than_hours '{}' is out of range", hours))
+                })
         })
-        .collect();
+        .transpose()?;
+    let result = session_ops::bulk_delete_sessions(
+        &state,
+        older_than,
+        q.project.as_deref().map(std::path::Path::new),
+    );
+    Ok(Json(result))
+}
 
-    sessions.sort_by(|a, b| {
-        let a_time = a["last_active"].as_str().unwrap_or("");
-        let b_time = b["last_active"].as_str().unwrap_or("");
-        b_time.cmp(a_time)
-    });
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SessionListQuery {
+    /// Only sessions attached to this project root
+    project: Option<String>,
+    /// Only sessions active at or after this RFC 3339 timestamp
+    active_since: Option<String>,
+    /// Only sessions with this variable set (e.g. `Final`)
+    has_var: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/sessions",
+    tag = "sessions",
+    params(SessionListQuery),
+    responses((status = 200, description = "`{ sessions: [SessionInfo], count }`, most recently active first", body = Value), AppError),
+)]
+async fn list_sessions(
+    State(state): State<AppState>,
+    Query(q): Query<SessionListQuery>,
+) -> Result<Json<Value>, AppError> {
+    let active_since = q
+        .active_since
+        .as_deref()
+        .map(|s| {
+            chrono::DateTime::parse_from_rfc3339(s)
+                .map(|t| t.with_timezone(&chrono::Utc))
+                .map_err(|e| AppError::BadRequest(format!("Invalid active_since '{}': {}", s, e)))
+        })
+        .transpose()?;
 
-    Json(json!({ "sessions": sessions, "count": sessions.len() }))
+    let sessions = session_ops::list_sessions_filtered(
+        &state,
+        q.project.as_deref().map(std::path::Path::new),
+        active_since,
+        q.has_var.as_deref(),
+    );
+
+    Ok(Json(json!({ "count": sessions.len(), "sessions": sessions })))
 }
 
 // ---------------------------------------------------------------------------
 // Structure
 // ---------------------------------------------------------------------------
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct StructureQuery {
     depth: Option<usize>,
     meta: Option<bool>,
+    /// Also list paths the default or configured ignore rules left out
+    include_ignored: Option<bool>,
+    /// Only files at or below this directory (or this one file); `depth`
+    /// then counts from it.
+    #[serde(default, deserialize_with = "rel_path::deserialize_option")]
+    path: Option<String>,
+    /// "json" (default) or "tree" for an indented plain-text rendering,
+    /// returned as `text/plain` when the request accepts it.
+    format: Option<String>,
+    /// With `format=tree`, append each file's size.
+    include_sizes: Option<bool>,
+    /// With `format=tree`, append each file's symbol count.
+    include_symbol_counts: Option<bool>,
+    /// With `format=tree`, flag defined files and their marks (default
+    /// true).
+    include_annotations: Option<bool>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/structure",
+    tag = "structure",
+    params(StructureQuery),
+    responses(
+        (status = 200, description = "File tree (metadata plus buffer name when `meta=true`)", body = StructureResponse),
+        (status = 200, description = "With `format=tree` and `Accept: text/plain`, the tree text alone", content_type = "text/plain", body = String),
+        AppError,
+    ),
+    security(("session" = [])),
+)]
 async fn get_structure(
     State(state): State<AppState>,
     headers: HeaderMap,
     Query(params): Query<StructureQuery>,
-) -> Result<Json<Value>, AppError> {
+) -> Result<Response, AppError> {
     let project = require_project(&state, &headers)?;
     let depth = params.depth.unwrap_or(0);
-    let result = structure::get_structure(&project.file_tree, depth);
+    let tree_format = match params.format.as_deref() {
+        None | Some("json") => false,
+        Some("tree") => true,
+        Some(other) => {
+            return Err(AppError::BadRequest(format!(
+                "Invalid format '{}': expected 'json' or 'tree'",
+                other
+            )));
+        }
+    };
+    let scope = params
+        .path
+        .as_deref()
+        .unwrap_or("")
+        .trim_start_matches("./")
+        .trim_end_matches('/');
+    let paths = structure::paths_under(&project.file_tree, scope);
+    if paths.is_empty() && !scope.is_empty() {
+        return Err(AppError::NotFound(format!("No indexed files under '{}'", scope)));
+    }
+
+    if tree_format {
+        let suffixes = structure::TreeSuffixes {
+            sizes: params.include_sizes.unwrap_or(false),
+            symbol_counts: params.include_symbol_counts.unwrap_or(false),
+            annotations: params.include_annotations.unwrap_or(true),
+        };
+        let tree = structure::render_compact_tree(
+            &project.file_tree,
+            &project.symbol_table,
+            &paths,
+            scope,
+            depth,
+            suffixes,
+        );
+        let preview = format!("{} files (tree)", paths.len());
+        record_history(&state, session_id(&headers).as_deref(), "GET", "/structure", &preview);
+        let wants_text = headers
+            .get(header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|accept| accept.contains("text/plain"));
+        if wants_text {
+            return Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], tree).into_response());
+        }
+        return Ok(Json(json!({
+            "format": "tree",
+            "tree": tree,
+            "file_count": paths.len(),
+        }))
+        .into_response());
+    }
+
+    let annotations = project.annotations.lock().clone();
+    let mut result = structure::get_structure(
+        &project.file_tree,
+        &project.extraction_errors,
+        annotations.as_ref(),
+        paths,
+        scope,
+        depth,
+    );
+    if params.include_ignored.unwrap_or(false) {
+        let project = project.clone();
+        result.ignored = Some(
+            state
+                .inner
+                .blocking
+                .run(move || {
+                    walker::list_ignored(
+                        &project.root,
+                        &project.file_tree,
+                        config::STRUCTURE_IGNORED_LIMIT,
+                    )
+                })
+                .await?,
+        );
+    }
     let preview = format!("{} files", result.file_count);
     record_history(&state, session_id(&headers).as_deref(), "GET", "/structure", &preview);
 
@@ -303,185 +1162,1310 @@ async fn get_structure(
         Ok(Json(json!({
             "file_count": result.file_count,
             "language_breakdown": result.language_breakdown,
+            "extraction_errors": result.extraction_errors,
+            "ignored": result.ignored,
             "buffer": buffer_name,
-        })))
+        }))
+        .into_response())
     } else {
-        Ok(Json(serde_json::to_value(result).unwrap()))
+        Ok(Json(serde_json::to_value(result).unwrap()).into_response())
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct DefineRequest {
+    #[serde(deserialize_with = "rel_path::deserialize")]
     file: String,
     definition: String,
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DefineQuery {
+    /// Index the file from disk first if it isn't in the index yet.
+    allow_unindexed: Option<bool>,
+    /// Succeed whether or not a definition exists, reporting which happened
+    upsert: Option<bool>,
+}
+
+/// `{ ok }`, plus with `upsert` whether the definition was created and the
+/// one it replaced.
+fn define_response(previous: Option<String>, upsert: bool) -> Json<Value> {
+    if !upsert {
+        return Json(json!({ "ok": true }));
+    }
+    let mut body = json!({ "ok": true, "created": previous.is_none() });
+    if let Some(previous) = previous {
+        body["previous"] = json!(previous);
+    }
+    Json(body)
+}
+
+/// History preview for a define: the target, and with `upsert` the outcome.
+fn define_preview(target: &str, previous: &Option<String>, upsert: bool) -> String {
+    match (upsert, previous) {
+        (false, _) => target.to_string(),
+        (true, None) => format!("{} (created)", target),
+        (true, Some(_)) => format!("{} (replaced)", target),
+    }
+}
+
+/// Apply `allow_unindexed` before a define/redefine.
+fn index_for_define(
+    project: &Project,
+    state: &AppState,
+    file: &str,
+    q: &DefineQuery,
+) -> Result<(), AppError> {
+    if !q.allow_unindexed.unwrap_or(false) {
+        return Ok(());
+    }
+    structure::index_if_missing(
+        &project.root,
+        &project.file_tree,
+        &project.symbol_table,
+        &project.extraction_errors,
+        state.inner.max_file_size,
+        file,
+    )
+    .map_err(AppError::BadRequest)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/structure/define",
+    tag = "structure",
+    params(DefineQuery),
+    request_body = DefineRequest,
+    responses((status = 200, description = "Definition set", body = Value), AppError),
+    security(("session" = [])),
+)]
 async fn define_file(
     State(state): State<AppState>,
     headers: HeaderMap,
+    Query(q): Query<DefineQuery>,
     Json(body): Json<DefineRequest>,
 ) -> Result<Json<Value>, AppError> {
     let project = require_project(&state, &headers)?;
-    structure::define_file(&project.file_tree, &body.file, &body.definition)
-        .map_err(AppError::BadRequest)?;
-    record_history(&state, session_id(&headers).as_deref(), "POST", "/structure/define", &body.file);
-    Ok(Json(json!({ "ok": true })))
+    index_for_define(&project, &state, &body.file, &q)?;
+    let upsert = q.upsert.unwrap_or(false);
+    let previous =
+        structure::define_file(&project.file_tree, &body.file, &body.definition, false, upsert)
+            .map_err(AppError::BadRequest)?;
+    project.mark_annotations_dirty();
+    record_annotation(&state, &headers, AnnotationAction::Define, &body.file, None);
+    let preview = define_preview(&body.file, &previous, upsert);
+    record_history(&state, session_id(&headers).as_deref(), "POST", "/structure/define", &preview);
+    Ok(define_response(previous, upsert))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/structure/redefine",
+    tag = "structure",
+    params(DefineQuery),
+    request_body = DefineRequest,
+    responses((status = 200, description = "Definition replaced", body = Value), AppError),
+    security(("session" = [])),
+)]
 async fn redefine_file(
     State(state): State<AppState>,
     headers: HeaderMap,
+    Query(q): Query<DefineQuery>,
     Json(body): Json<DefineRequest>,
 ) -> Result<Json<Value>, AppError> {
     let project = require_project(&state, &headers)?;
-    structure::redefine_file(&project.file_tree, &body.file, &body.definition)
+    index_for_define(&project, &state, &body.file, &q)?;
+    let upsert = q.upsert.unwrap_or(false);
+    let previous = structure::redefine_file(&project.file_tree, &body.file, &body.definition)
         .map_err(AppError::BadRequest)?;
-    record_history(&state, session_id(&headers).as_deref(), "POST", "/structure/redefine", &body.file);
-    Ok(Json(json!({ "ok": true })))
+    project.mark_annotations_dirty();
+    record_annotation(&state, &headers, AnnotationAction::Redefine, &body.file, None);
+    let preview = define_preview(&body.file, &previous, upsert);
+    record_history(&state, session_id(&headers).as_deref(), "POST", "/structure/redefine", &preview);
+    Ok(define_response(previous, upsert))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct AutoDefineQuery {
+    /// Only files whose path starts with this prefix
+    #[serde(default, deserialize_with = "rel_path::deserialize_option")]
+    path_prefix: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/structure/auto_define",
+    tag = "structure",
+    params(AutoDefineQuery),
+    responses((status = 200, description = "Generated definitions", body = structure::AutoDefineReport), AppError),
+    security(("session" = [])),
+)]
+async fn auto_define(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(q): Query<AutoDefineQuery>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let prefix = q.path_prefix.unwrap_or_default();
+    let indexed = project.clone();
+    let report = state
+        .inner
+        .blocking
+        .run(move || {
+            structure::auto_define(&indexed.root, &indexed.file_tree, &indexed.symbol_table, &prefix)
+        })
+        .await?;
+    if !report.defined.is_empty() {
+        project.mark_annotations_dirty();
+    }
+    let preview = format!(
+        "{} defined ({} regenerated), {} kept",
+        report.defined.len(),
+        report.regenerated,
+        report.kept
+    );
+    record_history(&state, session_id(&headers).as_deref(), "POST", "/structure/auto_define", &preview);
+    Ok(Json(serde_json::to_value(report).unwrap()))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct MarkRequest {
+    #[serde(deserialize_with = "rel_path::deserialize")]
     file: String,
     mark: String,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/structure/mark",
+    tag = "structure",
+    request_body = MarkRequest,
+    responses((status = 200, description = "Mark added", body = Value), AppError),
+    security(("session" = [])),
+)]
 async fn mark_file(
     State(state): State<AppState>,
     headers: HeaderMap,
     Json(body): Json<MarkRequest>,
 ) -> Result<Json<Value>, AppError> {
     let project = require_project(&state, &headers)?;
-    structure::mark_file(&project.file_tree, &body.file, &body.mark)
+    structure::mark_file(&project.file_tree, &project.symbol_table, &body.file, &body.mark)
         .map_err(AppError::BadRequest)?;
+    project.mark_annotations_dirty();
+    record_annotation(&state, &headers, AnnotationAction::Mark, &body.file, None);
     record_history(&state, session_id(&headers).as_deref(), "POST", "/structure/mark", &body.file);
     Ok(Json(json!({ "ok": true })))
 }
 
 // ---------------------------------------------------------------------------
-// Symbols
+// Index: per-file watches
 // ---------------------------------------------------------------------------
 
-#[derive(Deserialize)]
-struct SymbolListQuery {
-    kind: Option<String>,
-    file: Option<String>,
+#[utoipa::path(
+    get,
+    path = "/api/v1/index/errors",
+    tag = "index",
+    responses((status = 200, description = "`{ errors: [ExtractionError], count }`", body = Value), AppError),
+    security(("session" = [])),
+)]
+async fn index_errors(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let errors = structure::list_extraction_errors(&project.extraction_errors);
+    let preview = format!("{} extraction errors", errors.len());
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/index/errors", &preview);
+    Ok(Json(json!({ "errors": errors, "count": errors.len() })))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct CoverageQuery {
+    /// Statuses to list files for, comma-separated, or `all`. Defaults to
+    /// every status but `extracted`.
+    status: Option<String>,
+    /// Most files listed (default 200).
     limit: Option<usize>,
 }
 
-async fn list_symbols(
+#[utoipa::path(
+    get,
+    path = "/api/v1/index/coverage",
+    tag = "index",
+    params(CoverageQuery),
+    responses((status = 200, description = "Files by extraction status, overall and per language, plus files left out of the index", body = coverage::CoverageReport), AppError),
+    security(("session" = [])),
+)]
+async fn index_coverage(
     State(state): State<AppState>,
     headers: HeaderMap,
-    Query(params): Query<SymbolListQuery>,
+    Query(params): Query<CoverageQuery>,
 ) -> Result<Json<Value>, AppError> {
+    use coverage::CoverageStatus;
     let project = require_project(&state, &headers)?;
-    let kind_filter = params.kind.as_deref().and_then(SymbolKind::from_str);
-    let limit = params.limit.unwrap_or(100);
-    let results = symbol_ops::list_symbols(
-        &project.symbol_table,
-        kind_filter,
-        params.file.as_deref(),
-        limit,
+    let statuses: HashSet<CoverageStatus> = match params.status.as_deref() {
+        None => [
+            CoverageStatus::Empty,
+            CoverageStatus::Unsupported,
+            CoverageStatus::Failed,
+            CoverageStatus::Pending,
+        ]
+        .into(),
+        Some("all") => CoverageStatus::NAMES
+            .iter()
+            .filter_map(|name| CoverageStatus::from_str(name))
+            .collect(),
+        Some(raw) => raw
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                CoverageStatus::from_str(s).ok_or_else(|| {
+                    AppError::BadRequest(format!(
+                        "Unknown status '{}'. Valid: {}, all",
+                        s,
+                        CoverageStatus::NAMES.join(", ")
+                    ))
+                })
+            })
+            .collect::<Result<_, _>>()?,
+    };
+    let limit = params.limit.unwrap_or(config::COVERAGE_LIST_LIMIT);
+    let max_file_size = state.inner.max_file_size;
+    let indexed = project.clone();
+    let report = state
+        .inner
+        .blocking
+        .run(move || {
+            coverage::coverage_report(
+                &indexed.root,
+                &indexed.file_tree,
+                &indexed.symbol_table,
+                &indexed.extraction_errors,
+                max_file_size,
+                &statuses,
+                limit,
+            )
+        })
+        .await?;
+    let preview = format!(
+        "{} files: {} extracted, {} empty, {} unsupported, {} failed, {} pending",
+        report.totals.files,
+        report.totals.extracted,
+        report.totals.empty,
+        report.totals.unsupported,
+        report.totals.failed,
+        report.totals.pending
+    );
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/index/coverage", &preview);
+    Ok(Json(serde_json::to_value(report).unwrap()))
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ReindexBody {
+    /// Project-relative paths to re-read from disk.
+    #[serde(deserialize_with = "rel_path::deserialize_vec")]
+    files: Vec<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/index/partial",
+    tag = "index",
+    request_body = ReindexBody,
+    responses((status = 200, description = "Counts of re-indexed files and symbols, plus per-file errors", body = symbol_ops::ReindexResult), AppError),
+    security(("session" = [])),
+)]
+async fn reindex_partial(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<ReindexBody>,
+) -> Result<Json<symbol_ops::ReindexResult>, AppError> {
+    let project = require_project(&state, &headers)?;
+    if body.files.is_empty() {
+        return Err(AppError::BadRequest("'files' must list at least one path".into()));
+    }
+    let max_file_size = state.inner.max_file_size;
+    let indexed = project.clone();
+    let result = state
+        .inner
+        .blocking
+        .run(move || {
+            let mut result = symbol_ops::reindex_files(
+                &indexed.root,
+                &body.files,
+                &indexed.file_tree,
+                &indexed.symbol_table,
+                &indexed.extraction_errors,
+                max_file_size,
+            );
+            if result.files_reindexed > 0 {
+                let id = indexed.symbol_snapshots.lock().record("reindex", &indexed.symbol_table);
+                result.snapshot_id = Some(id);
+            }
+            result
+        })
+        .await?;
+    let preview = format!(
+        "{} files, +{} -{} symbols",
+        result.files_reindexed, result.symbols_added, result.symbols_removed
+    );
+    record_history(&state, session_id(&headers).as_deref(), "POST", "/index/partial", &preview);
+    Ok(Json(result))
+}
+
+#[derive(Deserialize, ToSchema)]
+struct AddFileBody {
+    /// Project-relative path of a file on disk.
+    #[serde(deserialize_with = "rel_path::deserialize")]
+    file: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/index/add_file",
+    tag = "index",
+    request_body = AddFileBody,
+    responses((status = 200, description = "The file's language and how many symbols were extracted", body = symbol_ops::AddFileResult), AppError),
+    security(("session" = [])),
+)]
+async fn add_file(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<AddFileBody>,
+) -> Result<Json<symbol_ops::AddFileResult>, AppError> {
+    let project = require_project(&state, &headers)?;
+    if !project.root.join(&body.file).exists() {
+        return Err(AppError::NotFound(format!(
+            "File '{}' does not exist on disk",
+            body.file
+        )));
+    }
+    let max_file_size = state.inner.max_file_size;
+    let indexed = project.clone();
+    let file = body.file.clone();
+    let result = state
+        .inner
+        .blocking
+        .run(move || {
+            let mut result = symbol_ops::add_file(
+                &indexed.root,
+                &file,
+                &indexed.file_tree,
+                &indexed.symbol_table,
+                &indexed.extraction_errors,
+                max_file_size,
+            )?;
+            let id = indexed.symbol_snapshots.lock().record("reindex", &indexed.symbol_table);
+            result.snapshot_id = Some(id);
+            Ok(result)
+        })
+        .await?
+        .map_err(AppError::BadRequest)?;
+    let preview = format!("{}: {} symbols", result.file, result.symbols_extracted);
+    record_history(&state, session_id(&headers).as_deref(), "POST", "/index/add_file", &preview);
+    Ok(Json(result))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct WatchFileQuery {
+    #[serde(deserialize_with = "rel_path::deserialize")]
+    file: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/index/watch_file",
+    tag = "index",
+    params(WatchFileQuery),
+    responses((status = 200, description = "Watch registered (or already active)", body = Value), AppError),
+    security(("session" = [])),
+)]
+async fn watch_file(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(q): Query<WatchFileQuery>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+
+    if project.file_watches.contains_key(&q.file) {
+        return Ok(Json(json!({ "file": q.file, "watching": true, "already_watching": true })));
+    }
+    if !project.file_tree.files.contains_key(&q.file) {
+        return Err(AppError::NotFound(format!("File '{}' not found", q.file)));
+    }
+    let slot = watch::WatchSlot::reserve(&state.inner.file_watch_slots, config::MAX_FILE_WATCHES)
+        .ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "Per-file watch limit reached ({}); unwatch a file first",
+                config::MAX_FILE_WATCHES
+            ))
+        })?;
+
+    let handle = watch::watch_single_file(
+        &project.root,
+        &q.file,
+        project.symbol_table.clone(),
+        project.file_tree.clone(),
+        project.extraction_errors.clone(),
+        state.inner.max_file_size,
+        slot,
+    )
+    .map_err(AppError::Internal)?;
+    project.file_watches.insert(q.file.clone(), handle);
+
+    record_history(&state, session_id(&headers).as_deref(), "POST", "/index/watch_file", &q.file);
+    Ok(Json(json!({ "file": q.file, "watching": true, "already_watching": false })))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/index/watch_file",
+    tag = "index",
+    params(WatchFileQuery),
+    responses((status = 200, description = "Watch removed", body = Value), AppError),
+    security(("session" = [])),
+)]
+async fn unwatch_file(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(q): Query<WatchFileQuery>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    project
+        .file_watches
+        .remove(&q.file)
+        .ok_or_else(|| AppError::NotFound(format!("No watch registered for '{}'", q.file)))?;
+    record_history(&state, session_id(&headers).as_deref(), "DELETE", "/index/watch_file", &q.file);
+    Ok(Json(json!({ "file": q.file, "watching": false })))
+}
+
+// ---------------------------------------------------------------------------
+// Git (read-only)
+// ---------------------------------------------------------------------------
+
+fn git_error(e: git::GitError) -> AppError {
+    match e {
+        git::GitError::NotARepository(msg) | git::GitError::Failed(msg) => {
+            AppError::BadRequest(msg)
+        }
+        git::GitError::Unavailable(msg) => AppError::Internal(msg),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/git/status",
+    tag = "git",
+    responses((status = 200, description = "Changed files under the project root that are in the index, plus deletions", body = GitStatus), AppError),
+    security(("session" = [])),
+)]
+async fn git_status(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<GitStatus>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let binary = state.inner.git_binary.clone();
+    let indexed = project.clone();
+    let status = state
+        .inner
+        .blocking
+        .run(move || git::Git::open(&binary, &indexed.root)?.status(&indexed.file_tree))
+        .await?
+        .map_err(git_error)?;
+    let preview = format!(
+        "{} changed files on {}",
+        status.count,
+        status.branch.as_deref().unwrap_or("no branch")
+    );
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/git/status", &preview);
+    Ok(Json(status))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct GitDiffQuery {
+    /// File to diff, relative to the project root; omit for a per-file stat
+    /// summary of the whole project
+    #[serde(default, deserialize_with = "rel_path::deserialize_option")]
+    path: Option<String>,
+    /// Ref to compare the working tree against
+    #[serde(default = "default_git_base")]
+    #[param(default = "HEAD")]
+    base: String,
+}
+
+fn default_git_base() -> String {
+    "HEAD".to_string()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/git/diff",
+    tag = "git",
+    params(GitDiffQuery),
+    responses((status = 200, description = "`GitFileDiff` for one `path`, otherwise a `GitDiffStat` summary", body = Value), AppError),
+    security(("session" = [])),
+)]
+async fn git_diff(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<GitDiffQuery>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let binary = state.inner.git_binary.clone();
+    let root = project.root.clone();
+    let base = params.base.clone();
+    let path = params.path.clone();
+    let (body, preview) = state
+        .inner
+        .blocking
+        .run(move || {
+            let git = git::Git::open(&binary, &root)?;
+            Ok::<_, git::GitError>(match path {
+                Some(path) => {
+                    let diff = git.diff_file(&base, &path)?;
+                    let preview = format!("{} vs {} ({} bytes)", path, base, diff.diff.len());
+                    (serde_json::to_value(diff).unwrap(), preview)
+                }
+                None => {
+                    let stat = git.diff_stat(&base)?;
+                    let preview = format!(
+                        "{} files changed vs {}, +{} -{}",
+                        stat.files_changed, base, stat.insertions, stat.deletions
+                    );
+                    (serde_json::to_value(stat).unwrap(), preview)
+                }
+            })
+        })
+        .await?
+        .map_err(git_error)?;
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/git/diff", &preview);
+    Ok(Json(body))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ChangedSymbolsQuery {
+    /// Ref the changes are measured from
+    #[serde(default = "default_git_base")]
+    #[param(default = "HEAD")]
+    base: String,
+    /// Ref with the changes; omit to use the working tree, which is what
+    /// the index reflects
+    head: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/git/changed_symbols",
+    tag = "git",
+    params(ChangedSymbolsQuery),
+    responses((status = 200, description = "Indexed symbols intersecting the diff's hunks, with per-symbol and per-file line counts", body = ChangedSymbols), AppError),
+    security(("session" = [])),
+)]
+async fn changed_symbols(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<ChangedSymbolsQuery>,
+) -> Result<Json<ChangedSymbols>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let binary = state.inner.git_binary.clone();
+    let indexed = project.clone();
+    let (base, head) = (params.base.clone(), params.head.clone());
+    let changed = state
+        .inner
+        .blocking
+        .run(move || {
+            git::Git::open(&binary, &indexed.root)?.changed_symbols(
+                &base,
+                head.as_deref(),
+                &indexed.symbol_table,
+            )
+        })
+        .await?
+        .map_err(git_error)?;
+    let preview = format!(
+        "{} symbols in {} files changed since {}",
+        changed.count,
+        changed.files.len(),
+        params.base
     );
-    let preview = format!("{} symbols", results.len());
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/git/changed_symbols", &preview);
+    Ok(Json(changed))
+}
+
+// ---------------------------------------------------------------------------
+// Symbols
+// ---------------------------------------------------------------------------
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SymbolListQuery {
+    /// One kind or a comma-separated list, e.g. `function,method`.
+    kind: Option<String>,
+    #[serde(default, deserialize_with = "rel_path::deserialize_option")]
+    file: Option<String>,
+    /// Only files carrying this mark
+    mark: Option<String>,
+    /// Skip files carrying this mark
+    exclude_mark: Option<String>,
+    limit: Option<usize>,
+    /// Skip this many matches; use `next_offset` from the previous page
+    offset: Option<usize>,
+    /// Only members of this parent, e.g. the methods of a type
+    parent: Option<String>,
+    /// Return only how many symbols of each kind match, not the symbols
+    kinds_summary: Option<bool>,
+    /// Include symbols from generated code
+    include_generated: Option<bool>,
+    /// List across every project attached to the session
+    all_projects: Option<bool>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/symbols",
+    tag = "symbols",
+    params(SymbolListQuery),
+    responses((status = 200, description = "`{ symbols: [Symbol], count, total_count, next_offset? }`; with `all_projects`, each symbol carries its `project` alias; with `kinds_summary`, `{ kinds: [KindCount], total_count }`; with `parent`, also `parent_status` (404 when the parent is unknown)", body = Value), AppError),
+    security(("session" = [])),
+)]
+async fn list_symbols(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<SymbolListQuery>,
+) -> Result<Json<Value>, AppError> {
+    let kind_filter = params
+        .kind
+        .as_deref()
+        .map(parse_kinds)
+        .transpose()?
+        .filter(|kinds| !kinds.is_empty());
+    let marks = parse_mark_filter(params.mark.as_deref(), params.exclude_mark.as_deref())?;
+    let limit = params.limit.unwrap_or(100);
+    let offset = params.offset.unwrap_or(0);
+    let include_generated = params.include_generated.unwrap_or(false);
+    let projects = if params.all_projects.unwrap_or(false) {
+        require_session_projects(&state, &headers)?
+    } else {
+        vec![(String::new(), require_project(&state, &headers)?)]
+    };
+    let tag_project = params.all_projects.unwrap_or(false);
+    let parent_status = params
+        .parent
+        .as_deref()
+        .map(|parent| check_parent(projects.iter().map(|(_, p)| p), parent))
+        .transpose()?;
+    let filter = symbol_ops::ListFilter {
+        kinds: kind_filter.as_ref(),
+        file: params.file.as_deref(),
+        files: None,
+        parent: params.parent.as_deref(),
+        include_generated,
+    };
+
+    if params.kinds_summary.unwrap_or(false) {
+        let mut totals: Vec<symbol_ops::KindCount> = Vec::new();
+        for (_, project) in &projects {
+            let files = project.file_tree.paths_matching(&marks);
+            for count in symbol_ops::count_symbol_kinds(
+                &project.symbol_table,
+                symbol_ops::ListFilter { files: files.as_ref(), ..filter },
+            ) {
+                match totals.iter_mut().find(|t| t.kind == count.kind) {
+                    Some(total) => total.count += count.count,
+                    None => totals.push(count),
+                }
+            }
+        }
+        totals.sort_by_key(|k| (std::cmp::Reverse(k.count), k.kind as u8));
+        let total_count: usize = totals.iter().map(|k| k.count).sum();
+        let preview = format!("{} symbols in {} kinds", total_count, totals.len());
+        record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols", &preview);
+        let mut body = json!({ "kinds": totals, "total_count": total_count });
+        if let Some(status) = parent_status {
+            body["parent_status"] = json!(status);
+        }
+        return Ok(Json(body));
+    }
+
+    // Projects are paged as one list, in attach order.
+    let mut symbols = Vec::new();
+    let mut total_count = 0;
+    for (alias, project) in &projects {
+        let files = project.file_tree.paths_matching(&marks);
+        let page = symbol_ops::list_symbols(
+            &project.symbol_table,
+            symbol_ops::ListFilter { files: files.as_ref(), ..filter },
+            offset.saturating_sub(total_count),
+            limit - symbols.len(),
+        );
+        total_count += page.total_count;
+        symbols.extend(page.symbols.into_iter().map(|sym| {
+            if tag_project {
+                with_project(sym, alias)
+            } else {
+                serde_json::to_value(sym).unwrap()
+            }
+        }));
+    }
+    let preview = if tag_project {
+        format!("{} symbols across projects", symbols.len())
+    } else {
+        format!("{} symbols", symbols.len())
+    };
     record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols", &preview);
-    Ok(Json(json!({ "symbols": results, "count": results.len() })))
+    let mut body = json!({
+        "symbols": symbols,
+        "count": symbols.len(),
+        "total_count": total_count,
+    });
+    if offset + symbols.len() < total_count {
+        body["next_offset"] = json!(offset + symbols.len());
+    }
+    if let Some(status) = parent_status {
+        body["parent_status"] = json!(status);
+    }
+    Ok(Json(body))
+}
+
+/// What `projects` know of a `parent` filter, at best. A parent none of
+/// them has heard of is a 404 with close names, so a typo isn't mistaken
+/// for a type without members.
+fn check_parent<'a>(
+    projects: impl Iterator<Item = &'a Arc<Project>> + Clone,
+    parent: &str,
+) -> Result<symbol_ops::ParentStatus, AppError> {
+    let best = projects
+        .clone()
+        .map(|project| symbol_ops::parent_status(&project.symbol_table, parent))
+        .max()
+        .unwrap_or(symbol_ops::ParentStatus::Unknown);
+    if best == symbol_ops::ParentStatus::Unknown {
+        return Err(AppError::NotFoundWithSuggestions {
+            message: format!("Unknown parent '{}': no symbol has that name or parent", parent),
+            suggestions: projects
+                .flat_map(|project| symbol_ops::suggest_symbols(&project.symbol_table, parent))
+                .take(config::SUGGESTION_LIMIT)
+                .collect(),
+        });
+    }
+    Ok(best)
+}
+
+/// Serialize a symbol with the alias of the attached project it came from.
+fn with_project(sym: impl serde::Serialize, alias: &str) -> Value {
+    let mut value = serde_json::to_value(sym).unwrap();
+    if let Some(obj) = value.as_object_mut() {
+        obj.insert("project".into(), json!(alias));
+    }
+    value
+}
+
+/// Parse a comma-separated `kind` parameter. Unknown kinds are an error
+/// rather than a silently dropped filter.
+fn parse_kinds(raw: &str) -> Result<HashSet<SymbolKind>, AppError> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|k| !k.is_empty())
+        .map(|k| {
+            SymbolKind::from_str(k).ok_or_else(|| {
+                AppError::BadRequest(format!(
+                    "Unknown symbol kind '{}'. Valid: {}",
+                    k,
+                    SymbolKind::NAMES.join(", ")
+                ))
+            })
+        })
+        .collect()
+}
+
+/// Parse `mark` / `exclude_mark` parameters into a file filter.
+fn parse_mark_filter(
+    mark: Option<&str>,
+    exclude_mark: Option<&str>,
+) -> Result<MarkFilter, AppError> {
+    let parse = |raw: &str| {
+        FileMark::from_str(raw).ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "Unknown mark '{}'. Valid: documentation, ignore, test, config, generated, custom",
+                raw
+            ))
+        })
+    };
+    Ok(MarkFilter {
+        include: mark.map(parse).transpose()?,
+        exclude: exclude_mark.map(parse).transpose()?,
+    })
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SymbolCompleteQuery {
+    /// Start of the name, matched ignoring case
+    prefix: String,
+    /// Most names returned (default 20, at most 200)
+    limit: Option<usize>,
+}
+
+/// Not recorded in the session history: an editor calls it per keystroke.
+#[utoipa::path(
+    get,
+    path = "/api/v1/symbols/complete",
+    tag = "symbols",
+    params(SymbolCompleteQuery),
+    responses((status = 200, description = "Names starting with `prefix`, most referenced first, each with the kind and file of its definition", body = symbol_ops::Completions), AppError),
+    security(("session" = [])),
+)]
+async fn complete_symbols(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<SymbolCompleteQuery>,
+) -> Result<Json<symbol_ops::Completions>, AppError> {
+    if params.prefix.is_empty() {
+        return Err(AppError::BadRequest("'prefix' must not be empty".into()));
+    }
+    let project = require_project(&state, &headers)?;
+    let limit = params
+        .limit
+        .unwrap_or(config::SYMBOL_COMPLETE_DEFAULT_LIMIT)
+        .clamp(1, config::SYMBOL_COMPLETE_MAX_LIMIT);
+    Ok(Json(symbol_ops::complete_symbols(&project.symbol_table, &params.prefix, limit)))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct SymbolSearchQuery {
     q: String,
+    /// One kind or a comma-separated list, e.g. `function,method`.
+    kind: Option<String>,
+    /// Only files carrying this mark
+    mark: Option<String>,
+    /// Skip files carrying this mark
+    exclude_mark: Option<String>,
+    /// Only members of this parent, e.g. the methods of a type
+    parent: Option<String>,
     limit: Option<usize>,
+    /// Include symbols from generated code
+    include_generated: Option<bool>,
+    /// Search every project attached to the session
+    all_projects: Option<bool>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/symbols/search",
+    tag = "symbols",
+    params(SymbolSearchQuery),
+    responses((status = 200, description = "`{ symbols: [Symbol], count }`; without a session, every indexed project is searched, best matches first, and each symbol carries its `project` root and `score`; with `all_projects`, the session's attached projects are searched and each symbol carries its `project` alias; with `parent`, also `parent_status` (404 when the parent is unknown)", body = Value), AppError),
+    security((), ("session" = [])),
+)]
 async fn search_symbols(
     State(state): State<AppState>,
     headers: HeaderMap,
     Query(params): Query<SymbolSearchQuery>,
 ) -> Result<Json<Value>, AppError> {
-    let project = require_project(&state, &headers)?;
+    let kind_filter = params
+        .kind
+        .as_deref()
+        .map(parse_kinds)
+        .transpose()?
+        .filter(|kinds| !kinds.is_empty());
+    let marks = parse_mark_filter(params.mark.as_deref(), params.exclude_mark.as_deref())?;
     let limit = params.limit.unwrap_or(20);
-    let results = symbol_ops::search_symbols(&project.symbol_table, &params.q, limit);
+    let include_generated = params.include_generated.unwrap_or(false);
+
+    let parent = params.parent.as_deref();
+    let with_parent_status = |mut body: Value, status: Option<symbol_ops::ParentStatus>| {
+        if let Some(status) = status {
+            body["parent_status"] = json!(status);
+        }
+        Json(body)
+    };
+
+    if session_id(&headers).is_none() {
+        let projects: Vec<Arc<Project>> =
+            state.inner.projects.iter().map(|e| e.value().clone()).collect();
+        let parent_status = parent.map(|p| check_parent(projects.iter(), p)).transpose()?;
+        let search = symbol_ops::ProjectSearch {
+            query: &params.q,
+            kind_filter: kind_filter.as_ref(),
+            parent,
+            marks: &marks,
+            include_generated,
+        };
+        let symbols = symbol_ops::search_all_projects(&state, &search, limit);
+        let body = json!({
+            "symbols": symbols,
+            "count": symbols.len(),
+            "projects": state.inner.projects.len(),
+        });
+        return Ok(with_parent_status(body, parent_status));
+    }
+
+    if params.all_projects.unwrap_or(false) {
+        let projects = require_session_projects(&state, &headers)?;
+        let parent_status = parent
+            .map(|p| check_parent(projects.iter().map(|(_, project)| project), p))
+            .transpose()?;
+        let mut symbols = Vec::new();
+        for (alias, project) in projects {
+            let files = project.file_tree.paths_matching(&marks);
+            let results = symbol_ops::search_symbols(
+                &project.symbol_table,
+                &params.q,
+                kind_filter.as_ref(),
+                files.as_ref(),
+                parent,
+                include_generated,
+                limit - symbols.len(),
+            );
+            symbols.extend(results.into_iter().map(|sym| with_project(sym, &alias)));
+            if symbols.len() >= limit {
+                break;
+            }
+        }
+        let preview = format!("{} matches for '{}' across projects", symbols.len(), params.q);
+        record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols/search", &preview);
+        let body = json!({ "symbols": symbols, "count": symbols.len() });
+        return Ok(with_parent_status(body, parent_status));
+    }
+
+    let project = require_project(&state, &headers)?;
+    let parent_status = parent
+        .map(|p| check_parent(std::iter::once(&project), p))
+        .transpose()?;
+    let files = project.file_tree.paths_matching(&marks);
+    let results = symbol_ops::search_symbols(
+        &project.symbol_table,
+        &params.q,
+        kind_filter.as_ref(),
+        files.as_ref(),
+        parent,
+        include_generated,
+        limit,
+    );
     let preview = format!("{} matches for '{}'", results.len(), params.q);
     record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols/search", &preview);
-    Ok(Json(json!({ "symbols": results, "count": results.len() })))
+    let body = json!({ "symbols": results, "count": results.len() });
+    Ok(with_parent_status(body, parent_status))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ResolveQuery {
+    /// Qualified name, e.g. `crate::ops::repl::buffer_create` or
+    /// `foo.bar.Baz`
+    name: String,
+    /// Most candidates returned (default 20)
+    limit: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/symbols/resolve",
+    tag = "symbols",
+    params(ResolveQuery),
+    responses((status = 200, description = "`{ name, segments, candidates: [ResolvedCandidate], count }`, best first", body = Value), AppError),
+    security(("session" = [])),
+)]
+async fn resolve_symbol(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<ResolveQuery>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let segments = symbol_ops::split_qualified_name(&params.name);
+    if segments.is_empty() {
+        return Err(AppError::BadRequest(format!(
+            "No name segments in '{}'",
+            params.name
+        )));
+    }
+    let limit = params.limit.unwrap_or(20);
+    let candidates = symbol_ops::resolve_qualified_name(&project.symbol_table, &params.name, limit);
+    let preview = match candidates.first() {
+        Some(best) => format!(
+            "{} candidates for '{}', best {}::{}",
+            candidates.len(),
+            params.name,
+            best.symbol.file,
+            best.symbol.name
+        ),
+        None => format!("0 candidates for '{}'", params.name),
+    };
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols/resolve", &preview);
+    Ok(Json(json!({
+        "name": params.name,
+        "segments": segments,
+        "count": candidates.len(),
+        "candidates": candidates,
+    })))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct GlobalSearchQuery {
+    q: String,
+    /// One kind or a comma-separated list, e.g. `struct,class`.
+    kinds: Option<String>,
+    /// Most matches from any one project (default 20)
+    per_project: Option<usize>,
+    /// Most matches in total (default 100)
+    limit: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/search/global",
+    tag = "symbols",
+    params(GlobalSearchQuery),
+    responses((status = 200, description = "`{ matches: [GlobalMatch], count, projects, truncated }`, ordered by project, then score", body = Value), AppError),
+    security((), ("session" = [])),
+)]
+async fn search_global(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<GlobalSearchQuery>,
+) -> Result<Json<Value>, AppError> {
+    let kind_filter = params
+        .kinds
+        .as_deref()
+        .map(parse_kinds)
+        .transpose()?
+        .filter(|kinds| !kinds.is_empty());
+    let per_project = params.per_project.unwrap_or(20);
+    let limit = params.limit.unwrap_or(100);
+    let projects = state.inner.projects.len();
+    let query = params.q.clone();
+    let search_state = state.clone();
+    let (matches, truncated) = state
+        .inner
+        .blocking
+        .run(move || {
+            symbol_ops::search_global(&search_state, &query, kind_filter.as_ref(), per_project, limit)
+        })
+        .await?;
+    // A session is optional and only used for history.
+    let preview = format!("{} matches for '{}' in {} projects", matches.len(), params.q, projects);
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/search/global", &preview);
+    Ok(Json(json!({
+        "matches": matches,
+        "count": matches.len(),
+        "projects": projects,
+        "truncated": truncated,
+    })))
+}
+
+#[derive(Deserialize, ToSchema)]
 struct SymbolDefineRequest {
     symbol: String,
+    #[serde(deserialize_with = "rel_path::deserialize")]
     file: String,
     definition: String,
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct UpsertQuery {
+    /// Succeed whether or not a definition exists, reporting which happened
+    upsert: Option<bool>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/symbols/define",
+    tag = "symbols",
+    params(UpsertQuery),
+    request_body = SymbolDefineRequest,
+    responses((status = 200, description = "Definition set", body = Value), AppError),
+    security(("session" = [])),
+)]
 async fn define_symbol(
     State(state): State<AppState>,
     headers: HeaderMap,
+    Query(q): Query<UpsertQuery>,
     Json(body): Json<SymbolDefineRequest>,
 ) -> Result<Json<Value>, AppError> {
     let project = require_project(&state, &headers)?;
-    symbol_ops::define_symbol(
+    let upsert = q.upsert.unwrap_or(false);
+    let previous = symbol_ops::define_symbol(
         &project.symbol_table,
         &body.symbol,
         &body.file,
         &body.definition,
+        upsert,
     )
     .map_err(AppError::BadRequest)?;
-    record_history(&state, session_id(&headers).as_deref(), "POST", "/symbols/define", &body.symbol);
-    Ok(Json(json!({ "ok": true })))
+    project.mark_annotations_dirty();
+    record_annotation(
+        &state,
+        &headers,
+        AnnotationAction::Define,
+        &body.file,
+        Some(&body.symbol),
+    );
+    let preview = define_preview(&body.symbol, &previous, upsert);
+    record_history(&state, session_id(&headers).as_deref(), "POST", "/symbols/define", &preview);
+    Ok(define_response(previous, upsert))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/symbols/redefine",
+    tag = "symbols",
+    params(UpsertQuery),
+    request_body = SymbolDefineRequest,
+    responses((status = 200, description = "Definition replaced", body = Value), AppError),
+    security(("session" = [])),
+)]
 async fn redefine_symbol(
     State(state): State<AppState>,
     headers: HeaderMap,
+    Query(q): Query<UpsertQuery>,
     Json(body): Json<SymbolDefineRequest>,
 ) -> Result<Json<Value>, AppError> {
     let project = require_project(&state, &headers)?;
-    symbol_ops::redefine_symbol(
+    let upsert = q.upsert.unwrap_or(false);
+    let previous = symbol_ops::redefine_symbol(
         &project.symbol_table,
         &body.symbol,
         &body.file,
         &body.definition,
     )
     .map_err(AppError::BadRequest)?;
-    record_history(&state, session_id(&headers).as_deref(), "POST", "/symbols/redefine", &body.symbol);
-    Ok(Json(json!({ "ok": true })))
+    project.mark_annotations_dirty();
+    record_annotation(
+        &state,
+        &headers,
+        AnnotationAction::Redefine,
+        &body.file,
+        Some(&body.symbol),
+    );
+    let preview = define_preview(&body.symbol, &previous, upsert);
+    record_history(&state, session_id(&headers).as_deref(), "POST", "/symbols/redefine", &preview);
+    Ok(define_response(previous, upsert))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SignatureQuery {
+    symbol: String,
+    #[serde(deserialize_with = "rel_path::deserialize")]
+    file: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/symbols/signature",
+    tag = "symbols",
+    params(SignatureQuery),
+    responses((status = 200, description = "`{ symbol, file, kind, signature, return_type, line_range }`", body = Value), AppError),
+    security(("session" = [])),
+)]
+async fn get_signature(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<SignatureQuery>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let (sym, folded) = lookup_symbol(&project, &params.file, &params.symbol)?;
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols/signature", &sym.signature);
+    let mut body = json!({
+        "symbol": sym.name,
+        "file": sym.file,
+        "kind": sym.kind,
+        "signature": sym.signature,
+        "return_type": sym.return_type,
+        "line_range": sym.line_range,
+    });
+    mark_case_insensitive(&mut body, folded);
+    Ok(Json(body))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ByHashQuery {
+    /// A symbol's `signature_hash`.
+    hash: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/symbols/by_hash",
+    tag = "symbols",
+    params(ByHashQuery),
+    responses((status = 200, description = "`{ symbol: Symbol, matches }`; `matches` > 1 means other symbols share the signature", body = Value), AppError),
+    security(("session" = [])),
+)]
+async fn get_symbol_by_hash(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<ByHashQuery>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let matches = project.symbol_table.list_by_signature_hash(&params.hash);
+    let sym = project
+        .symbol_table
+        .get_by_signature_hash(&params.hash)
+        .ok_or_else(|| {
+            AppError::NotFound(format!("No symbol with signature hash '{}'", params.hash))
+        })?;
+    let preview = format!("{}::{}", sym.file, sym.name);
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols/by_hash", &preview);
+    Ok(Json(json!({ "symbol": sym, "matches": matches.len() })))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct ImplementationQuery {
     symbol: String,
+    #[serde(deserialize_with = "rel_path::deserialize")]
     file: String,
     meta: Option<bool>,
+    /// Attached project (alias or root) the file belongs to; defaults to
+    /// the session's primary project. A `alias:path` file works too.
+    project: Option<String>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/symbols/implementation",
+    tag = "symbols",
+    params(ImplementationQuery),
+    responses((status = 200, description = "`{ symbol, file, source }`, or metadata plus buffer name when `meta=true`", body = Value), AppError),
+    security(("session" = [])),
+)]
 async fn get_implementation(
     State(state): State<AppState>,
     headers: HeaderMap,
     Query(params): Query<ImplementationQuery>,
 ) -> Result<Json<Value>, AppError> {
-    let project = require_project(&state, &headers)?;
-    let source = symbol_ops::get_implementation(
-        &project.root,
-        &project.symbol_table,
-        &params.symbol,
-        &params.file,
-    )
-    .map_err(AppError::NotFound)?;
-    let preview = format!("{}::{} ({} bytes)", params.file, params.symbol, source.len());
+    let (project, file) =
+        require_project_for(&state, &headers, params.project.as_deref(), &params.file)?;
+    let (symbol, folded) = resolve_symbol_name(&project, &file, &params.symbol);
+    let source = symbol_ops::get_implementation(&project, &symbol, &file)
+        .map_err(|e| symbol_not_found(&project, &symbol, &file, e))?;
+    let preview = format!("{}::{} ({} bytes)", file, symbol, source.len());
     record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols/implementation", &preview);
+    let repl = require_repl(&state, &headers)?;
+    symbol_ops::record_access(&repl, &file, &symbol);
 
     if params.meta.unwrap_or(false) {
-        let repl = require_repl(&state, &headers)?;
-        let buffer_name = format!("impl::{}::{}", params.file, params.symbol);
+        let buffer_name = format!("impl::{}::{}", file, symbol);
 
         // Look up symbol for line range info
-        let sym = project.symbol_table.get(&params.file, &params.symbol)
-            .ok_or_else(|| AppError::NotFound(format!("Symbol '{}' not found in '{}'", params.symbol, params.file)))?;
+        let sym = project.symbol_table.get(&file, &symbol)
+            .ok_or_else(|| AppError::NotFound(format!("Symbol '{}' not found in '{}'", symbol, file)))?;
 
         // Auto-create buffer with full source
         repl::buffer_create(&repl, &buffer_name, source.clone(),
-            &format!("impl of {} in {}", params.symbol, params.file));
+            &format!("impl of {} in {}", symbol, file));
 
         let meta_preview = if source.len() > 100 {
             format!("{}...", &source[..source.floor_char_boundary(100)])
@@ -489,111 +2473,871 @@ async fn get_implementation(
             source.clone()
         };
 
-        Ok(Json(json!({
-            "symbol": params.symbol,
-            "file": params.file,
+        let mut body = json!({
+            "symbol": symbol,
+            "file": file,
             "lines": [sym.line_range.0, sym.line_range.1],
             "bytes": source.len(),
             "preview": meta_preview,
             "buffer": buffer_name,
-        })))
+        });
+        mark_case_insensitive(&mut body, folded);
+        Ok(Json(body))
     } else {
-        Ok(Json(json!({
-            "symbol": params.symbol,
-            "file": params.file,
+        let mut body = json!({
+            "symbol": symbol,
+            "file": file,
             "source": source,
-        })))
+        });
+        mark_case_insensitive(&mut body, folded);
+        Ok(Json(body))
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SymbolGrepQuery {
+    symbol: String,
+    #[serde(deserialize_with = "rel_path::deserialize")]
+    file: String,
+    pattern: String,
+    max_matches: Option<usize>,
+    context_lines: Option<usize>,
+    /// Optional scope filter: "all" (default) or "code" (skip comments/strings).
+    scope: Option<String>,
+    /// With `scope=code`, fail with 400 instead of returning unfiltered
+    /// matches from files whose language has no grammar.
+    strict_scope: Option<bool>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/symbols/grep",
+    tag = "symbols",
+    params(SymbolGrepQuery),
+    responses((status = 200, description = "Matches inside the symbol, with absolute file line numbers", body = GrepResponse), AppError),
+    security(("session" = [])),
+)]
+async fn grep_symbol(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<SymbolGrepQuery>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let max_matches = params.max_matches.unwrap_or(50);
+    let context_lines = params.context_lines.unwrap_or(2);
+    let scope = params
+        .scope
+        .as_deref()
+        .and_then(content::GrepScope::from_str)
+        .unwrap_or(content::GrepScope::All);
+
+    if project.symbol_table.get(&params.file, &params.symbol).is_none() {
+        return Err(AppError::NotFound(format!(
+            "Symbol '{}' not found in '{}'",
+            params.symbol, params.file
+        )));
     }
+
+    let result = symbol_ops::grep_symbol(
+        &project.root,
+        &project.symbol_table,
+        &params.symbol,
+        &params.file,
+        &params.pattern,
+        max_matches,
+        context_lines,
+        scope,
+    )
+    .map_err(AppError::BadRequest)?;
+    check_strict_scope(params.strict_scope, &result)?;
+
+    let preview = format!(
+        "{} matches for '{}' in {}::{}",
+        result.total_matches, params.pattern, params.file, params.symbol
+    );
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols/grep", &preview);
+    Ok(Json(serde_json::to_value(result).unwrap()))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct TestsQuery {
     symbol: String,
+    #[serde(deserialize_with = "rel_path::deserialize")]
+    file: String,
+    limit: Option<usize>,
+    /// Include each test's source
+    include_source: Option<bool>,
+    /// Per-test source cap in bytes when `include_source=true`
+    #[serde(default = "default_test_source_bytes")]
+    #[param(default = 2000)]
+    max_bytes: usize,
+    meta: Option<bool>,
+}
+
+fn default_test_source_bytes() -> usize {
+    2000
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/symbols/tests",
+    tag = "symbols",
+    params(TestsQuery),
+    responses((status = 200, description = "`{ tests: [TestInfo], count }`", body = Value), AppError),
+    security(("session" = [])),
+)]
+async fn find_tests(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<TestsQuery>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let limit = params.limit.unwrap_or(20);
+    let source_max_bytes = params
+        .include_source
+        .unwrap_or(false)
+        .then_some(params.max_bytes);
+    let tests = symbol_ops::find_tests(
+        &project.root,
+        &project.file_tree,
+        &project.symbol_table,
+        &params.symbol,
+        &params.file,
+        limit,
+        source_max_bytes,
+    )
+    .map_err(AppError::NotFound)?;
+    let preview = format!("{} tests for {}", tests.len(), params.symbol);
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols/tests", &preview);
+
+    if params.meta.unwrap_or(false) {
+        let repl = require_repl(&state, &headers)?;
+        let buffer_name = format!("tests::{}::{}", params.file, params.symbol);
+
+        // Store full results in buffer
+        let full_json = serde_json::to_string_pretty(&tests).unwrap_or_default();
+        repl::buffer_create(&repl, &buffer_name, full_json,
+            &format!("tests for {} in {}", params.symbol, params.file));
+
+        // Return metadata-only (no signature)
+        let meta_tests: Vec<Value> = tests.iter().map(|t| json!({
+            "name": t.name,
+            "file": t.file,
+            "line": t.line,
+        })).collect();
+
+        Ok(Json(json!({
+            "tests": meta_tests,
+            "count": tests.len(),
+            "buffer": buffer_name,
+        })))
+    } else {
+        Ok(Json(json!({ "tests": tests, "count": tests.len() })))
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/symbols/test_coverage_hint",
+    tag = "symbols",
+    params(SignatureQuery),
+    responses((status = 200, description = "Whether test files call the symbol", body = CoverageHint), AppError),
+    security(("session" = [])),
+)]
+async fn test_coverage_hint(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<SignatureQuery>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let hint = symbol_ops::test_coverage_hint(&project.symbol_table, &params.symbol, &params.file)
+        .map_err(AppError::NotFound)?;
+    let preview = format!("{} test files call {}", hint.test_file_count, params.symbol);
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols/test_coverage_hint", &preview);
+    Ok(Json(serde_json::to_value(hint).unwrap()))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ComplianceQuery {
+    /// Rust struct, enum, or type alias to check
+    #[serde(rename = "type")]
+    type_name: String,
+    #[serde(deserialize_with = "rel_path::deserialize")]
+    file: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/symbols/interface_compliance",
+    tag = "symbols",
+    params(ComplianceQuery),
+    responses((status = 200, description = "Traits the type implements, with required, implemented, and missing methods", body = ComplianceReport), AppError),
+    security(("session" = [])),
+)]
+async fn interface_compliance(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<ComplianceQuery>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let sym = project
+        .symbol_table
+        .get(&params.file, &params.type_name)
+        .ok_or_else(|| {
+            let message = format!("Symbol '{}' not found in '{}'", params.type_name, params.file);
+            symbol_not_found(&project, &params.type_name, &params.file, message)
+        })?;
+    if sym.language != Language::Rust {
+        return Err(AppError::BadRequest(format!(
+            "Interface compliance is only supported for Rust, not {:?}",
+            sym.language
+        )));
+    }
+
+    let indexed = project.clone();
+    let type_name = params.type_name.clone();
+    let file = params.file.clone();
+    let report = state
+        .inner
+        .blocking
+        .run(move || {
+            symbol_ops::interface_compliance(
+                &indexed.root,
+                &indexed.file_tree,
+                &indexed.symbol_table,
+                &type_name,
+                &file,
+            )
+        })
+        .await?
+        .map_err(AppError::Internal)?;
+    let missing: usize = report.traits.iter().map(|t| t.missing.len()).sum();
+    let preview = format!(
+        "{} traits for {}, {} missing methods",
+        report.traits.len(),
+        params.type_name,
+        missing
+    );
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols/interface_compliance", &preview);
+    Ok(Json(serde_json::to_value(report).unwrap()))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct BlameQuery {
+    symbol: String,
+    #[serde(deserialize_with = "rel_path::deserialize")]
+    file: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/symbols/blame",
+    tag = "symbols",
+    params(BlameQuery),
+    responses((status = 200, description = "`GitBlame` summary of the symbol's lines, plus `symbol`, `file`, `line_range`, `head`, and `cached`", body = GitBlame), AppError),
+    security(("session" = [])),
+)]
+async fn symbol_blame(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<BlameQuery>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let (sym, folded) = lookup_symbol(&project, &params.file, &params.symbol)?;
+
+    let binary = state.inner.git_binary.clone();
+    let indexed = project.clone();
+    let (symbol, file, line_range) = (sym.name.clone(), sym.file.clone(), sym.line_range);
+    let (mut blame, head, cached) = state
+        .inner
+        .blocking
+        .run(move || {
+            let git = git::Git::open(&binary, &indexed.root)?;
+            let head = git.head();
+            let key = match &head {
+                Some(head) if git.is_clean(&file)? => {
+                    Some((file.clone(), symbol.clone(), line_range, head.clone()))
+                }
+                _ => None,
+            };
+            if let Some(key) = &key
+                && let Some(hit) = indexed.blame_cache.lock().get(key)
+            {
+                return Ok((hit.clone(), head, true));
+            }
+            let blame = git.blame(&file, line_range.0, line_range.1)?;
+            if let Some(key) = key
+                && !blame.dirty
+            {
+                let mut cache = indexed.blame_cache.lock();
+                if cache.len() >= config::BLAME_CACHE_LIMIT {
+                    cache.clear();
+                }
+                cache.insert(key, blame.clone());
+            }
+            Ok::<_, git::GitError>((blame, head, false))
+        })
+        .await?
+        .map_err(git_error)?;
+    blame.oldest_line_age_days = blame
+        .oldest_line_date
+        .map(|date| (chrono::Utc::now() - date).num_days());
+
+    let preview = format!(
+        "{}: {} authors, {} commits{}",
+        sym.name,
+        blame.authors.len(),
+        blame.commits,
+        if blame.dirty { ", uncommitted changes" } else { "" }
+    );
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols/blame", &preview);
+    let mut body = serde_json::to_value(blame).unwrap();
+    if let Some(obj) = body.as_object_mut() {
+        obj.insert("symbol".into(), json!(sym.name));
+        obj.insert("file".into(), json!(sym.file));
+        obj.insert("line_range".into(), json!(sym.line_range));
+        obj.insert("head".into(), json!(head));
+        obj.insert("cached".into(), json!(cached));
+    }
+    mark_case_insensitive(&mut body, folded);
+    Ok(Json(body))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct RecentlyUsedQuery {
+    limit: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/symbols/recently_used",
+    tag = "symbols",
+    params(RecentlyUsedQuery),
+    responses((status = 200, description = "`{ symbols: [SymbolAccess], count }`, most recently read first", body = Value), AppError),
+    security(("session" = [])),
+)]
+async fn recently_used(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<RecentlyUsedQuery>,
+) -> Result<Json<Value>, AppError> {
+    require_project(&state, &headers)?;
+    let repl = require_repl(&state, &headers)?;
+    let symbols = repl.recently_used.lock().recent(params.limit.unwrap_or(20));
+    let preview = format!("{} recently used symbols", symbols.len());
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols/recently_used", &preview);
+    Ok(Json(json!({ "symbols": symbols, "count": symbols.len() })))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SymbolDiffQuery {
+    /// `index` (default) for the snapshot taken when indexing finished, or
+    /// the id of a snapshot taken after a partial re-index
+    since: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/symbols/diff",
+    tag = "symbols",
+    params(SymbolDiffQuery),
+    responses((status = 200, description = "Symbols added, removed, or modified since the snapshot, by file", body = SymbolDiff), AppError),
+    security(("session" = [])),
+)]
+async fn symbol_diff(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<SymbolDiffQuery>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let since = params.since.unwrap_or_else(|| "index".to_string());
+    let indexed = project.clone();
+    let wanted = since.clone();
+    let (diff, snapshots) = state
+        .inner
+        .blocking
+        .run(move || {
+            let snapshots = indexed.symbol_snapshots.lock();
+            let diff = snapshots.get(&wanted).map(|s| s.diff(&indexed.symbol_table));
+            (diff, snapshots.list())
+        })
+        .await?;
+    let Some(diff) = diff else {
+        if snapshots.is_empty() {
+            return Err(AppError::NotFound(
+                "No symbol snapshot yet; indexing has not finished".to_string(),
+            ));
+        }
+        let mut suggestions = vec!["index".to_string()];
+        suggestions.extend(snapshots.iter().skip(1).map(|s| s.id.to_string()));
+        return Err(AppError::NotFoundWithSuggestions {
+            message: format!("No symbol snapshot '{}'", since),
+            suggestions,
+        });
+    };
+
+    let preview = format!(
+        "+{} -{} ~{} symbols since {}",
+        diff.added, diff.removed, diff.modified, since
+    );
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols/diff", &preview);
+    let mut body = serde_json::to_value(diff).unwrap();
+    body["snapshots"] = serde_json::to_value(snapshots).unwrap();
+    Ok(Json(body))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct HistogramQuery {
+    /// `log` (default) for fixed 0, 1-2, 3-5, ... 101+ buckets, or a number
+    /// of equal-width linear buckets
+    buckets: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/symbols/call_frequency_histogram",
+    tag = "symbols",
+    params(HistogramQuery),
+    responses((status = 200, description = "Caller-count distribution over all symbols", body = CallFrequencyHistogram), AppError),
+    security(("session" = [])),
+)]
+async fn call_frequency_histogram(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<HistogramQuery>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let buckets = match params.buckets.as_deref() {
+        None => symbol_ops::HistogramBuckets::Log,
+        Some(s) => symbol_ops::HistogramBuckets::from_str(s).ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "Invalid buckets '{}': expected 'log' or a count from 1 to {}",
+                s,
+                crate::config::HISTOGRAM_MAX_BUCKETS
+            ))
+        })?,
+    };
+    let symbol_table = project.symbol_table.clone();
+    let histogram = state
+        .inner
+        .blocking
+        .run(move || symbol_ops::call_frequency_histogram(&symbol_table, buckets))
+        .await?;
+    let preview = format!(
+        "{} symbols, median {} callers, p95 {}",
+        histogram.symbols, histogram.median, histogram.p95
+    );
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols/call_frequency_histogram", &preview);
+    Ok(Json(serde_json::to_value(histogram).unwrap()))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct LocalOnlyQuery {
+    /// Only symbols defined in files whose path starts with this prefix
+    #[serde(default, deserialize_with = "rel_path::deserialize_option")]
+    path_prefix: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/symbols/local_only",
+    tag = "symbols",
+    params(LocalOnlyQuery),
+    responses((status = 200, description = "Symbols only called from their own file, and symbols never called, grouped by file", body = symbol_ops::LocalOnlyReport), AppError),
+    security(("session" = [])),
+)]
+async fn local_only_symbols(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<LocalOnlyQuery>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let prefix = params.path_prefix.unwrap_or_default();
+    let symbol_table = project.symbol_table.clone();
+    let report = state
+        .inner
+        .blocking
+        .run(move || symbol_ops::local_only_symbols(&symbol_table, &prefix))
+        .await?;
+    let preview = format!(
+        "{} local-only, {} uncalled",
+        report.local_only_count, report.uncalled_count
+    );
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols/local_only", &preview);
+    let mut body = serde_json::to_value(report).unwrap();
+    // The call graph is incomplete until indexing finishes.
+    body["indexing"] = json!(project.is_indexing());
+    Ok(Json(body))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct HotspotQuery {
+    /// `complexity` (default) or `callers`
+    metric: Option<String>,
+    limit: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/symbols/hotspot_files",
+    tag = "symbols",
+    params(HotspotQuery),
+    responses((status = 200, description = "`{ metric, files: [FileHotspot], count }`", body = Value), AppError),
+    security(("session" = [])),
+)]
+async fn hotspot_files(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<HotspotQuery>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let metric_name = params.metric.as_deref().unwrap_or("complexity");
+    let metric = symbol_ops::HotspotMetric::from_str(metric_name).ok_or_else(|| {
+        AppError::BadRequest(format!(
+            "Invalid metric '{}': expected 'complexity' or 'callers'",
+            metric_name
+        ))
+    })?;
+    let limit = params.limit.unwrap_or(20);
+    let root = project.root.clone();
+    let symbol_table = project.symbol_table.clone();
+    let files = state
+        .inner
+        .blocking
+        .run(move || symbol_ops::hotspot_files(&root, &symbol_table, metric, limit))
+        .await?;
+    let preview = match files.first() {
+        Some(top) => format!("{} files by {}, top {}", files.len(), metric_name, top.file),
+        None => format!("0 files by {}", metric_name),
+    };
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols/hotspot_files", &preview);
+    Ok(Json(json!({
+        "metric": metric_name.to_lowercase(),
+        "count": files.len(),
+        "files": files,
+    })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/symbols/dependency_matrix",
+    tag = "symbols",
+    params(
+        ("files" = Vec<String>, Query, description = "Files to compare, at most 30; repeat the key, or use `files[]`"),
+    ),
+    responses((status = 200, description = "`matrix[i][j]` counts calls from `files[i]` to symbols defined in `files[j]`", body = DependencyMatrix), AppError),
+    security(("session" = [])),
+)]
+async fn dependency_matrix(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(pairs): Query<Vec<(String, String)>>,
+) -> Result<Json<DependencyMatrix>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let mut files: Vec<String> = Vec::new();
+    for (key, value) in pairs {
+        if (key == "files" || key == "files[]") && !files.contains(&value) {
+            files.push(value);
+        }
+    }
+    if files.is_empty() {
+        return Err(AppError::BadRequest("'files' must name at least one file".into()));
+    }
+    if files.len() > config::DEPENDENCY_MATRIX_MAX_FILES {
+        return Err(AppError::BadRequest(format!(
+            "At most {} files per request, got {}",
+            config::DEPENDENCY_MATRIX_MAX_FILES,
+            files.len()
+        )));
+    }
+    if let Some(missing) = files.iter().find(|f| project.file_tree.get(f).is_none()) {
+        return Err(file_not_found(&project, missing, format!("File '{}' not found", missing)));
+    }
+
+    let symbol_table = project.symbol_table.clone();
+    let result = state
+        .inner
+        .blocking
+        .run(move || symbol_ops::dependency_matrix(&symbol_table, &files))
+        .await?;
+    let edges: usize = result.matrix.iter().flatten().sum();
+    let preview = format!("{} files, {} cross-file calls", result.files.len(), edges);
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols/dependency_matrix", &preview);
+    Ok(Json(result))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct GraphNeighborsQuery {
+    symbol: String,
+    #[serde(deserialize_with = "rel_path::deserialize")]
+    file: String,
+    /// Hops to walk in each direction; clamped to 1..=3
+    #[serde(default = "default_graph_radius")]
+    #[param(default = 1)]
+    radius: usize,
+}
+
+fn default_graph_radius() -> usize {
+    1
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/symbols/graph_neighbors",
+    tag = "symbols",
+    params(GraphNeighborsQuery),
+    responses((status = 200, description = "Callers and callees within `radius` hops", body = GraphNeighborhood), AppError),
+    security(("session" = [])),
+)]
+async fn graph_neighbors(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<GraphNeighborsQuery>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let symbol_table = project.symbol_table.clone();
+    let symbol = params.symbol.clone();
+    let file = params.file.clone();
+    let radius = params.radius;
+    let neighborhood = state
+        .inner
+        .blocking
+        .run(move || symbol_ops::graph_neighbors(&symbol_table, &symbol, &file, radius))
+        .await?
+        .map_err(AppError::NotFound)?;
+    let preview = format!(
+        "{} callers, {} callees of {} (radius {})",
+        neighborhood.callers.len(),
+        neighborhood.callees.len(),
+        params.symbol,
+        neighborhood.radius
+    );
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols/graph_neighbors", &preview);
+    Ok(Json(serde_json::to_value(neighborhood).unwrap()))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SymbolContextQuery {
+    symbol: String,
+    #[serde(deserialize_with = "rel_path::deserialize")]
     file: String,
-    limit: Option<usize>,
-    meta: Option<bool>,
+    /// Most tokens in the assembled text (default 4000)
+    budget_tokens: Option<usize>,
+    /// Attached project (alias or root) the file belongs to; defaults to
+    /// the session's primary project. A `alias:path` file works too.
+    project: Option<String>,
 }
 
-async fn find_tests(
+#[utoipa::path(
+    get,
+    path = "/api/v1/symbols/context",
+    tag = "symbols",
+    params(SymbolContextQuery),
+    responses((status = 200, description = "A symbol's implementation, callee and caller signatures and referenced types, fitted to a token budget", body = SymbolContext), AppError),
+    security(("session" = [])),
+)]
+async fn symbol_context(
     State(state): State<AppState>,
     headers: HeaderMap,
-    Query(params): Query<TestsQuery>,
+    Query(params): Query<SymbolContextQuery>,
 ) -> Result<Json<Value>, AppError> {
-    let project = require_project(&state, &headers)?;
-    let limit = params.limit.unwrap_or(20);
-    let tests = symbol_ops::find_tests(
-        &project.root,
-        &project.file_tree,
-        &project.symbol_table,
-        &params.symbol,
-        &params.file,
-        limit,
-    )
-    .map_err(AppError::NotFound)?;
-    let preview = format!("{} tests for {}", tests.len(), params.symbol);
-    record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols/tests", &preview);
-
-    if params.meta.unwrap_or(false) {
-        let repl = require_repl(&state, &headers)?;
-        let buffer_name = format!("tests::{}::{}", params.file, params.symbol);
+    let (project, file) =
+        require_project_for(&state, &headers, params.project.as_deref(), &params.file)?;
+    let (symbol, folded) = resolve_symbol_name(&project, &file, &params.symbol);
+    if project.symbol_table.get(&file, &symbol).is_none() {
+        let message = format!("Symbol '{}' not found in '{}'", symbol, file);
+        return Err(symbol_not_found(&project, &symbol, &file, message));
+    }
+    let budget_tokens = params
+        .budget_tokens
+        .unwrap_or(config::SYMBOL_CONTEXT_DEFAULT_BUDGET_TOKENS);
+
+    let task_project = project.clone();
+    let task_symbol = symbol.clone();
+    let task_file = file.clone();
+    let context = state
+        .inner
+        .blocking
+        .run(move || {
+            symbol_context::symbol_context(&task_project, &task_symbol, &task_file, budget_tokens)
+        })
+        .await?
+        .map_err(AppError::BadRequest)?;
 
-        // Store full results in buffer
-        let full_json = serde_json::to_string_pretty(&tests).unwrap_or_default();
-        repl::buffer_create(&repl, &buffer_name, full_json,
-            &format!("tests for {} in {}", params.symbol, params.file));
+    let preview = format!(
+        "{}::{}: {} tokens, {} callees, {} callers, {} types",
+        file,
+        symbol,
+        context.tokens,
+        context.callees.included,
+        context.callers.included,
+        context.types.included
+    );
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols/context", &preview);
+    let repl = require_repl(&state, &headers)?;
+    symbol_ops::record_access(&repl, &file, &symbol);
+    let mut body = serde_json::to_value(context).unwrap();
+    mark_case_insensitive(&mut body, folded);
+    Ok(Json(body))
+}
 
-        // Return metadata-only (no signature)
-        let meta_tests: Vec<Value> = tests.iter().map(|t| json!({
-            "name": t.name,
-            "file": t.file,
-            "line": t.line,
-        })).collect();
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct RenameCandidatesQuery {
+    symbol: String,
+    #[serde(deserialize_with = "rel_path::deserialize")]
+    file: String,
+    /// Attached project (alias or root) the file belongs to; defaults to
+    /// the session's primary project. A `alias:path` file works too.
+    project: Option<String>,
+}
 
-        Ok(Json(json!({
-            "tests": meta_tests,
-            "count": tests.len(),
-            "buffer": buffer_name,
-        })))
-    } else {
-        Ok(Json(json!({ "tests": tests, "count": tests.len() })))
-    }
+#[utoipa::path(
+    get,
+    path = "/api/v1/symbols/rename_candidates",
+    tag = "symbols",
+    params(RenameCandidatesQuery),
+    responses((status = 200, description = "Sites a rename would need to update", body = symbol_ops::RenameCandidates), AppError),
+    security(("session" = [])),
+)]
+async fn rename_candidates(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<RenameCandidatesQuery>,
+) -> Result<Json<symbol_ops::RenameCandidates>, AppError> {
+    let (project, file) =
+        require_project_for(&state, &headers, params.project.as_deref(), &params.file)?;
+    let symbol = params.symbol.clone();
+    let candidates = state
+        .inner
+        .blocking
+        .run(move || {
+            symbol_ops::rename_candidates(
+                &project.root,
+                &project.file_tree,
+                &project.symbol_table,
+                &symbol,
+                &file,
+                project.call_graph_complete(),
+            )
+        })
+        .await?
+        .map_err(AppError::NotFound)?;
+    let preview = format!(
+        "{} edits to rename {}",
+        candidates.total_edits_needed, params.symbol
+    );
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols/rename_candidates", &preview);
+    Ok(Json(candidates))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct CallersQuery {
     symbol: String,
+    #[serde(deserialize_with = "rel_path::deserialize")]
     file: String,
+    /// Attached project (alias or root) the file belongs to; defaults to
+    /// the session's primary project. A `alias:path` file works too.
+    project: Option<String>,
     limit: Option<usize>,
+    /// Include this many source lines before and after each call site.
+    context_lines: Option<usize>,
+    /// Attach the symbol (function, method, ...) that contains each call site.
+    enrich: Option<bool>,
     meta: Option<bool>,
+    /// `method` or `function`: keep call sites that call the symbol as one
+    callee_kind: Option<String>,
+    /// Keep call sites inside a definition of this kind (implies `enrich`)
+    caller_kind: Option<String>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/symbols/callers",
+    tag = "symbols",
+    params(CallersQuery),
+    responses((status = 200, description = "`{ callers: [CallerInfo], count, source, indexing }`", body = Value), AppError),
+    security(("session" = [])),
+)]
 async fn find_callers(
     State(state): State<AppState>,
     headers: HeaderMap,
     Query(params): Query<CallersQuery>,
 ) -> Result<Json<Value>, AppError> {
-    let project = require_project(&state, &headers)?;
+    let (project, file) =
+        require_project_for(&state, &headers, params.project.as_deref(), &params.file)?;
     let limit = params.limit.unwrap_or(50);
-    let callers = symbol_ops::find_callers(
+    let callee_kind = match params.callee_kind.as_deref() {
+        None => None,
+        Some(raw) => match SymbolKind::from_str(raw) {
+            Some(kind @ (SymbolKind::Function | SymbolKind::Method)) => Some(kind),
+            _ => {
+                return Err(AppError::BadRequest(format!(
+                    "Invalid callee_kind '{}': expected 'function' or 'method'",
+                    raw
+                )));
+            }
+        },
+    };
+    let caller_kind = params
+        .caller_kind
+        .as_deref()
+        .map(|raw| {
+            SymbolKind::from_str(raw).ok_or_else(|| {
+                AppError::BadRequest(format!(
+                    "Unknown symbol kind '{}'. Valid: {}",
+                    raw,
+                    SymbolKind::NAMES.join(", ")
+                ))
+            })
+        })
+        .transpose()?;
+    let filter = symbol_ops::CallerFilter {
+        callee_kind,
+        caller_kind,
+    };
+    let indexing = project.is_indexing();
+    let symbol_ops::FoundCallers {
+        callers,
+        warning,
+        source,
+    } = symbol_ops::find_callers(
         &project.root,
         &project.file_tree,
         &project.symbol_table,
         &params.symbol,
-        &params.file,
+        &file,
         limit,
+        params.context_lines,
+        params.enrich.unwrap_or(false),
+        &filter,
+        project.call_graph_complete(),
     )
     .map_err(AppError::NotFound)?;
     let preview = format!("{} callers of {}", callers.len(), params.symbol);
     record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols/callers", &preview);
+    let repl = require_repl(&state, &headers)?;
+    symbol_ops::record_access(&repl, &file, &params.symbol);
 
     if params.meta.unwrap_or(false) {
-        let repl = require_repl(&state, &headers)?;
-        let buffer_name = format!("callers::{}::{}", params.file, params.symbol);
+        let buffer_name = format!("callers::{}::{}", file, params.symbol);
 
         // Store full results in buffer
         let full_json = serde_json::to_string_pretty(&callers).unwrap_or_default();
         repl::buffer_create(&repl, &buffer_name, full_json,
-            &format!("callers of {} in {}", params.symbol, params.file));
+            &format!("callers of {} in {}", params.symbol, file));
 
         // Return metadata-only (no text)
         let meta_callers: Vec<Value> = callers.iter().map(|c| json!({
@@ -601,36 +3345,95 @@ async fn find_callers(
             "line": c.line,
         })).collect();
 
-        Ok(Json(json!({
+        let mut body = json!({
             "callers": meta_callers,
             "count": callers.len(),
+            "source": source,
+            "indexing": indexing,
             "buffer": buffer_name,
-        })))
+        });
+        if let Some(warning) = warning {
+            body["warning"] = json!(warning);
+        }
+        Ok(Json(body))
     } else {
-        Ok(Json(json!({ "callers": callers, "count": callers.len() })))
+        let mut body = json!({
+            "callers": callers,
+            "count": callers.len(),
+            "source": source,
+            "indexing": indexing,
+        });
+        if let Some(warning) = warning {
+            body["warning"] = json!(warning);
+        }
+        Ok(Json(body))
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct VariablesQuery {
-    function: String,
+    /// Function to list; omit for the file's top-level bindings.
+    function: Option<String>,
+    #[serde(deserialize_with = "rel_path::deserialize")]
     file: String,
+    /// "top_level" (default) or "all" to include closure parameters and
+    /// nested-scope bindings, each with a `scope_path`. Only applies with
+    /// `function`.
+    scope: Option<String>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/symbols/variables",
+    tag = "symbols",
+    params(VariablesQuery),
+    responses((status = 200, description = "`{ variables: [VariableInfo], count }`", body = Value), AppError),
+    security(("session" = [])),
+)]
 async fn list_variables(
     State(state): State<AppState>,
     headers: HeaderMap,
     Query(params): Query<VariablesQuery>,
 ) -> Result<Json<Value>, AppError> {
     let project = require_project(&state, &headers)?;
+    let scope = match params.scope.as_deref() {
+        None => symbol_ops::VariableScope::TopLevel,
+        Some(s) => symbol_ops::VariableScope::from_str(s).ok_or_else(|| {
+            AppError::BadRequest(format!("Invalid scope '{}': expected 'top_level' or 'all'", s))
+        })?,
+    };
+    let Some(function) = params.function else {
+        let listing = symbol_ops::list_file_variables(
+            &project.root,
+            &project.file_tree,
+            &project.symbol_table,
+            &params.file,
+        )
+        .map_err(AppError::NotFound)?;
+        let (preview, body) = match listing {
+            symbol_ops::FileVariables::Supported(vars) => (
+                format!("{} top-level variables in {}", vars.len(), params.file),
+                json!({ "variables": vars, "count": vars.len() }),
+            ),
+            symbol_ops::FileVariables::Unsupported(reason) => (
+                format!("variables unsupported for {}", params.file),
+                json!({ "variables": [], "count": 0, "unsupported": true, "reason": reason }),
+            ),
+        };
+        record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols/variables", &preview);
+        return Ok(Json(body));
+    };
+
     let vars = symbol_ops::list_variables(
         &project.root,
         &project.symbol_table,
-        &params.function,
+        &function,
         &params.file,
+        scope,
     )
     .map_err(AppError::NotFound)?;
-    let preview = format!("{} variables in {}", vars.len(), params.function);
+    let preview = format!("{} variables in {}", vars.len(), function);
     record_history(&state, session_id(&headers).as_deref(), "GET", "/symbols/variables", &preview);
     Ok(Json(json!({ "variables": vars, "count": vars.len() })))
 }
@@ -639,40 +3442,114 @@ async fn list_variables(
 // Content
 // ---------------------------------------------------------------------------
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct PeekQuery {
+    #[serde(deserialize_with = "rel_path::deserialize")]
     file: String,
+    /// Attached project (alias or root) the file belongs to; defaults to
+    /// the session's primary project. A `alias:path` file works too.
+    project: Option<String>,
     start: Option<usize>,
     end: Option<usize>,
+    /// Peek this symbol's lines instead of `start`/`end`
+    symbol: Option<String>,
+    /// With `symbol`, also show this many lines before it
+    context_before: Option<usize>,
+    /// With `symbol`, also show this many lines after it
+    context_after: Option<usize>,
     meta: Option<bool>,
+    /// `symbol` widens the range to whole definitions it cuts through.
+    expand: Option<String>,
+    /// Also return each line with the column positions of its tokens.
+    include_columns: Option<bool>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/peek",
+    tag = "content",
+    params(PeekQuery),
+    responses((status = 200, description = "Numbered lines (metadata plus buffer name when `meta=true`)", body = PeekResponse), AppError),
+    security(("session" = [])),
+)]
 async fn peek(
     State(state): State<AppState>,
     headers: HeaderMap,
     Query(params): Query<PeekQuery>,
 ) -> Result<Json<Value>, AppError> {
-    let project = require_project(&state, &headers)?;
-    let start = params.start.unwrap_or(0);
-    let end = params.end.unwrap_or(100);
-    let result = content::peek(
+    let (project, file) =
+        require_project_for(&state, &headers, params.project.as_deref(), &params.file)?;
+    let symbol = match params.symbol.as_deref() {
+        Some(_) if params.start.is_some() || params.end.is_some() => {
+            return Err(AppError::BadRequest(
+                "Pass either symbol or start/end, not both".to_string(),
+            ));
+        }
+        Some(name) => Some(lookup_symbol(&project, &file, name)?),
+        None if params.context_before.is_some() || params.context_after.is_some() => {
+            return Err(AppError::BadRequest(
+                "context_before and context_after only apply with symbol".to_string(),
+            ));
+        }
+        None => None,
+    };
+    let (start, end) = match &symbol {
+        Some((sym, _)) => (
+            sym.line_range
+                .0
+                .saturating_sub(params.context_before.unwrap_or(0).saturating_add(1)),
+            sym.line_range.1.saturating_add(params.context_after.unwrap_or(0)),
+        ),
+        None => (params.start.unwrap_or(0), params.end.unwrap_or(100)),
+    };
+    let expand_symbols = match params.expand.as_deref() {
+        None => None,
+        Some("symbol") => Some(project.symbol_table.as_ref()),
+        Some(other) => {
+            return Err(AppError::BadRequest(format!(
+                "Invalid expand '{}': expected 'symbol'",
+                other
+            )));
+        }
+    };
+    let mut result = content::peek(
         &project.root,
         &project.file_tree,
-        &params.file,
+        &file,
         start,
         end,
+        expand_symbols,
+        params.include_columns.unwrap_or(false),
     )
-    .map_err(AppError::NotFound)?;
-    let preview = format!("{}:{}-{}", params.file, start, end);
+    .map_err(|e| file_not_found(&project, &file, e))?;
+    if let Some((sym, _)) = &symbol {
+        result.symbol_resolved = true;
+        result.resolved_lines = Some(content::ResolvedLines {
+            start: sym.line_range.0.saturating_sub(1),
+            end: sym.line_range.1,
+        });
+    }
+    result.symbol = symbol.map(|(sym, folded)| content::PeekedSymbol {
+        name: sym.name,
+        kind: sym.kind,
+        line_range: sym.line_range,
+        byte_range: sym.byte_range,
+        matched_case_insensitively: folded,
+    });
+    let preview = match &result.symbol {
+        Some(sym) => format!("{}::{}", file, sym.name),
+        None => format!("{}:{}-{}", file, start, end),
+    };
     record_history(&state, session_id(&headers).as_deref(), "GET", "/peek", &preview);
 
     if params.meta.unwrap_or(false) {
         let repl = require_repl(&state, &headers)?;
-        let buffer_name = format!("peek::{}::{}-{}", params.file, result.start_line, result.end_line);
+        let buffer_name = format!("peek::{}::{}-{}", file, result.start_line, result.end_line);
 
         // Store full content in buffer
         repl::buffer_create(&repl, &buffer_name, result.content.clone(),
-            &format!("peek {}:{}-{}", params.file, result.start_line, result.end_line));
+            &format!("peek {}:{}-{}", file, result.start_line, result.end_line));
 
         let content_preview = if result.content.len() > 100 {
             format!("{}...", &result.content[..result.content.floor_char_boundary(100)])
@@ -685,6 +3562,12 @@ async fn peek(
             "start": result.start_line,
             "end": result.end_line,
             "total_lines": result.total_lines,
+            "clamped": result.clamped,
+            "expanded_by": result.expanded_by,
+            "symbol": result.symbol,
+            "symbol_resolved": result.symbol_resolved,
+            "resolved_lines": result.resolved_lines,
+            "encoding": result.encoding,
             "bytes": result.content.len(),
             "preview": content_preview,
             "buffer": buffer_name,
@@ -694,20 +3577,99 @@ async fn peek(
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
+struct MultiPeekBody {
+    files: Vec<PeekRequest>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/multi_peek",
+    tag = "content",
+    request_body = MultiPeekBody,
+    responses((status = 200, description = "Numbered lines per file, plus per-file errors", body = MultiPeekResult), AppError),
+    security(("session" = [])),
+)]
+async fn multi_peek(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<MultiPeekBody>,
+) -> Result<Json<MultiPeekResult>, AppError> {
+    let project = require_project(&state, &headers)?;
+    if body.files.is_empty() {
+        return Err(AppError::BadRequest("'files' must list at least one file".into()));
+    }
+    if body.files.len() > config::MULTI_PEEK_MAX_FILES {
+        return Err(AppError::BadRequest(format!(
+            "'files' lists {} files; at most {} are allowed",
+            body.files.len(),
+            config::MULTI_PEEK_MAX_FILES
+        )));
+    }
+    let result = state
+        .inner
+        .blocking
+        .run(move || content::multi_peek(&project.root, &project.file_tree, &body.files))
+        .await?;
+    let preview = format!("{} files, {} errors", result.results.len(), result.errors.len());
+    record_history(&state, session_id(&headers).as_deref(), "POST", "/multi_peek", &preview);
+    Ok(Json(result))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct GrepQuery {
     pattern: String,
     max_matches: Option<usize>,
     context_lines: Option<usize>,
     /// Optional scope filter: "all" (default) or "code" (skip comments/strings).
     scope: Option<String>,
+    /// With `scope=code`, fail with 400 instead of returning unfiltered
+    /// matches from files whose language has no grammar.
+    strict_scope: Option<bool>,
     meta: Option<bool>,
+    /// Only files carrying this mark
+    mark: Option<String>,
+    /// Skip files carrying this mark
+    exclude_mark: Option<String>,
+    /// "flat" (default): one entry per match. "blocks": matches merged into
+    /// context blocks per file.
+    format: Option<String>,
+    /// With `format=blocks`, most blocks returned per file (default 10).
+    max_blocks_per_file: Option<usize>,
+    /// Most matches returned from any one file; the rest are counted but
+    /// don't use up `max_matches`.
+    max_matches_per_file: Option<usize>,
+    /// "file" to return matches grouped per file.
+    group_by: Option<String>,
+    /// Group order with `group_by=file`: "count" (default) or "path".
+    sort: Option<String>,
+    /// Annotate each match with its innermost enclosing symbol (flat format
+    /// only)
+    resolve_symbols: Option<bool>,
+    /// Match the lines that do not contain the pattern, like `grep -v`.
+    invert: Option<bool>,
+    /// Return only the paths of matching files; with `invert`, the files
+    /// that never contain the pattern.
+    files_only: Option<bool>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/grep",
+    tag = "content",
+    params(
+        GrepQuery,
+        ("path_pattern" = Option<Vec<String>>, Query, description = "Only grep files matching one of these globs (`src/**/*.rs`); repeat the key, or use `path_pattern[]`"),
+    ),
+    responses((status = 200, description = "Matches (metadata plus buffer name when `meta=true`)", body = GrepResponse), AppError),
+    security(("session" = [])),
+)]
 async fn grep_handler(
     State(state): State<AppState>,
     headers: HeaderMap,
     Query(params): Query<GrepQuery>,
+    Query(pairs): Query<Vec<(String, String)>>,
 ) -> Result<Json<Value>, AppError> {
     let project = require_project(&state, &headers)?;
     let max_matches = params.max_matches.unwrap_or(50);
@@ -718,20 +3680,115 @@ async fn grep_handler(
         .map(|s| content::GrepScope::from_str(s))
         .flatten()
         .unwrap_or(content::GrepScope::All);
+    // Repeatable, so read from the raw pairs rather than `GrepQuery`.
+    let path_patterns: Vec<String> = pairs
+        .into_iter()
+        .filter(|(key, _)| key == "path_pattern" || key == "path_pattern[]")
+        .map(|(_, value)| value)
+        .collect();
+    let path_patterns =
+        content::compile_path_patterns(&path_patterns).map_err(AppError::BadRequest)?;
+    let max_blocks = params.max_blocks_per_file.unwrap_or(config::GREP_MAX_BLOCKS_PER_FILE);
+    let format = match params.format.as_deref() {
+        None | Some("flat") => content::GrepFormat::Flat,
+        Some("blocks") if max_blocks == 0 => {
+            return Err(AppError::BadRequest("'max_blocks_per_file' must be at least 1".into()));
+        }
+        Some("blocks") => content::GrepFormat::Blocks { max_per_file: max_blocks },
+        Some(other) => {
+            return Err(AppError::BadRequest(format!(
+                "Invalid format '{}': expected 'flat' or 'blocks'",
+                other
+            )));
+        }
+    };
+    let group_sort = match params.group_by.as_deref() {
+        None => None,
+        Some("file") if format != content::GrepFormat::Flat => {
+            return Err(AppError::BadRequest(
+                "group_by=file can't be combined with format=blocks, which is already per file"
+                    .into(),
+            ));
+        }
+        Some("file") => Some(match params.sort.as_deref() {
+            None => content::GroupSort::Count,
+            Some(s) => content::GroupSort::from_str(s).ok_or_else(|| {
+                AppError::BadRequest(format!("Invalid sort '{}': expected 'count' or 'path'", s))
+            })?,
+        }),
+        Some(other) => {
+            return Err(AppError::BadRequest(format!(
+                "Invalid group_by '{}': expected 'file'",
+                other
+            )));
+        }
+    };
+    if params.max_matches_per_file == Some(0) {
+        return Err(AppError::BadRequest("'max_matches_per_file' must be at least 1".into()));
+    }
+    let max_matches_per_file = params.max_matches_per_file;
+    let marks = parse_mark_filter(params.mark.as_deref(), params.exclude_mark.as_deref())?;
+    let resolve_symbols = params.resolve_symbols.unwrap_or(false);
+    if resolve_symbols && format != content::GrepFormat::Flat {
+        return Err(AppError::BadRequest(
+            "resolve_symbols can't be combined with format=blocks".into(),
+        ));
+    }
+    let format = if params.files_only.unwrap_or(false) {
+        if params.format.is_some() || group_sort.is_some() || resolve_symbols {
+            return Err(AppError::BadRequest(
+                "files_only can't be combined with format, group_by or resolve_symbols".into(),
+            ));
+        }
+        content::GrepFormat::FilesOnly
+    } else {
+        format
+    };
+    let invert = params.invert.unwrap_or(false);
 
     // Run grep on a blocking thread since it reads many files
     let root = project.root.clone();
     let file_tree = project.file_tree.clone();
+    let symbol_table = project.symbol_table.clone();
     let pattern = params.pattern.clone();
 
-    let result = tokio::task::spawn_blocking(move || {
-        content::grep_with_scope(&root, &file_tree, &pattern, max_matches, context_lines, scope)
-    })
-    .await
-    .map_err(|e| AppError::Internal(e.to_string()))?
-    .map_err(AppError::BadRequest)?;
+    let mut result = state
+        .inner
+        .blocking
+        .run(move || {
+            content::grep_with_scope(
+                &root,
+                &file_tree,
+                &pattern,
+                max_matches,
+                context_lines,
+                scope,
+                &path_patterns,
+                format,
+                max_matches_per_file,
+                &marks,
+                invert,
+            )
+            .map(|mut result| {
+                if resolve_symbols {
+                    result.resolve_enclosing_symbols(&file_tree, &symbol_table);
+                }
+                result
+            })
+        })
+        .await?
+        .map_err(AppError::BadRequest)?;
+    check_strict_scope(params.strict_scope, &result)?;
+    if let Some(sort) = group_sort {
+        result.group_by_file(sort);
+    }
 
-    let preview = format!("{} matches for '{}'", result.total_matches, params.pattern);
+    let preview = match (&result.file_paths, invert) {
+        (Some(paths), true) => format!("{} files without '{}'", paths.len(), params.pattern),
+        (Some(paths), false) => format!("{} files with '{}'", paths.len(), params.pattern),
+        (None, true) => format!("{} lines without '{}'", result.total_matches, params.pattern),
+        (None, false) => format!("{} matches for '{}'", result.total_matches, params.pattern),
+    };
     record_history(&state, session_id(&headers).as_deref(), "GET", "/grep", &preview);
 
     if params.meta.unwrap_or(false) {
@@ -744,16 +3801,24 @@ async fn grep_handler(
             &format!("grep results for '{}'", params.pattern));
 
         // Return metadata-only (no text/context)
-        let meta_matches: Vec<Value> = result.matches.iter().map(|m| json!({
-            "file": m.file,
-            "line": m.line,
-        })).collect();
+        let meta_matches: Vec<Value> = result
+            .match_locations()
+            .into_iter()
+            .map(|(file, line)| json!({ "file": file, "line": line }))
+            .collect();
 
         Ok(Json(json!({
             "pattern": result.pattern,
             "matches": meta_matches,
             "total_matches": result.total_matches,
             "truncated": result.truncated,
+            "searched_files": result.searched_files,
+            "filtered_files": result.filtered_files,
+            "scope_used": result.scope_used,
+            "scope_fallback_reason": result.scope_fallback_reason,
+            "file_paths": result.file_paths,
+            "inverted": result.inverted,
+            "cancelled": result.cancelled,
             "buffer": buffer_name,
         })))
     } else {
@@ -761,13 +3826,40 @@ async fn grep_handler(
     }
 }
 
-#[derive(Deserialize)]
+/// With `strict_scope`, a grep whose `scope=code` fell back to unfiltered
+/// matches is an error rather than a result.
+fn check_strict_scope(strict: Option<bool>, result: &GrepResponse) -> Result<(), AppError> {
+    match &result.scope_fallback_reason {
+        Some(reason) if strict.unwrap_or(false) => Err(AppError::BadRequest(format!(
+            "scope=code could not be applied: {}",
+            reason
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct ChunkQuery {
+    #[serde(deserialize_with = "rel_path::deserialize")]
     file: String,
     size: Option<usize>,
+    /// Overlap in bytes for `align=byte`.
     overlap: Option<usize>,
+    /// "byte" (default) or "line" to end chunks on line boundaries.
+    align: Option<String>,
+    /// Overlap in lines for `align=line` (default 5).
+    overlap_lines: Option<usize>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/chunk_indices",
+    tag = "content",
+    params(ChunkQuery),
+    responses((status = 200, description = "Chunk boundaries as byte offsets and lines", body = ChunkIndicesResponse), AppError),
+    security(("session" = [])),
+)]
 async fn chunk_indices(
     State(state): State<AppState>,
     headers: HeaderMap,
@@ -775,13 +3867,23 @@ async fn chunk_indices(
 ) -> Result<Json<Value>, AppError> {
     let project = require_project(&state, &headers)?;
     let size = params.size.unwrap_or(5000);
-    let overlap = params.overlap.unwrap_or(200);
+    let align = match params.align.as_deref() {
+        None => content::ChunkAlign::Byte,
+        Some(s) => content::ChunkAlign::from_str(s).ok_or_else(|| {
+            AppError::BadRequest(format!("Invalid align '{}': expected 'byte' or 'line'", s))
+        })?,
+    };
+    let overlap = match align {
+        content::ChunkAlign::Byte => params.overlap.unwrap_or(200),
+        content::ChunkAlign::Line => params.overlap_lines.unwrap_or(5),
+    };
     let result = content::chunk_indices(
         &project.root,
         &project.file_tree,
         &params.file,
         size,
         overlap,
+        align,
     )
     .map_err(AppError::BadRequest)?;
     let preview = format!("{} chunks for {}", result.chunks.len(), params.file);
@@ -789,15 +3891,162 @@ async fn chunk_indices(
     Ok(Json(serde_json::to_value(result).unwrap()))
 }
 
+/// Capture cap for a raw query request. A `limit` of 0 would come back
+/// empty yet `truncated`, so it is raised to 1 like an oversized one is
+/// lowered to the maximum.
+fn raw_query_limit(limit: Option<usize>) -> usize {
+    limit
+        .unwrap_or(config::RAW_QUERY_DEFAULT_LIMIT)
+        .clamp(1, config::RAW_QUERY_MAX_LIMIT)
+}
+
+#[derive(Deserialize, ToSchema)]
+struct RawQueryBody {
+    #[serde(deserialize_with = "rel_path::deserialize")]
+    file: String,
+    /// Tree-sitter query source, e.g. `(unsafe_block) @block`
+    query: String,
+    /// Max captures returned (default 100, clamped to 1..=1000)
+    limit: Option<usize>,
+    /// Attached project (alias or root) the file belongs to; defaults to
+    /// the session's primary project. A `alias:path` file works too.
+    project: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/query",
+    tag = "content",
+    request_body = RawQueryBody,
+    responses((status = 200, description = "Captures in document order", body = raw_query::RawQueryResult), AppError),
+    security(("session" = [])),
+)]
+async fn raw_query_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<RawQueryBody>,
+) -> Result<Json<Value>, AppError> {
+    if !state.inner.raw_queries {
+        return Err(AppError::Forbidden(
+            "Raw tree-sitter queries are disabled on this server".into(),
+        ));
+    }
+    let (project, file) =
+        require_project_for(&state, &headers, body.project.as_deref(), &body.file)?;
+    if project.file_tree.get(&file).is_none() {
+        return Err(file_not_found(
+            &project,
+            &file,
+            format!("File '{}' not found in index", file),
+        ));
+    }
+    let limit = raw_query_limit(body.limit);
+    let root = project.root.clone();
+    let file_tree = project.file_tree.clone();
+    let (query_file, query) = (file.clone(), body.query);
+    let result = state
+        .inner
+        .blocking
+        .run(move || raw_query::run_query(&root, &file_tree, &query_file, &query, limit))
+        .await?
+        .map_err(AppError::BadRequest)?;
+    record_history(
+        &state,
+        session_id(&headers).as_deref(),
+        "POST",
+        "/query",
+        &format!("{} ({} captures)", file, result.count),
+    );
+    Ok(Json(serde_json::to_value(result).unwrap()))
+}
+
+#[derive(Deserialize, ToSchema)]
+struct QueryScanBody {
+    language: Language,
+    /// Tree-sitter query source, compiled once for `language`
+    query: String,
+    /// Only files whose path matches one of these globs (default: all)
+    #[serde(default)]
+    include_globs: Vec<String>,
+    /// Max captures per page (default 100, clamped to 1..=1000)
+    limit: Option<usize>,
+    /// Index into the sorted file list to start from; use `next_offset`
+    #[serde(default)]
+    offset: usize,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/query/scan",
+    tag = "content",
+    request_body = QueryScanBody,
+    responses((status = 200, description = "One page of captures, grouped by file", body = raw_query::ScanResult), AppError),
+    security(("session" = [])),
+)]
+async fn raw_query_scan(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<QueryScanBody>,
+) -> Result<Json<Value>, AppError> {
+    if !state.inner.raw_queries {
+        return Err(AppError::Forbidden(
+            "Raw tree-sitter queries are disabled on this server".into(),
+        ));
+    }
+    let project = require_project(&state, &headers)?;
+    let include = body
+        .include_globs
+        .iter()
+        .map(|g| {
+            glob::Pattern::new(g)
+                .map_err(|e| AppError::BadRequest(format!("Invalid glob '{}': {}", g, e)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let limit = raw_query_limit(body.limit);
+    let root = project.root.clone();
+    let file_tree = project.file_tree.clone();
+    let (language, query, offset) = (body.language, body.query, body.offset);
+    let result = state
+        .inner
+        .blocking
+        .run(move || {
+            raw_query::scan_query(&root, &file_tree, language, &query, &include, offset, limit)
+        })
+        .await?
+        .map_err(AppError::BadRequest)?;
+    record_history(
+        &state,
+        session_id(&headers).as_deref(),
+        "POST",
+        "/query/scan",
+        &format!(
+            "{:?}: {} captures in {} files",
+            language,
+            result.count,
+            result.files.len()
+        ),
+    );
+    Ok(Json(serde_json::to_value(result).unwrap()))
+}
+
 // ---------------------------------------------------------------------------
 // History
 // ---------------------------------------------------------------------------
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct HistoryQuery {
     limit: Option<usize>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/history",
+    tag = "history",
+    params(HistoryQuery),
+    responses((status = 200, description = "Session history, or every session's history without a session header", body = Value), AppError),
+    security((), ("session" = [])),
+)]
 async fn get_history(
     State(state): State<AppState>,
     headers: HeaderMap,
@@ -821,11 +4070,20 @@ async fn get_history(
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct CompactQuery {
     keep_recent: Option<usize>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/history/compact",
+    tag = "history",
+    params(CompactQuery),
+    responses((status = 200, description = "Compaction summary", body = CompactResult), AppError),
+    security(("session" = [])),
+)]
 async fn compact_history(
     State(state): State<AppState>,
     headers: HeaderMap,
@@ -839,12 +4097,27 @@ async fn compact_history(
     Ok(Json(serde_json::to_value(result).unwrap()))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/context_budget",
+    tag = "history",
+    responses((status = 200, description = "Stored REPL state size, response totals, and token estimates", body = Value), AppError),
+    security(("session" = [])),
+)]
 async fn context_budget(
     State(state): State<AppState>,
     headers: HeaderMap,
 ) -> Result<Json<Value>, AppError> {
     let _project = require_project(&state, &headers)?;
     let repl = require_repl(&state, &headers)?;
+    let sid = require_session(&headers)?;
+    let response_tokens = state
+        .inner
+        .sessions
+        .get(&sid)
+        .map(|s| s.response_tokens)
+        .unwrap_or(0);
+    let budget = state.inner.response_token_budget;
 
     let buffer_bytes: usize = repl.buffers.iter().map(|e| e.value().content.len()).sum();
     let var_bytes: usize = repl
@@ -867,6 +4140,8 @@ async fn context_budget(
         "subcall_count": subcall_count,
         "total_bytes": total_bytes,
         "estimated_tokens": estimated_tokens,
+        "estimated_response_tokens": response_tokens,
+        "response_token_budget": (budget > 0).then_some(budget),
     })))
 }
 
@@ -874,28 +4149,49 @@ async fn context_budget(
 // Annotations
 // ---------------------------------------------------------------------------
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/annotations/save",
+    tag = "annotations",
+    responses((status = 200, description = "Annotations written to `.coderlm/annotations.json`", body = Value), AppError),
+    security(("session" = [])),
+)]
 async fn save_annotations(
     State(state): State<AppState>,
     headers: HeaderMap,
 ) -> Result<Json<Value>, AppError> {
     let project = require_project(&state, &headers)?;
-    annotations::save_annotations(&project.root, &project.file_tree, &project.symbol_table)
-        .map_err(AppError::Internal)?;
+    project.annotations_dirty.store(false, Ordering::SeqCst);
+    match annotations::save_annotations(&project.root, &project.file_tree, &project.symbol_table) {
+        Ok(data) => project.record_annotations_saved(data),
+        Err(e) => {
+            project.mark_annotations_dirty();
+            return Err(AppError::Internal(e));
+        }
+    }
     record_history(&state, session_id(&headers).as_deref(), "POST", "/annotations/save", "saved");
     Ok(Json(json!({ "ok": true })))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/annotations/load",
+    tag = "annotations",
+    responses((status = 200, description = "Annotations loaded", body = Value), AppError),
+    security(("session" = [])),
+)]
 async fn load_annotations(
     State(state): State<AppState>,
     headers: HeaderMap,
 ) -> Result<Json<Value>, AppError> {
     let project = require_project(&state, &headers)?;
-    let data = annotations::load_annotations(
+    let result = annotations::load_annotations(
         &project.root,
         &project.file_tree,
         &project.symbol_table,
-    )
-    .map_err(AppError::Internal)?;
+    );
+    project.record_annotations_load(&result);
+    let data = result.map_err(AppError::Internal)?;
     let summary = json!({
         "file_definitions": data.file_definitions.len(),
         "file_marks": data.file_marks.len(),
@@ -909,19 +4205,45 @@ async fn load_annotations(
 // Buffers
 // ---------------------------------------------------------------------------
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ListBuffersQuery {
+    /// `name` (default), `lru` (least recently read first), `size`
+    /// (largest first) or `created` (oldest first)
+    sort: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/buffers",
+    tag = "repl",
+    params(ListBuffersQuery),
+    responses((status = 200, description = "`{ buffers: [BufferInfo], count }`", body = Value), AppError),
+    security(("session" = [])),
+)]
 async fn list_buffers(
     State(state): State<AppState>,
     headers: HeaderMap,
+    Query(params): Query<ListBuffersQuery>,
 ) -> Result<Json<Value>, AppError> {
     let _project = require_project(&state, &headers)?;
     let repl = require_repl(&state, &headers)?;
-    let buffers = repl::buffer_list(&repl);
+    let sort = match params.sort.as_deref() {
+        None => repl::BufferSort::default(),
+        Some(s) => repl::BufferSort::from_str(s).ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "Invalid sort '{}': expected 'name', 'lru', 'size' or 'created'",
+                s
+            ))
+        })?,
+    };
+    let buffers = repl::buffer_list(&repl, sort);
     let count = buffers.len();
     record_history(&state, session_id(&headers).as_deref(), "GET", "/buffers", &format!("{} buffers", count));
     Ok(Json(json!({ "buffers": buffers, "count": count })))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct CreateBufferBody {
     name: String,
     content: String,
@@ -929,6 +4251,14 @@ struct CreateBufferBody {
     description: String,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/buffers",
+    tag = "repl",
+    request_body = CreateBufferBody,
+    responses((status = 200, description = "Buffer created", body = BufferInfo), AppError),
+    security(("session" = [])),
+)]
 async fn create_buffer(
     State(state): State<AppState>,
     headers: HeaderMap,
@@ -941,10 +4271,14 @@ async fn create_buffer(
     Ok(Json(serde_json::to_value(info).unwrap()))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct BufferFromFileBody {
     name: String,
+    #[serde(deserialize_with = "rel_path::deserialize")]
     file: String,
+    /// Attached project (alias or root) the file belongs to; defaults to
+    /// the session's primary project. A `alias:path` file works too.
+    project: Option<String>,
     #[serde(default)]
     start: usize,
     #[serde(default = "default_end_line")]
@@ -955,19 +4289,28 @@ fn default_end_line() -> usize {
     100
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/buffers/from-file",
+    tag = "repl",
+    request_body = BufferFromFileBody,
+    responses((status = 200, description = "Buffer created", body = BufferInfo), AppError),
+    security(("session" = [])),
+)]
 async fn buffer_from_file(
     State(state): State<AppState>,
     headers: HeaderMap,
     Json(body): Json<BufferFromFileBody>,
 ) -> Result<Json<Value>, AppError> {
-    let project = require_project(&state, &headers)?;
+    let (project, file) =
+        require_project_for(&state, &headers, body.project.as_deref(), &body.file)?;
     let repl = require_repl(&state, &headers)?;
     let info = repl::buffer_from_file(
         &repl,
         &project.root,
         &project.file_tree,
         &body.name,
-        &body.file,
+        &file,
         body.start,
         body.end,
     )
@@ -976,38 +4319,124 @@ async fn buffer_from_file(
     Ok(Json(serde_json::to_value(info).unwrap()))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct BufferFromSymbolBody {
     name: String,
     symbol: String,
+    #[serde(deserialize_with = "rel_path::deserialize")]
     file: String,
+    /// Attached project (alias or root) the file belongs to; defaults to
+    /// the session's primary project. A `alias:path` file works too.
+    project: Option<String>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/buffers/from-symbol",
+    tag = "repl",
+    request_body = BufferFromSymbolBody,
+    responses((status = 200, description = "Buffer created", body = BufferInfo), AppError),
+    security(("session" = [])),
+)]
 async fn buffer_from_symbol(
     State(state): State<AppState>,
     headers: HeaderMap,
     Json(body): Json<BufferFromSymbolBody>,
 ) -> Result<Json<Value>, AppError> {
-    let project = require_project(&state, &headers)?;
+    let (project, file) =
+        require_project_for(&state, &headers, body.project.as_deref(), &body.file)?;
     let repl = require_repl(&state, &headers)?;
-    let info = repl::buffer_from_symbol(
-        &repl,
-        &project.root,
-        &project.symbol_table,
-        &body.name,
-        &body.symbol,
-        &body.file,
-    )
-    .map_err(AppError::NotFound)?;
+    let info = repl::buffer_from_symbol(&repl, &project, &body.name, &body.symbol, &file)
+        .map_err(AppError::NotFound)?;
+    symbol_ops::record_access(&repl, &file, &body.symbol);
     record_history(&state, session_id(&headers).as_deref(), "POST", "/buffers/from-symbol", &body.name);
     Ok(Json(serde_json::to_value(info).unwrap()))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
+struct BufferFromDirBody {
+    name: String,
+    /// Directory relative to the project root; `""` or `.` for all of it
+    #[serde(deserialize_with = "rel_path::deserialize")]
+    path: String,
+    max_total_bytes: usize,
+    /// Only files matching one of these globs, relative to `path`
+    #[serde(default)]
+    include_globs: Vec<String>,
+    /// Skip files matching any of these globs, relative to `path`
+    #[serde(default)]
+    exclude_globs: Vec<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/buffers/from-dir",
+    tag = "repl",
+    request_body = BufferFromDirBody,
+    responses((status = 200, description = "Buffer created, with the files included and skipped", body = DirectoryBufferInfo), AppError),
+    security(("session" = [])),
+)]
+async fn buffer_from_dir(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<BufferFromDirBody>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let repl = require_repl(&state, &headers)?;
+    let compile = |globs: &[String]| -> Result<Vec<glob::Pattern>, AppError> {
+        globs
+            .iter()
+            .map(|g| {
+                glob::Pattern::new(g)
+                    .map_err(|e| AppError::BadRequest(format!("Invalid glob '{}': {}", g, e)))
+            })
+            .collect()
+    };
+    let include = compile(&body.include_globs)?;
+    let exclude = compile(&body.exclude_globs)?;
+    let name = body.name.clone();
+    let info = state
+        .inner
+        .blocking
+        .run(move || {
+            repl::buffer_from_dir(
+                &repl,
+                &project.root,
+                &project.file_tree,
+                &body.name,
+                &body.path,
+                body.max_total_bytes,
+                &include,
+                &exclude,
+            )
+        })
+        .await?
+        .map_err(AppError::NotFound)?;
+    let preview = format!(
+        "{}: {} files, {} skipped{}",
+        name,
+        info.included.len(),
+        info.skipped.len(),
+        if info.truncated { ", truncated" } else { "" }
+    );
+    record_history(&state, session_id(&headers).as_deref(), "POST", "/buffers/from-dir", &preview);
+    Ok(Json(serde_json::to_value(info).unwrap()))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
 struct BufferPath {
     name: String,
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/buffers/{name}",
+    tag = "repl",
+    params(BufferPath),
+    responses((status = 200, description = "Buffer metadata", body = BufferInfo), AppError),
+    security(("session" = [])),
+)]
 async fn get_buffer_info(
     State(state): State<AppState>,
     headers: HeaderMap,
@@ -1019,7 +4448,8 @@ async fn get_buffer_info(
     Ok(Json(serde_json::to_value(info).unwrap()))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct BufferPeekQuery {
     #[serde(default)]
     start: usize,
@@ -1031,6 +4461,14 @@ fn default_peek_end() -> usize {
     500
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/buffers/{name}/peek",
+    tag = "repl",
+    params(BufferPath, BufferPeekQuery),
+    responses((status = 200, description = "Byte slice of the buffer: `{ buffer, requested_start, requested_end, resolved_start, resolved_end, clamped, total_bytes, content }`", body = Value), AppError),
+    security(("session" = [])),
+)]
 async fn peek_buffer(
     State(state): State<AppState>,
     headers: HeaderMap,
@@ -1039,25 +4477,134 @@ async fn peek_buffer(
 ) -> Result<Json<Value>, AppError> {
     let _project = require_project(&state, &headers)?;
     let repl = require_repl(&state, &headers)?;
-    let content = repl::buffer_peek(&repl, &params.name, query.start, query.end)
+    let peek = repl::buffer_peek(&repl, &params.name, query.start, query.end)
         .map_err(AppError::NotFound)?;
     Ok(Json(json!({
         "buffer": params.name,
-        "start": query.start,
-        "end": query.end,
-        "content": content,
+        "requested_start": query.start,
+        "requested_end": query.end,
+        "resolved_start": peek.resolved_start,
+        "resolved_end": peek.resolved_end,
+        "clamped": peek.clamped,
+        "total_bytes": peek.total_bytes,
+        "content": peek.content,
     })))
 }
 
-async fn delete_buffer(
+#[utoipa::path(
+    delete,
+    path = "/api/v1/buffers/{name}",
+    tag = "repl",
+    params(BufferPath),
+    responses((status = 200, description = "Buffer deleted", body = Value), AppError),
+    security(("session" = [])),
+)]
+async fn delete_buffer(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    axum::extract::Path(params): axum::extract::Path<BufferPath>,
+) -> Result<Json<Value>, AppError> {
+    let _project = require_project(&state, &headers)?;
+    let repl = require_repl(&state, &headers)?;
+    repl::buffer_delete(&repl, &params.name).map_err(AppError::NotFound)?;
+    record_history(&state, session_id(&headers).as_deref(), "DELETE", "/buffers", &params.name);
+    Ok(Json(json!({ "deleted": true })))
+}
+
+// ---------------------------------------------------------------------------
+// Bookmarks
+// ---------------------------------------------------------------------------
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/bookmarks",
+    tag = "repl",
+    responses((status = 200, description = "`{ bookmarks: [BookmarkView], count }`", body = Value), AppError),
+    security(("session" = [])),
+)]
+async fn list_bookmarks(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let repl = require_repl(&state, &headers)?;
+    let bookmarks = state
+        .inner
+        .blocking
+        .run(move || {
+            repl::bookmark_list(&repl, &project.root, &project.file_tree, &project.symbol_table)
+        })
+        .await?;
+    let count = bookmarks.len();
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/bookmarks", &format!("{} bookmarks", count));
+    Ok(Json(json!({ "bookmarks": bookmarks, "count": count })))
+}
+
+#[derive(Deserialize, ToSchema)]
+struct CreateBookmarkBody {
+    #[serde(deserialize_with = "rel_path::deserialize")]
+    file: String,
+    /// 1-based line
+    line: Option<usize>,
+    /// Symbol in `file`; the bookmark then follows the symbol's start line
+    symbol: Option<String>,
+    #[serde(default)]
+    note: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/bookmarks",
+    tag = "repl",
+    request_body = CreateBookmarkBody,
+    responses((status = 200, description = "Bookmark created", body = Bookmark), AppError),
+    security(("session" = [])),
+)]
+async fn create_bookmark(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<CreateBookmarkBody>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let repl = require_repl(&state, &headers)?;
+    let bookmark = repl::bookmark_add(
+        &repl,
+        &project.file_tree,
+        &project.symbol_table,
+        &body.file,
+        body.line,
+        body.symbol.as_deref(),
+        &body.note,
+    )
+    .map_err(AppError::BadRequest)?;
+    let preview = format!("#{} {}", bookmark.id, bookmark.file);
+    record_history(&state, session_id(&headers).as_deref(), "POST", "/bookmarks", &preview);
+    Ok(Json(serde_json::to_value(bookmark).unwrap()))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+struct BookmarkPath {
+    id: u64,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/bookmarks/{id}",
+    tag = "repl",
+    params(BookmarkPath),
+    responses((status = 200, description = "Bookmark deleted", body = Value), AppError),
+    security(("session" = [])),
+)]
+async fn delete_bookmark(
     State(state): State<AppState>,
     headers: HeaderMap,
-    axum::extract::Path(params): axum::extract::Path<BufferPath>,
+    axum::extract::Path(params): axum::extract::Path<BookmarkPath>,
 ) -> Result<Json<Value>, AppError> {
     let _project = require_project(&state, &headers)?;
     let repl = require_repl(&state, &headers)?;
-    repl::buffer_delete(&repl, &params.name).map_err(AppError::NotFound)?;
-    record_history(&state, session_id(&headers).as_deref(), "DELETE", "/buffers", &params.name);
+    repl::bookmark_delete(&repl, params.id).map_err(AppError::NotFound)?;
+    record_history(&state, session_id(&headers).as_deref(), "DELETE", "/bookmarks", &params.id.to_string());
     Ok(Json(json!({ "deleted": true })))
 }
 
@@ -1065,27 +4612,94 @@ async fn delete_buffer(
 // Variables
 // ---------------------------------------------------------------------------
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ListVarsQuery {
+    /// Comma-separated variable names to fetch.
+    names: Option<String>,
+    /// Fetch every variable whose name starts with this.
+    prefix: Option<String>,
+    /// Cut values whose JSON encoding is larger than this many bytes.
+    max_value_bytes: Option<usize>,
+    /// `name` (default) or `size`, largest JSON encoding first.
+    sort_by: Option<String>,
+    /// Number of matching variables to skip (default 0).
+    offset: Option<usize>,
+    /// Return at most this many variables.
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct IncludeProjectQuery {
+    /// Also list the project's variables; session variables shadow them.
+    include_project: Option<bool>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/vars",
+    tag = "repl",
+    params(ListVarsQuery, IncludeProjectQuery),
+    responses((status = 200, description = "One page of matching variables", body = VarListing), AppError),
+    security(("session" = [])),
+)]
 async fn list_vars(
     State(state): State<AppState>,
     headers: HeaderMap,
+    Query(params): Query<ListVarsQuery>,
+    Query(include): Query<IncludeProjectQuery>,
 ) -> Result<Json<Value>, AppError> {
-    let _project = require_project(&state, &headers)?;
+    let project = require_project(&state, &headers)?;
     let repl = require_repl(&state, &headers)?;
-    let vars = repl::var_list(&repl);
-    let entries: Vec<Value> = vars
-        .iter()
-        .map(|(name, value)| json!({ "name": name, "value": value }))
-        .collect();
-    let count = entries.len();
-    Ok(Json(json!({ "variables": entries, "count": count })))
+    let selection = var_selection(params)?;
+    let listing = if include.include_project.unwrap_or(false) {
+        repl::var_list_with_project(&repl, &project.vars, &selection)
+    } else {
+        repl::var_list(&repl, &selection)
+    };
+    Ok(Json(serde_json::to_value(listing).unwrap()))
 }
 
-#[derive(Deserialize)]
+fn var_selection(params: ListVarsQuery) -> Result<repl::VarSelection, AppError> {
+    let sort = match params.sort_by.as_deref() {
+        None => repl::VarSort::Name,
+        Some(s) => repl::VarSort::from_str(s).ok_or_else(|| {
+            AppError::BadRequest(format!("Invalid sort_by '{}': expected 'name' or 'size'", s))
+        })?,
+    };
+    let selection = repl::VarSelection {
+        names: params.names.map(|names| {
+            names
+                .split(',')
+                .map(str::trim)
+                .filter(|n| !n.is_empty())
+                .map(String::from)
+                .collect()
+        }),
+        prefix: params.prefix,
+        max_value_bytes: params.max_value_bytes,
+        sort,
+        offset: params.offset.unwrap_or(0),
+        limit: params.limit,
+    };
+    Ok(selection)
+}
+
+#[derive(Deserialize, ToSchema)]
 struct SetVarBody {
     name: String,
     value: Value,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/vars",
+    tag = "repl",
+    request_body = SetVarBody,
+    responses((status = 200, description = "Variable set", body = Value), AppError),
+    security(("session" = [])),
+)]
 async fn set_var(
     State(state): State<AppState>,
     headers: HeaderMap,
@@ -1098,11 +4712,59 @@ async fn set_var(
     Ok(Json(json!({ "ok": true })))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
+struct ComputeVarBody {
+    /// Variable to store the result in.
+    name: String,
+    /// Expression evaluated against an object of all variables.
+    expr: String,
+    /// Expression language; only `jmespath` (the default) is supported.
+    lang: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/vars/compute",
+    tag = "repl",
+    request_body = ComputeVarBody,
+    responses((status = 200, description = "`{ name, value }` with the stored result", body = Value), AppError),
+    security(("session" = [])),
+)]
+async fn compute_var(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<ComputeVarBody>,
+) -> Result<Json<Value>, AppError> {
+    let _project = require_project(&state, &headers)?;
+    let repl = require_repl(&state, &headers)?;
+    if let Some(lang) = body.lang.as_deref()
+        && !lang.eq_ignore_ascii_case("jmespath")
+    {
+        return Err(AppError::BadRequest(format!(
+            "Unsupported lang '{}': expected 'jmespath'",
+            lang
+        )));
+    }
+    let value = repl::var_compute(&repl, &body.name, &body.expr).map_err(AppError::BadRequest)?;
+    let preview = format!("{} = {}", body.name, body.expr);
+    record_history(&state, session_id(&headers).as_deref(), "POST", "/vars/compute", &preview);
+    Ok(Json(json!({ "name": body.name, "value": value })))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
 struct VarPath {
     name: String,
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/vars/{name}",
+    tag = "repl",
+    params(VarPath),
+    responses((status = 200, description = "`{ name, value }`", body = Value), AppError),
+    security(("session" = [])),
+)]
 async fn get_var(
     State(state): State<AppState>,
     headers: HeaderMap,
@@ -1114,6 +4776,14 @@ async fn get_var(
     Ok(Json(json!({ "name": params.name, "value": value })))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/api/v1/vars/{name}",
+    tag = "repl",
+    params(VarPath),
+    responses((status = 200, description = "Variable deleted", body = Value), AppError),
+    security(("session" = [])),
+)]
 async fn delete_var(
     State(state): State<AppState>,
     headers: HeaderMap,
@@ -1126,28 +4796,234 @@ async fn delete_var(
     Ok(Json(json!({ "deleted": true })))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/project_vars",
+    tag = "repl",
+    params(ListVarsQuery),
+    responses((status = 200, description = "One page of the project's matching variables", body = VarListing), AppError),
+    security(("session" = [])),
+)]
+async fn list_project_vars(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<ListVarsQuery>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let listing = repl::list_vars_in(&project.vars, &var_selection(params)?);
+    Ok(Json(serde_json::to_value(listing).unwrap()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/project_vars",
+    tag = "repl",
+    request_body = SetVarBody,
+    responses((status = 200, description = "Variable set and saved", body = Value), AppError),
+    security(("session" = [])),
+)]
+async fn set_project_var(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<SetVarBody>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    project.vars.insert(body.name.clone(), body.value);
+    project_vars::save_project_vars(&project.root, &project.vars).map_err(AppError::Internal)?;
+    record_history(&state, session_id(&headers).as_deref(), "POST", "/project_vars", &body.name);
+    Ok(Json(json!({ "ok": true })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/project_vars/{name}",
+    tag = "repl",
+    params(VarPath),
+    responses((status = 200, description = "`{ name, value }`", body = Value), AppError),
+    security(("session" = [])),
+)]
+async fn get_project_var(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    axum::extract::Path(params): axum::extract::Path<VarPath>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let value = project
+        .vars
+        .get(&params.name)
+        .map(|v| v.value().clone())
+        .ok_or_else(|| AppError::NotFound(format!("Project variable '{}' not found", params.name)))?;
+    Ok(Json(json!({ "name": params.name, "value": value })))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/project_vars/{name}",
+    tag = "repl",
+    params(VarPath),
+    responses((status = 200, description = "Variable deleted and the change saved", body = Value), AppError),
+    security(("session" = [])),
+)]
+async fn delete_project_var(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    axum::extract::Path(params): axum::extract::Path<VarPath>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    project
+        .vars
+        .remove(&params.name)
+        .ok_or_else(|| AppError::NotFound(format!("Project variable '{}' not found", params.name)))?;
+    project_vars::save_project_vars(&project.root, &project.vars).map_err(AppError::Internal)?;
+    record_history(&state, session_id(&headers).as_deref(), "DELETE", "/project_vars", &params.name);
+    Ok(Json(json!({ "deleted": true })))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct CheckFinalQuery {
+    /// Return 404 instead of `{ is_set: false }` when `Final` is unset
+    require: Option<bool>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/vars/final",
+    tag = "repl",
+    params(CheckFinalQuery),
+    responses((status = 200, description = "`{ is_set, value? }`", body = Value), AppError),
+    security(("session" = [])),
+)]
 async fn check_final(
     State(state): State<AppState>,
     headers: HeaderMap,
+    Query(q): Query<CheckFinalQuery>,
 ) -> Result<Json<Value>, AppError> {
     let _project = require_project(&state, &headers)?;
     let repl = require_repl(&state, &headers)?;
     match repl::check_final(&repl) {
         Some(value) => Ok(Json(json!({ "is_set": true, "value": value }))),
+        None if q.require.unwrap_or(false) => {
+            Err(AppError::NotFound("Final variable not yet set".into()))
+        }
         None => Ok(Json(json!({ "is_set": false }))),
     }
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct WaitFinalQuery {
+    /// Seconds to wait before giving up (default 30, max 300)
+    timeout_secs: Option<u64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/vars/final/wait",
+    tag = "repl",
+    params(WaitFinalQuery),
+    responses((status = 200, description = "`{ is_set: true, value }` once `Final` is set", body = Value), AppError),
+    security(("session" = [])),
+)]
+async fn wait_final(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(q): Query<WaitFinalQuery>,
+) -> Result<Json<Value>, AppError> {
+    let _project = require_project(&state, &headers)?;
+    let repl = require_repl(&state, &headers)?;
+    let timeout_secs = q
+        .timeout_secs
+        .unwrap_or(config::FINAL_WAIT_DEFAULT_SECS)
+        .min(config::FINAL_WAIT_MAX_SECS);
+    match repl::wait_for_final(&repl, std::time::Duration::from_secs(timeout_secs)).await {
+        Some(value) => Ok(Json(json!({ "is_set": true, "value": value }))),
+        None => Err(AppError::Timeout(format!(
+            "Final variable not set within {}s",
+            timeout_secs
+        ))),
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ValidateFinalBody {
+    /// Text or JSON to check instead of the session's `Final`
+    value: Option<Value>,
+    /// Regex for references, with a named `path` group and optional `line`
+    /// and `end` groups (default: `path[:line[-end]]`)
+    pattern: Option<String>,
+    /// Extensions a bare `name.ext` needs to count as a file reference
+    /// (default: every indexed language's, plus a few config formats)
+    extensions: Option<Vec<String>>,
+    /// Check that quoted snippets appear near the cited lines (default true)
+    check_snippets: Option<bool>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/vars/final/validate",
+    tag = "repl",
+    request_body = ValidateFinalBody,
+    responses((status = 200, description = "Per-citation verdicts", body = citations::CitationReport), AppError),
+    security(("session" = [])),
+)]
+async fn validate_final(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<ValidateFinalBody>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let repl = require_repl(&state, &headers)?;
+    let value = match body.value {
+        Some(value) => value,
+        None => repl::check_final(&repl)
+            .ok_or_else(|| AppError::NotFound("Final variable not yet set".into()))?,
+    };
+    let rules = citations::CitationRules::new(
+        body.pattern.as_deref(),
+        body.extensions,
+        body.check_snippets.unwrap_or(true),
+    )
+    .map_err(AppError::BadRequest)?;
+    let root = project.root.clone();
+    let file_tree = project.file_tree.clone();
+    let report = state
+        .inner
+        .blocking
+        .run(move || citations::validate_citations(&root, &file_tree, &value, &rules))
+        .await?;
+    record_history(
+        &state,
+        session_id(&headers).as_deref(),
+        "POST",
+        "/vars/final/validate",
+        &format!("{} citations, {} invalid", report.total, report.invalid),
+    );
+    Ok(Json(serde_json::to_value(report).unwrap()))
+}
+
 // ---------------------------------------------------------------------------
 // Semantic chunks
 // ---------------------------------------------------------------------------
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct SemanticChunkQuery {
+    #[serde(deserialize_with = "rel_path::deserialize")]
     file: String,
     max_chunk_bytes: Option<usize>,
+    /// Label each chunk with a `topic` derived from its symbols.
+    include_topic: Option<bool>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/semantic_chunks",
+    tag = "repl",
+    params(SemanticChunkQuery),
+    responses((status = 200, description = "`{ file, chunks: [SemanticChunk], count }`", body = Value), AppError),
+    security(("session" = [])),
+)]
 async fn semantic_chunks(
     State(state): State<AppState>,
     headers: HeaderMap,
@@ -1161,6 +5037,7 @@ async fn semantic_chunks(
         &project.symbol_table,
         &params.file,
         max_bytes,
+        params.include_topic.unwrap_or(false),
     )
     .map_err(AppError::NotFound)?;
     let count = chunks.len();
@@ -1169,11 +5046,159 @@ async fn semantic_chunks(
     Ok(Json(json!({ "file": params.file, "chunks": chunks, "count": count })))
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SemanticChunkGrepQuery {
+    #[serde(deserialize_with = "rel_path::deserialize")]
+    file: String,
+    pattern: String,
+    max_chunk_bytes: Option<usize>,
+    /// Matching lines to show per chunk (default 3)
+    max_lines_per_chunk: Option<usize>,
+    /// Optional scope filter: "all" (default) or "code" (skip comments/strings).
+    scope: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/semantic_chunks/grep",
+    tag = "repl",
+    params(SemanticChunkGrepQuery),
+    responses((status = 200, description = "`{ file, pattern, chunks: [ChunkGrepHit], count, total_matches }`", body = Value), AppError),
+    security(("session" = [])),
+)]
+async fn semantic_chunk_grep(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<SemanticChunkGrepQuery>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    let max_bytes = params.max_chunk_bytes.unwrap_or(5000);
+    let lines_per_chunk = params.max_lines_per_chunk.unwrap_or(3);
+    let scope = match params.scope.as_deref() {
+        None => content::GrepScope::All,
+        Some(s) => content::GrepScope::from_str(s).ok_or_else(|| {
+            AppError::BadRequest(format!("Invalid scope '{}': expected all or code", s))
+        })?,
+    };
+    if project.file_tree.get(&params.file).is_none() {
+        return Err(AppError::NotFound(format!("File '{}' not found in index", params.file)));
+    }
+
+    let root = project.root.clone();
+    let file_tree = project.file_tree.clone();
+    let symbol_table = project.symbol_table.clone();
+    let file = params.file.clone();
+    let pattern = params.pattern.clone();
+    let chunks = state
+        .inner
+        .blocking
+        .run(move || {
+            repl::semantic_chunk_grep(
+                &root,
+                &file_tree,
+                &symbol_table,
+                &file,
+                &pattern,
+                max_bytes,
+                lines_per_chunk,
+                scope,
+            )
+        })
+        .await?
+        .map_err(AppError::BadRequest)?;
+
+    let count = chunks.len();
+    let total_matches: usize = chunks.iter().map(|c| c.match_count).sum();
+    let preview = format!(
+        "{} matches for '{}' across {} chunks of {}",
+        total_matches, params.pattern, count, params.file
+    );
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/semantic_chunks/grep", &preview);
+    Ok(Json(json!({
+        "file": params.file,
+        "pattern": params.pattern,
+        "chunks": chunks,
+        "count": count,
+        "total_matches": total_matches,
+    })))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ChunkPackageQuery {
+    #[serde(deserialize_with = "rel_path::deserialize")]
+    file: String,
+    /// Chunk `index` from `/semantic_chunks` with the same `max_chunk_bytes`
+    chunk_id: usize,
+    query: String,
+    /// Most tokens in the packaged text (default 8000)
+    budget_tokens: Option<usize>,
+    max_chunk_bytes: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/semantic_chunks/package",
+    tag = "repl",
+    params(ChunkPackageQuery),
+    responses((status = 200, description = "Prompt text for a sub-LM call over one chunk, with its token count", body = repl::ChunkPackage), AppError),
+    security(("session" = [])),
+)]
+async fn package_chunk(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<ChunkPackageQuery>,
+) -> Result<Json<Value>, AppError> {
+    let project = require_project(&state, &headers)?;
+    if project.file_tree.get(&params.file).is_none() {
+        let message = format!("File '{}' not found in index", params.file);
+        return Err(file_not_found(&project, &params.file, message));
+    }
+    let max_bytes = params.max_chunk_bytes.unwrap_or(5000);
+    let budget_tokens = params
+        .budget_tokens
+        .unwrap_or(config::CHUNK_PACKAGE_DEFAULT_BUDGET_TOKENS);
+
+    let root = project.root.clone();
+    let file_tree = project.file_tree.clone();
+    let symbol_table = project.symbol_table.clone();
+    let file = params.file.clone();
+    let query = params.query.clone();
+    let package = state
+        .inner
+        .blocking
+        .run(move || {
+            repl::package_chunk(
+                &root,
+                &file_tree,
+                &symbol_table,
+                &file,
+                params.chunk_id,
+                max_bytes,
+                &query,
+                budget_tokens,
+            )
+        })
+        .await?
+        .map_err(AppError::BadRequest)?;
+
+    let preview = format!(
+        "chunk {} of {}: {} tokens{}",
+        package.chunk_id,
+        package.file,
+        package.tokens,
+        if package.truncated { ", truncated" } else { "" }
+    );
+    record_history(&state, session_id(&headers).as_deref(), "GET", "/semantic_chunks/package", &preview);
+    Ok(Json(serde_json::to_value(package).unwrap()))
+}
+
 // ---------------------------------------------------------------------------
 // Subcall results
 // ---------------------------------------------------------------------------
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct StoreSubcallBody {
     chunk_id: String,
     query: String,
@@ -1184,7 +5209,7 @@ struct StoreSubcallBody {
     answer_if_complete: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct FindingBody {
     point: String,
     #[serde(default)]
@@ -1197,14 +5222,32 @@ fn default_confidence() -> String {
     "medium".to_string()
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct StoreSubcallQuery {
+    /// Move long findings and answers into a `subcall/<chunk_id>` buffer,
+    /// storing only previews
+    materialize: Option<bool>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/subcall_results",
+    tag = "repl",
+    params(StoreSubcallQuery),
+    request_body = StoreSubcallBody,
+    responses((status = 200, description = "Result stored; `buffer` names the buffer holding the full text when it was materialized", body = Value), AppError),
+    security(("session" = [])),
+)]
 async fn store_subcall_result(
     State(state): State<AppState>,
     headers: HeaderMap,
+    Query(params): Query<StoreSubcallQuery>,
     Json(body): Json<StoreSubcallBody>,
 ) -> Result<Json<Value>, AppError> {
     let _project = require_project(&state, &headers)?;
     let repl = require_repl(&state, &headers)?;
-    let result = SubcallResult {
+    let mut result = SubcallResult {
         chunk_id: body.chunk_id.clone(),
         query: body.query,
         findings: body
@@ -1219,18 +5262,40 @@ async fn store_subcall_result(
         suggested_queries: body.suggested_queries,
         answer_if_complete: body.answer_if_complete,
         created_at: chrono::Utc::now(),
+        buffer: None,
+    };
+    let materialized = if params.materialize.unwrap_or(false) {
+        repl::materialize_subcall_result(&repl, &mut result)
+    } else {
+        None
     };
     repl::add_subcall_result(&repl, result);
-    record_history(
-        &state,
-        session_id(&headers).as_deref(),
-        "POST",
-        "/subcall_results",
-        &body.chunk_id,
-    );
-    Ok(Json(json!({ "ok": true })))
+    let preview = match &materialized {
+        Some(buf) => format!("{} -> {}", body.chunk_id, buf.name),
+        None => body.chunk_id.clone(),
+    };
+    record_history(&state, session_id(&headers).as_deref(), "POST", "/subcall_results", &preview);
+    let mut response = json!({ "ok": true });
+    if let Some(buf) = materialized {
+        response["buffer"] = json!(buf.name);
+    }
+    Ok(Json(response))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ClearSubcallQuery {
+    /// Also delete the buffers the results were materialized into
+    delete_buffers: Option<bool>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/subcall_results",
+    tag = "repl",
+    responses((status = 200, description = "`{ results: [SubcallResult], count }`", body = Value), AppError),
+    security(("session" = [])),
+)]
 async fn list_subcall_results(
     State(state): State<AppState>,
     headers: HeaderMap,
@@ -1242,13 +5307,23 @@ async fn list_subcall_results(
     Ok(Json(json!({ "results": results, "count": count })))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/api/v1/subcall_results",
+    tag = "repl",
+    params(ClearSubcallQuery),
+    responses((status = 200, description = "All results cleared; `buffers_removed` with `delete_buffers`", body = Value), AppError),
+    security(("session" = [])),
+)]
 async fn clear_subcall_results(
     State(state): State<AppState>,
     headers: HeaderMap,
+    Query(params): Query<ClearSubcallQuery>,
 ) -> Result<Json<Value>, AppError> {
     let _project = require_project(&state, &headers)?;
     let repl = require_repl(&state, &headers)?;
-    repl::clear_subcall_results(&repl);
+    let delete_buffers = params.delete_buffers.unwrap_or(false);
+    let (_, buffers_removed) = repl::clear_subcall_results(&repl, delete_buffers);
     record_history(
         &state,
         session_id(&headers).as_deref(),
@@ -1256,5 +5331,437 @@ async fn clear_subcall_results(
         "/subcall_results",
         "cleared",
     );
-    Ok(Json(json!({ "ok": true })))
+    let mut response = json!({ "ok": true });
+    if delete_buffers {
+        response["buffers_removed"] = json!(buffers_removed);
+    }
+    Ok(Json(response))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+struct ChunkPath {
+    /// Chunk id as stored; percent-encode `/` and other reserved characters
+    chunk_id: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/subcall_results/{chunk_id}",
+    tag = "repl",
+    params(ChunkPath),
+    responses((status = 200, description = "`{ chunk_id, results: [SubcallResult], count }`; empty when the chunk hasn't been analyzed", body = Value), AppError),
+    security(("session" = [])),
+)]
+async fn get_chunk_subcall_results(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    axum::extract::Path(params): axum::extract::Path<ChunkPath>,
+) -> Result<Json<Value>, AppError> {
+    let _project = require_project(&state, &headers)?;
+    let repl = require_repl(&state, &headers)?;
+    let results = repl::get_subcall_results_for_chunk(&repl, &params.chunk_id);
+    let count = results.len();
+    Ok(Json(json!({ "chunk_id": params.chunk_id, "results": results, "count": count })))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/subcall_results/{chunk_id}",
+    tag = "repl",
+    params(ChunkPath, ClearSubcallQuery),
+    responses((status = 200, description = "`{ ok, removed }`, plus `buffers_removed` with `delete_buffers`", body = Value), AppError),
+    security(("session" = [])),
+)]
+async fn clear_chunk_subcall_results(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    axum::extract::Path(params): axum::extract::Path<ChunkPath>,
+    Query(query): Query<ClearSubcallQuery>,
+) -> Result<Json<Value>, AppError> {
+    let _project = require_project(&state, &headers)?;
+    let repl = require_repl(&state, &headers)?;
+    let delete_buffers = query.delete_buffers.unwrap_or(false);
+    let (removed, buffers_removed) =
+        repl::clear_subcall_results_for_chunk(&repl, &params.chunk_id, delete_buffers);
+    record_history(
+        &state,
+        session_id(&headers).as_deref(),
+        "DELETE",
+        "/subcall_results",
+        &format!("{}: {} removed", params.chunk_id, removed),
+    );
+    let mut response = json!({ "ok": true, "removed": removed });
+    if delete_buffers {
+        response["buffers_removed"] = json!(buffers_removed);
+    }
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/subcall_results/{chunk_id}/aggregate",
+    tag = "repl",
+    params(ChunkPath),
+    responses((status = 200, description = "The chunk's results merged", body = ChunkAggregate), AppError),
+    security(("session" = [])),
+)]
+async fn aggregate_chunk_subcall_results(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    axum::extract::Path(params): axum::extract::Path<ChunkPath>,
+) -> Result<Json<ChunkAggregate>, AppError> {
+    let _project = require_project(&state, &headers)?;
+    let repl = require_repl(&state, &headers)?;
+    let results = repl::get_subcall_results_for_chunk(&repl, &params.chunk_id);
+    Ok(Json(repl::aggregate_subcall_results(&params.chunk_id, &results)))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::server::build_router;
+    use crate::server::state::temp_project;
+
+    fn router(state: &AppState, compression: bool) -> Router {
+        let compression = CompressionConfig {
+            enabled: compression,
+            min_bytes: config::DEFAULT_COMPRESSION_MIN_BYTES,
+        };
+        build_router(state.clone(), &compression)
+    }
+
+    /// Open a session on `dir` and wait until its symbols are extracted.
+    async fn open_session(state: &AppState, dir: &std::path::Path) -> String {
+        let request = Request::post("/api/v1/sessions")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(json!({ "cwd": dir }).to_string()))
+            .unwrap();
+        let response = router(state, false).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&bytes).unwrap();
+        let project = state.get_or_create_project(dir, None, false).unwrap();
+        for _ in 0..500 {
+            if project.call_graph_complete() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(project.call_graph_complete(), "indexing did not finish");
+        body["session_id"].as_str().unwrap().to_string()
+    }
+
+    async fn get(state: &AppState, session: &str, uri: &str) -> (StatusCode, Value) {
+        let request = Request::get(uri)
+            .header("x-session-id", session)
+            .body(Body::empty())
+            .unwrap();
+        let response = router(state, false).oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    async fn shutdown_status(state: &AppState, token: Option<&str>) -> StatusCode {
+        let mut request = Request::post("/api/v1/admin/shutdown");
+        if let Some(token) = token {
+            request = request.header("x-admin-token", token);
+        }
+        router(state, false)
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    fn shutdown_requested(state: &AppState) -> bool {
+        *state.inner.shutdown.borrow()
+    }
+
+    #[tokio::test]
+    async fn shutdown_is_disabled_without_a_token() {
+        let state = AppState::for_tests();
+        assert_eq!(shutdown_status(&state, None).await, StatusCode::FORBIDDEN);
+        assert_eq!(shutdown_status(&state, Some("")).await, StatusCode::FORBIDDEN);
+        assert!(!shutdown_requested(&state));
+    }
+
+    #[tokio::test]
+    async fn shutdown_requires_the_configured_token() {
+        let state = AppState::for_tests_with_admin_token(Some("s3cret"));
+        assert_eq!(shutdown_status(&state, None).await, StatusCode::FORBIDDEN);
+        assert_eq!(shutdown_status(&state, Some("s3cre")).await, StatusCode::FORBIDDEN);
+        assert_eq!(shutdown_status(&state, Some("s3cret!")).await, StatusCode::FORBIDDEN);
+        assert!(!shutdown_requested(&state));
+
+        assert_eq!(shutdown_status(&state, Some("s3cret")).await, StatusCode::OK);
+        assert!(shutdown_requested(&state));
+    }
+
+    #[tokio::test]
+    async fn symbol_listing_is_gzipped_on_request() {
+        let source: String = (0..200).map(|i| format!("pub fn function_{}() {{}}\n", i)).collect();
+        let dir = temp_project(&[("src/lib.rs", &source)]);
+        let state = AppState::for_tests();
+        let session = open_session(&state, &dir).await;
+
+        let list = |encoding: Option<&str>| {
+            let mut request = Request::get("/api/v1/symbols?limit=200").header("x-session-id", &session);
+            if let Some(encoding) = encoding {
+                request = request.header(header::ACCEPT_ENCODING, encoding);
+            }
+            router(&state, true).oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        let plain = list(None).await.unwrap();
+        assert_eq!(plain.status(), StatusCode::OK);
+        assert!(plain.headers().get(header::CONTENT_ENCODING).is_none());
+        let plain = axum::body::to_bytes(plain.into_body(), usize::MAX).await.unwrap();
+        let listing: Value = serde_json::from_slice(&plain).unwrap();
+        assert_eq!(listing["count"], 200);
+
+        let gzipped = list(Some("gzip")).await.unwrap();
+        assert_eq!(gzipped.status(), StatusCode::OK);
+        assert_eq!(gzipped.headers()[header::CONTENT_ENCODING], "gzip");
+        let gzipped = axum::body::to_bytes(gzipped.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(gzipped[..2], [0x1f, 0x8b], "body should be a gzip stream");
+        assert!(gzipped.len() < plain.len() / 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn small_responses_are_not_compressed() {
+        let request = Request::get("/api/v1/health")
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .body(Body::empty())
+            .unwrap();
+        let response = router(&AppState::for_tests(), true).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+    }
+
+    const PEEK_SOURCE: &str = "use std::fmt;\n\n/// Adds.\npub fn add(a: u32, b: u32) -> u32 {\n    a + b\n}\n\npub fn sub(a: u32, b: u32) -> u32 {\n    a - b\n}\n";
+
+    #[tokio::test]
+    async fn peek_by_symbol_serves_its_lines_and_byte_range() {
+        let dir = temp_project(&[("src/lib.rs", PEEK_SOURCE)]);
+        let state = AppState::for_tests();
+        let session = open_session(&state, &dir).await;
+
+        let (status, body) = get(&state, &session, "/api/v1/peek?file=src/lib.rs&symbol=sub").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["symbol"]["name"], "sub");
+        assert_eq!(body["symbol"]["line_range"], json!([8, 10]));
+        assert_eq!(body["symbol_resolved"], true);
+        assert_eq!(body["resolved_lines"], json!({ "start": 7, "end": 10 }));
+        assert!(body["content"].as_str().unwrap().contains("a - b"));
+        assert!(!body["content"].as_str().unwrap().contains("a + b"));
+
+        let (start, end) = (
+            body["symbol"]["byte_range"][0].as_u64().unwrap() as usize,
+            body["symbol"]["byte_range"][1].as_u64().unwrap() as usize,
+        );
+        assert!(PEEK_SOURCE[start..end].starts_with("pub fn sub"));
+        assert!(PEEK_SOURCE[start..end].ends_with('}'));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn peek_by_symbol_saturates_huge_context() {
+        let dir = temp_project(&[("src/lib.rs", PEEK_SOURCE)]);
+        let state = AppState::for_tests();
+        let session = open_session(&state, &dir).await;
+
+        let uri = format!(
+            "/api/v1/peek?file=src/lib.rs&symbol=add&context_before={0}&context_after={0}",
+            usize::MAX
+        );
+        let (status, body) = get(&state, &session, &uri).await;
+        assert_eq!(status, StatusCode::OK);
+        let content = body["content"].as_str().unwrap();
+        assert!(content.contains("use std::fmt;"));
+        assert!(content.contains("a - b"));
+        assert_eq!(body["resolved_lines"], json!({ "start": 3, "end": 6 }));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn peek_by_symbol_adds_context_lines() {
+        let dir = temp_project(&[("src/lib.rs", PEEK_SOURCE)]);
+        let state = AppState::for_tests();
+        let session = open_session(&state, &dir).await;
+
+        let uri = "/api/v1/peek?file=src/lib.rs&symbol=add&context_before=1&context_after=1";
+        let (status, body) = get(&state, &session, uri).await;
+        assert_eq!(status, StatusCode::OK);
+        let content = body["content"].as_str().unwrap();
+        assert!(content.contains("/// Adds."));
+        assert!(content.contains("a + b"));
+        // The line after `add` is blank; `sub` stays out.
+        assert!(!content.contains("pub fn sub"));
+        // `resolved_lines` still names the symbol alone.
+        assert_eq!(body["resolved_lines"], json!({ "start": 3, "end": 6 }));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn peek_by_symbol_rejects_conflicting_params() {
+        let dir = temp_project(&[("src/lib.rs", PEEK_SOURCE)]);
+        let state = AppState::for_tests();
+        let session = open_session(&state, &dir).await;
+
+        for uri in [
+            "/api/v1/peek?file=src/lib.rs&symbol=add&start=0",
+            "/api/v1/peek?file=src/lib.rs&symbol=add&end=3",
+            "/api/v1/peek?file=src/lib.rs&context_before=2",
+        ] {
+            let (status, _) = get(&state, &session, uri).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+        }
+        let (status, _) = get(&state, &session, "/api/v1/peek?file=src/lib.rs&symbol=mul").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn peek_by_ambiguous_folded_symbol_lists_candidates() {
+        let dir = temp_project(&[("src/lib.rs", PEEK_SOURCE)]);
+        let state = AppState::for_tests();
+        let session = open_session(&state, &dir).await;
+        // No bundled grammar is case-insensitive, so fold two names by hand.
+        let project = state.get_or_create_project(&dir, None, false).unwrap();
+        for (name, line) in [("Parse", 8), ("PARSE", 4)] {
+            let sym = Symbol::for_tests(name, SymbolKind::Function, "src/lib.rs", line);
+            project.symbol_table.insert(sym.clone());
+            project.symbol_table.index_folded_name(&sym);
+        }
+
+        let (status, body) = get(&state, &session, "/api/v1/peek?file=src/lib.rs&symbol=parse").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body["candidates"],
+            json!([
+                { "name": "PARSE", "line_range": [4, 4] },
+                { "name": "Parse", "line_range": [8, 8] },
+            ])
+        );
+        // The exact name still resolves.
+        let (status, body) = get(&state, &session, "/api/v1/peek?file=src/lib.rs&symbol=Parse").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["symbol"]["name"], "Parse");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn callers_report_source_and_indexing_state() {
+        let dir = temp_project(&[
+            ("src/lib.rs", "pub fn target() {}\n"),
+            ("src/a.rs", "fn one() {\n    target();\n}\n"),
+        ]);
+        let state = AppState::for_tests();
+        let session = open_session(&state, &dir).await;
+        let uri = "/api/v1/symbols/callers?file=src/lib.rs&symbol=target";
+
+        let (status, body) = get(&state, &session, uri).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["source"], "cached");
+        assert_eq!(body["indexing"], false);
+        assert_eq!(body["callers"][0]["file"], "src/a.rs");
+        assert_eq!(body["callers"][0]["line"], 2);
+
+        // Back to extracting, with the graph entry gone: the endpoint says
+        // so and scans instead of answering from a partial graph.
+        let project = state.get_or_create_project(&dir, None, false).unwrap();
+        project.index_status.lock().state = crate::server::state::IndexingState::Extracting;
+        project.symbol_table.remove_callers_from_file("src/a.rs");
+        let (status, body) = get(&state, &session, uri).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["source"], "scanned");
+        assert_eq!(body["indexing"], true);
+        assert_eq!(body["callers"][0]["file"], "src/a.rs");
+        assert_eq!(body["callers"][0]["line"], 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn raw_query_limit_is_clamped() {
+        assert_eq!(raw_query_limit(None), config::RAW_QUERY_DEFAULT_LIMIT);
+        assert_eq!(raw_query_limit(Some(0)), 1);
+        assert_eq!(raw_query_limit(Some(25)), 25);
+        assert_eq!(raw_query_limit(Some(usize::MAX)), config::RAW_QUERY_MAX_LIMIT);
+    }
+
+    #[tokio::test]
+    async fn memory_usage_redirects_to_memory() {
+        let state = AppState::for_tests();
+        let request = Request::get("/api/v1/admin/memory_usage").body(Body::empty()).unwrap();
+        let response = router(&state, false).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(response.headers()[header::LOCATION], "/api/v1/admin/memory");
+    }
+
+    #[tokio::test]
+    async fn bulk_delete_rejects_out_of_range_age() {
+        let state = AppState::for_tests();
+        for hours in ["1e12", "1e300"] {
+            let uri = format!("/api/v1/sessions?older_than_hours={}", hours);
+            let request = Request::delete(uri).body(Body::empty()).unwrap();
+            let response = router(&state, false).oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "older_than_hours={}", hours);
+        }
+    }
+
+    #[tokio::test]
+    async fn session_less_search_ranks_each_project_like_global_search() {
+        let first = temp_project(&[("lib.rs", "fn reparse() {}\nfn parse_all() {}\nfn parse() {}\n")]);
+        let second = temp_project(&[("lib.rs", "fn unparsed() {}\n")]);
+        let state = AppState::for_tests();
+        open_session(&state, &first).await;
+        open_session(&state, &second).await;
+
+        let request = Request::get("/api/v1/symbols/search?q=parse&limit=3").body(Body::empty()).unwrap();
+        let response = router(&state, false).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&bytes).unwrap();
+        let got: Vec<(String, String, f64)> = body["symbols"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| {
+                let project = s["project"].as_str().unwrap().to_string();
+                (project, s["name"].as_str().unwrap().to_string(), s["score"].as_f64().unwrap())
+            })
+            .collect();
+
+        // Projects take turns in root order, each giving its best remaining
+        // match: the exact name before the prefix match, `reparse` cut.
+        let root = |dir: &std::path::Path| dir.canonicalize().unwrap().display().to_string();
+        let first_turns = [
+            (root(&first), "parse".to_string(), 1.0),
+            (root(&first), "parse_all".to_string(), 0.7),
+        ];
+        let second_turn = (root(&second), "unparsed".to_string(), 0.5);
+        let expected = if root(&first) < root(&second) {
+            vec![first_turns[0].clone(), second_turn, first_turns[1].clone()]
+        } else {
+            vec![second_turn, first_turns[0].clone(), first_turns[1].clone()]
+        };
+        assert_eq!(got, expected);
+
+        std::fs::remove_dir_all(&first).unwrap();
+        std::fs::remove_dir_all(&second).unwrap();
+    }
 }
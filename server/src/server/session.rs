@@ -1,9 +1,14 @@
-use std::path::PathBuf;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
 use std::sync::Arc;
 
 use chrono::{DateTime, Utc};
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::config::{RECENTLY_USED_LIMIT, SESSION_SUMMARY_MAX_NAMES};
 
 // ── Buffer types ─────────────────────────────────────────────────────
 
@@ -13,9 +18,48 @@ pub struct Buffer {
     pub content: String,
     pub source: BufferSource,
     pub created_at: DateTime<Utc>,
+    #[serde(skip)]
+    pub access: BufferAccess,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// When and how often a buffer was read. Atomic so reads can record
+/// themselves through a shared map guard without taking a write lock.
+#[derive(Debug, Default)]
+pub struct BufferAccess {
+    /// Milliseconds since the epoch; 0 until the first read.
+    last_accessed_ms: AtomicI64,
+    count: AtomicU64,
+}
+
+impl BufferAccess {
+    pub fn touch(&self) {
+        self.last_accessed_ms
+            .store(Utc::now().timestamp_millis(), Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn last_accessed_at(&self) -> Option<DateTime<Utc>> {
+        match self.last_accessed_ms.load(Ordering::Relaxed) {
+            0 => None,
+            ms => DateTime::from_timestamp_millis(ms),
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+}
+
+impl Clone for BufferAccess {
+    fn clone(&self) -> Self {
+        Self {
+            last_accessed_ms: AtomicI64::new(self.last_accessed_ms.load(Ordering::Relaxed)),
+            count: AtomicU64::new(self.count()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum BufferSource {
     File {
@@ -27,6 +71,13 @@ pub enum BufferSource {
         name: String,
         file: String,
     },
+    /// Files under `path`, concatenated in path order.
+    Directory {
+        path: String,
+        include_globs: Vec<String>,
+        exclude_globs: Vec<String>,
+        max_total_bytes: usize,
+    },
     Grep {
         pattern: String,
     },
@@ -39,7 +90,7 @@ pub enum BufferSource {
 }
 
 /// Metadata-only view of a buffer (never includes full content).
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct BufferInfo {
     pub name: String,
     pub size_bytes: usize,
@@ -47,6 +98,9 @@ pub struct BufferInfo {
     pub source: BufferSource,
     pub preview: String,
     pub created_at: DateTime<Utc>,
+    /// Last peek or info read; `None` if never read since creation.
+    pub last_accessed_at: Option<DateTime<Utc>>,
+    pub access_count: u64,
 }
 
 impl BufferInfo {
@@ -64,13 +118,15 @@ impl BufferInfo {
             source: buf.source.clone(),
             preview,
             created_at: buf.created_at,
+            last_accessed_at: buf.access.last_accessed_at(),
+            access_count: buf.access.count(),
         }
     }
 }
 
 // ── Subcall results ──────────────────────────────────────────────────
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct SubcallResult {
     pub chunk_id: String,
     pub query: String,
@@ -78,15 +134,129 @@ pub struct SubcallResult {
     pub suggested_queries: Vec<String>,
     pub answer_if_complete: Option<String>,
     pub created_at: DateTime<Utc>,
+    /// When stored with `materialize`, the buffer holding the full findings
+    /// and answer; the fields above are then previews.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buffer: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Finding {
     pub point: String,
     pub evidence: String,
     pub confidence: String,
 }
 
+// ── Bookmarks ────────────────────────────────────────────────────────
+
+/// A place to come back to. When `symbol` is set, the bookmark follows the
+/// symbol's current start line rather than the stored `line`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Bookmark {
+    pub id: u64,
+    pub file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    pub note: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// ── Recently used symbols ────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SymbolAccess {
+    pub file: String,
+    pub symbol: String,
+    pub accessed_at: DateTime<Utc>,
+}
+
+/// Symbols the session has read, most recent first. Reading one again moves
+/// it to the front; past `RECENTLY_USED_LIMIT` the oldest is dropped.
+#[derive(Debug, Default)]
+pub struct RecentlyUsed {
+    entries: VecDeque<SymbolAccess>,
+}
+
+impl RecentlyUsed {
+    pub fn touch(&mut self, file: &str, symbol: &str) {
+        self.entries.retain(|e| e.file != file || e.symbol != symbol);
+        self.entries.push_front(SymbolAccess {
+            file: file.to_string(),
+            symbol: symbol.to_string(),
+            accessed_at: Utc::now(),
+        });
+        self.entries.truncate(RECENTLY_USED_LIMIT);
+    }
+
+    pub fn recent(&self, limit: usize) -> Vec<SymbolAccess> {
+        self.entries.iter().take(limit).cloned().collect()
+    }
+}
+
+// ── Annotations written by the session ───────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnotationAction {
+    Define,
+    Redefine,
+    Mark,
+}
+
+/// A file or symbol the session annotated, with its latest change.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AnnotationChange {
+    pub file: String,
+    /// Set for symbol definitions; absent for file definitions and marks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    pub action: AnnotationAction,
+    /// How many times the session changed this target.
+    pub count: usize,
+    pub at: DateTime<Utc>,
+}
+
+/// Definitions and marks the session wrote, one entry per target in the
+/// order they were first touched. Project annotations are shared, so this
+/// is the only record of which session wrote what.
+#[derive(Debug, Default)]
+pub struct SessionAnnotations {
+    entries: Vec<AnnotationChange>,
+}
+
+impl SessionAnnotations {
+    pub fn record(&mut self, action: AnnotationAction, file: &str, symbol: Option<&str>) {
+        let now = Utc::now();
+        // Marks and definitions of the same file are separate targets.
+        let is_mark = action == AnnotationAction::Mark;
+        let existing = self.entries.iter_mut().find(|e| {
+            e.file == file
+                && e.symbol.as_deref() == symbol
+                && (e.action == AnnotationAction::Mark) == is_mark
+        });
+        match existing {
+            Some(entry) => {
+                entry.action = action;
+                entry.count += 1;
+                entry.at = now;
+            }
+            None => self.entries.push(AnnotationChange {
+                file: file.to_string(),
+                symbol: symbol.map(str::to_string),
+                action,
+                count: 1,
+                at: now,
+            }),
+        }
+    }
+
+    pub fn list(&self) -> Vec<AnnotationChange> {
+        self.entries.clone()
+    }
+}
+
 // ── REPL state ───────────────────────────────────────────────────────
 
 #[derive(Debug, Default)]
@@ -94,6 +264,12 @@ pub struct ReplState {
     pub buffers: DashMap<String, Buffer>,
     pub variables: DashMap<String, serde_json::Value>,
     pub subcall_results: parking_lot::Mutex<Vec<SubcallResult>>,
+    pub bookmarks: parking_lot::Mutex<Vec<Bookmark>>,
+    pub next_bookmark_id: std::sync::atomic::AtomicU64,
+    /// Symbols read through implementation, callers, or buffer-from-symbol.
+    pub recently_used: parking_lot::Mutex<RecentlyUsed>,
+    /// Definitions and marks written through this session.
+    pub annotations: parking_lot::Mutex<SessionAnnotations>,
     /// Rough estimate of total bytes stored (buffers + variables).
     #[allow(dead_code)]
     pub estimated_bytes: std::sync::atomic::AtomicUsize,
@@ -101,12 +277,15 @@ pub struct ReplState {
 
 // ── History & Session ────────────────────────────────────────────────
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct HistoryEntry {
     pub timestamp: DateTime<Utc>,
     pub method: String,
     pub path: String,
     pub response_preview: String,
+    /// Estimated tokens in the JSON response, when one was sent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_tokens: Option<usize>,
 }
 
 #[derive(Debug, Clone)]
@@ -117,6 +296,39 @@ pub struct Session {
     pub last_active: DateTime<Utc>,
     pub history: Vec<HistoryEntry>,
     pub repl_state: Arc<ReplState>,
+    /// Estimated tokens of all JSON responses sent to this session since it
+    /// was created or its history was last compacted.
+    pub response_tokens: usize,
+    /// Projects attached after creation, addressed by alias.
+    pub linked_projects: Vec<LinkedProject>,
+}
+
+/// Counts and names of what a session's REPL holds, without the contents.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReplSummary {
+    pub buffer_count: usize,
+    /// Sorted; at most `SESSION_SUMMARY_MAX_NAMES`.
+    pub buffer_names: Vec<String>,
+    pub variable_count: usize,
+    /// Sorted; at most `SESSION_SUMMARY_MAX_NAMES`.
+    pub variable_names: Vec<String>,
+    pub subcall_result_count: usize,
+    /// Whether the `Final` variable has been set.
+    pub final_set: bool,
+}
+
+/// A project a session can address besides its primary one.
+#[derive(Debug, Clone)]
+pub struct LinkedProject {
+    pub alias: String,
+    pub root: PathBuf,
+}
+
+/// Default alias for a project: its directory name.
+fn default_alias(root: &Path) -> String {
+    root.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| root.display().to_string())
 }
 
 impl Session {
@@ -129,6 +341,83 @@ impl Session {
             last_active: now,
             history: Vec::new(),
             repl_state: Arc::new(ReplState::default()),
+            response_tokens: 0,
+            linked_projects: Vec::new(),
+        }
+    }
+
+    /// The session's projects as `(alias, root)`, primary first.
+    pub fn projects(&self) -> Vec<(String, PathBuf)> {
+        std::iter::once((default_alias(&self.project_path), self.project_path.clone()))
+            .chain(
+                self.linked_projects
+                    .iter()
+                    .map(|p| (p.alias.clone(), p.root.clone())),
+            )
+            .collect()
+    }
+
+    /// Root of the project named by alias or root path, if attached.
+    pub fn resolve_project(&self, name: &str) -> Option<PathBuf> {
+        let canonical = Path::new(name).canonicalize().ok();
+        self.projects()
+            .into_iter()
+            .find(|(alias, root)| {
+                alias == name || Path::new(name) == root || canonical.as_ref() == Some(root)
+            })
+            .map(|(_, root)| root)
+    }
+
+    /// Attach `root` and return its alias. Attaching the primary project or
+    /// one already linked returns the existing alias. Without a requested
+    /// alias the directory name is used, with a numeric suffix on clashes.
+    pub fn link_project(&mut self, root: PathBuf, alias: Option<&str>) -> Result<String, String> {
+        let projects = self.projects();
+        if let Some((existing, _)) = projects.iter().find(|(_, r)| *r == root) {
+            return Ok(existing.clone());
+        }
+        let taken = |name: &str| projects.iter().any(|(a, _)| a == name);
+        let alias = match alias {
+            Some(name) if name.is_empty() || name.contains(':') => {
+                return Err(format!("Invalid alias '{}': must be non-empty without ':'", name));
+            }
+            Some(name) if taken(name) => {
+                return Err(format!("Alias '{}' is already used in this session", name));
+            }
+            Some(name) => name.to_string(),
+            None => {
+                let base = default_alias(&root);
+                let mut alias = base.clone();
+                let mut n = 2;
+                while taken(&alias) {
+                    alias = format!("{}-{}", base, n);
+                    n += 1;
+                }
+                alias
+            }
+        };
+        self.linked_projects.push(LinkedProject {
+            alias: alias.clone(),
+            root,
+        });
+        Ok(alias)
+    }
+
+    pub fn repl_summary(&self) -> ReplSummary {
+        fn sorted_names(names: impl Iterator<Item = String>) -> Vec<String> {
+            let mut names: Vec<String> = names.collect();
+            names.sort();
+            names.truncate(SESSION_SUMMARY_MAX_NAMES);
+            names
+        }
+        let repl = &self.repl_state;
+        ReplSummary {
+            buffer_count: repl.buffers.len(),
+            buffer_names: sorted_names(repl.buffers.iter().map(|b| b.key().clone())),
+            variable_count: repl.variables.len(),
+            variable_names: sorted_names(repl.variables.iter().map(|v| v.key().clone())),
+            subcall_result_count: repl.subcall_results.lock().len(),
+            final_set: repl.variables.contains_key("Final"),
         }
     }
 
@@ -144,6 +433,7 @@ impl Session {
             } else {
                 response_preview.to_string()
             },
+            estimated_tokens: None,
         });
     }
 }
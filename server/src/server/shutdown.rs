@@ -0,0 +1,151 @@
+use std::future::IntoFuture;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::config;
+use crate::index::pdf;
+use crate::ops::annotations;
+use crate::server::state::AppState;
+
+/// What the flush step did on the way down.
+#[derive(Debug, Default, Serialize)]
+pub struct FlushReport {
+    pub annotations_saved: usize,
+    pub annotation_errors: Vec<String>,
+    pub converters_killed: usize,
+}
+
+/// Trip the shutdown flag on SIGINT or SIGTERM.
+pub fn spawn_signal_listener(state: AppState) {
+    tokio::spawn(async move {
+        let ctrl_c = async {
+            if let Err(e) = tokio::signal::ctrl_c().await {
+                warn!("Failed to listen for Ctrl-C: {}", e);
+                std::future::pending::<()>().await;
+            }
+        };
+
+        #[cfg(unix)]
+        let terminate = async {
+            match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                Ok(mut sig) => {
+                    sig.recv().await;
+                }
+                Err(e) => {
+                    warn!("Failed to listen for SIGTERM: {}", e);
+                    std::future::pending::<()>().await;
+                }
+            }
+        };
+        #[cfg(not(unix))]
+        let terminate = std::future::pending::<()>();
+
+        tokio::select! {
+            _ = ctrl_c => info!("Received SIGINT, shutting down"),
+            _ = terminate => info!("Received SIGTERM, shutting down"),
+        }
+        state.request_shutdown();
+    });
+}
+
+/// Resolves once shutdown has been requested by a signal or the admin route.
+pub async fn requested(state: AppState) {
+    let mut rx = state.inner.shutdown.subscribe();
+    let _ = rx.wait_for(|requested| *requested).await;
+}
+
+/// Drive `server` (which should already stop accepting on `requested`) until
+/// it finishes draining or the drain period after the request runs out.
+pub async fn serve_with_drain<F>(server: F, state: AppState) -> anyhow::Result<()>
+where
+    F: IntoFuture<Output = std::io::Result<()>>,
+{
+    let deadline = async {
+        requested(state).await;
+        tokio::time::sleep(Duration::from_secs(config::SHUTDOWN_DRAIN_SECS)).await;
+    };
+
+    tokio::select! {
+        result = server => result?,
+        _ = deadline => warn!(
+            "In-flight requests did not drain within {}s; shutting down anyway",
+            config::SHUTDOWN_DRAIN_SECS
+        ),
+    }
+    Ok(())
+}
+
+/// Persist what would otherwise be lost: annotations for every project with
+/// unsaved changes. Also kills any PDF converters still running.
+pub fn flush(state: &AppState) -> FlushReport {
+    let mut report = FlushReport::default();
+
+    for entry in state.inner.projects.iter() {
+        let project = entry.value();
+        if !project.annotations_dirty.swap(false, Ordering::SeqCst) {
+            continue;
+        }
+        match annotations::save_annotations(&project.root, &project.file_tree, &project.symbol_table) {
+            Ok(data) => {
+                project.record_annotations_saved(data);
+                report.annotations_saved += 1;
+            }
+            Err(e) => {
+                // Leave it dirty so a retry (or a second flush) still sees it.
+                project.annotations_dirty.store(true, Ordering::SeqCst);
+                report
+                    .annotation_errors
+                    .push(format!("{}: {}", project.root.display(), e));
+            }
+        }
+    }
+
+    report.converters_killed = pdf::kill_converters();
+
+    info!(
+        "Shutdown flush: saved annotations for {} project(s), {} error(s), killed {} converter(s)",
+        report.annotations_saved,
+        report.annotation_errors.len(),
+        report.converters_killed
+    );
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::state::temp_project;
+
+    #[tokio::test]
+    async fn flush_saves_dirty_annotations_once() {
+        let dir = temp_project(&[("src/lib.rs", "pub fn answer() -> u32 { 42 }\n")]);
+        let state = AppState::for_tests();
+        let project = state.get_or_create_project(&dir, None, false).unwrap();
+
+        // Nothing changed yet, so there is nothing to write.
+        assert_eq!(flush(&state).annotations_saved, 0);
+
+        project
+            .file_tree
+            .files
+            .get_mut("src/lib.rs")
+            .unwrap()
+            .definition = Some("The answer".into());
+        project.mark_annotations_dirty();
+
+        let report = flush(&state);
+        assert_eq!(report.annotations_saved, 1);
+        assert!(report.annotation_errors.is_empty());
+        let saved = std::fs::read_to_string(project.root.join(".coderlm/annotations.json")).unwrap();
+        let saved: serde_json::Value = serde_json::from_str(&saved).unwrap();
+        assert_eq!(saved["file_definitions"]["src/lib.rs"], "The answer");
+
+        // The first flush cleared the dirty flag.
+        assert_eq!(flush(&state).annotations_saved, 0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,16 +1,28 @@
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use chrono::{DateTime, Utc};
 use dashmap::DashMap;
 use parking_lot::Mutex;
+use serde::Serialize;
 use tracing::info;
 
 use crate::index::file_tree::FileTree;
 use crate::index::{walker, watcher};
+use crate::config::{self, IgnoreConfig, LimitsConfig};
+use crate::ops::annotations::{self, AnnotationData};
+use crate::ops::git::BlameCache;
+use crate::ops::project_vars;
+use crate::ops::watch::FileWatch;
+use crate::server::cancel::CancelRegistry;
 use crate::server::errors::AppError;
+use crate::server::limits::{BlockingPool, RateLimiter};
 use crate::server::session::Session;
-use crate::symbols::{parser, SymbolTable};
+use crate::symbols::fingerprint::FingerprintHistory;
+use crate::symbols::parser::{self, ExtractionErrors};
+use crate::symbols::snapshot;
+use crate::symbols::SymbolTable;
 
 /// A single indexed project with its own file tree, symbol table, and watcher.
 pub struct Project {
@@ -21,6 +33,107 @@ pub struct Project {
     #[allow(dead_code)]
     pub watcher: Option<watcher::WatcherHandle>,
     pub last_active: Mutex<DateTime<Utc>>,
+    /// Explicit single-file watches, keyed by relative path.
+    pub file_watches: DashMap<String, FileWatch>,
+    /// Set when definitions or marks change; cleared when they are saved.
+    pub annotations_dirty: AtomicBool,
+    pub index_status: Mutex<IndexStatus>,
+    /// Latest symbol extraction failure per file, shared with the watchers.
+    pub extraction_errors: Arc<ExtractionErrors>,
+    /// Annotations as last loaded from or saved to disk; `None` until the
+    /// first load after indexing.
+    pub annotations: Arc<Mutex<Option<AnnotationData>>>,
+    /// Symbol fingerprints from indexing and re-indexing, for `/symbols/diff`.
+    pub symbol_snapshots: Mutex<FingerprintHistory>,
+    /// Per-symbol blame summaries for `/symbols/blame`.
+    pub blame_cache: Mutex<BlameCache>,
+    /// Variables shared by every session on the project, saved to
+    /// `.coderlm/project_vars.json` on each change.
+    pub vars: DashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexingState {
+    /// Files are scanned; waiting for a blocking permit to extract symbols.
+    Queued,
+    Extracting,
+    Ready,
+    Failed,
+}
+
+/// What happened during the last symbol extraction and annotation load.
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexStatus {
+    pub state: IndexingState,
+    pub last_index_duration_ms: Option<u64>,
+    pub failed_files: usize,
+    /// First few per-file extraction errors.
+    pub errors: Vec<String>,
+    /// Set when the extraction pass as a whole failed.
+    pub fatal_error: Option<String>,
+    /// `None` until annotations have been loaded once.
+    pub annotations_loaded: Option<bool>,
+    pub annotations_error: Option<String>,
+    /// Files taken from the `snapshot_from` snapshot; `None` without one.
+    pub warm_start_files: Option<usize>,
+    /// Why a requested warm start was abandoned for a full index.
+    pub warm_start_error: Option<String>,
+}
+
+impl Default for IndexStatus {
+    fn default() -> Self {
+        Self {
+            state: IndexingState::Queued,
+            last_index_duration_ms: None,
+            failed_files: 0,
+            errors: Vec::new(),
+            fatal_error: None,
+            annotations_loaded: None,
+            annotations_error: None,
+            warm_start_files: None,
+            warm_start_error: None,
+        }
+    }
+}
+
+impl Project {
+    /// Record the outcome of loading `.coderlm/annotations.json`.
+    pub fn record_annotations_load(&self, result: &Result<AnnotationData, String>) {
+        {
+            let mut status = self.index_status.lock();
+            status.annotations_loaded = Some(result.is_ok());
+            status.annotations_error = result.as_ref().err().cloned();
+        }
+        if let Ok(data) = result {
+            if data.migrated {
+                self.mark_annotations_dirty();
+            }
+            *self.annotations.lock() = Some(data.clone());
+        }
+    }
+
+    pub fn record_annotations_saved(&self, data: AnnotationData) {
+        *self.annotations.lock() = Some(data);
+    }
+
+    pub fn mark_annotations_dirty(&self) {
+        self.annotations_dirty.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the reverse call graph has been fully built. It fills in
+    /// during extraction and stays partial if extraction failed.
+    pub fn call_graph_complete(&self) -> bool {
+        self.index_status.lock().state == IndexingState::Ready
+    }
+
+    /// Whether symbol extraction is queued or running.
+    pub fn is_indexing(&self) -> bool {
+        matches!(
+            self.index_status.lock().state,
+            IndexingState::Queued | IndexingState::Extracting
+        )
+    }
 }
 
 /// Shared application state, wrapped in Arc for axum handlers.
@@ -34,29 +147,131 @@ pub struct AppStateInner {
     pub sessions: DashMap<String, Session>,
     pub max_projects: usize,
     pub max_file_size: u64,
+    /// Flips to `true` once shutdown is requested (signal or admin route).
+    pub shutdown: tokio::sync::watch::Sender<bool>,
+    pub blocking: BlockingPool,
+    /// Cancellation tokens of the requests in flight.
+    pub cancellations: CancelRegistry,
+    pub rate_limiter: RateLimiter,
+    /// Estimated response tokens after which a session's responses carry a
+    /// `budget_warning`; 0 disables.
+    pub response_token_budget: usize,
+    /// Patterns marking files as generated in every project.
+    pub generated_patterns: Vec<String>,
+    /// Default and configured exclusions applied to every project.
+    pub ignore: IgnoreConfig,
+    /// Whether projects keep a trigram index for grep.
+    pub text_index: bool,
+    pub git_binary: PathBuf,
+    /// Whether `POST /query` accepts caller-supplied tree-sitter queries.
+    pub raw_queries: bool,
+    /// Secret gating `POST /admin/shutdown`; unset disables it.
+    pub admin_token: Option<String>,
+    /// Per-file watches held across all projects; see `WatchSlot`.
+    pub file_watch_slots: Arc<AtomicUsize>,
+    /// Held while a session is attached to its project and while bulk
+    /// session deletion evicts projects left unused, so one never acts on
+    /// a stale view of the other.
+    pub session_attach: Mutex<()>,
+}
+
+#[cfg(test)]
+impl AppState {
+    /// A state with the default limits and nothing indexed.
+    pub fn for_tests() -> Self {
+        Self::for_tests_with_admin_token(None)
+    }
+
+    pub fn for_tests_with_admin_token(admin_token: Option<&str>) -> Self {
+        AppState::new(
+            config::DEFAULT_MAX_PROJECTS,
+            config::DEFAULT_MAX_FILE_SIZE,
+            &LimitsConfig {
+                max_blocking: config::DEFAULT_MAX_BLOCKING,
+                rate_limit: 0.0,
+                rate_burst: 0.0,
+                response_token_budget: config::DEFAULT_RESPONSE_TOKEN_BUDGET,
+            },
+            Vec::new(),
+            IgnoreConfig::default(),
+            false,
+            PathBuf::from(config::DEFAULT_GIT_BINARY),
+            false,
+            admin_token.map(str::to_string),
+        )
+    }
+}
+
+/// A fresh directory under the system temp dir holding `files`, for tests
+/// that need a real project on disk. Callers remove it when done.
+#[cfg(test)]
+pub fn temp_project(files: &[(&str, &str)]) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("coderlm-test-{}", uuid::Uuid::new_v4()));
+    for (rel_path, contents) in files {
+        let path = root.join(rel_path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+    std::fs::create_dir_all(&root).unwrap();
+    root
 }
 
 impl AppState {
-    pub fn new(max_projects: usize, max_file_size: u64) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        max_projects: usize,
+        max_file_size: u64,
+        limits: &LimitsConfig,
+        generated_patterns: Vec<String>,
+        ignore: IgnoreConfig,
+        text_index: bool,
+        git_binary: PathBuf,
+        raw_queries: bool,
+        admin_token: Option<String>,
+    ) -> Self {
         Self {
             inner: Arc::new(AppStateInner {
                 projects: DashMap::new(),
                 sessions: DashMap::new(),
                 max_projects,
                 max_file_size,
+                shutdown: tokio::sync::watch::Sender::new(false),
+                blocking: BlockingPool::new(limits.max_blocking),
+                cancellations: CancelRegistry::default(),
+                rate_limiter: RateLimiter::new(limits.rate_limit, limits.rate_burst),
+                response_token_budget: limits.response_token_budget,
+                generated_patterns,
+                ignore,
+                text_index,
+                git_binary,
+                raw_queries,
+                admin_token,
+                file_watch_slots: Arc::new(AtomicUsize::new(0)),
+                session_attach: Mutex::new(()),
             }),
         }
     }
 
     /// Look up an existing project or index a new one. Evicts LRU if at capacity.
-    pub fn get_or_create_project(&self, cwd: &Path) -> Result<Arc<Project>, AppError> {
+    /// A new project warm-starts from the index snapshot at `snapshot_from`
+    /// (a snapshot file or the `.coderlm` directory holding one) when it
+    /// matches; an existing project ignores it. Paths are canonicalized, so
+    /// `/repo/`, `/repo/../repo` and a symlink to it share one project. A
+    /// new project may only be rooted at the filesystem root or the home
+    /// directory with `allow_large_root`.
+    pub fn get_or_create_project(
+        &self,
+        cwd: &Path,
+        snapshot_from: Option<&Path>,
+        allow_large_root: bool,
+    ) -> Result<Arc<Project>, AppError> {
         let canonical = cwd.canonicalize().map_err(|e| {
-            AppError::BadRequest(format!("Path not accessible: {}", e))
+            AppError::BadRequest(format!("Path '{}' not accessible: {}", cwd.display(), e))
         })?;
 
         if !canonical.is_dir() {
             return Err(AppError::BadRequest(format!(
-                "'{}' is not a directory",
+                "'{}' is not a directory; open a session on the project directory instead",
                 canonical.display()
             )));
         }
@@ -67,14 +282,39 @@ impl AppState {
             return Ok(project.clone());
         }
 
+        if !allow_large_root && let Some(what) = large_root_kind(&canonical) {
+            return Err(AppError::BadRequest(format!(
+                "'{}' is {}; indexing it would walk far more than one project. \
+                 Pass allow_large_root=true to index it anyway.",
+                canonical.display(),
+                what
+            )));
+        }
+
+        let snapshot_file = snapshot_from.map(snapshot::snapshot_path);
+        if let Some(path) = &snapshot_file
+            && !path.is_file()
+        {
+            return Err(AppError::BadRequest(format!(
+                "No index snapshot at '{}'",
+                path.display()
+            )));
+        }
+
         // Check capacity, evict if needed
         if self.inner.projects.len() >= self.inner.max_projects {
             self.evict_lru()?;
         }
 
         // Scan directory
-        let file_tree = Arc::new(FileTree::new());
+        let file_tree = Arc::new(
+            FileTree::new()
+                .with_generated_patterns(&canonical, &self.inner.generated_patterns)
+                .with_ignore_rules(&canonical, &self.inner.ignore)
+                .with_text_index(self.inner.text_index),
+        );
         let symbol_table = Arc::new(SymbolTable::new());
+        let extraction_errors = Arc::new(ExtractionErrors::new());
         let max_file_size = self.inner.max_file_size;
 
         info!("Indexing new project: {}", canonical.display());
@@ -88,6 +328,7 @@ impl AppState {
             &canonical,
             file_tree.clone(),
             symbol_table.clone(),
+            extraction_errors.clone(),
             max_file_size,
         )
         .ok();
@@ -98,6 +339,17 @@ impl AppState {
             symbol_table: symbol_table.clone(),
             watcher: watcher_handle,
             last_active: Mutex::new(Utc::now()),
+            file_watches: DashMap::new(),
+            annotations_dirty: AtomicBool::new(false),
+            index_status: Mutex::new(IndexStatus::default()),
+            extraction_errors: extraction_errors.clone(),
+            annotations: Arc::new(Mutex::new(None)),
+            symbol_snapshots: Mutex::new(FingerprintHistory::default()),
+            blame_cache: Mutex::new(BlameCache::default()),
+            vars: project_vars::load_project_vars(&canonical).unwrap_or_else(|e| {
+                tracing::warn!("{} for {}", e, canonical.display());
+                DashMap::new()
+            }),
         });
 
         self.inner.projects.insert(canonical, project.clone());
@@ -106,11 +358,79 @@ impl AppState {
         let ft = file_tree;
         let st = symbol_table;
         let root = project.root.clone();
+        let state = self.clone();
+        let indexed = project.clone();
         tokio::spawn(async move {
+            let _permit = state.inner.blocking.acquire().await;
             info!("Starting symbol extraction for {}...", root.display());
-            match parser::extract_all_symbols(&root, &ft, &st).await {
-                Ok(count) => info!("Extracted {} symbols for {}", count, root.display()),
-                Err(e) => tracing::error!("Symbol extraction failed for {}: {}", root.display(), e),
+            indexed.index_status.lock().state = IndexingState::Extracting;
+            let started = std::time::Instant::now();
+            let warm_start = match snapshot_file {
+                Some(path) => load_warm_start(&indexed, path).await,
+                None => None,
+            };
+            let warm = warm_start.is_some();
+            let result =
+                parser::extract_all_symbols(&root, &ft, &st, &extraction_errors, warm_start).await;
+            let elapsed = started.elapsed();
+
+            // Symbols exist now, so symbol definitions can be applied too.
+            let loaded = annotations::load_annotations(&root, &ft, &st);
+            indexed.record_annotations_load(&loaded);
+
+            if ft.text_index().is_some() {
+                let (tree, dir) = (ft.clone(), root.clone());
+                if let Err(e) =
+                    tokio::task::spawn_blocking(move || tree.build_text_index(&dir)).await
+                {
+                    tracing::warn!("Text index build failed for {}: {}", root.display(), e);
+                }
+            }
+
+            let succeeded = result.is_ok();
+            {
+                let mut status = indexed.index_status.lock();
+                status.last_index_duration_ms = Some(elapsed.as_millis() as u64);
+                match result {
+                    Ok(report) => {
+                        info!(
+                            "Extracted {} symbols for {} ({} files failed, {} from snapshot)",
+                            report.symbols,
+                            root.display(),
+                            report.failed_files,
+                            report.reused_files
+                        );
+                        status.state = IndexingState::Ready;
+                        status.failed_files = report.failed_files;
+                        status.errors = report.errors;
+                        status.fatal_error = None;
+                        status.warm_start_files = warm.then_some(report.reused_files);
+                    }
+                    Err(e) => {
+                        tracing::error!("Symbol extraction failed for {}: {}", root.display(), e);
+                        status.state = IndexingState::Failed;
+                        status.fatal_error = Some(e.to_string());
+                    }
+                }
+            }
+
+            // Fingerprint the fresh index as the `/symbols/diff` baseline, and
+            // leave a snapshot behind for the next checkout to warm-start from.
+            if succeeded {
+                let saved = tokio::task::spawn_blocking(move || {
+                    indexed.symbol_snapshots.lock().record("index", &st);
+                    snapshot::save_snapshot(&root, &ft, &st).map(|files| (root, files))
+                })
+                .await;
+                match saved {
+                    Ok(Ok((root, files))) => tracing::debug!(
+                        "Saved index snapshot of {} files for {}",
+                        files,
+                        root.display()
+                    ),
+                    Ok(Err(e)) => tracing::warn!("Failed to save index snapshot: {}", e),
+                    Err(e) => tracing::warn!("Failed to save index snapshot: {}", e),
+                }
             }
         });
 
@@ -143,6 +463,18 @@ impl AppState {
         Ok(project.clone())
     }
 
+    /// Register `session` if its project is still indexed. A project can be
+    /// evicted between being looked up and the session being added; this
+    /// returns `false` then, for the caller to look the project up again.
+    pub fn insert_session(&self, session: Session) -> bool {
+        let _attach = self.inner.session_attach.lock();
+        if !self.inner.projects.contains_key(&session.project_path) {
+            return false;
+        }
+        self.inner.sessions.insert(session.id.clone(), session);
+        true
+    }
+
     /// Update the last-active timestamp on a project.
     pub fn touch_project(&self, project_path: &Path) {
         if let Some(project) = self.inner.projects.get(project_path) {
@@ -150,6 +482,11 @@ impl AppState {
         }
     }
 
+    /// Ask the server to stop accepting connections and drain.
+    pub fn request_shutdown(&self) {
+        self.inner.shutdown.send_replace(true);
+    }
+
     /// Evict the least recently used project. Removes all sessions pointing to it.
     fn evict_lru(&self) -> Result<(), AppError> {
         // Find the project with the oldest last_active
@@ -171,7 +508,57 @@ impl AppState {
 
         // Remove all sessions attached to this project
         self.inner.sessions.retain(|_, session| session.project_path != path);
+        self.inner
+            .rate_limiter
+            .retain(|id| self.inner.sessions.contains_key(id));
 
         Ok(())
     }
 }
+
+/// Load and validate the snapshot a new project should warm-start from.
+/// Problems are recorded in the index status and mean a full index instead.
+async fn load_warm_start(project: &Project, path: PathBuf) -> Option<snapshot::IndexSnapshot> {
+    let file_tree = project.file_tree.clone();
+    let loaded = tokio::task::spawn_blocking(move || {
+        let snapshot = snapshot::load_snapshot(&path)?;
+        snapshot.check_alignment(&file_tree)?;
+        Ok::<_, String>(snapshot)
+    })
+    .await
+    .unwrap_or_else(|e| Err(e.to_string()));
+    match loaded {
+        Ok(snapshot) => Some(snapshot),
+        Err(e) => {
+            tracing::warn!(
+                "Not warm-starting {}: {}; indexing from scratch",
+                project.root.display(),
+                e
+            );
+            project.index_status.lock().warm_start_error = Some(e);
+            None
+        }
+    }
+}
+
+/// "the filesystem root" or "your home directory" when `root` is one.
+fn large_root_kind(root: &Path) -> Option<&'static str> {
+    if root.parent().is_none() {
+        return Some("the filesystem root");
+    }
+    let home = std::env::var_os("HOME").and_then(|home| PathBuf::from(home).canonicalize().ok());
+    (home.as_deref() == Some(root)).then_some("your home directory")
+}
+
+/// A warning for a project root with none of `PROJECT_MARKERS` in it,
+/// which usually means the session was opened in the wrong directory.
+pub fn project_root_warning(root: &Path) -> Option<String> {
+    if config::PROJECT_MARKERS.iter().any(|marker| root.join(marker).exists()) {
+        return None;
+    }
+    Some(format!(
+        "'{}' doesn't look like a project root (no .git, Cargo.toml, package.json, ...); \
+         symbols and paths are relative to it",
+        root.display()
+    ))
+}
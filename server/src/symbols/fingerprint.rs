@@ -0,0 +1,227 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::mem::size_of;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::config::SYMBOL_SNAPSHOT_LIMIT;
+use crate::symbols::symbol::SymbolKind;
+use crate::symbols::SymbolTable;
+
+/// The parts of a symbol that make up its public shape. Body edits don't
+/// change it; header, kind, or location changes do.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct SymbolFingerprint {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub file: String,
+    pub signature_hash: String,
+}
+
+/// Fingerprints of every symbol in a project at one point in time, keyed
+/// like `SymbolTable::symbols`.
+pub struct FingerprintSnapshot {
+    pub info: SnapshotInfo,
+    fingerprints: HashMap<String, SymbolFingerprint>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SnapshotInfo {
+    pub id: u64,
+    /// What prompted it: `index` (initial extraction) or `reindex`.
+    pub reason: String,
+    pub taken_at: DateTime<Utc>,
+    pub symbols: usize,
+}
+
+impl FingerprintSnapshot {
+    fn take(id: u64, reason: &str, symbol_table: &SymbolTable) -> Self {
+        let fingerprints: HashMap<String, SymbolFingerprint> = symbol_table
+            .symbols
+            .iter()
+            .map(|entry| (entry.key().clone(), fingerprint(entry.value())))
+            .collect();
+        Self {
+            info: SnapshotInfo {
+                id,
+                reason: reason.to_string(),
+                taken_at: Utc::now(),
+                symbols: fingerprints.len(),
+            },
+            fingerprints,
+        }
+    }
+
+    fn estimated_bytes(&self) -> usize {
+        self.fingerprints
+            .iter()
+            .map(|(key, fp)| {
+                key.len()
+                    + size_of::<SymbolFingerprint>()
+                    + fp.name.len()
+                    + fp.file.len()
+                    + fp.signature_hash.len()
+            })
+            .sum()
+    }
+
+    /// Symbols added, removed, or modified (signature or kind changed) in
+    /// `symbol_table` since this snapshot, grouped by file.
+    pub fn diff(&self, symbol_table: &SymbolTable) -> SymbolDiff {
+        let mut added = Vec::new();
+        let mut modified = Vec::new();
+        for entry in symbol_table.symbols.iter() {
+            let now = fingerprint(entry.value());
+            match self.fingerprints.get(entry.key()) {
+                None => added.push(now),
+                Some(before) if *before != now => modified.push(ModifiedSymbol {
+                    name: now.name.clone(),
+                    file: now.file.clone(),
+                    kind: now.kind,
+                    old_kind: (before.kind != now.kind).then_some(before.kind),
+                    old_signature_hash: before.signature_hash.clone(),
+                    new_signature_hash: now.signature_hash,
+                }),
+                Some(_) => {}
+            }
+        }
+        let removed: Vec<SymbolFingerprint> = self
+            .fingerprints
+            .iter()
+            .filter(|(key, _)| !symbol_table.symbols.contains_key(*key))
+            .map(|(_, fp)| fp.clone())
+            .collect();
+
+        let (added_count, removed_count, modified_count) =
+            (added.len(), removed.len(), modified.len());
+        let mut files: BTreeMap<String, FileDiff> = BTreeMap::new();
+        fn group<'a>(files: &'a mut BTreeMap<String, FileDiff>, file: &str) -> &'a mut FileDiff {
+            files.entry(file.to_string()).or_insert_with(|| FileDiff {
+                file: file.to_string(),
+                added: Vec::new(),
+                removed: Vec::new(),
+                modified: Vec::new(),
+            })
+        }
+        for fp in added {
+            group(&mut files, &fp.file).added.push(fp);
+        }
+        for fp in removed {
+            group(&mut files, &fp.file).removed.push(fp);
+        }
+        for m in modified {
+            group(&mut files, &m.file).modified.push(m);
+        }
+        let mut files: Vec<FileDiff> = files.into_values().collect();
+        for diff in &mut files {
+            diff.added.sort_by(|a, b| a.name.cmp(&b.name));
+            diff.removed.sort_by(|a, b| a.name.cmp(&b.name));
+            diff.modified.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+
+        SymbolDiff {
+            since: self.info.clone(),
+            added: added_count,
+            removed: removed_count,
+            modified: modified_count,
+            files,
+        }
+    }
+}
+
+fn fingerprint(sym: &crate::symbols::symbol::Symbol) -> SymbolFingerprint {
+    SymbolFingerprint {
+        name: sym.name.clone(),
+        kind: sym.kind,
+        file: sym.file.clone(),
+        signature_hash: sym.signature_hash.clone(),
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ModifiedSymbol {
+    pub name: String,
+    #[serde(skip)]
+    file: String,
+    pub kind: SymbolKind,
+    /// Previous kind, when that is what changed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_kind: Option<SymbolKind>,
+    pub old_signature_hash: String,
+    pub new_signature_hash: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FileDiff {
+    pub file: String,
+    pub added: Vec<SymbolFingerprint>,
+    pub removed: Vec<SymbolFingerprint>,
+    pub modified: Vec<ModifiedSymbol>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SymbolDiff {
+    /// The snapshot compared against the current index.
+    pub since: SnapshotInfo,
+    pub added: usize,
+    pub removed: usize,
+    pub modified: usize,
+    pub files: Vec<FileDiff>,
+}
+
+/// The fingerprint snapshots a project keeps: the one taken when indexing
+/// finished, plus the last `SYMBOL_SNAPSHOT_LIMIT` taken after re-indexing.
+#[derive(Default)]
+pub struct FingerprintHistory {
+    index: Option<FingerprintSnapshot>,
+    recent: VecDeque<FingerprintSnapshot>,
+    next_id: u64,
+}
+
+impl FingerprintHistory {
+    /// Snapshot `symbol_table` now. The first snapshot becomes the `index`
+    /// baseline; later ones push out the oldest beyond the limit.
+    pub fn record(&mut self, reason: &str, symbol_table: &SymbolTable) -> u64 {
+        self.next_id += 1;
+        let snapshot = FingerprintSnapshot::take(self.next_id, reason, symbol_table);
+        if self.index.is_none() {
+            self.index = Some(snapshot);
+        } else {
+            self.recent.push_back(snapshot);
+            while self.recent.len() > SYMBOL_SNAPSHOT_LIMIT {
+                self.recent.pop_front();
+            }
+        }
+        self.next_id
+    }
+
+    /// Look up `since`: `index` or a snapshot id.
+    pub fn get(&self, since: &str) -> Option<&FingerprintSnapshot> {
+        if since == "index" {
+            return self.index.as_ref();
+        }
+        let id: u64 = since.parse().ok()?;
+        self.index
+            .iter()
+            .chain(self.recent.iter())
+            .find(|s| s.info.id == id)
+    }
+
+    /// Snapshots currently available, oldest first.
+    pub fn list(&self) -> Vec<SnapshotInfo> {
+        self.index
+            .iter()
+            .chain(self.recent.iter())
+            .map(|s| s.info.clone())
+            .collect()
+    }
+
+    pub fn estimated_bytes(&self) -> usize {
+        self.index
+            .iter()
+            .chain(self.recent.iter())
+            .map(FingerprintSnapshot::estimated_bytes)
+            .sum()
+    }
+}
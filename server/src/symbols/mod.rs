@@ -1,10 +1,15 @@
+pub mod fingerprint;
 pub mod parser;
 pub mod queries;
+pub mod snapshot;
 pub mod symbol;
 
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
-use std::collections::HashSet;
+use parking_lot::RwLock;
+use std::collections::{BTreeSet, HashSet};
 
+use crate::index::rel_path;
 use symbol::Symbol;
 
 /// A cached reference to a call site.
@@ -13,6 +18,9 @@ pub struct CallerRef {
     pub file: String,
     pub line: usize,
     pub text: String,
+    /// Surrounding source lines, filled in on request by `find_callers`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
 }
 
 /// Thread-safe symbol table with secondary indices for fast lookup.
@@ -23,9 +31,26 @@ pub struct SymbolTable {
     pub by_name: DashMap<String, HashSet<String>>,
     /// Secondary index: file path -> set of primary keys
     pub by_file: DashMap<String, HashSet<String>>,
+    /// Secondary index: signature hash -> set of primary keys. Identical
+    /// headers (e.g. `fn new() -> Self {` in several impls) share a hash.
+    pub by_signature_hash: DashMap<String, HashSet<String>>,
+    /// Secondary index: lowercased name -> set of primary keys, for symbols
+    /// of case-insensitive languages only.
+    pub by_folded_name: DashMap<String, HashSet<String>>,
+    /// Secondary index: parent name (e.g. the type of a method) -> set of
+    /// primary keys.
+    pub by_parent: DashMap<String, HashSet<String>>,
     /// Reverse call graph: callee name -> list of call sites.
     /// Populated during symbol extraction for O(1) caller lookup.
     pub reverse_call_graph: DashMap<String, Vec<CallerRef>>,
+    /// Forward index into `reverse_call_graph`: file path -> callee names it
+    /// has call sites for, so dropping a file's call sites only touches
+    /// those lists.
+    pub callees_by_file: DashMap<String, HashSet<String>>,
+    /// `by_name`'s keys in order as (lowercased name, name), for prefix
+    /// completion. Changed only while the `by_name` entry is locked, so the
+    /// two agree.
+    pub sorted_names: RwLock<BTreeSet<(String, String)>>,
 }
 
 impl SymbolTable {
@@ -34,7 +59,12 @@ impl SymbolTable {
             symbols: DashMap::new(),
             by_name: DashMap::new(),
             by_file: DashMap::new(),
+            by_signature_hash: DashMap::new(),
+            by_folded_name: DashMap::new(),
+            by_parent: DashMap::new(),
             reverse_call_graph: DashMap::new(),
+            callees_by_file: DashMap::new(),
+            sorted_names: RwLock::new(BTreeSet::new()),
         }
     }
 
@@ -47,7 +77,12 @@ impl SymbolTable {
                 file: file.to_string(),
                 line,
                 text: text.to_string(),
+                context: None,
             });
+        self.callees_by_file
+            .entry(file.to_string())
+            .or_default()
+            .insert(callee_name.to_string());
     }
 
     /// Get cached callers for a symbol name. Returns None if not populated.
@@ -56,32 +91,94 @@ impl SymbolTable {
     }
 
     pub fn make_key(file: &str, name: &str) -> String {
-        format!("{}::{}", file, name)
+        format!("{}::{}", rel_path::normalize(file), name)
     }
 
     pub fn insert(&self, symbol: Symbol) {
         let key = Self::make_key(&symbol.file, &symbol.name);
 
+        // A same-named symbol in the same file replaces the previous one;
+        // drop the old signature's and parent's index entries so they don't
+        // dangle.
+        let old = self
+            .symbols
+            .get(&key)
+            .map(|old| (old.signature_hash.clone(), old.parent.clone()));
+        if let Some((old_hash, old_parent)) = old {
+            if old_hash != symbol.signature_hash {
+                self.unindex_signature_hash(&old_hash, &key);
+            }
+            if let Some(old_parent) = old_parent.filter(|p| symbol.parent.as_ref() != Some(p)) {
+                self.unindex_parent(&old_parent, &key);
+            }
+        }
+
         // Update secondary indices
-        self.by_name
-            .entry(symbol.name.clone())
-            .or_insert_with(HashSet::new)
-            .insert(key.clone());
+        match self.by_name.entry(symbol.name.clone()) {
+            Entry::Occupied(mut keys) => {
+                keys.get_mut().insert(key.clone());
+            }
+            Entry::Vacant(slot) => {
+                self.sorted_names
+                    .write()
+                    .insert((symbol.name.to_lowercase(), symbol.name.clone()));
+                slot.insert(HashSet::from([key.clone()]));
+            }
+        }
         self.by_file
             .entry(symbol.file.clone())
             .or_insert_with(HashSet::new)
             .insert(key.clone());
+        self.by_signature_hash
+            .entry(symbol.signature_hash.clone())
+            .or_default()
+            .insert(key.clone());
+        if let Some(parent) = &symbol.parent {
+            self.by_parent
+                .entry(parent.clone())
+                .or_default()
+                .insert(key.clone());
+        }
 
         self.symbols.insert(key, symbol);
     }
 
+    /// Also index `symbol` (already inserted) under its lowercased name, so
+    /// `get_case_aware` finds it whatever case it is asked for in.
+    pub fn index_folded_name(&self, symbol: &Symbol) {
+        self.by_folded_name
+            .entry(symbol.name.to_lowercase())
+            .or_default()
+            .insert(Self::make_key(&symbol.file, &symbol.name));
+    }
+
     /// Remove call graph entries originating from a file.
     pub fn remove_callers_from_file(&self, file: &str) {
-        for mut entry in self.reverse_call_graph.iter_mut() {
-            entry.value_mut().retain(|c| c.file != file);
+        self.remove_callers_from_files(&[file]);
+    }
+
+    /// Remove call graph entries originating from any of `files`, visiting
+    /// each affected callee list once however many of the files call it.
+    pub fn remove_callers_from_files(&self, files: &[&str]) {
+        let mut callees = HashSet::new();
+        for file in files {
+            if let Some((_, names)) = self.callees_by_file.remove(*file) {
+                callees.extend(names);
+            }
+        }
+        if callees.is_empty() {
+            return;
+        }
+        let files: HashSet<&str> = files.iter().copied().collect();
+        for callee in &callees {
+            if let Some(mut callers) = self.reverse_call_graph.get_mut(callee) {
+                callers.retain(|c| !files.contains(c.file.as_str()));
+            }
+            // Rechecked under the shard lock, so a call site added since the
+            // retain keeps the list alive.
+            self.reverse_call_graph
+                .remove_if(callee, |_, callers| callers.is_empty());
         }
-        // Clean up empty entries
-        self.reverse_call_graph.retain(|_, v| !v.is_empty());
     }
 
     pub fn remove_file(&self, file: &str) {
@@ -89,28 +186,146 @@ impl SymbolTable {
         if let Some((_, keys)) = self.by_file.remove(file) {
             for key in &keys {
                 if let Some((_, sym)) = self.symbols.remove(key) {
-                    if let Some(mut name_set) = self.by_name.get_mut(&sym.name) {
-                        name_set.remove(key);
-                        if name_set.is_empty() {
-                            drop(name_set);
-                            self.by_name.remove(&sym.name);
+                    if let Entry::Occupied(mut name_set) = self.by_name.entry(sym.name.clone()) {
+                        name_set.get_mut().remove(key);
+                        if name_set.get().is_empty() {
+                            self.sorted_names
+                                .write()
+                                .remove(&(sym.name.to_lowercase(), sym.name.clone()));
+                            name_set.remove();
                         }
                     }
+                    self.unindex_signature_hash(&sym.signature_hash, key);
+                    if let Some(parent) = &sym.parent {
+                        self.unindex_parent(parent, key);
+                    }
+                    let folded = sym.name.to_lowercase();
+                    if let Some(mut keys) = self.by_folded_name.get_mut(&folded) {
+                        keys.remove(key);
+                    }
+                    // Rechecked under the shard lock, as in `unindex_parent`.
+                    self.by_folded_name.remove_if(&folded, |_, keys| keys.is_empty());
                 }
             }
         }
     }
 
+    fn unindex_signature_hash(&self, hash: &str, key: &str) {
+        if let Some(mut keys) = self.by_signature_hash.get_mut(hash) {
+            keys.remove(key);
+        }
+        // Rechecked under the shard lock, as in `unindex_parent`.
+        self.by_signature_hash.remove_if(hash, |_, keys| keys.is_empty());
+    }
+
+    fn unindex_parent(&self, parent: &str, key: &str) {
+        if let Some(mut keys) = self.by_parent.get_mut(parent) {
+            keys.remove(key);
+        }
+        // Rechecked under the shard lock, so a key inserted since the
+        // removal keeps the set alive.
+        self.by_parent.remove_if(parent, |_, keys| keys.is_empty());
+    }
+
+    /// Tag or untag every symbol in `file` as generated.
+    pub fn set_generated(&self, file: &str, generated: bool) {
+        let Some(keys) = self.by_file.get(file) else {
+            return;
+        };
+        for key in keys.iter() {
+            if let Some(mut sym) = self.symbols.get_mut(key) {
+                sym.generated = generated;
+            }
+        }
+    }
+
     pub fn get(&self, file: &str, name: &str) -> Option<Symbol> {
         let key = Self::make_key(file, name);
         self.symbols.get(&key).map(|r| r.value().clone())
     }
 
-    pub fn search(&self, query: &str, limit: usize) -> Vec<Symbol> {
+    /// `get`, falling back to a case-insensitive match among symbols of
+    /// case-insensitive languages in `file` when the exact name misses. The
+    /// flag is set when the fallback matched. Several folded matches are
+    /// ambiguous and give `None`; `folded_matches` lists them.
+    pub fn get_case_aware(&self, file: &str, name: &str) -> Option<(Symbol, bool)> {
+        if let Some(sym) = self.get(file, name) {
+            return Some((sym, false));
+        }
+        let mut matches = self.folded_matches(file, name);
+        if matches.len() != 1 {
+            return None;
+        }
+        matches.pop().map(|sym| (sym, true))
+    }
+
+    /// Symbols in `file` whose lowercased name equals `name`'s, ordered by
+    /// key.
+    pub fn folded_matches(&self, file: &str, name: &str) -> Vec<Symbol> {
+        let Some(keys) = self.by_folded_name.get(&name.to_lowercase()) else {
+            return Vec::new();
+        };
+        let prefix = Self::make_key(file, "");
+        let mut keys: Vec<&String> = keys.iter().filter(|k| k.starts_with(&prefix)).collect();
+        keys.sort();
+        keys.into_iter()
+            .filter_map(|key| self.symbols.get(key).map(|r| r.value().clone()))
+            .collect()
+    }
+
+    /// Symbol whose signature hashes to `hash`. When several share it, the
+    /// one with the lowest `file::name` key is returned.
+    pub fn get_by_signature_hash(&self, hash: &str) -> Option<Symbol> {
+        self.list_by_signature_hash(hash).into_iter().next()
+    }
+
+    /// Every symbol whose signature hashes to `hash`, ordered by key.
+    pub fn list_by_signature_hash(&self, hash: &str) -> Vec<Symbol> {
+        let Some(keys) = self.by_signature_hash.get(hash) else {
+            return Vec::new();
+        };
+        let mut keys: Vec<&String> = keys.iter().collect();
+        keys.sort();
+        keys.into_iter()
+            .filter_map(|key| self.symbols.get(key).map(|r| r.value().clone()))
+            .collect()
+    }
+
+    /// Symbols whose name contains `query`, ignoring case. With `parent`,
+    /// only that parent's members are visited, in key order.
+    pub fn search(
+        &self,
+        query: &str,
+        kinds: Option<&HashSet<symbol::SymbolKind>>,
+        files: Option<&HashSet<String>>,
+        parent: Option<&str>,
+        limit: usize,
+        include_generated: bool,
+    ) -> Vec<Symbol> {
         let query_lower = query.to_lowercase();
+        let keep = |sym: &Symbol| {
+            (include_generated || !sym.generated)
+                && kinds.is_none_or(|kinds| kinds.contains(&sym.kind))
+                && files.is_none_or(|files| files.contains(&sym.file))
+                && sym.name.to_lowercase().contains(&query_lower)
+        };
+        if let Some(parent) = parent {
+            let Some(keys) = self.by_parent.get(parent) else {
+                return Vec::new();
+            };
+            let mut keys: Vec<&String> = keys.iter().collect();
+            keys.sort();
+            return keys
+                .into_iter()
+                .filter_map(|key| self.symbols.get(key))
+                .filter(|sym| keep(sym))
+                .take(limit)
+                .map(|sym| sym.value().clone())
+                .collect();
+        }
         let mut results = Vec::new();
         for entry in self.symbols.iter() {
-            if entry.value().name.to_lowercase().contains(&query_lower) {
+            if keep(entry.value()) {
                 results.push(entry.value().clone());
                 if results.len() >= limit {
                     break;
@@ -121,7 +336,7 @@ impl SymbolTable {
     }
 
     pub fn list_by_file(&self, file: &str) -> Vec<Symbol> {
-        if let Some(keys) = self.by_file.get(file) {
+        if let Some(keys) = self.by_file.get(&*rel_path::normalize(file)) {
             keys.iter()
                 .filter_map(|key| self.symbols.get(key).map(|r| r.value().clone()))
                 .collect()
@@ -130,11 +345,222 @@ impl SymbolTable {
         }
     }
 
-    pub fn all_symbols(&self) -> Vec<Symbol> {
-        self.symbols.iter().map(|r| r.value().clone()).collect()
+    /// Every symbol named exactly `name`, in any file.
+    pub fn list_by_name(&self, name: &str) -> Vec<Symbol> {
+        if let Some(keys) = self.by_name.get(name) {
+            keys.iter()
+                .filter_map(|key| self.symbols.get(key).map(|r| r.value().clone()))
+                .collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// The first `max` indexed names starting with `prefix`, ignoring case,
+    /// in case-insensitive order.
+    pub fn names_with_prefix(&self, prefix: &str, max: usize) -> Vec<String> {
+        let prefix = prefix.to_lowercase();
+        let start = (prefix.clone(), String::new());
+        self.sorted_names
+            .read()
+            .range(start..)
+            .take_while(|(lower, _)| lower.starts_with(&prefix))
+            .take(max)
+            .map(|(_, name)| name.clone())
+            .collect()
     }
 
     pub fn len(&self) -> usize {
         self.symbols.len()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols::symbol::SymbolKind;
+    use std::time::{Duration, Instant};
+
+    fn callers_of(table: &SymbolTable, callee: &str) -> Vec<(String, usize)> {
+        let mut sites: Vec<(String, usize)> = table
+            .get_callers(callee)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|c| (c.file, c.line))
+            .collect();
+        sites.sort();
+        sites
+    }
+
+    #[test]
+    fn case_aware_lookup_refuses_ambiguous_folds() {
+        let table = SymbolTable::new();
+        let folded = |name: &str, file: &str| {
+            let sym = Symbol::for_tests(name, SymbolKind::Function, file, 1);
+            table.insert(sym.clone());
+            table.index_folded_name(&sym);
+        };
+        folded("Parse", "a.f90");
+        folded("Render", "a.f90");
+        folded("RENDER", "a.f90");
+        folded("render", "b.f90");
+
+        let (sym, was_folded) = table.get_case_aware("a.f90", "PARSE").unwrap();
+        assert_eq!((sym.name.as_str(), was_folded), ("Parse", true));
+        assert!(!table.get_case_aware("a.f90", "Render").unwrap().1);
+        assert!(table.get_case_aware("a.f90", "render").is_none());
+        let names: Vec<String> =
+            table.folded_matches("a.f90", "render").into_iter().map(|s| s.name).collect();
+        assert_eq!(names, ["RENDER", "Render"]);
+    }
+
+    #[test]
+    fn concurrent_removal_keeps_new_children_indexed() {
+        let table = SymbolTable::new();
+        let child = |name: &str, file: &str| {
+            let mut sym = Symbol::for_tests(name, SymbolKind::Method, file, 1);
+            sym.parent = Some("Widget".to_string());
+            sym
+        };
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for _ in 0..2_000 {
+                    table.insert(child("churn", "a.rs"));
+                    table.remove_file("a.rs");
+                }
+            });
+            scope.spawn(|| {
+                for i in 0..2_000 {
+                    table.insert(child(&format!("m{}", i), "b.rs"));
+                }
+            });
+        });
+        let children = table.by_parent.get("Widget").map_or(0, |keys| keys.len());
+        assert_eq!(children, 2_000);
+    }
+
+    #[test]
+    fn concurrent_removal_keeps_new_signature_hashes_indexed() {
+        let table = SymbolTable::new();
+        let hashed = |name: &str, file: &str| {
+            let mut sym = Symbol::for_tests(name, SymbolKind::Function, file, 1);
+            sym.signature_hash = "shared".to_string();
+            sym
+        };
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for _ in 0..2_000 {
+                    table.insert(hashed("churn", "a.rs"));
+                    table.remove_file("a.rs");
+                }
+            });
+            scope.spawn(|| {
+                for i in 0..2_000 {
+                    table.insert(hashed(&format!("f{}", i), "b.rs"));
+                }
+            });
+        });
+        assert_eq!(table.list_by_signature_hash("shared").len(), 2_000);
+    }
+
+    #[test]
+    fn concurrent_removal_keeps_new_folded_names_indexed() {
+        let table = SymbolTable::new();
+        let folded = |file: &str| {
+            let sym = Symbol::for_tests("Parse", SymbolKind::Function, file, 1);
+            table.insert(sym.clone());
+            table.index_folded_name(&sym);
+        };
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for _ in 0..2_000 {
+                    folded("a.f90");
+                    table.remove_file("a.f90");
+                }
+            });
+            scope.spawn(|| {
+                for i in 0..2_000 {
+                    folded(&format!("b{}.f90", i));
+                }
+            });
+        });
+        let keys = table.by_folded_name.get("parse").map_or(0, |keys| keys.len());
+        assert_eq!(keys, 2_000);
+    }
+
+    #[test]
+    fn removing_a_file_keeps_other_files_call_sites() {
+        let table = SymbolTable::new();
+        table.add_caller("parse", "a.rs", 1, "parse()");
+        table.add_caller("parse", "b.rs", 2, "parse()");
+        table.add_caller("render", "a.rs", 3, "render()");
+
+        table.remove_callers_from_file("a.rs");
+
+        assert_eq!(callers_of(&table, "parse"), vec![("b.rs".to_string(), 2)]);
+        assert!(table.get_callers("render").is_none());
+        assert!(!table.callees_by_file.contains_key("a.rs"));
+        assert!(table.callees_by_file.contains_key("b.rs"));
+    }
+
+    #[test]
+    fn batch_removal_drops_every_listed_file() {
+        let table = SymbolTable::new();
+        for file in ["a.rs", "b.rs", "c.rs"] {
+            table.add_caller("shared", file, 1, "shared()");
+        }
+        table.add_caller("only_c", "c.rs", 2, "only_c()");
+
+        table.remove_callers_from_files(&["a.rs", "b.rs"]);
+
+        assert_eq!(callers_of(&table, "shared"), vec![("c.rs".to_string(), 1)]);
+        assert_eq!(callers_of(&table, "only_c"), vec![("c.rs".to_string(), 2)]);
+
+        table.remove_callers_from_files(&["c.rs", "missing.rs"]);
+        assert!(table.reverse_call_graph.is_empty());
+        assert!(table.callees_by_file.is_empty());
+    }
+
+    #[test]
+    fn removal_leaves_unrelated_callee_lists_untouched() {
+        let table = SymbolTable::new();
+        for i in 0..1_000 {
+            table.add_caller(&format!("f{}", i), "other.rs", i, "call");
+        }
+        table.add_caller("f0", "target.rs", 1, "f0()");
+
+        table.remove_callers_from_file("target.rs");
+
+        assert_eq!(table.reverse_call_graph.len(), 1_000);
+        assert_eq!(callers_of(&table, "f0"), vec![("other.rs".to_string(), 0)]);
+    }
+
+    /// Time to remove one file's call sites from a table holding `total`
+    /// other call sites.
+    fn removal_time(total: usize) -> Duration {
+        let table = SymbolTable::new();
+        for i in 0..total {
+            table.add_caller(&format!("f{}", i % 5_000), &format!("f{}.rs", i / 50), i, "call");
+        }
+        for i in 0..20 {
+            table.add_caller(&format!("f{}", i), "target.rs", i, "call");
+        }
+        let started = Instant::now();
+        table.remove_callers_from_file("target.rs");
+        started.elapsed()
+    }
+
+    /// Removal cost should track the file's own call sites, not the table
+    /// size. Timing-based, so run on demand:
+    /// `cargo test --release -- --ignored remove_callers_bench --nocapture`.
+    #[test]
+    #[ignore]
+    fn remove_callers_bench() {
+        let small = (0..5).map(|_| removal_time(10_000)).min().unwrap();
+        let large = (0..5).map(|_| removal_time(1_000_000)).min().unwrap();
+        println!("10k call sites: {:?}, 1M call sites: {:?}", small, large);
+        // A scan of every list would be ~100x slower on the large table;
+        // allow generous noise for the larger per-callee lists.
+        assert!(large < small * 20 + Duration::from_millis(1));
+    }
+}
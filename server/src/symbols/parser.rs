@@ -1,32 +1,67 @@
 use anyhow::Result;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 use std::sync::Arc;
 use tree_sitter::StreamingIterator;
 use tracing::{debug, warn};
+use utoipa::ToSchema;
 
-use crate::index::file_entry::Language;
+use crate::index::encoding::{self, SourceEncoding};
+use crate::index::file_entry::{FileMark, Language};
+use crate::index::generated;
 use crate::index::file_tree::FileTree;
+use crate::server::cancel::CancelToken;
 use crate::symbols::queries;
-use crate::symbols::symbol::{Symbol, SymbolKind};
+use crate::symbols::snapshot::{content_hash, IndexSnapshot};
+use crate::symbols::symbol::{signature_hash, source_hash, Symbol, SymbolKind};
 use crate::symbols::SymbolTable;
 
-/// Extract symbols from a single file.
+/// How the text symbols were extracted from was read.
+#[derive(Debug, Clone, Default)]
+pub struct SourceInfo {
+    pub encoding: SourceEncoding,
+    /// `snapshot::content_hash` of the file bytes the symbols came from,
+    /// so a snapshot records exactly those contents.
+    pub content_hash: Option<String>,
+}
+
+/// Extract symbols from a single file, along with how its text was read.
+/// `cancel` stops a PDF conversion.
 pub fn extract_symbols_from_file(
     root: &Path,
     rel_path: &str,
     language: Language,
-) -> Result<Vec<Symbol>> {
+    cancel: &CancelToken,
+) -> Result<(Vec<Symbol>, SourceInfo)> {
     let config = match queries::get_language_config(language) {
         Some(c) => c,
-        None => return Ok(Vec::new()),
+        None => return Ok((Vec::new(), SourceInfo::default())),
     };
 
     let abs_path = root.join(rel_path);
-    let source = if language == Language::Pdf {
-        crate::index::pdf::convert_pdf(root, rel_path)
-            .map_err(|e| { warn!("PDF conversion failed for {}: {}", rel_path, e); e })?
+    let (source, source_info) = if language == Language::Pdf {
+        // Hash the PDF before converting, so a change mid-conversion makes
+        // the snapshot entry stale rather than wrong.
+        let content_hash = content_hash(&std::fs::read(&abs_path)?);
+        let text = crate::index::pdf::convert_pdf(root, rel_path, cancel)
+            .map_err(|e| { warn!("PDF conversion failed for {}: {}", rel_path, e); e })?;
+        let info = SourceInfo {
+            encoding: SourceEncoding::default(),
+            content_hash: Some(content_hash),
+        };
+        (text, info)
     } else {
-        std::fs::read_to_string(&abs_path)?
+        let bytes = std::fs::read(&abs_path)?;
+        let content_hash = content_hash(&bytes);
+        let (text, encoding) = encoding::decode(bytes);
+        let info = SourceInfo {
+            encoding,
+            content_hash: Some(content_hash),
+        };
+        (text, info)
     };
 
     let mut parser = tree_sitter::Parser::new();
@@ -36,7 +71,7 @@ pub fn extract_symbols_from_file(
         Some(t) => t,
         None => {
             warn!("Failed to parse {}", rel_path);
-            return Ok(Vec::new());
+            return Ok((Vec::new(), source_info));
         }
     };
 
@@ -46,8 +81,13 @@ pub fn extract_symbols_from_file(
 
     let capture_names: Vec<String> = query.capture_names().iter().map(|s| s.to_string()).collect();
 
+    let converted_hash = (language == Language::Pdf).then(|| source_hash(&source));
     let mut symbols = Vec::new();
     let mut current_impl_type: Option<String> = None;
+    // Byte ranges already extracted as methods. A method also matches the
+    // plain function pattern, whose match comes later and would drop its
+    // parent.
+    let mut method_ranges: HashSet<(usize, usize)> = HashSet::new();
 
     while let Some(m) = matches.next() {
         let mut name: Option<String> = None;
@@ -150,10 +190,25 @@ pub fn extract_symbols_from_file(
             let end = node.end_position();
             let byte_range = (node.start_byte(), node.end_byte());
             let line_range = (start.row + 1, end.row + 1); // 1-indexed
+            if kind == SymbolKind::Method {
+                method_ranges.insert(byte_range);
+            } else if method_ranges.contains(&byte_range) {
+                continue;
+            }
 
-            // Extract signature (first line of the definition)
-            let node_text = node.utf8_text(source.as_bytes()).unwrap_or("");
-            let signature = node_text.lines().next().unwrap_or("").to_string();
+            let is_callable = matches!(kind, SymbolKind::Function | SymbolKind::Method);
+            let signature = if is_callable {
+                function_header(node, &source)
+            } else {
+                // First line of the definition
+                let node_text = node.utf8_text(source.as_bytes()).unwrap_or("");
+                node_text.lines().next().unwrap_or("").to_string()
+            };
+            let return_type = if is_callable {
+                return_type_of(node, &source, language)
+            } else {
+                None
+            };
 
             symbols.push(Symbol {
                 name,
@@ -162,22 +217,99 @@ pub fn extract_symbols_from_file(
                 byte_range,
                 line_range,
                 language,
+                signature_hash: signature_hash(&signature),
                 signature,
+                return_type,
                 definition: None,
                 parent,
+                generated: false,
+                source_hash: converted_hash.clone(),
             });
         }
     }
 
+    if generated::has_generated_header(&source) {
+        for sym in &mut symbols {
+            sym.generated = true;
+        }
+    }
+
     debug!("Extracted {} symbols from {}", symbols.len(), rel_path);
-    Ok(symbols)
+    Ok((symbols, source_info))
+}
+
+/// Insert one file's freshly extracted symbols. A generated header found
+/// during extraction marks the file `generated`; symbols of a file with
+/// that mark are tagged `generated`. The file's entry keeps the encoding
+/// note when its text had to be normalized.
+pub fn insert_file_symbols(
+    file_tree: &FileTree,
+    symbol_table: &SymbolTable,
+    rel_path: &str,
+    symbols: Vec<Symbol>,
+    source: SourceInfo,
+) {
+    let mut generated = symbols.iter().any(|s| s.generated);
+    if let Some(mut entry) = file_tree.files.get_mut(rel_path) {
+        entry.encoding = source.encoding.note();
+        entry.content_hash = source.content_hash;
+        if generated && !entry.marks.contains(&FileMark::Generated) {
+            entry.marks.push(FileMark::Generated);
+        }
+        generated |= entry.marks.contains(&FileMark::Generated);
+        entry.symbols_extracted = true;
+    }
+    let fold_case = symbols
+        .first()
+        .is_some_and(|s| queries::is_case_insensitive(s.language));
+    for mut sym in symbols {
+        sym.generated = generated;
+        if fold_case {
+            symbol_table.index_folded_name(&sym);
+        }
+        symbol_table.insert(sym);
+    }
+}
+
+/// Full header of a function-like node: everything before its body, plus the
+/// opening `{` for brace-delimited bodies. Falls back to the whole node for
+/// bodiless declarations (e.g. trait method signatures).
+fn function_header(node: tree_sitter::Node, source: &str) -> String {
+    let start = node.start_byte();
+    let end = match node.child_by_field_name("body") {
+        Some(body) if source.as_bytes().get(body.start_byte()) == Some(&b'{') => {
+            body.start_byte() + 1
+        }
+        Some(body) => body.start_byte(),
+        None => node.end_byte(),
+    };
+    source.get(start..end).unwrap_or("").trim_end().to_string()
+}
+
+/// Declared return type of a function-like node. Grammars differ on the field
+/// name (`return_type` for Rust/Python/TypeScript, `result` for Go).
+fn return_type_of(node: tree_sitter::Node, source: &str, language: Language) -> Option<String> {
+    let type_node = node
+        .child_by_field_name("return_type")
+        .or_else(|| node.child_by_field_name("result"));
+    match type_node {
+        Some(n) => {
+            let text = n.utf8_text(source.as_bytes()).unwrap_or("");
+            // TypeScript's type_annotation includes the leading colon
+            let text = text.trim_start_matches(':').trim();
+            Some(text.to_string())
+        }
+        None if language == Language::Rust => Some("()".to_string()),
+        None => None,
+    }
 }
 
 /// Extract call expressions from a file and return (callee_name, line, text) tuples.
-fn extract_call_sites(
+pub fn extract_call_sites(
     root: &Path,
     rel_path: &str,
     language: Language,
+    cancel: &CancelToken,
 ) -> Vec<(String, usize, String)> {
     let config = match queries::get_language_config(language) {
         Some(c) => c,
@@ -186,12 +318,12 @@ fn extract_call_sites(
 
     let abs_path = root.join(rel_path);
     let source = if language == Language::Pdf {
-        match crate::index::pdf::convert_pdf(root, rel_path) {
+        match crate::index::pdf::convert_pdf(root, rel_path, cancel) {
             Ok(s) => s,
             Err(_) => return Vec::new(),
         }
     } else {
-        match std::fs::read_to_string(&abs_path) {
+        match encoding::read_to_string(&abs_path) {
             Ok(s) => s,
             Err(_) => return Vec::new(),
         }
@@ -239,18 +371,71 @@ fn extract_call_sites(
     results
 }
 
+/// Why symbol extraction failed for one file.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ExtractionError {
+    pub file: String,
+    pub language: Language,
+    pub error: String,
+    pub at: DateTime<Utc>,
+}
+
+/// Latest extraction failure per relative path. Entries are cleared when the
+/// file is re-extracted successfully or deleted.
+pub type ExtractionErrors = DashMap<String, ExtractionError>;
+
+/// Store or clear the failure entry for `rel_path` based on an extraction result.
+pub fn record_extraction<T>(
+    errors: &ExtractionErrors,
+    rel_path: &str,
+    language: Language,
+    result: &Result<T>,
+) {
+    match result {
+        Ok(_) => {
+            errors.remove(rel_path);
+        }
+        Err(e) => {
+            errors.insert(
+                rel_path.to_string(),
+                ExtractionError {
+                    file: rel_path.to_string(),
+                    language,
+                    error: format!("{:#}", e),
+                    at: Utc::now(),
+                },
+            );
+        }
+    }
+}
+
+/// Outcome of a full extraction pass.
+#[derive(Debug, Default)]
+pub struct ExtractionReport {
+    pub symbols: usize,
+    /// Files taken from a warm-start snapshot instead of being parsed.
+    pub reused_files: usize,
+    pub failed_files: usize,
+    /// `"<file>: <error>"` for the first few failures only.
+    pub errors: Vec<String>,
+}
+
 /// Extract symbols from all files in the tree using rayon for parallelism.
-/// Also builds the reverse call graph for O(1) caller lookups.
+/// Also builds the reverse call graph for O(1) caller lookups. Files whose
+/// contents match an entry in `warm_start` reuse its symbols and call sites.
 pub async fn extract_all_symbols(
     root: &Path,
     file_tree: &Arc<FileTree>,
     symbol_table: &Arc<SymbolTable>,
-) -> Result<usize> {
+    errors: &Arc<ExtractionErrors>,
+    warm_start: Option<IndexSnapshot>,
+) -> Result<ExtractionReport> {
     let root = root.to_path_buf();
     let file_tree = file_tree.clone();
     let symbol_table = symbol_table.clone();
+    let errors = errors.clone();
 
-    let count = tokio::task::spawn_blocking(move || -> Result<usize> {
+    let report = tokio::task::spawn_blocking(move || -> Result<ExtractionReport> {
         use rayon::prelude::*;
 
         let paths: Vec<(String, Language)> = file_tree
@@ -261,37 +446,59 @@ pub async fn extract_all_symbols(
             .collect();
 
         // Phase 1: Extract symbols in parallel
-        let results: Vec<(String, Language, Vec<Symbol>)> = paths
+        let (results, failures): (Vec<_>, Vec<_>) = paths
             .par_iter()
-            .filter_map(|(rel_path, language)| {
-                match extract_symbols_from_file(&root, rel_path, *language) {
-                    Ok(symbols) => Some((rel_path.clone(), *language, symbols)),
-                    Err(e) => {
-                        debug!("Failed to extract symbols from {}: {}", rel_path, e);
-                        None
-                    }
+            .map(|(rel_path, language)| {
+                if let Some((symbols, source, cached)) = warm_start
+                    .as_ref()
+                    .and_then(|snapshot| snapshot.reusable(&root, rel_path))
+                {
+                    errors.remove(rel_path);
+                    return Ok((rel_path.clone(), symbols, source, Some(&cached.call_sites)));
                 }
+                let result =
+                    extract_symbols_from_file(&root, rel_path, *language, &CancelToken::default());
+                record_extraction(&errors, rel_path, *language, &result);
+                result
+                    .map(|(symbols, source)| (rel_path.clone(), symbols, source, None))
+                    .map_err(|e| {
+                        debug!("Failed to extract symbols from {}: {}", rel_path, e);
+                        format!("{}: {}", rel_path, e)
+                    })
             })
-            .collect();
+            .partition_map(|r| match r {
+                Ok(ok) => rayon::iter::Either::Left(ok),
+                Err(err) => rayon::iter::Either::Right(err),
+            });
+
+        let mut report = ExtractionReport {
+            failed_files: failures.len(),
+            errors: failures
+                .into_iter()
+                .take(crate::config::HEALTH_MAX_ERRORS)
+                .collect(),
+            ..Default::default()
+        };
 
         // Insert symbols (sequential — DashMap is thread-safe but we batch for efficiency)
-        let mut total = 0;
-        for (rel_path, _, symbols) in &results {
-            let count = symbols.len();
-            for sym in symbols {
-                symbol_table.insert(sym.clone());
-            }
-            if let Some(mut entry) = file_tree.files.get_mut(rel_path) {
-                entry.symbols_extracted = true;
+        let mut reused_sites = HashMap::new();
+        for (rel_path, symbols, source, cached_sites) in results {
+            report.symbols += symbols.len();
+            insert_file_symbols(&file_tree, &symbol_table, &rel_path, symbols, source);
+            if let Some(sites) = cached_sites {
+                report.reused_files += 1;
+                reused_sites.insert(rel_path, sites.clone());
             }
-            total += count;
         }
 
         // Phase 2: Build reverse call graph in parallel
         let call_sites: Vec<(String, Vec<(String, usize, String)>)> = paths
             .par_iter()
             .map(|(rel_path, language)| {
-                let sites = extract_call_sites(&root, rel_path, *language);
+                let sites = match reused_sites.get(rel_path) {
+                    Some(sites) => sites.clone(),
+                    None => extract_call_sites(&root, rel_path, *language, &CancelToken::default()),
+                };
                 (rel_path.clone(), sites)
             })
             .collect();
@@ -302,9 +509,9 @@ pub async fn extract_all_symbols(
             }
         }
 
-        Ok(total)
+        Ok(report)
     })
     .await??;
 
-    Ok(count)
+    Ok(report)
 }
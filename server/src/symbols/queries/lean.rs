@@ -53,6 +53,8 @@ pub fn config() -> LanguageConfig {
         symbols_query: SYMBOLS_QUERY,
         callers_query: CALLERS_QUERY,
         variables_query: VARIABLES_QUERY,
+        nested_variables_query: "",
+        case_insensitive: false,
         test_patterns: vec![],
     }
 }
@@ -29,9 +29,33 @@ pub struct LanguageConfig {
     pub callers_query: &'static str,
     /// Tree-sitter query for local variable bindings. Captures `@var.name`.
     pub variables_query: &'static str,
+    /// Extra bindings only reported for `scope=all` (closure and lambda
+    /// parameters). Captures `@var.name`; empty when the language has none.
+    pub nested_variables_query: &'static str,
+    /// Symbol names are case-insensitive in the language (SQL, some config
+    /// formats), so lookups that miss on the exact name retry with folded
+    /// case. Off for every language with case-significant identifiers.
+    pub case_insensitive: bool,
     pub test_patterns: Vec<TestPattern>,
 }
 
+/// Whether `lang` treats symbol names case-insensitively.
+pub fn is_case_insensitive(lang: Language) -> bool {
+    get_language_config(lang).is_some_and(|c| c.case_insensitive)
+}
+
+/// Whether a function named `name` is a test by one of the language's
+/// `FunctionPrefix` patterns. Attribute and call-expression patterns need the
+/// source and aren't checked.
+pub fn is_test_name(lang: Language, name: &str) -> bool {
+    get_language_config(lang).is_some_and(|c| {
+        c.test_patterns.iter().any(|p| match p {
+            TestPattern::FunctionPrefix(prefix) => name.starts_with(prefix),
+            _ => false,
+        })
+    })
+}
+
 #[allow(dead_code)]
 pub enum TestPattern {
     /// Match functions whose name starts with a prefix (e.g., "test_" in Python)
@@ -60,12 +60,19 @@ pub const VARIABLES_QUERY: &str = r#"
     name: (identifier) @var.name))
 "#;
 
+pub const NESTED_VARIABLES_QUERY: &str = r#"
+(lambda_parameters
+  (identifier) @var.name)
+"#;
+
 pub fn config() -> LanguageConfig {
     LanguageConfig {
         language: tree_sitter_python::LANGUAGE.into(),
         symbols_query: SYMBOLS_QUERY,
         callers_query: CALLERS_QUERY,
         variables_query: VARIABLES_QUERY,
+        nested_variables_query: NESTED_VARIABLES_QUERY,
+        case_insensitive: false,
         test_patterns: vec![TestPattern::FunctionPrefix("test_")],
     }
 }
@@ -63,7 +63,7 @@ pub const VARIABLES_QUERY: &str = r#"
 (for_expression
   pattern: (identifier) @var.name)
 
-(if_let_expression
+(let_condition
   pattern: (_
     (identifier) @var.name))
 
@@ -71,12 +71,23 @@ pub const VARIABLES_QUERY: &str = r#"
   pattern: (identifier) @var.name)
 "#;
 
+pub const NESTED_VARIABLES_QUERY: &str = r#"
+(closure_parameters
+  (identifier) @var.name)
+
+(closure_parameters
+  (parameter
+    pattern: (identifier) @var.name))
+"#;
+
 pub fn config() -> LanguageConfig {
     LanguageConfig {
         language: tree_sitter_rust::LANGUAGE.into(),
         symbols_query: SYMBOLS_QUERY,
         callers_query: CALLERS_QUERY,
         variables_query: VARIABLES_QUERY,
+        nested_variables_query: NESTED_VARIABLES_QUERY,
+        case_insensitive: false,
         test_patterns: vec![TestPattern::Attribute("test")],
     }
 }
@@ -61,12 +61,21 @@ pub const VARIABLES_QUERY: &str = r#"
   pattern: (identifier) @var.name)
 "#;
 
+/// Shared by TypeScript and JavaScript: `x => ...` binds `x` without a
+/// parameter list.
+pub const NESTED_VARIABLES_QUERY: &str = r#"
+(arrow_function
+  parameter: (identifier) @var.name)
+"#;
+
 pub fn config() -> LanguageConfig {
     LanguageConfig {
         language: tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
         symbols_query: SYMBOLS_QUERY,
         callers_query: CALLERS_QUERY,
         variables_query: VARIABLES_QUERY,
+        nested_variables_query: NESTED_VARIABLES_QUERY,
+        case_insensitive: false,
         test_patterns: vec![
             TestPattern::CallExpression("it"),
             TestPattern::CallExpression("test"),
@@ -125,6 +134,8 @@ pub fn js_config() -> LanguageConfig {
         symbols_query: JS_SYMBOLS_QUERY,
         callers_query: JS_CALLERS_QUERY,
         variables_query: JS_VARIABLES_QUERY,
+        nested_variables_query: NESTED_VARIABLES_QUERY,
+        case_insensitive: false,
         test_patterns: vec![
             TestPattern::CallExpression("it"),
             TestPattern::CallExpression("test"),
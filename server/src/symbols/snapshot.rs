@@ -0,0 +1,219 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::index::encoding::SourceEncoding;
+use crate::index::file_tree::FileTree;
+use crate::symbols::parser::SourceInfo;
+use crate::symbols::symbol::Symbol;
+use crate::symbols::SymbolTable;
+
+/// Snapshot file name inside a project's `.coderlm` directory.
+pub const SNAPSHOT_FILE: &str = "index_snapshot.json";
+
+/// Bumped to 2 when symbol byte ranges started referring to normalized
+/// text (no BOM, LF line endings), and to 3 when Rust methods kept their
+/// parent; older snapshots are ignored.
+const SNAPSHOT_VERSION: u32 = 3;
+
+/// Extraction results for every parsed file, keyed by path relative to the
+/// project root. Because nothing in it is absolute, a snapshot saved under
+/// one checkout can warm-start the same repository at another path.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IndexSnapshot {
+    pub version: u32,
+    /// Root the snapshot was taken from, for diagnostics only.
+    pub root: String,
+    pub files: BTreeMap<String, FileSnapshot>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileSnapshot {
+    /// SHA-256 of the file contents the entry was extracted from.
+    pub hash: String,
+    pub symbols: Vec<Symbol>,
+    /// `(callee, line, text)` for each call site in the file.
+    pub call_sites: Vec<(String, usize, String)>,
+}
+
+impl IndexSnapshot {
+    /// The entry for `rel_path` if the file on disk still has the contents
+    /// it was extracted from. Symbols come back re-pointed at `rel_path`,
+    /// along with the file's encoding note and hash.
+    pub fn reusable(
+        &self,
+        root: &Path,
+        rel_path: &str,
+    ) -> Option<(Vec<Symbol>, SourceInfo, &FileSnapshot)> {
+        let entry = self.files.get(rel_path)?;
+        let bytes = std::fs::read(root.join(rel_path)).ok()?;
+        if content_hash(&bytes) != entry.hash {
+            return None;
+        }
+        let symbols = entry
+            .symbols
+            .iter()
+            .cloned()
+            .map(|mut sym| {
+                sym.file = rel_path.to_string();
+                sym
+            })
+            .collect();
+        let source = SourceInfo {
+            encoding: SourceEncoding::detect(&bytes),
+            content_hash: Some(entry.hash.clone()),
+        };
+        Some((symbols, source, entry))
+    }
+
+    /// Check that the snapshot describes this tree: at least half of its
+    /// paths must exist here. A snapshot from a different repository, or
+    /// from a parent or child directory, fails this.
+    pub fn check_alignment(&self, file_tree: &FileTree) -> Result<(), String> {
+        let present = self
+            .files
+            .keys()
+            .filter(|path| file_tree.get(path).is_some())
+            .count();
+        if present == 0 || present * 2 < self.files.len() {
+            return Err(format!(
+                "only {} of {} snapshot paths exist under this root",
+                present,
+                self.files.len()
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub fn content_hash(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Resolve `snapshot_from` to a snapshot file: either the file itself or a
+/// `.coderlm` directory containing it.
+pub fn snapshot_path(snapshot_from: &Path) -> PathBuf {
+    if snapshot_from.is_dir() {
+        snapshot_from.join(SNAPSHOT_FILE)
+    } else {
+        snapshot_from.to_path_buf()
+    }
+}
+
+pub fn load_snapshot(path: &Path) -> Result<IndexSnapshot, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let snapshot: IndexSnapshot = serde_json::from_str(&text)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+    if snapshot.version != SNAPSHOT_VERSION {
+        return Err(format!(
+            "Unsupported snapshot version {} in {}",
+            snapshot.version,
+            path.display()
+        ));
+    }
+    Ok(snapshot)
+}
+
+/// Write `.coderlm/index_snapshot.json` for every file whose symbols have
+/// been extracted. Each entry carries the hash taken when the file was
+/// read for extraction, so a file edited since then is saved stale rather
+/// than paired with symbols from its old contents. Agent-set definitions
+/// are left out; they live in the annotations file. Returns the number of
+/// files written.
+pub fn save_snapshot(
+    root: &Path,
+    file_tree: &FileTree,
+    symbol_table: &SymbolTable,
+) -> Result<usize, String> {
+    let mut call_sites: HashMap<String, Vec<(String, usize, String)>> = HashMap::new();
+    for entry in symbol_table.reverse_call_graph.iter() {
+        for caller in entry.value() {
+            call_sites.entry(caller.file.clone()).or_default().push((
+                entry.key().clone(),
+                caller.line,
+                caller.text.clone(),
+            ));
+        }
+    }
+
+    let mut files = BTreeMap::new();
+    for entry in file_tree.files.iter() {
+        let Some(hash) = entry.value().content_hash.clone() else {
+            continue;
+        };
+        let rel_path = entry.key();
+        let symbols = symbol_table
+            .list_by_file(rel_path)
+            .into_iter()
+            .map(|mut sym| {
+                sym.definition = None;
+                sym
+            })
+            .collect();
+        files.insert(
+            rel_path.clone(),
+            FileSnapshot {
+                hash,
+                symbols,
+                call_sites: call_sites.remove(rel_path).unwrap_or_default(),
+            },
+        );
+    }
+
+    let snapshot = IndexSnapshot {
+        version: SNAPSHOT_VERSION,
+        root: root.display().to_string(),
+        files,
+    };
+    let dir = root.join(".coderlm");
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(SNAPSHOT_FILE);
+    let json = serde_json::to_string(&snapshot).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(snapshot.files.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::config::DEFAULT_MAX_FILE_SIZE;
+    use crate::index::file_entry::Language;
+    use crate::index::walker;
+    use crate::server::cancel::CancelToken;
+    use crate::server::state::temp_project;
+    use crate::symbols::parser;
+
+    #[test]
+    fn save_keeps_the_hash_symbols_were_extracted_from() {
+        let dir = temp_project(&[("lib.rs", "fn original() {}\n")]);
+        let file_tree = Arc::new(FileTree::new());
+        walker::scan_directory(&dir, &file_tree, DEFAULT_MAX_FILE_SIZE).unwrap();
+        let symbol_table = SymbolTable::new();
+        let (symbols, source) =
+            parser::extract_symbols_from_file(&dir, "lib.rs", Language::Rust, &CancelToken::default())
+                .unwrap();
+        let extracted_hash = source.content_hash.clone().unwrap();
+        parser::insert_file_symbols(&file_tree, &symbol_table, "lib.rs", symbols, source);
+
+        // Edited after extraction, before the save: the snapshot must not
+        // pair `original` with the new contents.
+        std::fs::write(dir.join("lib.rs"), "fn renamed() {}\n").unwrap();
+        assert_eq!(save_snapshot(&dir, &file_tree, &symbol_table), Ok(1));
+
+        let snapshot = load_snapshot(&snapshot_path(&dir.join(".coderlm"))).unwrap();
+        assert_eq!(snapshot.files["lib.rs"].hash, extracted_hash);
+        assert!(snapshot.reusable(&dir, "lib.rs").is_none());
+
+        std::fs::write(dir.join("lib.rs"), "fn original() {}\n").unwrap();
+        let (symbols, source, _) = snapshot.reusable(&dir, "lib.rs").unwrap();
+        assert_eq!(symbols[0].name, "original");
+        assert_eq!(source.content_hash, Some(extracted_hash));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
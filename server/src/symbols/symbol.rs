@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum SymbolKind {
     Function,
@@ -19,6 +21,12 @@ pub enum SymbolKind {
 }
 
 impl SymbolKind {
+    /// Names accepted by `from_str`, for error messages (aliases omitted).
+    pub const NAMES: &'static [&'static str] = &[
+        "function", "method", "class", "struct", "enum", "trait", "interface", "constant",
+        "variable", "type", "module", "import",
+    ];
+
     pub fn from_str(s: &str) -> Option<Self> {
         match s.to_lowercase().as_str() {
             "function" | "fn" | "func" => Some(SymbolKind::Function),
@@ -38,7 +46,7 @@ impl SymbolKind {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Symbol {
     pub name: String,
     pub kind: SymbolKind,
@@ -46,10 +54,75 @@ pub struct Symbol {
     pub byte_range: (usize, usize),
     pub line_range: (usize, usize),
     pub language: crate::index::file_entry::Language,
-    /// First line of the symbol (e.g. function signature).
+    /// Header of the symbol. For functions and methods this is the full
+    /// header up to and including the opening `{` (multi-line signatures and
+    /// where-clauses included); for everything else, the first line.
     pub signature: String,
+    /// First 12 hex characters of the SHA-256 of `signature`. Stays the same
+    /// across moves and body edits as long as the header is unchanged.
+    #[serde(default)]
+    pub signature_hash: String,
+    /// Declared return type of a function or method. Rust functions without
+    /// one report `()`.
+    #[serde(default)]
+    pub return_type: Option<String>,
     /// Agent-set human-readable description.
     pub definition: Option<String>,
     /// Parent symbol name (e.g. struct for a method).
     pub parent: Option<String>,
+    /// Defined in generated code (a `generated` file mark, a configured
+    /// pattern, or an `@generated` header). Hidden from listings by default.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub generated: bool,
+    /// For symbols of converted documents (PDFs), `source_hash` of the
+    /// converted text `byte_range` indexes into, so a later conversion that
+    /// came out different is caught before slicing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_hash: Option<String>,
+}
+
+#[cfg(test)]
+impl Symbol {
+    /// A one-line Rust symbol at `line` of `file`, for tests.
+    pub fn for_tests(name: &str, kind: SymbolKind, file: &str, line: usize) -> Self {
+        let signature = format!("{} {}", kind_keyword(kind), name);
+        Symbol {
+            name: name.to_string(),
+            kind,
+            file: file.to_string(),
+            byte_range: (0, signature.len()),
+            line_range: (line, line),
+            language: crate::index::file_entry::Language::Rust,
+            signature_hash: signature_hash(&signature),
+            signature,
+            return_type: None,
+            definition: None,
+            parent: None,
+            generated: false,
+            source_hash: None,
+        }
+    }
+}
+
+#[cfg(test)]
+fn kind_keyword(kind: SymbolKind) -> &'static str {
+    match kind {
+        SymbolKind::Struct => "struct",
+        SymbolKind::Enum => "enum",
+        SymbolKind::Trait => "trait",
+        SymbolKind::Constant => "const",
+        SymbolKind::Module => "mod",
+        _ => "fn",
+    }
+}
+
+/// Hash used for `Symbol::source_hash`.
+pub fn source_hash(text: &str) -> String {
+    signature_hash(text)
+}
+
+/// Hash used for `Symbol::signature_hash`.
+pub fn signature_hash(signature: &str) -> String {
+    let digest = Sha256::digest(signature.as_bytes());
+    digest.iter().take(6).map(|b| format!("{:02x}", b)).collect()
 }